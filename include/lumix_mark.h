@@ -0,0 +1,73 @@
+#ifndef LUMIX_MARK_H
+#define LUMIX_MARK_H
+
+/* 由cbindgen生成，请勿手动修改 */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * 处理成功
+ */
+#define LUMIX_MARK_OK 0
+
+/**
+ * 参数无效：空指针、JSON格式错误或不支持的输出格式
+ */
+#define LUMIX_MARK_INVALID_ARGUMENT 1
+
+/**
+ * 解码、绘制或编码失败
+ */
+#define LUMIX_MARK_FAILED 2
+
+/**
+ * 处理过程中发生panic
+ */
+#define LUMIX_MARK_PANIC 3
+
+/**
+ * 输出的图片数据，使用后需要调用`lumix_mark_buffer_free`释放
+ */
+typedef struct LumixMarkBuffer {
+  uint8_t *data;
+  size_t len;
+} LumixMarkBuffer;
+
+/**
+ * # 为内存中的图片添加水印
+ *
+ * 成功时返回`LUMIX_MARK_OK`并将输出写入`out_buf`，失败时返回错误码，
+ * 错误信息通过`lumix_mark_last_error`获取
+ *
+ * # Safety
+ *
+ * * `buf`指向至少`len`字节的可读内存
+ * * `style_json`为空指针或以`\0`结尾的UTF-8字符串，空指针时使用默认样式和JPEG输出
+ * * `out_buf`指向可写的`LumixMarkBuffer`
+ */
+int lumix_mark_process(const uint8_t *buf,
+                       size_t len,
+                       const char *style_json,
+                       struct LumixMarkBuffer *out_buf);
+
+/**
+ * # 释放`lumix_mark_process`输出的图片数据
+ *
+ * # Safety
+ *
+ * `buffer`必须来自`lumix_mark_process`且只能释放一次，`data`为空指针时不做任何处理
+ */
+void lumix_mark_buffer_free(struct LumixMarkBuffer buffer);
+
+/**
+ * # 当前线程最近一次失败的错误信息
+ *
+ * 没有错误时返回空指针，返回的字符串在同一线程下一次调用`lumix_mark_process`前有效
+ */
+const char *lumix_mark_last_error(void);
+
+#endif /* LUMIX_MARK_H */
@@ -0,0 +1,121 @@
+use serde::{Deserialize, Serialize};
+
+/// 元素在水印区域中的水平对齐方式
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Align {
+   /// 靠左，与左侧边距对齐
+   Left,
+   /// 水平居中
+   Center,
+   /// 靠右，与右侧边距对齐
+   Right,
+}
+
+/// # 元素的位置覆盖
+///
+/// 指定`align`时元素按对齐方式放到水印区域中（绝对位置），否则保持布局计算出的位置（相对位置），
+/// 之后再移动偏移量；偏移量相对水印高度，向右、向下为正。竖向的水印条中按旋转为横向后的方向计算
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Anchor {
+   /// 水平对齐方式，为空时保持原来的水平位置
+   pub align: Option<Align>,
+   /// 水平偏移
+   pub offset_x: f32,
+   /// 垂直偏移
+   pub offset_y: f32,
+}
+
+/// # 水印中各元素的位置覆盖
+///
+/// 未设置的元素按布局排列，设置后元素之间不再互相避让，需要自行避免重叠
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Anchors {
+   /// Logo
+   pub logo: Option<Anchor>,
+   /// 机型
+   pub model: Option<Anchor>,
+   /// 分隔线
+   pub divider: Option<Anchor>,
+   /// Exif信息，折为两行时整体移动
+   pub exif: Option<Anchor>,
+   /// 拍摄时间、地点和版权信息等附加信息
+   pub secondary: Option<Anchor>,
+   /// 说明文字，每行分别对齐
+   pub caption: Option<Anchor>,
+}
+
+/// 按位置覆盖移动水印区域中的元素
+pub(crate) struct Anchoring {
+   area: (u32, u32, u32, u32),
+   padding: u32,
+   mark_height: f32,
+}
+
+impl Anchoring {
+   /// # 创建
+   ///
+   /// # 参数
+   /// * `area` - 元素所在的区域（左、上、右、下）
+   /// * `padding` - 左右两侧的边距
+   /// * `mark_height` - 水印高度，偏移量的单位
+   pub(crate) fn new(
+      area: (u32, u32, u32, u32),
+      padding: u32,
+      mark_height: f32,
+   ) -> Self {
+      Self {
+         area,
+         padding,
+         mark_height,
+      }
+   }
+
+   /// 宽度为`width`、左上角位于`(x, y)`的元素覆盖后左上角的位置，没有覆盖时为原位置
+   pub(crate) fn place(
+      &self,
+      anchor: Option<Anchor>,
+      (x, y): (i32, i32),
+      width: u32,
+   ) -> (i32, i32) {
+      let (dx, dy) = self.offset(anchor, x, width);
+      (x + dx, y + dy)
+   }
+
+   /// 左边缘位于`x`、宽度为`width`的元素覆盖后移动的距离，用于整体移动多行文字
+   pub(crate) fn offset(
+      &self,
+      anchor: Option<Anchor>,
+      x: i32,
+      width: u32,
+   ) -> (i32, i32) {
+      let Some(anchor) = anchor else {
+         return (0, 0);
+      };
+      let (start_x, _, end_x, _) = self.area;
+      let (width, padding) = (width as i32, self.padding as i32);
+      let aligned = match anchor.align {
+         None => x,
+         Some(Align::Left) => start_x as i32 + padding,
+         Some(Align::Center) => (start_x + end_x) as i32 / 2 - width / 2,
+         Some(Align::Right) => end_x as i32 - padding - width,
+      };
+      (
+         aligned - x + (anchor.offset_x * self.mark_height).round() as i32,
+         (anchor.offset_y * self.mark_height).round() as i32,
+      )
+   }
+
+   /// 与[`Anchoring::place`]相同，用于以无符号坐标绘制的Logo等元素，超出左上边界时取0
+   pub(crate) fn place_unsigned(
+      &self,
+      anchor: Option<Anchor>,
+      (x, y): (u32, u32),
+      width: u32,
+   ) -> (u32, u32) {
+      let (x, y) = self.place(anchor, (x as i32, y as i32), width);
+      (x.max(0) as u32, y.max(0) as u32)
+   }
+}
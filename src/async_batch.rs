@@ -0,0 +1,172 @@
+use crate::input::InputImage;
+use crate::memory::estimate_memory;
+use crate::report::TaskDetails;
+use crate::{BatchSummary, LumixMarkCli, LumixMarkError, Result, TaskOutcome};
+use std::io;
+use std::num::NonZeroUsize;
+use std::ops::ControlFlow;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::Instant;
+use tokio::sync::Semaphore;
+use tokio::task::{JoinSet, spawn_blocking};
+use tracing::{Instrument, Span, debug, error, info, info_span};
+
+impl LumixMarkCli {
+   /// # 异步处理所有图片，不阻塞tokio运行时
+   ///
+   /// 读写图片文件使用tokio，解码、绘制和编码在阻塞线程池中执行；同时处理的图片数由`par_count`
+   /// 限制，默认为CPU核心数，指定`max_memory`时同时按估计的内存占用限制。需要在tokio运行时中调用，
+   /// 单张失败不影响其他图片（`fail_fast`时遇到失败后不再开始新的图片），结束后打印汇总
+   pub async fn process_all_async(self: Arc<Self>) -> BatchSummary {
+      let permits = self
+         .par_count
+         .or_else(|| thread::available_parallelism().ok());
+      let semaphore = Arc::new(Semaphore::new(permits.map_or(1, NonZeroUsize::get)));
+      // 内存预算以MB为单位
+      let budget = self.max_memory.filter(|_| !self.dry_run).map(|limit| {
+         let limit = limit.div_ceil(MB).clamp(1, u32::MAX as u64);
+         (limit, Arc::new(Semaphore::new(limit as usize)))
+      });
+      let failed = Arc::new(AtomicBool::new(false));
+      let mut tasks = JoinSet::new();
+      for (index, input) in self.inputs.iter().cloned().enumerate() {
+         let cli = Arc::clone(&self);
+         let semaphore = Arc::clone(&semaphore);
+         let failed = Arc::clone(&failed);
+         let budget = budget.clone();
+         tasks.spawn(async move {
+            let _permit = semaphore.acquire_owned().await;
+            if cli.fail_fast && failed.load(Ordering::Relaxed) {
+               return (index, input.path, None);
+            }
+            let _memory = match budget {
+               Some((limit, budget)) => {
+                  let path = input.path.clone();
+                  let bytes = blocking(move || Ok(estimate_memory(&path)))
+                     .await
+                     .unwrap_or_default();
+                  let needed = bytes.div_ceil(MB).clamp(1, limit) as u32;
+                  if budget.available_permits() < needed as usize {
+                     debug!(
+                        "等待内存预算：需要{}MB，剩余{}MB",
+                        needed,
+                        budget.available_permits()
+                     );
+                  }
+                  budget.acquire_many_owned(needed).await.ok()
+               }
+               None => None,
+            };
+            let start = Instant::now();
+            let (result, details) = match cli.dry_run {
+               true => {
+                  let cli = Arc::clone(&cli);
+                  let input = input.clone();
+                  blocking(move || {
+                     let mut details = TaskDetails::default();
+                     Ok((cli.plan_task(&input, &mut details), details))
+                  })
+                  .await
+                  .unwrap_or_else(|err| (Err(err), TaskDetails::default()))
+               }
+               false => {
+                  let mut details = TaskDetails::default();
+                  let result = cli.mark_task_async(&input, &mut details).await;
+                  (result, details)
+               }
+            };
+            if let Err(err) = &result {
+               error!("处理图片失败：{}, 图片地址：{:?}", err, input.path);
+               failed.store(true, Ordering::Relaxed);
+            }
+            (index, input.path, Some((result, details, start.elapsed())))
+         });
+      }
+      let mut results = tasks.join_all().await;
+      results.sort_by_key(|(index, ..)| *index);
+      let mut summary = BatchSummary::default();
+      for (_, path, task) in results {
+         match task {
+            Some((result, details, duration)) => {
+               summary.add(path, result, details, duration, self.dry_run)
+            }
+            None => summary.cancel(path),
+         }
+      }
+      self.finish_batch(&mut summary);
+      summary
+   }
+
+   /// 异步处理单张图片：解析、绘制水印并保存，返回输出文件路径
+   pub async fn draw_logo_exif_task_async(
+      self: &Arc<Self>,
+      input: &InputImage,
+   ) -> Result<TaskOutcome> {
+      self
+         .mark_task_async(input, &mut TaskDetails::default())
+         .await
+   }
+
+   async fn mark_task_async(
+      self: &Arc<Self>,
+      input: &InputImage,
+      details: &mut TaskDetails,
+   ) -> Result<TaskOutcome> {
+      let cli = Arc::clone(self);
+      let claimed = input.clone();
+      let target = match blocking(move || cli.claim_target(&claimed)).await? {
+         ControlFlow::Continue(target) => target,
+         ControlFlow::Break(outcome) => return Ok(outcome),
+      };
+      let span = info_span!("image", path = ?input.path);
+      let result = async {
+         let file_bytes = tokio::fs::read(&input.path).await?;
+         info!("开始处理图片：{:?}", input.path);
+         let cli = Arc::clone(self);
+         let input = input.clone();
+         let span = Span::current();
+         let (output, recorded) = blocking(move || {
+            let _span = span.entered();
+            let style = cli.style_for(&input)?;
+            let context = cli.run_pipeline(&input, &file_bytes, &style)?;
+            let mut recorded = TaskDetails::default();
+            recorded.record_context(&context, &style);
+            Ok((context.into_output()?, recorded))
+         })
+         .await?;
+         *details = recorded;
+         // 输出文件夹也经过临时文件写入，中断时不会在输出路径上留下不完整的文件
+         let cli = Arc::clone(self);
+         let target = target.clone();
+         blocking(move || cli.sink().write(&target, &output)).await
+      }
+      .instrument(span)
+      .await;
+      if let Err(err) = result {
+         let cli = Arc::clone(self);
+         let failed = target.clone();
+         let _ = spawn_blocking(move || cli.release_target(&failed)).await;
+         return self.skip_input(err, &input.path, target);
+      }
+      let cli = Arc::clone(self);
+      let (path, written) = (input.path.clone(), target.clone());
+      let _ = spawn_blocking(move || cli.record_written(&path, &written)).await;
+      Ok(TaskOutcome::Written(target))
+   }
+}
+
+/// 内存预算的单位
+const MB: u64 = 1_000_000;
+
+/// 在阻塞线程池中执行，任务panic时转换为错误
+async fn blocking<T, F>(task: F) -> Result<T>
+where
+   T: Send + 'static,
+   F: FnOnce() -> Result<T> + Send + 'static,
+{
+   spawn_blocking(task)
+      .await
+      .map_err(|err| LumixMarkError::Io(io::Error::other(err)))?
+}
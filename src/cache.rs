@@ -0,0 +1,143 @@
+use crate::{Empty, Exif, LumixMark, Metadata, Result, SidecarPolicy};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+use std::time::UNIX_EPOCH;
+
+/// 新增多少条缓存后写回文件，批量处理中断时已读取的信息不会全部丢失
+const SAVE_INTERVAL: usize = 64;
+
+/// 缓存文件的内容，程序版本不同时整体作废
+#[derive(Default, Serialize, Deserialize)]
+struct CacheFile {
+   version: String,
+   entries: HashMap<PathBuf, CacheEntry>,
+}
+
+/// 一张图片的缓存
+#[derive(Clone, Serialize, Deserialize)]
+struct CacheEntry {
+   stamp: FileStamp,
+   width: u32,
+   height: u32,
+   exif: Exif,
+}
+
+/// 判断文件是否改变的依据：修改时间和文件大小
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct FileStamp {
+   modified_secs: u64,
+   modified_nanos: u32,
+   size: u64,
+}
+
+impl FileStamp {
+   pub(crate) fn read(path: &Path) -> Option<Self> {
+      let meta = fs::metadata(path).ok()?;
+      let modified = meta.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+      Some(Self {
+         modified_secs: modified.as_secs(),
+         modified_nanos: modified.subsec_nanos(),
+         size: meta.len(),
+      })
+   }
+}
+
+/// # EXIF缓存
+///
+/// 按路径、修改时间和文件大小缓存图片的尺寸和EXIF信息，保存为JSON文件，跨次运行复用；
+/// 文件未改变时排序、预览和按EXIF生成文件名不必重新读取原图，中断的批量处理重新运行时可以很快跳过已完成的图片
+pub struct ExifCache {
+   path: PathBuf,
+   state: Mutex<CacheState>,
+}
+
+struct CacheState {
+   file: CacheFile,
+   /// 上次写回文件后新增的条目数
+   pending: usize,
+}
+
+impl ExifCache {
+   /// 打开缓存文件，文件不存在、无法解析或由其他版本写入时从空缓存开始
+   pub fn open<P: Into<PathBuf>>(path: P) -> Self {
+      let path = path.into();
+      let file = fs::read(&path)
+         .ok()
+         .and_then(|bytes| serde_json::from_slice::<CacheFile>(&bytes).ok())
+         .filter(|file| file.version == env!("CARGO_PKG_VERSION"))
+         .unwrap_or_else(|| {
+            tracing::debug!("EXIF缓存不存在或已失效，重新建立：{:?}", path);
+            CacheFile {
+               version: env!("CARGO_PKG_VERSION").to_string(),
+               ..CacheFile::default()
+            }
+         });
+      Self {
+         path,
+         state: Mutex::new(CacheState { file, pending: 0 }),
+      }
+   }
+
+   /// 与[`LumixMark::probe`]相同，文件未改变时直接返回缓存的结果
+   pub fn probe<P: AsRef<Path>>(&self, file_path: P) -> Result<(u32, u32, Exif)> {
+      let file_path = file_path.as_ref();
+      let key = fs::canonicalize(file_path).unwrap_or_else(|_| file_path.to_path_buf());
+      let stamp = FileStamp::read(file_path);
+      let cached = self.lock().file.entries.get(&key).cloned();
+      let cached = cached.filter(|entry| Some(entry.stamp) == stamp);
+      if let Some(entry) = cached {
+         return Ok((entry.width, entry.height, entry.exif));
+      }
+      let (width, height, exif) = LumixMark::probe(file_path)?;
+      if let Some(stamp) = stamp {
+         let entry = CacheEntry {
+            stamp,
+            width,
+            height,
+            exif: exif.clone(),
+         };
+         let save = {
+            let mut state = self.lock();
+            state.file.entries.insert(key, entry);
+            state.pending += 1;
+            state.pending >= SAVE_INTERVAL
+         };
+         if let Some(Err(err)) = save.then(|| self.save()) {
+            tracing::warn!("写入EXIF缓存失败：{}, path:{:?}", err, self.path);
+         }
+      }
+      Ok((width, height, exif))
+   }
+
+   /// 与[`Exif::from_image`]相同，读取EXIF信息并合并XMP附属文件，附属文件不缓存
+   pub fn exif<P: AsRef<Path>>(&self, file_path: P) -> Result<Exif> {
+      let (_, _, mut exif) = self.probe(&file_path)?;
+      if let Some(sidecar) = Metadata::read_sidecar(file_path.as_ref()) {
+         exif.apply_sidecar(&sidecar, SidecarPolicy::default());
+      }
+      Ok(exif)
+   }
+
+   /// 有新增的条目时写回缓存文件，先写入临时文件再替换，写入中断时不会损坏原有的缓存
+   pub fn save(&self) -> Empty {
+      let mut state = self.lock();
+      if state.pending == 0 {
+         return Ok(());
+      }
+      let json = serde_json::to_vec(&state.file).map_err(io::Error::from)?;
+      let temp = self.path.with_extension("tmp");
+      fs::write(&temp, json)?;
+      fs::rename(&temp, &self.path)?;
+      state.pending = 0;
+      Ok(())
+   }
+
+   fn lock(&self) -> MutexGuard<'_, CacheState> {
+      // 其他线程在持有锁时panic不影响缓存内容的有效性
+      self.state.lock().unwrap_or_else(|err| err.into_inner())
+   }
+}
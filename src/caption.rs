@@ -0,0 +1,74 @@
+use crate::fit::FitText;
+use crate::font::FontStack;
+use crate::{CanvasLayout, Exif, MarkPosition, MarkStyle, Result};
+
+/// 说明文字最多的行数，超出时截断最后一行
+const MAX_LINES: usize = 2;
+
+/// 按Exif信息填充的说明文字，未设置模板或填充后为空时返回`None`
+pub(crate) fn caption_text(style: &MarkStyle, exif: &Exif) -> Option<String> {
+   let text = exif.render(&style.caption);
+   (!text.trim().is_empty()).then_some(text)
+}
+
+/// # 折行后的说明文字
+///
+/// 文字大小与拍摄时间相同，按水印条的长度减去两侧边距折行
+///
+/// # 参数
+/// * `style` - 水印样式
+/// * `fonts` - 说明文字的字体栈
+/// * `text` - 说明文字
+/// * `mark_height` - 水印高度
+/// * `length` - 横向绘制时水印条的宽度
+pub(crate) fn caption_lines<'a>(
+   style: &MarkStyle,
+   fonts: &'a FontStack<'a>,
+   text: &str,
+   mark_height: f32,
+   length: u32,
+) -> Vec<FitText<'a>> {
+   let padding = (mark_height * style.padding_ratio) as u32;
+   let size = mark_height * style.date_text_ratio;
+   let flow = style.text_direction.flow(text, style.strip_position());
+   FitText::new(text, size, fonts, flow)
+      .wrap(length.saturating_sub(padding * 2), MAX_LINES)
+}
+
+/// 说明文字占用的厚度，每行下方留出一个间距
+pub(crate) fn caption_height(style: &MarkStyle, mark_height: f32, lines: usize) -> u32 {
+   let gap = (mark_height * style.gap_ratio).max(0.0);
+   (lines as f32 * (mark_height * style.date_text_ratio + gap)).round() as u32
+}
+
+/// # 计算画布布局，水印条按说明文字的行数加厚
+///
+/// 在创建画布前按照片尺寸折行，与绘制时的折行结果相同
+///
+/// # 参数
+/// * `style` - 水印样式
+/// * `exif` - 填充说明文字的EXIF信息
+/// * `width` - 照片宽度
+/// * `height` - 照片高度
+/// * `mark_height` - 水印高度
+pub(crate) fn canvas_layout(
+   style: &MarkStyle,
+   exif: &Exif,
+   width: u32,
+   height: u32,
+   mark_height: u32,
+) -> Result<CanvasLayout> {
+   let layout = style.canvas_layout(width, height, mark_height);
+   let Some(text) = caption_text(style, exif) else {
+      return Ok(layout);
+   };
+   let (start_x, start_y, end_x, end_y) = layout.mark_area;
+   let length = match style.strip_position() {
+      MarkPosition::Left | MarkPosition::Right => end_y - start_y,
+      MarkPosition::Bottom | MarkPosition::Top => end_x - start_x,
+   };
+   let fonts = FontStack::new(style.exif_font()?, style, [text.as_str()])?;
+   let lines = caption_lines(style, &fonts, &text, mark_height as f32, length).len();
+   let caption_height = caption_height(style, mark_height as f32, lines);
+   Ok(style.caption_layout(width, height, mark_height, caption_height))
+}
@@ -0,0 +1,337 @@
+use crate::LumixMarkError;
+use image::{Pixel, Rgb, Rgba};
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::str::FromStr;
+
+/// CSS中除黑色和白色以外的颜色名称，按名称排序
+const NAMED_COLORS: [(&str, u32); 146] = [
+   ("aliceblue", 0xF0F8FF),
+   ("antiquewhite", 0xFAEBD7),
+   ("aqua", 0x00FFFF),
+   ("aquamarine", 0x7FFFD4),
+   ("azure", 0xF0FFFF),
+   ("beige", 0xF5F5DC),
+   ("bisque", 0xFFE4C4),
+   ("blanchedalmond", 0xFFEBCD),
+   ("blue", 0x0000FF),
+   ("blueviolet", 0x8A2BE2),
+   ("brown", 0xA52A2A),
+   ("burlywood", 0xDEB887),
+   ("cadetblue", 0x5F9EA0),
+   ("chartreuse", 0x7FFF00),
+   ("chocolate", 0xD2691E),
+   ("coral", 0xFF7F50),
+   ("cornflowerblue", 0x6495ED),
+   ("cornsilk", 0xFFF8DC),
+   ("crimson", 0xDC143C),
+   ("cyan", 0x00FFFF),
+   ("darkblue", 0x00008B),
+   ("darkcyan", 0x008B8B),
+   ("darkgoldenrod", 0xB8860B),
+   ("darkgray", 0xA9A9A9),
+   ("darkgreen", 0x006400),
+   ("darkgrey", 0xA9A9A9),
+   ("darkkhaki", 0xBDB76B),
+   ("darkmagenta", 0x8B008B),
+   ("darkolivegreen", 0x556B2F),
+   ("darkorange", 0xFF8C00),
+   ("darkorchid", 0x9932CC),
+   ("darkred", 0x8B0000),
+   ("darksalmon", 0xE9967A),
+   ("darkseagreen", 0x8FBC8F),
+   ("darkslateblue", 0x483D8B),
+   ("darkslategray", 0x2F4F4F),
+   ("darkslategrey", 0x2F4F4F),
+   ("darkturquoise", 0x00CED1),
+   ("darkviolet", 0x9400D3),
+   ("deeppink", 0xFF1493),
+   ("deepskyblue", 0x00BFFF),
+   ("dimgray", 0x696969),
+   ("dimgrey", 0x696969),
+   ("dodgerblue", 0x1E90FF),
+   ("firebrick", 0xB22222),
+   ("floralwhite", 0xFFFAF0),
+   ("forestgreen", 0x228B22),
+   ("fuchsia", 0xFF00FF),
+   ("gainsboro", 0xDCDCDC),
+   ("ghostwhite", 0xF8F8FF),
+   ("gold", 0xFFD700),
+   ("goldenrod", 0xDAA520),
+   ("gray", 0x808080),
+   ("green", 0x008000),
+   ("greenyellow", 0xADFF2F),
+   ("grey", 0x808080),
+   ("honeydew", 0xF0FFF0),
+   ("hotpink", 0xFF69B4),
+   ("indianred", 0xCD5C5C),
+   ("indigo", 0x4B0082),
+   ("ivory", 0xFFFFF0),
+   ("khaki", 0xF0E68C),
+   ("lavender", 0xE6E6FA),
+   ("lavenderblush", 0xFFF0F5),
+   ("lawngreen", 0x7CFC00),
+   ("lemonchiffon", 0xFFFACD),
+   ("lightblue", 0xADD8E6),
+   ("lightcoral", 0xF08080),
+   ("lightcyan", 0xE0FFFF),
+   ("lightgoldenrodyellow", 0xFAFAD2),
+   ("lightgray", 0xD3D3D3),
+   ("lightgreen", 0x90EE90),
+   ("lightgrey", 0xD3D3D3),
+   ("lightpink", 0xFFB6C1),
+   ("lightsalmon", 0xFFA07A),
+   ("lightseagreen", 0x20B2AA),
+   ("lightskyblue", 0x87CEFA),
+   ("lightslategray", 0x778899),
+   ("lightslategrey", 0x778899),
+   ("lightsteelblue", 0xB0C4DE),
+   ("lightyellow", 0xFFFFE0),
+   ("lime", 0x00FF00),
+   ("limegreen", 0x32CD32),
+   ("linen", 0xFAF0E6),
+   ("magenta", 0xFF00FF),
+   ("maroon", 0x800000),
+   ("mediumaquamarine", 0x66CDAA),
+   ("mediumblue", 0x0000CD),
+   ("mediumorchid", 0xBA55D3),
+   ("mediumpurple", 0x9370DB),
+   ("mediumseagreen", 0x3CB371),
+   ("mediumslateblue", 0x7B68EE),
+   ("mediumspringgreen", 0x00FA9A),
+   ("mediumturquoise", 0x48D1CC),
+   ("mediumvioletred", 0xC71585),
+   ("midnightblue", 0x191970),
+   ("mintcream", 0xF5FFFA),
+   ("mistyrose", 0xFFE4E1),
+   ("moccasin", 0xFFE4B5),
+   ("navajowhite", 0xFFDEAD),
+   ("navy", 0x000080),
+   ("oldlace", 0xFDF5E6),
+   ("olive", 0x808000),
+   ("olivedrab", 0x6B8E23),
+   ("orange", 0xFFA500),
+   ("orangered", 0xFF4500),
+   ("orchid", 0xDA70D6),
+   ("palegoldenrod", 0xEEE8AA),
+   ("palegreen", 0x98FB98),
+   ("paleturquoise", 0xAFEEEE),
+   ("palevioletred", 0xDB7093),
+   ("papayawhip", 0xFFEFD5),
+   ("peachpuff", 0xFFDAB9),
+   ("peru", 0xCD853F),
+   ("pink", 0xFFC0CB),
+   ("plum", 0xDDA0DD),
+   ("powderblue", 0xB0E0E6),
+   ("purple", 0x800080),
+   ("rebeccapurple", 0x663399),
+   ("red", 0xFF0000),
+   ("rosybrown", 0xBC8F8F),
+   ("royalblue", 0x4169E1),
+   ("saddlebrown", 0x8B4513),
+   ("salmon", 0xFA8072),
+   ("sandybrown", 0xF4A460),
+   ("seagreen", 0x2E8B57),
+   ("seashell", 0xFFF5EE),
+   ("sienna", 0xA0522D),
+   ("silver", 0xC0C0C0),
+   ("skyblue", 0x87CEEB),
+   ("slateblue", 0x6A5ACD),
+   ("slategray", 0x708090),
+   ("slategrey", 0x708090),
+   ("snow", 0xFFFAFA),
+   ("springgreen", 0x00FF7F),
+   ("steelblue", 0x4682B4),
+   ("tan", 0xD2B48C),
+   ("teal", 0x008080),
+   ("thistle", 0xD8BFD8),
+   ("tomato", 0xFF6347),
+   ("turquoise", 0x40E0D0),
+   ("violet", 0xEE82EE),
+   ("wheat", 0xF5DEB3),
+   ("whitesmoke", 0xF5F5F5),
+   ("yellow", 0xFFFF00),
+   ("yellowgreen", 0x9ACD32),
+];
+
+/// # 颜色
+///
+/// 可以由字符串解析，见[`Color::from_str`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Color {
+   Black,
+   White,
+   RGB(u8, u8, u8),
+   /// 带不透明度的颜色，最后一个分量为0（透明）到255（不透明）
+   RGBA(u8, u8, u8, u8),
+   /// `#RRGGBB`或`#RRGGBBAA`形式的颜色，无效时为黑色
+   #[deprecated(note = "只能使用字符串常量且无效时为黑色，请使用`str::parse`解析颜色")]
+   HEX(&'static str),
+   /// 根据绘制位置背景的亮度自动选择黑色或白色
+   Auto,
+}
+
+impl Color {
+   /// 不透明度（0 - 1）
+   pub fn opacity(&self) -> f32 {
+      let Rgba([_, _, _, a]) = (*self).into();
+      a as f32 / 255.0
+   }
+
+   /// 确定在`background`背景上绘制时的颜色，`Auto`按背景亮度选择黑色或白色
+   pub fn resolve(self, background: Rgb<u8>) -> Color {
+      match self {
+         Color::Auto if background.to_luma()[0] < 128 => Color::White,
+         Color::Auto => Color::Black,
+         color => color,
+      }
+   }
+}
+
+impl From<Color> for Rgba<u8> {
+   #[allow(deprecated)]
+   fn from(color: Color) -> Self {
+      match color {
+         Color::Black | Color::Auto => Rgba([0, 0, 0, 255]),
+         Color::White => Rgba([255, 255, 255, 255]),
+         Color::RGB(r, g, b) => Rgba([r, g, b, 255]),
+         Color::RGBA(r, g, b, a) => Rgba([r, g, b, a]),
+         Color::HEX(hex) => {
+            let hex = hex.trim_start_matches('#');
+            if hex.len() != 6 && hex.len() != 8 {
+               return Rgba([0, 0, 0, 255]);
+            }
+            let channel = |i: usize| {
+               hex.get(i..i + 2)
+                  .and_then(|value| u8::from_str_radix(value, 16).ok())
+                  .unwrap_or_default()
+            };
+            let a = if hex.len() == 8 { channel(6) } else { 255 };
+            Rgba([channel(0), channel(2), channel(4), a])
+         }
+      }
+   }
+}
+
+/// 转换为不透明的颜色，忽略不透明度
+impl From<Color> for Rgb<u8> {
+   fn from(color: Color) -> Self {
+      let Rgba([r, g, b, _]) = color.into();
+      Rgb([r, g, b])
+   }
+}
+
+/// 配置文件中颜色写为`#RRGGBB`形式的字符串，半透明的颜色写为`#RRGGBBAA`，自动颜色写为`auto`
+impl Serialize for Color {
+   fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+      if *self == Color::Auto {
+         return serializer.serialize_str("auto");
+      }
+      let Rgba([r, g, b, a]) = (*self).into();
+      if a == 255 {
+         serializer.serialize_str(&format!("#{r:02X}{g:02X}{b:02X}"))
+      } else {
+         serializer.serialize_str(&format!("#{r:02X}{g:02X}{b:02X}{a:02X}"))
+      }
+   }
+}
+
+impl<'de> Deserialize<'de> for Color {
+   fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+      let value = String::deserialize(deserializer)?;
+      parse_color(&value).map_err(D::Error::custom)
+   }
+}
+
+impl FromStr for Color {
+   type Err = LumixMarkError;
+
+   /// # 解析颜色
+   ///
+   /// 支持`#RGB`、`#RGBA`、`#RRGGBB`、`#RRGGBBAA`（可以省略`#`），`rgb(255, 128, 0)`、
+   /// `rgba(255, 128, 0, 0.5)`（不透明度为0 - 1或百分比），CSS颜色名称（如`steelblue`）、
+   /// `transparent`和`auto`，不区分大小写
+   ///
+   /// ```
+   /// use lumix_mark::Color;
+   ///
+   /// assert_eq!("#FF8000".parse::<Color>().unwrap(), Color::RGB(255, 128, 0));
+   /// assert_eq!("f80".parse::<Color>().unwrap(), Color::RGB(255, 136, 0));
+   /// assert_eq!("rgba(0, 0, 0, 50%)".parse::<Color>().unwrap(), Color::RGBA(0, 0, 0, 128));
+   /// assert_eq!("SteelBlue".parse::<Color>().unwrap(), Color::RGB(70, 130, 180));
+   /// assert!("#12345".parse::<Color>().is_err());
+   /// ```
+   fn from_str(value: &str) -> Result<Self, Self::Err> {
+      parse_color(value).map_err(LumixMarkError::InvalidConfig)
+   }
+}
+
+/// 解析颜色，格式见[`Color::from_str`]，无效时返回错误信息
+fn parse_color(value: &str) -> Result<Color, String> {
+   let invalid = || {
+      format!("无效的颜色：{value}，请使用#RRGGBB、#RRGGBBAA、rgb(r, g, b)或颜色名称")
+   };
+   let name = value.trim().to_ascii_lowercase();
+   match name.as_str() {
+      "black" => return Ok(Color::Black),
+      "white" => return Ok(Color::White),
+      "auto" => return Ok(Color::Auto),
+      "transparent" => return Ok(Color::RGBA(0, 0, 0, 0)),
+      _ => {}
+   }
+   if let Ok(index) = NAMED_COLORS.binary_search_by_key(&name.as_str(), |&(n, _)| n) {
+      let [_, r, g, b] = NAMED_COLORS[index].1.to_be_bytes();
+      return Ok(Color::RGB(r, g, b));
+   }
+   if let Some(arguments) = name
+      .strip_prefix("rgba(")
+      .or_else(|| name.strip_prefix("rgb("))
+   {
+      let arguments = arguments.strip_suffix(')').ok_or_else(invalid)?;
+      return parse_rgb(arguments).ok_or_else(invalid);
+   }
+   let hex = name.strip_prefix('#').unwrap_or(&name);
+   if !hex.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+      return Err(invalid());
+   }
+   let digits: Vec<u8> = match hex.len() {
+      // 简写形式每位重复一次
+      3 | 4 => hex.bytes().flat_map(|byte| [byte, byte]).collect(),
+      6 | 8 => hex.bytes().collect(),
+      _ => return Err(invalid()),
+   };
+   let digit = |byte: u8| (byte as char).to_digit(16).unwrap_or_default() as u8;
+   let channels: Vec<u8> = digits
+      .chunks(2)
+      .map(|pair| digit(pair[0]) << 4 | digit(pair[1]))
+      .collect();
+   match channels[..] {
+      [r, g, b] => Ok(Color::RGB(r, g, b)),
+      [r, g, b, a] => Ok(Color::RGBA(r, g, b, a)),
+      _ => Err(invalid()),
+   }
+}
+
+/// 解析`rgb()`或`rgba()`括号中的参数，三个分量为0 - 255，不透明度为0 - 1或百分比
+fn parse_rgb(arguments: &str) -> Option<Color> {
+   let values: Vec<&str> = arguments.split(',').map(str::trim).collect();
+   let channel = |value: &str| value.parse::<u8>().ok();
+   let (r, g, b) = (
+      channel(values.first()?)?,
+      channel(values.get(1)?)?,
+      channel(values.get(2)?)?,
+   );
+   match values.get(3..)? {
+      [] => Some(Color::RGB(r, g, b)),
+      [alpha] => {
+         let alpha = match alpha.strip_suffix('%') {
+            Some(percent) => percent.trim().parse::<f32>().ok()? / 100.0,
+            None => alpha.parse::<f32>().ok()?,
+         };
+         (0.0..=1.0)
+            .contains(&alpha)
+            .then(|| Color::RGBA(r, g, b, (alpha * 255.0).round() as u8))
+      }
+      _ => None,
+   }
+}
@@ -0,0 +1,94 @@
+use crate::{Fill, LumixMark, MarkStyle};
+use clap::ValueEnum;
+use image::{Rgb, RgbImage, imageops};
+
+/// 对比图四周和两张图之间的间距，相对水印图的短边
+const GAP_RATIO: f32 = 0.02;
+
+/// 原图与水印图对比图的排列方式
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum CompareLayout {
+   /// 左右排列，原图在左
+   #[default]
+   SideBySide,
+   /// 上下排列，原图在上
+   Stacked,
+}
+
+impl LumixMark {
+   /// 画布中照片区域的副本，需要在绘制水印前获取
+   pub(crate) fn photo_copy(&self) -> RgbImage {
+      let (left, top, right, bottom) = self.photo_area;
+      imageops::crop_imm(&self.canvas, left, top, right - left, bottom - top).to_image()
+   }
+
+   /// # 将画布替换为原图与水印图的对比图
+   ///
+   /// 原图与画布中的照片尺寸相同，在另一方向上居中；背景与水印条相同（模糊背景时为白色）。
+   /// 水印区域、元素和照片区域随水印图平移，`unmark`仍然可以从对比图中取出照片
+   ///
+   /// # 参数
+   /// * `original` - 绘制水印前的照片，见[`LumixMark::photo_copy`]
+   /// * `layout` - 排列方式
+   /// * `style` - 水印样式，提供背景颜色
+   pub(crate) fn compose_comparison(
+      &mut self,
+      original: &RgbImage,
+      layout: CompareLayout,
+      style: &MarkStyle,
+   ) {
+      let (width, height) = self.canvas.dimensions();
+      let (photo_width, photo_height) = original.dimensions();
+      let gap = (width.min(height) as f32 * GAP_RATIO).round() as u32;
+      let (canvas_width, canvas_height, original_at, marked_at) = match layout {
+         CompareLayout::SideBySide => (
+            photo_width + width + gap * 3,
+            height + gap * 2,
+            (gap, gap + (height - photo_height) / 2),
+            (photo_width + gap * 2, gap),
+         ),
+         CompareLayout::Stacked => (
+            width + gap * 2,
+            photo_height + height + gap * 3,
+            (gap + (width - photo_width) / 2, gap),
+            (gap, photo_height + gap * 2),
+         ),
+      };
+      let background = match style.background {
+         Fill::Solid(color) | Fill::Gradient { from: color, .. } => Rgb::from(color),
+         Fill::Auto { .. } | Fill::Blur { .. } => Rgb([255, 255, 255]),
+      };
+      let mut canvas = RgbImage::from_pixel(canvas_width, canvas_height, background);
+      imageops::replace(
+         &mut canvas,
+         original,
+         original_at.0 as i64,
+         original_at.1 as i64,
+      );
+      imageops::replace(
+         &mut canvas,
+         &self.canvas,
+         marked_at.0 as i64,
+         marked_at.1 as i64,
+      );
+
+      let (dx, dy) = marked_at;
+      let offset = |(left, top, right, bottom): (u32, u32, u32, u32)| {
+         (left + dx, top + dy, right + dx, bottom + dy)
+      };
+      self.mark_area = offset(self.mark_area);
+      self.photo_area = offset(self.photo_area);
+      if let Some(provenance) = &mut self.provenance {
+         provenance.photo_area = provenance.photo_area.map(offset);
+      }
+      for element in &mut self.elements {
+         element.x += dx;
+         element.y += dy;
+      }
+      self.canvas = canvas;
+      self.width = canvas_width;
+      self.height = canvas_height;
+      // 16位照片的像素按原画布中的位置合并，对比图只输出8位
+      self.deep_photo = None;
+   }
+}
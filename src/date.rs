@@ -0,0 +1,416 @@
+use crate::{LumixMarkError, Result};
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+
+/// 格式中可以使用的占位符
+const SPECIFIERS: &[char] = &[
+   'Y', 'y', 'm', 'd', 'H', 'I', 'M', 'S', 'f', 'z', 'p', 'b', 'B', '%',
+];
+
+const MONTHS: [&str; 12] = [
+   "January",
+   "February",
+   "March",
+   "April",
+   "May",
+   "June",
+   "July",
+   "August",
+   "September",
+   "October",
+   "November",
+   "December",
+];
+
+/// # 拍摄时间的显示格式
+///
+/// 与strftime类似，支持`%Y`（2024）、`%y`（24）、`%m`（05）、`%d`（01）、`%H`（17）、
+/// `%I`（05）、`%M`（30）、`%S`（12）、`%f`（亚秒，如123）、`%z`（时差，如+08:00）、
+/// `%p`（PM）、`%b`（May）、`%B`（May）和`%%`；EXIF中没有亚秒或时差时`%f`和`%z`为空
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DateFormat(String);
+
+impl Default for DateFormat {
+   fn default() -> Self {
+      Self("%Y.%m.%d %H:%M".to_string())
+   }
+}
+
+impl DateFormat {
+   /// 解析显示格式，存在不支持的占位符时返回错误
+   pub fn parse(format: &str) -> Result<Self> {
+      let mut chars = format.chars();
+      while let Some(c) = chars.next() {
+         if c != '%' {
+            continue;
+         }
+         match chars.next() {
+            Some(specifier) if SPECIFIERS.contains(&specifier) => {}
+            Some(specifier) => {
+               return Err(LumixMarkError::InvalidConfig(format!(
+                  "时间格式中存在不支持的占位符%{specifier}：{format}"
+               )));
+            }
+            None => {
+               return Err(LumixMarkError::InvalidConfig(format!(
+                  "时间格式不能以%结尾：{format}"
+               )));
+            }
+         }
+      }
+      Ok(Self(format.to_string()))
+   }
+
+   /// # 格式化EXIF中的拍摄时间
+   ///
+   /// `shoot_time`为EXIF格式的`2024:05:01 17:30:12`，无法解析时返回`None`
+   pub fn format(&self, shoot_time: &str) -> Option<String> {
+      ShootTime::parse(shoot_time).map(|time| self.format_time(&time))
+   }
+
+   /// 格式化拍摄时间，见[`Exif::shoot_date_time`](crate::Exif::shoot_date_time)
+   pub fn format_time(&self, time: &ShootTime) -> String {
+      let ShootTime {
+         year,
+         month,
+         day,
+         hour,
+         minute,
+         second,
+         ..
+      } = *time;
+      let month_name = (month as usize)
+         .checked_sub(1)
+         .and_then(|index| MONTHS.get(index))
+         .copied()
+         .unwrap_or_default();
+      let hour12 = match hour % 12 {
+         0 => 12,
+         hour => hour,
+      };
+      let mut result = String::new();
+      let mut chars = self.0.chars();
+      while let Some(c) = chars.next() {
+         if c != '%' {
+            result.push(c);
+            continue;
+         }
+         match chars.next() {
+            Some('Y') => result.push_str(&format!("{year:04}")),
+            Some('y') => result.push_str(&format!("{:02}", year % 100)),
+            Some('m') => result.push_str(&format!("{month:02}")),
+            Some('d') => result.push_str(&format!("{day:02}")),
+            Some('H') => result.push_str(&format!("{hour:02}")),
+            Some('I') => result.push_str(&format!("{hour12:02}")),
+            Some('M') => result.push_str(&format!("{minute:02}")),
+            Some('S') => result.push_str(&format!("{second:02}")),
+            Some('f') => result.push_str(&time.sub_second),
+            Some('z') => {
+               if let Some(offset) = time.offset {
+                  result.push_str(&format_offset(offset));
+               }
+            }
+            Some('p') => result.push_str(if hour < 12 { "AM" } else { "PM" }),
+            Some('b') => result.push_str(&month_name[..3]),
+            Some('B') => result.push_str(month_name),
+            Some(c) => result.push(c),
+            None => {}
+         }
+      }
+      result
+   }
+}
+
+impl Display for DateFormat {
+   fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+      f.write_str(&self.0)
+   }
+}
+
+impl Serialize for DateFormat {
+   fn serialize<S: Serializer>(
+      &self,
+      serializer: S,
+   ) -> std::result::Result<S::Ok, S::Error> {
+      serializer.serialize_str(&self.0)
+   }
+}
+
+impl<'de> Deserialize<'de> for DateFormat {
+   fn deserialize<D: Deserializer<'de>>(
+      deserializer: D,
+   ) -> std::result::Result<Self, D::Error> {
+      let format = String::deserialize(deserializer)?;
+      Self::parse(&format).map_err(D::Error::custom)
+   }
+}
+
+/// # 拍摄时间
+///
+/// 由EXIF中的`DateTimeOriginal`、`SubSecTimeOriginal`和`OffsetTimeOriginal`组成，
+/// 年月日和时分秒为相机记录的当地时间
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ShootTime {
+   pub year: u32,
+   pub month: u32,
+   pub day: u32,
+   pub hour: u32,
+   pub minute: u32,
+   pub second: u32,
+   /// 亚秒的数字，如`123`表示0.123秒，缺失时为空
+   pub sub_second: String,
+   /// 相对UTC的时差（分钟，东为正），缺失时为`None`
+   pub offset: Option<i32>,
+}
+
+impl ShootTime {
+   /// 解析EXIF格式的`2024:05:01 17:30:12`，不包含亚秒和时差，无法解析时返回`None`
+   pub fn parse(shoot_time: &str) -> Option<Self> {
+      let numbers: Vec<u32> = shoot_time
+         .trim()
+         .split([':', ' ', '-'])
+         .map(|value| value.trim().parse().ok())
+         .collect::<Option<_>>()?;
+      let [year, month, day, hour, minute, second] = numbers[..] else {
+         return None;
+      };
+      ((1..=12).contains(&month) && (1..=31).contains(&day)).then(|| Self {
+         year,
+         month,
+         day,
+         hour,
+         minute,
+         second,
+         sub_second: String::new(),
+         offset: None,
+      })
+   }
+
+   /// UNIX时间戳（秒）对应的UTC时间
+   pub(crate) fn from_unix(seconds: i64) -> Self {
+      let (year, month, day) = civil_from_days(seconds.div_euclid(86400));
+      let seconds = seconds.rem_euclid(86400) as u32;
+      Self {
+         year: year.max(0) as u32,
+         month,
+         day,
+         hour: seconds / 3600,
+         minute: seconds / 60 % 60,
+         second: seconds % 60,
+         sub_second: String::new(),
+         offset: Some(0),
+      }
+   }
+
+   /// # 换算到时区`zone`
+   ///
+   /// 没有时差信息时无法换算，保持相机记录的时间
+   ///
+   /// ```
+   /// use lumix_mark::{ShootTime, TimeZone};
+   ///
+   /// let mut time = ShootTime::parse("2024:05:01 07:30:12").unwrap();
+   /// time.offset = Some(9 * 60);
+   /// let utc = time.clone().to_zone(TimeZone::Utc);
+   /// assert_eq!((utc.month, utc.day, utc.hour), (4, 30, 22));
+   /// let new_york = time.to_zone("-04:00".parse().unwrap());
+   /// assert_eq!((new_york.day, new_york.hour, new_york.offset), (30, 18, Some(-240)));
+   /// ```
+   pub fn to_zone(self, zone: TimeZone) -> Self {
+      let (Some(offset), Some(target)) = (self.offset, zone.offset()) else {
+         return self;
+      };
+      let days = days_from_civil(self.year as i64, self.month, self.day);
+      let minutes = days * 1440 + (self.hour * 60 + self.minute) as i64 - offset as i64
+         + target as i64;
+      let (year, month, day) = civil_from_days(minutes.div_euclid(1440));
+      let minutes = minutes.rem_euclid(1440) as u32;
+      Self {
+         year: year.max(0) as u32,
+         month,
+         day,
+         hour: minutes / 60,
+         minute: minutes % 60,
+         offset: Some(target),
+         ..self
+      }
+   }
+}
+
+/// # 显示拍摄时间的时区
+///
+/// 配置中写为`local`时按相机记录的时间显示（拍摄地的当地时间），`utc`时换算为UTC，
+/// `+08:00`、`-0530`等换算为固定的时差
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TimeZone {
+   /// 相机记录的当地时间
+   #[default]
+   Local,
+   /// 协调世界时
+   Utc,
+   /// 固定的时差（分钟，东为正）
+   Fixed(i32),
+}
+
+impl TimeZone {
+   /// 解析时区，无效时返回错误
+   pub fn parse(zone: &str) -> Result<Self> {
+      zone.parse()
+   }
+
+   /// 换算到的时差（分钟），按相机记录的时间显示时为`None`
+   fn offset(self) -> Option<i32> {
+      match self {
+         Self::Local => None,
+         Self::Utc => Some(0),
+         Self::Fixed(offset) => Some(offset),
+      }
+   }
+}
+
+impl FromStr for TimeZone {
+   type Err = LumixMarkError;
+
+   fn from_str(zone: &str) -> Result<Self> {
+      let zone = zone.trim();
+      if zone.eq_ignore_ascii_case("local") {
+         return Ok(Self::Local);
+      }
+      if zone.eq_ignore_ascii_case("utc") || zone.eq_ignore_ascii_case("z") {
+         return Ok(Self::Utc);
+      }
+      parse_offset(zone).map(Self::Fixed).ok_or_else(|| {
+         LumixMarkError::InvalidConfig(format!(
+            "无效的时区：{zone}，应为local、utc或+08:00形式的时差"
+         ))
+      })
+   }
+}
+
+impl Display for TimeZone {
+   fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+      match self {
+         Self::Local => f.write_str("local"),
+         Self::Utc => f.write_str("utc"),
+         Self::Fixed(offset) => f.write_str(&format_offset(*offset)),
+      }
+   }
+}
+
+impl Serialize for TimeZone {
+   fn serialize<S: Serializer>(
+      &self,
+      serializer: S,
+   ) -> std::result::Result<S::Ok, S::Error> {
+      serializer.collect_str(self)
+   }
+}
+
+impl<'de> Deserialize<'de> for TimeZone {
+   fn deserialize<D: Deserializer<'de>>(
+      deserializer: D,
+   ) -> std::result::Result<Self, D::Error> {
+      let zone = String::deserialize(deserializer)?;
+      zone.parse().map_err(D::Error::custom)
+   }
+}
+
+/// 解析`+08:00`、`-0530`或`+8`形式的时差，返回分钟数，`Z`为0
+pub(crate) fn parse_offset(text: &str) -> Option<i32> {
+   let text = text.trim();
+   if text == "Z" {
+      return Some(0);
+   }
+   let sign = match text.chars().next()? {
+      '+' => 1,
+      '-' => -1,
+      _ => return None,
+   };
+   let digits = &text[1..];
+   // 按字节拆分`HHMM`，非ASCII的输入不是有效的时差
+   if !digits.is_ascii() {
+      return None;
+   }
+   let (hours, minutes) = match digits.split_once(':') {
+      Some(parts) => parts,
+      None if digits.len() == 4 => digits.split_at(2),
+      None => (digits, "0"),
+   };
+   let number = |value: &str| {
+      (!value.is_empty()
+         && value.len() <= 2
+         && value.bytes().all(|b| b.is_ascii_digit()))
+      .then(|| value.parse::<i32>().ok())
+      .flatten()
+   };
+   let (hours, minutes) = (number(hours)?, number(minutes)?);
+   (hours <= 14 && minutes < 60).then_some(sign * (hours * 60 + minutes))
+}
+
+/// 将时差格式化为`+08:00`
+fn format_offset(offset: i32) -> String {
+   let sign = if offset < 0 { '-' } else { '+' };
+   let offset = offset.unsigned_abs();
+   format!("{sign}{:02}:{:02}", offset / 60, offset % 60)
+}
+
+/// 公历日期距1970-01-01的天数
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+   let year = if month <= 2 { year - 1 } else { year };
+   let era = year.div_euclid(400);
+   let year_of_era = year - era * 400;
+   let month = month as i64;
+   let day_of_year =
+      (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + day as i64 - 1;
+   let day_of_era =
+      year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+   era * 146097 + day_of_era - 719468
+}
+
+/// 距1970-01-01的天数对应的公历日期
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+   let days = days + 719468;
+   let era = days.div_euclid(146097);
+   let day_of_era = days - era * 146097;
+   let year_of_era =
+      (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+   let day_of_year =
+      day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+   let month_index = (5 * day_of_year + 2) / 153;
+   let day = (day_of_year - (153 * month_index + 2) / 5 + 1) as u32;
+   let month = if month_index < 10 {
+      month_index + 3
+   } else {
+      month_index - 9
+   } as u32;
+   let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+   (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+   use super::parse_offset;
+
+   #[test]
+   fn offset() {
+      for (text, expected) in [
+         ("Z", Some(0)),
+         ("+08:00", Some(480)),
+         ("-0530", Some(-330)),
+         ("+8", Some(480)),
+         ("+14:00", Some(840)),
+         ("+15", None),
+         ("+08:60", None),
+         ("08:00", None),
+         ("+", None),
+         ("+1€", None),
+         ("+€1", None),
+         ("-0€", None),
+         ("+€", None),
+      ] {
+         assert_eq!(parse_offset(text), expected, "{text:?}");
+      }
+   }
+}
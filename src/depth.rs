@@ -0,0 +1,63 @@
+use image::{DynamicImage, ImageBuffer, Rgb, RgbImage};
+
+/// 每通道16位的RGB图像
+pub(crate) type Rgb16Image = ImageBuffer<Rgb<u16>, Vec<u16>>;
+
+/// # 高位深原图的照片像素
+///
+/// 水印始终在8位画布上绘制，16位TIFF/PNG原图的像素单独保留，
+/// 编码为PNG/TIFF时再与画布合并，避免天空等平滑渐变出现色带
+#[derive(Clone, Debug)]
+pub(crate) struct DeepPhoto {
+   pixels: Rgb16Image,
+   /// 照片在画布中的左上角坐标
+   offset: (u32, u32),
+}
+
+impl DeepPhoto {
+   /// 原图每通道超过8位时保留原始像素，否则返回`None`
+   pub(crate) fn from_image(image: &DynamicImage) -> Option<Self> {
+      let color = image.color();
+      let bits = color.bits_per_pixel() / color.channel_count() as u16;
+      (bits > 8).then(|| Self {
+         pixels: image.to_rgb16(),
+         offset: (0, 0),
+      })
+   }
+
+   /// 设置照片在画布中的位置
+   pub(crate) fn place(&mut self, x: u32, y: u32) {
+      self.offset = (x, y);
+   }
+
+   /// 量化为绘制水印使用的8位图像
+   pub(crate) fn quantize(&self) -> RgbImage {
+      let (width, height) = self.pixels.dimensions();
+      RgbImage::from_fn(width, height, |x, y| {
+         Rgb(self.pixels.get_pixel(x, y).0.map(quantize))
+      })
+   }
+
+   /// # 合并为16位画布
+   ///
+   /// 照片区域中与量化结果相同（未被水印覆盖）的像素使用原始的16位值，其余像素由8位值扩展
+   pub(crate) fn merge(&self, canvas: &RgbImage) -> Rgb16Image {
+      let (left, top) = self.offset;
+      let (width, height) = self.pixels.dimensions();
+      Rgb16Image::from_fn(canvas.width(), canvas.height(), |x, y| {
+         let Rgb(drawn) = *canvas.get_pixel(x, y);
+         let deep = x
+            .checked_sub(left)
+            .zip(y.checked_sub(top))
+            .filter(|&(px, py)| px < width && py < height)
+            .map(|(px, py)| self.pixels.get_pixel(px, py).0)
+            .filter(|deep| deep.map(quantize) == drawn);
+         Rgb(deep.unwrap_or(drawn.map(|value| value as u16 * 257)))
+      })
+   }
+}
+
+/// 16位通道值四舍五入为8位
+fn quantize(value: u16) -> u8 {
+   ((value as u32 + 128) / 257) as u8
+}
@@ -0,0 +1,126 @@
+use crate::Color;
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+/// 圆点直径相对分隔线宽度的倍数
+const DOT_SCALE: f32 = 4.0;
+
+/// Logo与文字之间的分隔线样式
+#[derive(
+   Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, ValueEnum,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum Divider {
+   /// 细线
+   #[default]
+   Line,
+   /// 两端为半圆的竖条
+   Bar,
+   /// 圆点，直径为分隔线宽度的4倍
+   Dot,
+   /// 不绘制分隔线
+   None,
+}
+
+impl Divider {
+   /// # 分隔线实际占用的宽度和高度
+   ///
+   /// # 参数
+   /// * `width` - 样式中分隔线的宽度
+   /// * `height` - 样式中分隔线的高度
+   pub fn size(self, width: f32, height: f32) -> (f32, f32) {
+      match self {
+         Divider::Line | Divider::Bar => (width, height),
+         Divider::Dot => (width * DOT_SCALE, width * DOT_SCALE),
+         Divider::None => (0.0, 0.0),
+      }
+   }
+
+   /// # 像素被分隔线覆盖的比例（0 - 1），用于抗锯齿
+   ///
+   /// # 参数
+   /// * `(dx, dy)` - 像素中心相对分隔线中心的偏移
+   /// * `(width, height)` - [`Divider::size`]计算的宽度和高度
+   pub(crate) fn coverage(
+      self,
+      (dx, dy): (f32, f32),
+      (width, height): (f32, f32),
+   ) -> f32 {
+      let (half_width, half_height) = (width / 2.0, height / 2.0);
+      match self {
+         Divider::None => 0.0,
+         // 矩形按像素与矩形重叠的面积计算
+         Divider::Line => overlap(dx, half_width) * overlap(dy, half_height),
+         // 圆角按像素中心到边缘的有向距离计算
+         Divider::Bar | Divider::Dot => {
+            let radius = half_width.min(half_height);
+            let qx = dx.abs() - (half_width - radius);
+            let qy = dy.abs() - (half_height - radius);
+            let distance =
+               qx.max(0.0).hypot(qy.max(0.0)) + qx.max(qy).min(0.0) - radius;
+            (0.5 - distance).clamp(0.0, 1.0)
+         }
+      }
+   }
+}
+
+/// 分隔线所在的位置
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DividerSlot {
+   /// 机型和附加信息的右侧，叠加水印中没有机型，不绘制
+   AfterModel,
+   /// Logo的左侧
+   BeforeLogo,
+   /// Logo与Exif信息（居中布局中为机型）之间
+   #[default]
+   AfterLogo,
+}
+
+/// # 单条分隔线的位置和样式
+///
+/// 缺省的样式、颜色和尺寸使用水印样式中的`divider`、`divider_color`、`divider_width_ratio`和`divider_height_ratio`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DividerSpec {
+   /// 所在的位置
+   pub slot: DividerSlot,
+   /// 分隔线样式
+   pub style: Option<Divider>,
+   /// 分隔线颜色
+   pub color: Option<Color>,
+   /// 分隔线宽度，相对水印高度的比例
+   pub width_ratio: Option<f32>,
+   /// 分隔线高度，相对水印高度的比例
+   pub height_ratio: Option<f32>,
+}
+
+/// 按水印高度计算出尺寸的分隔线
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct PlacedDivider {
+   pub(crate) style: Divider,
+   /// [`Divider::size`]计算的宽度和高度
+   pub(crate) size: (f32, f32),
+   pub(crate) color: Color,
+}
+
+impl PlacedDivider {
+   pub(crate) fn new(style: Divider, size: (f32, f32), color: Color) -> Self {
+      Self { style, size, color }
+   }
+
+   /// 占用的整像素宽度
+   pub(crate) fn width(&self) -> u32 {
+      self.size.0 as u32
+   }
+}
+
+/// 同一位置的分隔线依次排列占用的宽度，每条分隔线的一侧留出一个间距
+pub(crate) fn run_width(dividers: &[PlacedDivider], gap: u32) -> u32 {
+   dividers.iter().map(|divider| divider.width() + gap).sum()
+}
+
+/// 中心偏移为`offset`、宽度为1的像素与`[-half, half]`重叠的长度
+fn overlap(offset: f32, half: f32) -> f32 {
+   ((offset + 0.5).min(half) - (offset - 0.5).max(-half)).clamp(0.0, 1.0)
+}
@@ -0,0 +1,425 @@
+use crate::font::Run;
+use crate::icon::ExifIcon;
+use crate::text::{Flow, TextRun};
+use crate::{
+   Color, Divider, ElementKind, LogoFilter, LumixMarkError, MarkElement, Result,
+};
+use image::imageops::{self, resize, rotate90, rotate270};
+use image::{GrayImage, Luma, Pixel, Rgb, RgbImage, Rgba, RgbaImage, load_from_memory};
+use imageproc::drawing::{Canvas, draw_text_mut, text_size};
+
+/// # 水印中的Logo
+///
+/// 位图Logo绘制时按目标尺寸缩放；SVG Logo（需要`svg`特性）按目标尺寸直接栅格化，不会因缩放而模糊。
+/// 像素均为预乘Alpha的RGBA，缩放时透明像素的颜色不会渗入边缘
+pub(crate) enum Logo {
+   /// 位图
+   Raster(RgbaImage),
+   /// SVG矢量图
+   #[cfg(feature = "svg")]
+   Svg(Box<resvg::usvg::Tree>),
+}
+
+impl Logo {
+   /// 解码Logo数据，按内容识别SVG和位图格式
+   pub(crate) fn decode(bytes: &[u8]) -> Result<Self> {
+      if !is_svg(bytes) {
+         let mut image = load_from_memory(bytes)?.to_rgba8();
+         premultiply(&mut image);
+         return Ok(Logo::Raster(image));
+      }
+      #[cfg(feature = "svg")]
+      {
+         let tree = resvg::usvg::Tree::from_data(bytes, &Default::default()).map_err(
+            |err| LumixMarkError::InvalidConfig(format!("SVG解析失败：{err}")),
+         )?;
+         Ok(Logo::Svg(Box::new(tree)))
+      }
+      #[cfg(not(feature = "svg"))]
+      Err(LumixMarkError::InvalidConfig(
+         "SVG格式的Logo需要通过svg特性编译".to_string(),
+      ))
+   }
+
+   /// Logo的原始尺寸
+   pub(crate) fn dimensions(&self) -> (u32, u32) {
+      match self {
+         Logo::Raster(image) => image.dimensions(),
+         #[cfg(feature = "svg")]
+         Logo::Svg(tree) => {
+            let size = tree.size().to_int_size();
+            (size.width(), size.height())
+         }
+      }
+   }
+
+   /// # 按目标尺寸绘制Logo，返回预乘Alpha的图片
+   ///
+   /// # 参数
+   /// * `(width, height)` - 目标尺寸
+   /// * `filter` - 位图缩放使用的重采样算法
+   pub(crate) fn render(
+      &self,
+      width: u32,
+      height: u32,
+      filter: LogoFilter,
+   ) -> RgbaImage {
+      match self {
+         Logo::Raster(image) => {
+            let mut image = resize(image, width, height, filter.into());
+            // Lanczos3等算法的振铃可能使颜色超过Alpha，限制在合法范围内
+            for Rgba([r, g, b, a]) in image.pixels_mut() {
+               for channel in [r, g, b] {
+                  *channel = (*channel).min(*a);
+               }
+            }
+            image
+         }
+         #[cfg(feature = "svg")]
+         Logo::Svg(tree) => {
+            use resvg::tiny_skia::{Pixmap, Transform};
+            let Some(mut pixmap) = Pixmap::new(width.max(1), height.max(1)) else {
+               return RgbaImage::new(width, height);
+            };
+            let size = tree.size();
+            let transform = Transform::from_scale(
+               width as f32 / size.width(),
+               height as f32 / size.height(),
+            );
+            resvg::render(tree, transform, &mut pixmap.as_mut());
+            // tiny-skia的像素同样为预乘Alpha
+            RgbaImage::from_fn(width, height, |x, y| {
+               pixmap.pixel(x, y).map_or(Rgba([0, 0, 0, 0]), |pixel| {
+                  Rgba([pixel.red(), pixel.green(), pixel.blue(), pixel.alpha()])
+               })
+            })
+         }
+      }
+   }
+}
+
+/// 将颜色乘以Alpha
+fn premultiply(image: &mut RgbaImage) {
+   for Rgba([r, g, b, a]) in image.pixels_mut() {
+      for channel in [r, g, b] {
+         *channel = (*channel as u16 * *a as u16 / 255) as u8;
+      }
+   }
+}
+
+/// 将预乘Alpha的颜色还原
+pub(crate) fn unpremultiply(image: &mut RgbaImage) {
+   for Rgba([r, g, b, a]) in image.pixels_mut() {
+      if *a > 0 {
+         for channel in [r, g, b] {
+            *channel = (*channel as u32 * 255 / *a as u32).min(255) as u8;
+         }
+      }
+   }
+}
+
+/// 文件开头为XML且包含`<svg`标签时视为SVG
+fn is_svg(bytes: &[u8]) -> bool {
+   let head = String::from_utf8_lossy(&bytes[..bytes.len().min(1024)]);
+   let head = head.trim_start_matches('\u{feff}').trim_start();
+   head.starts_with('<') && head.contains("<svg")
+}
+
+/// # 按不透明度混合绘制的画布
+///
+/// 绘制到画布上的像素与原像素按不透明度混合，文字、分隔线和Logo都通过它绘制；
+/// 每个元素的不透明度为整体不透明度与元素自身不透明度的乘积
+pub(crate) struct Blend<'a> {
+   image: &'a mut RgbImage,
+   /// 整体不透明度
+   base_opacity: f32,
+   /// 当前绘制元素的不透明度
+   opacity: f32,
+   /// 已绘制元素的位置
+   elements: Vec<MarkElement>,
+}
+
+impl<'a> Blend<'a> {
+   pub(crate) fn new(image: &'a mut RgbImage, opacity: f32) -> Self {
+      let opacity = opacity.clamp(0.0, 1.0);
+      Self {
+         image,
+         base_opacity: opacity,
+         opacity,
+         elements: Vec::new(),
+      }
+   }
+
+   /// 记录绘制的元素，超出画布左上角的部分会被裁剪
+   pub(crate) fn record(
+      &mut self,
+      kind: ElementKind,
+      x: i64,
+      y: i64,
+      width: u32,
+      height: u32,
+   ) {
+      tracing::trace!(?kind, x, y, width, height, "绘制元素");
+      self.elements.push(MarkElement {
+         kind,
+         x: x.max(0) as u32,
+         y: y.max(0) as u32,
+         width: (width as i64 + x.min(0)).max(0) as u32,
+         height: (height as i64 + y.min(0)).max(0) as u32,
+      });
+   }
+
+   /// 已绘制元素在画布中的位置
+   pub(crate) fn into_elements(self) -> Vec<MarkElement> {
+      self.elements
+   }
+
+   fn set_opacity(&mut self, opacity: f32) {
+      self.opacity = (self.base_opacity * opacity).clamp(0.0, 1.0);
+   }
+
+   /// 计算区域内背景的平均颜色，超出画布的部分会被忽略
+   fn average(&self, x: i64, y: i64, width: u32, height: u32) -> Rgb<u8> {
+      let (canvas_width, canvas_height) = self.image.dimensions();
+      let x_range =
+         x.max(0) as u32..(x + width as i64).clamp(0, canvas_width as i64) as u32;
+      let y_range =
+         y.max(0) as u32..(y + height as i64).clamp(0, canvas_height as i64) as u32;
+      let mut sum = [0u64; 3];
+      let mut count = 0u64;
+      for py in y_range {
+         for px in x_range.clone() {
+            let Rgb(pixel) = self.image.get_pixel(px, py);
+            for (total, value) in sum.iter_mut().zip(pixel) {
+               *total += *value as u64;
+            }
+            count += 1;
+         }
+      }
+      if count == 0 {
+         return Rgb([255, 255, 255]);
+      }
+      Rgb(sum.map(|total| (total / count) as u8))
+   }
+
+   /// 绘制文字，`(x, y)`为文字左上角；颜色为`Auto`时按文字所在区域的背景选择
+   pub(crate) fn draw_text(&mut self, color: Color, x: i32, y: i32, run: &TextRun) {
+      let (width, height) = run.size();
+      self.record(
+         ElementKind::Text(ExifIcon::plain_text(&run.text)),
+         x as i64,
+         y as i64,
+         width,
+         height,
+      );
+      let color = color.resolve(self.average(x as i64, y as i64, width, height));
+      let opacity = color.opacity();
+      self.set_opacity(opacity);
+      let (fonts, size) = (run.fonts, run.size);
+      // 图标的底边与文字的基线对齐
+      let side = ExifIcon::side(size);
+      let icon_y = (fonts.ascent(size) - side as f32).round() as i32;
+      let clockwise = match run.flow {
+         Flow::Ltr | Flow::Rtl => {
+            // 不同字体的片段依次绘制，按主字体的基线对齐
+            let mut x = x;
+            for text_run in fonts.runs(&run.text) {
+               match text_run {
+                  Run::Text(font, text) => {
+                     let offset = fonts.baseline_offset(font, size).round() as i32;
+                     draw_text_mut(self, color.into(), x, y + offset, size, font, text);
+                     x += text_size(size, font, text).0 as i32;
+                  }
+                  Run::Icon(icon) => {
+                     let color = icon.color().unwrap_or(color);
+                     self.draw_mask(&icon.mask(side), x, y + icon_y, color, opacity);
+                     x += ExifIcon::advance(size) as i32;
+                  }
+               }
+            }
+            return;
+         }
+         Flow::Vertical { clockwise } => clockwise,
+      };
+      // 竖排时每个字符在边长为文字大小的方格中水平居中，旋转后依次排列
+      let cell = size.ceil() as u32;
+      let mut chars: Vec<char> = run.text.chars().collect();
+      if clockwise {
+         chars.reverse();
+      }
+      let mut offset = 0.0;
+      for c in chars {
+         let advance = run.advance(c);
+         let text = c.to_string();
+         let mut mask = GrayImage::new(cell, cell);
+         let mut char_color = color;
+         match fonts.runs(&text).first() {
+            _ if c.is_whitespace() => {}
+            Some(&Run::Text(font, glyph)) => {
+               let glyph_x = (cell as i32 - text_size(size, font, glyph).0 as i32) / 2;
+               let glyph_y = fonts.baseline_offset(font, size).round() as i32;
+               draw_text_mut(
+                  &mut mask,
+                  Luma([255]),
+                  glyph_x,
+                  glyph_y,
+                  size,
+                  font,
+                  glyph,
+               );
+            }
+            Some(&Run::Icon(icon)) => {
+               let icon_x = (cell - side.min(cell)) as i64 / 2;
+               imageops::replace(&mut mask, &icon.mask(side), icon_x, icon_y as i64);
+               char_color = icon.color().unwrap_or(color);
+            }
+            None => {}
+         }
+         let mask = match clockwise {
+            true => rotate90(&mask),
+            false => rotate270(&mask),
+         };
+         self.draw_mask(&mask, x + offset as i32, y, char_color, opacity);
+         offset += advance;
+      }
+   }
+
+   /// 以灰度为覆盖率用`color`绘制，`(x, y)`为左上角，超出画布的部分会被裁剪
+   fn draw_mask(
+      &mut self,
+      mask: &GrayImage,
+      x: i32,
+      y: i32,
+      color: Color,
+      opacity: f32,
+   ) {
+      for (dx, dy, Luma([coverage])) in mask.enumerate_pixels() {
+         let (px, py) = (x + dx as i32, y + dy as i32);
+         if *coverage > 0 && px >= 0 && py >= 0 && self.in_bounds(px, py) {
+            self.set_opacity(opacity * *coverage as f32 / 255.0);
+            self.draw_pixel(px as u32, py as u32, color.into());
+         }
+      }
+      self.set_opacity(opacity);
+   }
+
+   fn in_bounds(&self, x: i32, y: i32) -> bool {
+      let (width, height) = self.image.dimensions();
+      (x as u32) < width && (y as u32) < height
+   }
+
+   /// # 绘制抗锯齿的分隔线
+   ///
+   /// # 参数
+   /// * `divider` - 分隔线样式
+   /// * `(x, y)` - 分隔线中心，可以位于像素之间
+   /// * `size` - [`Divider::size`]计算的宽度和高度
+   /// * `color` - 分隔线颜色，为`Auto`时按所在区域的背景选择
+   pub(crate) fn draw_divider(
+      &mut self,
+      divider: Divider,
+      (x, y): (f32, f32),
+      size: (f32, f32),
+      color: Color,
+   ) {
+      let (width, height) = size;
+      if divider == Divider::None || width <= 0.0 || height <= 0.0 {
+         return;
+      }
+      let left = (x - width / 2.0).floor() as i64;
+      let top = (y - height / 2.0).floor() as i64;
+      let right = (x + width / 2.0).ceil() as i64;
+      let bottom = (y + height / 2.0).ceil() as i64;
+      let (box_width, box_height) = ((right - left) as u32, (bottom - top) as u32);
+      self.record(ElementKind::Divider, left, top, box_width, box_height);
+      let background = self.average(left, top, box_width, box_height);
+      let color = color.resolve(background);
+      let opacity = color.opacity();
+      let (canvas_width, canvas_height) = self.image.dimensions();
+      for py in top.max(0)..bottom.min(canvas_height as i64) {
+         for px in left.max(0)..right.min(canvas_width as i64) {
+            let offset = (px as f32 + 0.5 - x, py as f32 + 0.5 - y);
+            let coverage = divider.coverage(offset, size);
+            if coverage > 0.0 {
+               self.set_opacity(opacity * coverage);
+               self.draw_pixel(px as u32, py as u32, color.into());
+            }
+         }
+      }
+   }
+
+   /// # 按不透明度将预乘Alpha的图片绘制到画布的指定位置
+   ///
+   /// 超出画布的部分会被裁剪，透明区域露出原画布
+   pub(crate) fn draw_image(
+      &mut self,
+      image: &RgbaImage,
+      x: u32,
+      y: u32,
+      opacity: f32,
+   ) {
+      self.set_opacity(opacity);
+      let opacity = self.opacity;
+      let (width, height) = self.image.dimensions();
+      for (dx, dy, Rgba([r, g, b, a])) in image.enumerate_pixels() {
+         let (px, py) = (x + dx, y + dy);
+         if px < width && py < height && *a > 0 {
+            let keep = 1.0 - *a as f32 / 255.0 * opacity;
+            let pixel = self.image.get_pixel_mut(px, py);
+            *pixel = Rgb([*r, *g, *b]).map2(pixel, |new, old| {
+               (new as f32 * opacity + old as f32 * keep).round() as u8
+            });
+         }
+      }
+   }
+
+   /// # 将预乘Alpha的图片作为剪影绘制
+   ///
+   /// 带透明区域的图片以Alpha作为不透明度，完全不透明的图片以像素与白色背景的差异作为不透明度，
+   /// 用`color`绘制，透明区域和白色背景都不会被绘制
+   pub(crate) fn draw_silhouette(
+      &mut self,
+      image: &RgbaImage,
+      x: u32,
+      y: u32,
+      color: Color,
+      opacity: f32,
+   ) {
+      let (width, height) = self.image.dimensions();
+      let background = self.average(x as i64, y as i64, image.width(), image.height());
+      let color = color.resolve(background);
+      let opacity = opacity * color.opacity();
+      let opaque = image.pixels().all(|pixel| pixel[3] == 255);
+      for (dx, dy, Rgba([r, g, b, a])) in image.enumerate_pixels() {
+         let (px, py) = (x + dx, y + dy);
+         if px < width && py < height {
+            let coverage = match opaque {
+               true => 255 - *r.min(g).min(b),
+               false => *a,
+            };
+            self.set_opacity(opacity * coverage as f32 / 255.0);
+            self.draw_pixel(px, py, color.into());
+         }
+      }
+   }
+}
+
+impl Canvas for Blend<'_> {
+   type Pixel = Rgb<u8>;
+
+   fn dimensions(&self) -> (u32, u32) {
+      self.image.dimensions()
+   }
+
+   fn get_pixel(&self, x: u32, y: u32) -> Self::Pixel {
+      *self.image.get_pixel(x, y)
+   }
+
+   fn draw_pixel(&mut self, x: u32, y: u32, color: Self::Pixel) {
+      let opacity = self.opacity;
+      let pixel = self.image.get_pixel_mut(x, y);
+      *pixel = pixel.map2(&color, |old, new| {
+         (old as f32 + (new as f32 - old as f32) * opacity).round() as u8
+      });
+   }
+}
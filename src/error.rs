@@ -0,0 +1,150 @@
+use crate::MultiFrame;
+use image::ImageError;
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+use std::io;
+
+/// 水印处理过程中可能出现的错误
+#[derive(Debug)]
+pub enum LumixMarkError {
+   /// 文件读写失败
+   Io(io::Error),
+   /// 图片解码失败（文件损坏或格式不支持）
+   Decode(Box<dyn Error + Send + Sync>),
+   /// EXIF解析失败
+   ExifParse(rexif::ExifError),
+   /// 字体文件无效
+   FontLoad(ab_glyph::InvalidFont),
+   /// 字体中缺少要绘制的字符
+   MissingGlyphs(String),
+   /// 图片编码失败
+   Encode(Box<dyn Error + Send + Sync>),
+   /// 配置或参数无效
+   InvalidConfig(String),
+   /// 水印内容超出可用宽度
+   LayoutOverflow { required: u32, available: u32 },
+   /// 输入为多帧图片，且处理方式不是只处理第一帧
+   MultiFrame(MultiFrame),
+   /// 输入图片已带有lumix_mark水印，且处理方式为跳过
+   AlreadyMarked,
+}
+
+impl Display for LumixMarkError {
+   fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+      match self {
+         LumixMarkError::Io(err) => write!(f, "文件读写失败：{err}"),
+         LumixMarkError::Decode(err) => {
+            write!(f, "图片解码失败，请确认文件完整且格式受支持：{err}")
+         }
+         LumixMarkError::ExifParse(err) => write!(f, "EXIF解析失败：{err}"),
+         LumixMarkError::FontLoad(err) => {
+            write!(f, "字体加载失败，请确认字体文件为有效的TTF/OTF：{err}")
+         }
+         LumixMarkError::MissingGlyphs(chars) => {
+            write!(
+               f,
+               "字体中缺少字符：{chars}，请使用--font或--fallback-font指定包含这些字符的字体"
+            )
+         }
+         LumixMarkError::Encode(err) => write!(f, "图片编码失败：{err}"),
+         LumixMarkError::InvalidConfig(msg) => write!(f, "配置无效：{msg}"),
+         LumixMarkError::LayoutOverflow {
+            required,
+            available,
+         } => write!(
+            f,
+            "水印内容超出宽度：需要{required}像素，可用{available}像素，请减小文字或增大水印比例"
+         ),
+         LumixMarkError::MultiFrame(kind) => {
+            write!(f, "输入为{kind}，可以使用--multi-frame first只处理第一帧")
+         }
+         LumixMarkError::AlreadyMarked => {
+            write!(f, "图片已带有水印，可以使用--marked warn再次添加")
+         }
+      }
+   }
+}
+
+impl Error for LumixMarkError {
+   fn source(&self) -> Option<&(dyn Error + 'static)> {
+      match self {
+         LumixMarkError::Io(err) => Some(err),
+         LumixMarkError::Decode(err) | LumixMarkError::Encode(err) => {
+            Some(err.as_ref())
+         }
+         LumixMarkError::ExifParse(err) => Some(err),
+         LumixMarkError::FontLoad(err) => Some(err),
+         LumixMarkError::MissingGlyphs(_)
+         | LumixMarkError::InvalidConfig(_)
+         | LumixMarkError::LayoutOverflow { .. }
+         | LumixMarkError::MultiFrame(_)
+         | LumixMarkError::AlreadyMarked => None,
+      }
+   }
+}
+
+impl From<io::Error> for LumixMarkError {
+   fn from(err: io::Error) -> Self {
+      LumixMarkError::Io(err)
+   }
+}
+
+impl From<ImageError> for LumixMarkError {
+   fn from(err: ImageError) -> Self {
+      match err {
+         ImageError::IoError(err) => LumixMarkError::Io(err),
+         ImageError::Encoding(_) => LumixMarkError::Encode(Box::new(err)),
+         _ => LumixMarkError::Decode(Box::new(err)),
+      }
+   }
+}
+
+impl From<jpeg_encoder::EncodingError> for LumixMarkError {
+   fn from(err: jpeg_encoder::EncodingError) -> Self {
+      match err {
+         jpeg_encoder::EncodingError::IoError(err) => LumixMarkError::Io(err),
+         _ => LumixMarkError::Encode(Box::new(err)),
+      }
+   }
+}
+
+impl From<rexif::ExifError> for LumixMarkError {
+   fn from(err: rexif::ExifError) -> Self {
+      LumixMarkError::ExifParse(err)
+   }
+}
+
+impl From<ab_glyph::InvalidFont> for LumixMarkError {
+   fn from(err: ab_glyph::InvalidFont) -> Self {
+      LumixMarkError::FontLoad(err)
+   }
+}
+
+impl From<toml::de::Error> for LumixMarkError {
+   fn from(err: toml::de::Error) -> Self {
+      LumixMarkError::InvalidConfig(err.to_string())
+   }
+}
+
+impl From<toml::ser::Error> for LumixMarkError {
+   fn from(err: toml::ser::Error) -> Self {
+      LumixMarkError::InvalidConfig(err.to_string())
+   }
+}
+
+#[cfg(feature = "cli")]
+impl From<notify_debouncer_mini::notify::Error> for LumixMarkError {
+   fn from(err: notify_debouncer_mini::notify::Error) -> Self {
+      match err.kind {
+         notify_debouncer_mini::notify::ErrorKind::Io(err) => LumixMarkError::Io(err),
+         _ => LumixMarkError::Io(io::Error::other(err)),
+      }
+   }
+}
+
+#[cfg(feature = "heic")]
+impl From<libheif_rs::HeifError> for LumixMarkError {
+   fn from(err: libheif_rs::HeifError) -> Self {
+      LumixMarkError::Decode(Box::new(err))
+   }
+}
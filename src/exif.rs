@@ -0,0 +1,555 @@
+use crate::date::parse_offset;
+use crate::icon::ExifIcon;
+use crate::{
+   DEFAULT_EXIF_TEMPLATE, GpsPosition, Metadata, ModelNames, Result, ShootTime,
+   SidecarPolicy, Template, parse_exif_template, xmp_property,
+};
+use clap::Args;
+use image::metadata::Orientation as ImageOrientation;
+use rexif::ExifTag::*;
+use rexif::{ExifEntry, ExifError, ExifTag, TagValue, parse_buffer, parse_file};
+use serde::{Deserialize, Serialize};
+use std::fmt::{self, Display, Formatter};
+use std::fs::File;
+use std::io::{BufRead, BufReader, Cursor, Seek};
+use std::path::Path;
+
+/// rexif无法解析时使用的备用解析器
+mod fallback;
+/// 将EXIF中的原始数值转换为水印中显示的形式
+pub mod format;
+/// 读取厂商私有的MakerNote中的照片风格，格式参考ExifTool的标签文档
+mod maker_note;
+
+#[derive(Clone, Default, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Exif {
+   pub make: String,
+   pub model: String,
+   pub model_title: String,
+   pub shoot_time: String,
+   /// 拍摄时间的亚秒，取自`SubSecTimeOriginal`，如`123`
+   pub sub_second: String,
+   /// 拍摄时间相对UTC的时差（分钟），取自`OffsetTimeOriginal`
+   pub time_offset: Option<i32>,
+   pub exposure_time: String,
+   pub aperture: String,
+   pub iso: String,
+   pub focal_length: String,
+   /// 曝光模式，如`A`表示光圈优先
+   pub exposure_program: String,
+   /// 测光模式，如`MULTI`表示多区测光
+   pub metering_mode: String,
+   /// 白平衡模式，`AWB`或`MWB`
+   pub white_balance: String,
+   /// 曝光补偿，如`+0.7`
+   pub exposure_bias: String,
+   /// 曝光补偿的EV值，用于`{ev}`占位符
+   pub exposure_compensation: Option<f64>,
+   /// 闪光灯是否闪光，`ON`或`OFF`
+   pub flash: String,
+   /// 照片风格或胶片模拟，取自富士和松下的MakerNote，如`Classic Chrome`
+   pub profile: String,
+   /// EXIF中的方向代码（1 - 8），缺失时为0
+   pub orientation: u16,
+   /// 作者
+   pub artist: String,
+   /// 版权信息
+   pub copyright: String,
+   /// 照片标题，取自XMP中的`dc:title`
+   pub headline: String,
+   /// 拍摄地点的GPS位置
+   pub gps: Option<GpsPosition>,
+   /// XMP中的星级（0 - 5，-1为拒绝），取自嵌入的XMP或XMP附属文件
+   pub rating: Option<i8>,
+   /// XMP中的颜色标签，如`Red`
+   pub label: String,
+}
+
+impl Exif {
+   /// 可以在模板中使用的EXIF占位符
+   pub const FIELDS: &'static [&'static str] = &[
+      "make",
+      "model",
+      "title",
+      "date",
+      "time",
+      "focal",
+      "aperture",
+      "shutter",
+      "iso",
+      "program",
+      "metering",
+      "wb",
+      "bias",
+      "ev",
+      "flash",
+      "profile",
+      "artist",
+      "copyright",
+      "headline",
+      "rating",
+      "label",
+   ];
+
+   /// # 获取模板占位符对应的值
+   ///
+   /// `date`和`time`取自拍摄时间，格式为`2024-05-01`和`17:30:12`；未知占位符返回`None`
+   pub fn field(&self, name: &str) -> Option<String> {
+      let (date, time) = self
+         .shoot_time
+         .trim()
+         .split_once(' ')
+         .unwrap_or((self.shoot_time.trim(), ""));
+      Some(match name {
+         "make" => self.make.clone(),
+         "model" => self.model.clone(),
+         "title" => self.model_title.clone(),
+         "date" => date.replace(':', "-"),
+         "time" => time.to_string(),
+         "focal" => self.focal_length.clone(),
+         "aperture" => self.aperture.clone(),
+         "shutter" => self.exposure_time.clone(),
+         "iso" => self.iso.clone(),
+         "program" => self.exposure_program.clone(),
+         "metering" => self.metering_mode.clone(),
+         "wb" => self.white_balance.clone(),
+         "bias" => self.exposure_bias.clone(),
+         "ev" => self
+            .exposure_compensation
+            .map_or(String::new(), format::exposure_value),
+         "flash" => self.flash.clone(),
+         "profile" => self.profile.clone(),
+         "artist" => self.artist.clone(),
+         "copyright" => self.copyright.clone(),
+         "headline" => self.headline.clone(),
+         "rating" => self
+            .rating
+            .filter(|&rating| rating > 0)
+            .map(|rating| rating.to_string())
+            .unwrap_or_default(),
+         "label" => self.label.clone(),
+         _ => return None,
+      })
+   }
+
+   /// # 水印中的版权信息
+   ///
+   /// 优先使用版权信息，没有时使用作者；不包含`©`时在前面添加`©`，均为空时返回`None`
+   pub fn copyright_text(&self) -> Option<String> {
+      let text = [&self.copyright, &self.artist]
+         .into_iter()
+         .map(|value| value.trim())
+         .find(|value| !value.is_empty())?;
+      if text.contains('©') {
+         Some(text.to_string())
+      } else {
+         Some(format!("© {text}"))
+      }
+   }
+
+   /// 包含亚秒和时差的拍摄时间，拍摄时间缺失或无法解析时返回`None`
+   pub fn shoot_date_time(&self) -> Option<ShootTime> {
+      ShootTime::parse(&self.shoot_time).map(|time| ShootTime {
+         sub_second: self.sub_second.clone(),
+         offset: self.time_offset,
+         ..time
+      })
+   }
+
+   /// 将照片转为正向显示需要的旋转和翻转，方向代码缺失或无效时不做变换
+   pub fn image_orientation(&self) -> ImageOrientation {
+      u8::try_from(self.orientation)
+         .ok()
+         .and_then(ImageOrientation::from_exif)
+         .unwrap_or(ImageOrientation::NoTransforms)
+   }
+
+   /// 按模板生成水印中的Exif信息，省略缺少的值及其修饰文字，见[`Template::render_compact`]
+   pub fn render(&self, template: &Template) -> String {
+      template.render_compact(|field| self.field(field).unwrap_or_default())
+   }
+
+   /// 按模板生成绘制到水印中的Exif信息，`icons`为真时在焦距、光圈、快门速度和感光度的单词前添加图标
+   pub(crate) fn render_with_icons(&self, template: &Template, icons: bool) -> String {
+      template.render_compact_with(
+         |field| self.field(field).unwrap_or_default(),
+         |field| {
+            ExifIcon::for_field(field)
+               .filter(|_| icons)
+               .map(ExifIcon::char)
+         },
+      )
+   }
+
+   /// # 从图片文件路径解析EXIF信息
+   ///
+   /// rexif无法解析时改用kamadak-exif，两者都失败时返回rexif的错误；
+   /// 图片旁有XMP附属文件时按[`SidecarPolicy::Prefer`]合并
+   pub fn from_image<P: AsRef<Path>>(file_path: P) -> Result<Self> {
+      let mut exif = match parse_file(&file_path) {
+         Ok(parsed) => Self::from_entries(&parsed.entries),
+         Err(err) => File::open(&file_path)
+            .ok()
+            .and_then(|file| Self::fallback(&mut BufReader::new(file), &err))
+            .ok_or(err)?,
+      };
+      if let Some(sidecar) = Metadata::read_sidecar(file_path.as_ref()) {
+         exif.apply_sidecar(&sidecar, SidecarPolicy::default());
+      }
+      Ok(exif)
+   }
+
+   /// 从原图元数据中解析EXIF信息，嵌入的XMP补充其中缺少的值，没有EXIF段或解析失败时其余信息为空
+   pub fn from_metadata(metadata: &Metadata) -> Self {
+      let mut exif: Self = metadata
+         .exif
+         .as_deref()
+         .and_then(|tiff| Self::from_bytes(tiff).ok())
+         .unwrap_or_default();
+      if let Some(packet) = metadata.xmp() {
+         exif.apply_xmp(&packet, false);
+      }
+      exif
+   }
+
+   /// 按处理方式合并XMP附属文件中的信息，见[`Exif::apply_xmp`]
+   pub fn apply_sidecar(&mut self, sidecar: &str, policy: SidecarPolicy) {
+      match policy {
+         SidecarPolicy::Prefer => self.apply_xmp(sidecar, true),
+         SidecarPolicy::Fill => self.apply_xmp(sidecar, false),
+         SidecarPolicy::Ignore => {}
+      }
+   }
+
+   /// # 读取XMP数据包中Lightroom等软件写入的信息
+   ///
+   /// 包括星级（`xmp:Rating`）、颜色标签（`xmp:Label`）、标题（`dc:title`）、作者（`dc:creator`）、
+   /// 版权信息（`dc:rights`）和拍摄时间（`exif:DateTimeOriginal`或`photoshop:DateCreated`，
+   /// 在Lightroom中修改拍摄时间后会更新）。`overwrite`为假时只补充缺少的值，XMP中缺少的值保持不变
+   pub fn apply_xmp(&mut self, packet: &str, overwrite: bool) {
+      if let Some(rating) = xmp_property(packet, "xmp:Rating")
+         .and_then(|rating| rating.parse::<f32>().ok())
+         .filter(|_| overwrite || self.rating.is_none())
+      {
+         self.rating = Some(rating.round().clamp(-1.0, 5.0) as i8);
+      }
+      let xmp_date = xmp_property(packet, "exif:DateTimeOriginal")
+         .or_else(|| xmp_property(packet, "photoshop:DateCreated"));
+      let shoot_time = xmp_date.as_deref().and_then(format::xmp_date_time);
+      // 使用XMP中的拍摄时间时，亚秒和时差也取自XMP
+      if let Some(date) = xmp_date
+         .as_deref()
+         .filter(|_| shoot_time.is_some())
+         .filter(|_| overwrite || self.shoot_time.trim().is_empty())
+      {
+         (self.sub_second, self.time_offset) = format::xmp_sub_second_offset(date);
+      }
+      let values = [
+         (&mut self.label, xmp_property(packet, "xmp:Label")),
+         (&mut self.headline, xmp_property(packet, "dc:title")),
+         (&mut self.artist, xmp_property(packet, "dc:creator")),
+         (&mut self.copyright, xmp_property(packet, "dc:rights")),
+         (&mut self.shoot_time, shoot_time),
+      ];
+      for (field, value) in values {
+         match value.filter(|value| !value.is_empty()) {
+            Some(value) if overwrite || field.trim().is_empty() => *field = value,
+            _ => {}
+         }
+      }
+   }
+
+   /// 从TIFF格式的EXIF数据或图片文件的内容解析EXIF信息，rexif无法解析时改用kamadak-exif
+   pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+      match parse_buffer(bytes) {
+         Ok(parsed) => Ok(Self::from_entries(&parsed.entries)),
+         Err(err) => Self::fallback(&mut Cursor::new(bytes), &err).ok_or(err.into()),
+      }
+   }
+
+   /// 处理rexif解析出的所有EXIF条目
+   fn from_entries(entries: &[ExifEntry]) -> Self {
+      let mut exif = Exif::default();
+      for entry in entries {
+         Self::process_entry(&mut exif, entry.tag, &entry.value_more_readable, entry);
+      }
+      exif.gps = GpsPosition::from_entries(entries);
+      exif
+   }
+
+   /// rexif解析失败时使用的备用解析器，见[`fallback::read`]
+   fn fallback<R: BufRead + Seek>(reader: &mut R, err: &ExifError) -> Option<Self> {
+      let exif = fallback::read(reader)?;
+      tracing::debug!("rexif无法解析EXIF（{err}），已使用备用解析器");
+      Some(exif)
+   }
+
+   /// 处理单个EXIF条目，更新Exif结构体字段
+   fn process_entry(exif: &mut Exif, tag: ExifTag, value: &str, entry: &ExifEntry) {
+      match tag {
+         // 相机厂商
+         Make => {
+            exif.make = value.trim().to_string();
+         }
+         // 相机型号：修剪空白，按内置的映射表转换为显示名称
+         Model => {
+            exif.model = value.trim().to_string();
+            exif.model_title = ModelNames::default().title(&exif.model);
+         }
+         // 拍摄时间：直接使用原始值
+         DateTimeOriginal => {
+            exif.shoot_time = value.to_string();
+         }
+         // 曝光时间：格式化为分数或秒数，如`1/250`
+         ExposureTime => {
+            exif.exposure_time = match numbers(entry) {
+               Some(values) => values
+                  .first()
+                  .map_or(String::new(), |&seconds| format::shutter_speed(seconds)),
+               None => value.trim().trim_end_matches('s').trim().to_string(),
+            };
+         }
+         // 光圈值：格式化显示，如`F2.8`
+         FNumber => {
+            exif.aperture = match numbers(entry) {
+               Some(values) => values
+                  .first()
+                  .map_or(String::new(), |&f_number| format::aperture(f_number)),
+               None => value.replace("f/", "F"),
+            };
+         }
+         // ISO值：只保留数值，如`400`
+         ISOSpeedRatings => {
+            exif.iso = match numbers(entry) {
+               Some(values) => {
+                  format::iso(&values.iter().map(|&v| v as u32).collect::<Vec<_>>())
+               }
+               None => value.trim().trim_start_matches("ISO").trim().to_string(),
+            };
+         }
+         // 等效焦距：为0时表示未知，使用实际焦距
+         FocalLengthIn35mmFilm => {
+            let focal_length = match numbers(entry) {
+               Some(values) => values
+                  .first()
+                  .map_or(String::new(), |&mm| format::focal_length(mm)),
+               None => value.replace(' ', "").to_uppercase(),
+            };
+            if !focal_length.is_empty() {
+               exif.focal_length = focal_length;
+            }
+         }
+         // 焦距：格式化显示，如`35MM`
+         FocalLength if exif.focal_length.is_empty() => {
+            exif.focal_length = match numbers(entry) {
+               Some(values) => values
+                  .first()
+                  .map_or(String::new(), |&mm| format::focal_length(mm)),
+               None => value.replace(' ', "").to_uppercase(),
+            };
+         }
+         // 曝光模式：按代码转换为简写，如`A`表示光圈优先
+         ExposureProgram => {
+            exif.exposure_program =
+               code(entry).map_or(String::new(), format::exposure_program);
+         }
+         // 测光模式：按代码转换为简写，如`SPOT`
+         MeteringMode => {
+            exif.metering_mode =
+               code(entry).map_or(String::new(), format::metering_mode);
+         }
+         // 白平衡模式：`AWB`或`MWB`
+         WhiteBalanceMode => {
+            exif.white_balance =
+               code(entry).map_or(String::new(), format::white_balance);
+         }
+         // 闪光灯：`ON`或`OFF`
+         Flash => {
+            exif.flash = code(entry).map_or(String::new(), format::flash);
+         }
+         // 曝光补偿：带符号显示，如`+0.7`
+         ExposureBiasValue => {
+            exif.exposure_compensation = numbers(entry)
+               .and_then(|values| values.first().copied())
+               .filter(|ev| ev.is_finite());
+            exif.exposure_bias = exif
+               .exposure_compensation
+               .map_or(String::new(), format::exposure_bias);
+         }
+         // 厂商私有的MakerNote：读取其中的照片风格
+         MakerNote => {
+            if let Some(profile) =
+               maker_note::picture_profile(&entry.ifd.data, entry.ifd.le)
+            {
+               exif.profile = profile;
+            }
+         }
+         // 方向：使用数值代码，如`6`表示需要顺时针旋转90°
+         Orientation => {
+            if let TagValue::U16(values) = &entry.value {
+               exif.orientation = values.first().copied().unwrap_or_default();
+            }
+         }
+         // 版权信息
+         Copyright => {
+            exif.copyright = ascii_value(entry).unwrap_or(value).trim().to_string();
+         }
+         // 拍摄时间的时差和亚秒：rexif不识别OffsetTimeOriginal（0x9011）和
+         // SubSecTimeOriginal（0x9291）标签，按原始标签号读取
+         UnknownToMe if entry.ifd.tag == 0x9011 => {
+            exif.time_offset = ascii_value(entry).and_then(parse_offset);
+         }
+         UnknownToMe if entry.ifd.tag == 0x9291 => {
+            if let Some(sub_second) = ascii_value(entry) {
+               exif.sub_second = sub_second.trim().to_string();
+            }
+         }
+         // 作者：rexif不识别Artist（0x013B）标签，按原始标签号读取
+         UnknownToMe if entry.ifd.tag == 0x013B => {
+            if let Some(artist) = ascii_value(entry) {
+               exif.artist = artist.trim().to_string();
+            }
+         }
+         // 忽略其他标签
+         _ => {}
+      }
+   }
+}
+
+/// 读取数值类型的EXIF条目，分母为0的分数会被忽略，非数值类型返回`None`
+fn numbers(entry: &ExifEntry) -> Option<Vec<f64>> {
+   Some(match &entry.value {
+      TagValue::URational(values) => values
+         .iter()
+         .filter(|value| value.denominator != 0)
+         .map(|value| value.value())
+         .collect(),
+      TagValue::IRational(values) => values
+         .iter()
+         .filter(|value| value.denominator != 0)
+         .map(|value| value.value())
+         .collect(),
+      TagValue::U16(values) => values.iter().map(|&value| value as f64).collect(),
+      TagValue::U32(values) => values.iter().map(|&value| value as f64).collect(),
+      _ => return None,
+   })
+}
+
+/// 读取EXIF条目中的第一个整数代码，如曝光模式
+fn code(entry: &ExifEntry) -> Option<u16> {
+   match &entry.value {
+      TagValue::U16(values) => values.first().copied(),
+      _ => None,
+   }
+}
+
+/// 读取ASCII类型的EXIF条目，去掉末尾的空字符
+fn ascii_value(entry: &ExifEntry) -> Option<&str> {
+   match &entry.value {
+      TagValue::Ascii(value) => Some(value.trim_end_matches('\0')),
+      _ => None,
+   }
+}
+
+/// 按默认模板显示Exif信息，省略缺少的值
+impl Display for Exif {
+   fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+      let template =
+         parse_exif_template(DEFAULT_EXIF_TEMPLATE).map_err(|_| fmt::Error)?;
+      f.write_str(&self.render(&template))
+   }
+}
+
+/// # 手动指定的EXIF信息
+///
+/// 用于扫描件、导出后丢失EXIF的照片，指定的值覆盖照片中的值；
+/// 焦距、光圈、快门速度和ISO可以写为数值（如`35`、`2.8`、`1/250`），按[`format`]中的规则格式化
+#[derive(Args, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ExifOverride {
+   #[arg(long, global = true)]
+   /// 指定相机厂商，覆盖EXIF中的值
+   pub make: Option<String>,
+   #[arg(long, global = true)]
+   /// 指定相机型号，覆盖EXIF中的值，同样按机型映射表转换为显示名称
+   pub model: Option<String>,
+   #[arg(long, global = true, value_name = "MM")]
+   /// 指定焦距（如35），覆盖EXIF中的值
+   pub focal: Option<String>,
+   #[arg(long, global = true, value_name = "F")]
+   /// 指定光圈（如2.8），覆盖EXIF中的值
+   pub aperture: Option<String>,
+   #[arg(long, global = true, value_name = "SECONDS")]
+   /// 指定快门速度（如1/250），覆盖EXIF中的值
+   pub shutter: Option<String>,
+   #[arg(long, global = true)]
+   /// 指定ISO（如400），覆盖EXIF中的值
+   pub iso: Option<String>,
+}
+
+impl ExifOverride {
+   /// 合并另一组指定的值，`other`中的值优先
+   pub fn merge(&mut self, other: &ExifOverride) {
+      let pairs = [
+         (&mut self.make, &other.make),
+         (&mut self.model, &other.model),
+         (&mut self.focal, &other.focal),
+         (&mut self.aperture, &other.aperture),
+         (&mut self.shutter, &other.shutter),
+         (&mut self.iso, &other.iso),
+      ];
+      for (value, other) in pairs {
+         if other.is_some() {
+            value.clone_from(other);
+         }
+      }
+   }
+
+   /// 用指定的值覆盖EXIF信息，无法解析为数值的值原样显示
+   pub fn apply(&self, exif: &mut Exif) {
+      let formatted = |value: &Option<String>, format: fn(f64) -> String| {
+         value.as_deref().map(|value| {
+            parse_number(value).map_or_else(|| value.trim().to_string(), format)
+         })
+      };
+      if let Some(make) = &self.make {
+         exif.make = make.trim().to_string();
+      }
+      if let Some(model) = &self.model {
+         exif.model = model.trim().to_string();
+      }
+      if let Some(focal) = formatted(&self.focal, format::focal_length) {
+         exif.focal_length = focal;
+      }
+      if let Some(aperture) = formatted(&self.aperture, format::aperture) {
+         exif.aperture = aperture;
+      }
+      if let Some(shutter) = formatted(&self.shutter, format::shutter_speed) {
+         exif.exposure_time = shutter;
+      }
+      if let Some(iso) = formatted(&self.iso, |iso| format::iso(&[iso as u32])) {
+         exif.iso = iso;
+      }
+   }
+}
+
+/// 解析手动指定的数值，忽略`F`、`f/`、`ISO`前缀和`mm`、`s`单位，支持`1/250`形式的分数
+fn parse_number(value: &str) -> Option<f64> {
+   let value = value.trim().to_lowercase();
+   let value = value
+      .trim_start_matches("iso")
+      .trim_start_matches("f/")
+      .trim_start_matches('f')
+      .trim_end_matches("mm")
+      .trim_end_matches('s')
+      .trim();
+   match value.split_once('/') {
+      Some((numerator, denominator)) => {
+         let denominator: f64 = denominator.trim().parse().ok()?;
+         (denominator != 0.0)
+            .then_some(numerator.trim().parse::<f64>().ok()? / denominator)
+      }
+      None => value.parse().ok(),
+   }
+}
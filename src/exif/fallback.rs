@@ -0,0 +1,142 @@
+use super::{Exif, format, maker_note};
+use crate::date::parse_offset;
+use crate::{GpsPosition, ModelNames};
+use kamadak_exif::{Field, In, Reader, Tag, Value};
+use std::io::{BufRead, Seek};
+
+/// # 使用kamadak-exif读取EXIF信息
+///
+/// 支持JPEG、TIFF、HEIF、PNG和WebP，跳过无法解析的条目，其余条目按与rexif相同的方式转换；
+/// 完全无法解析时返回`None`
+pub(super) fn read<R: BufRead + Seek>(reader: &mut R) -> Option<Exif> {
+   let mut parser = Reader::new();
+   parser.continue_on_error(true);
+   let source = parser
+      .read_from_container(reader)
+      .or_else(|err| {
+         err.distill_partial_result(|errors| {
+            tracing::debug!("备用EXIF解析器跳过了{}个无效条目", errors.len());
+         })
+      })
+      .ok()?;
+   Some(convert(&source))
+}
+
+/// 将kamadak-exif的条目转换为[`Exif`]，格式化方式与rexif的条目相同
+fn convert(source: &kamadak_exif::Exif) -> Exif {
+   let field = |tag| source.get_field(tag, In::PRIMARY);
+   let text = |tag| field(tag).and_then(ascii).unwrap_or_default();
+   let number = |tag| field(tag).and_then(|field| number(&field.value, 0));
+   let code = |tag| field(tag).and_then(|field| field.value.get_uint(0));
+   let formatted = |tag, format: fn(u16) -> String| {
+      code(tag).map_or(String::new(), |code| format(code as u16))
+   };
+   let model = text(Tag::Model);
+   // 等效焦距为0时表示未知，使用实际焦距
+   let focal_length = [Tag::FocalLengthIn35mmFilm, Tag::FocalLength]
+      .into_iter()
+      .filter_map(|tag| number(tag).map(format::focal_length))
+      .find(|focal_length| !focal_length.is_empty())
+      .unwrap_or_default();
+   let iso = field(Tag::PhotographicSensitivity)
+      .and_then(|field| field.value.iter_uint())
+      .map_or(String::new(), |values| {
+         format::iso(&values.collect::<Vec<_>>())
+      });
+   let exposure_compensation =
+      number(Tag::ExposureBiasValue).filter(|ev| ev.is_finite());
+   let profile = match field(Tag::MakerNote).map(|field| &field.value) {
+      Some(Value::Undefined(data, _)) => {
+         maker_note::picture_profile(data, source.little_endian())
+      }
+      _ => None,
+   };
+   Exif {
+      make: text(Tag::Make),
+      model_title: ModelNames::default().title(&model),
+      model,
+      shoot_time: text(Tag::DateTimeOriginal),
+      sub_second: text(Tag::SubSecTimeOriginal),
+      time_offset: field(Tag::OffsetTimeOriginal)
+         .and_then(ascii)
+         .and_then(|offset| parse_offset(&offset)),
+      exposure_time: number(Tag::ExposureTime)
+         .map_or(String::new(), format::shutter_speed),
+      aperture: number(Tag::FNumber).map_or(String::new(), format::aperture),
+      iso,
+      focal_length,
+      exposure_program: formatted(Tag::ExposureProgram, format::exposure_program),
+      metering_mode: formatted(Tag::MeteringMode, format::metering_mode),
+      white_balance: formatted(Tag::WhiteBalance, format::white_balance),
+      exposure_bias: exposure_compensation.map_or(String::new(), format::exposure_bias),
+      exposure_compensation,
+      flash: formatted(Tag::Flash, format::flash),
+      profile: profile.unwrap_or_default(),
+      orientation: code(Tag::Orientation).unwrap_or_default() as u16,
+      artist: text(Tag::Artist),
+      copyright: text(Tag::Copyright),
+      gps: gps(source),
+      ..Exif::default()
+   }
+}
+
+/// 读取GPS位置，与[`GpsPosition::from_entries`]相同
+fn gps(source: &kamadak_exif::Exif) -> Option<GpsPosition> {
+   let field = |tag| source.get_field(tag, In::PRIMARY);
+   // 参考方向为`S`、`W`时取负数
+   let reference = |tag, negative: &str| match field(tag).and_then(ascii) {
+      Some(value) if value == negative => -1.0,
+      _ => 1.0,
+   };
+   let degrees = |tag| {
+      let value = &field(tag)?.value;
+      let (d, m, s) = (number(value, 0)?, number(value, 1)?, number(value, 2)?);
+      Some(d + m / 60.0 + s / 3600.0).filter(|degrees| degrees.is_finite())
+   };
+   let latitude = degrees(Tag::GPSLatitude)?;
+   let longitude = degrees(Tag::GPSLongitude)?;
+   // 海拔参考为1时表示海平面以下
+   let altitude = field(Tag::GPSAltitude)
+      .and_then(|field| number(&field.value, 0))
+      .filter(|altitude| altitude.is_finite())
+      .map(|altitude| {
+         match field(Tag::GPSAltitudeRef).and_then(|field| field.value.get_uint(0)) {
+            Some(1) => -altitude,
+            _ => altitude,
+         }
+      });
+   Some(GpsPosition {
+      latitude: latitude * reference(Tag::GPSLatitudeRef, "S"),
+      longitude: longitude * reference(Tag::GPSLongitudeRef, "W"),
+      altitude,
+   })
+}
+
+/// 读取ASCII类型条目中的第一个字符串，去掉首尾的空白和空字符
+fn ascii(field: &Field) -> Option<String> {
+   match &field.value {
+      Value::Ascii(values) => values.first().map(|value| {
+         String::from_utf8_lossy(value)
+            .trim_matches(|c: char| c == '\0' || c.is_whitespace())
+            .to_string()
+      }),
+      _ => None,
+   }
+}
+
+/// 读取数值类型条目中的第`index`个值，分母为0的分数返回`None`
+fn number(value: &Value, index: usize) -> Option<f64> {
+   match value {
+      Value::Rational(values) => values
+         .get(index)
+         .filter(|value| value.denom != 0)
+         .map(|value| value.to_f64()),
+      Value::SRational(values) => values
+         .get(index)
+         .filter(|value| value.denom != 0)
+         .map(|value| value.to_f64()),
+      Value::Short(values) => values.get(index).map(|&value| value as f64),
+      Value::Long(values) => values.get(index).map(|&value| value as f64),
+      _ => None,
+   }
+}
@@ -0,0 +1,380 @@
+/// 倒数与整数的相对误差在该范围内时，快门速度显示为分数
+const FRACTION_TOLERANCE: f64 = 0.02;
+
+/// # 快门速度，不含单位
+///
+/// 短于1秒且倒数接近整数时显示为分数，其余显示为最多一位小数的秒数；无效值返回空字符串
+///
+/// ```
+/// use lumix_mark::exif::format::shutter_speed;
+///
+/// assert_eq!(shutter_speed(1.0 / 250.0), "1/250");
+/// // 部分相机写入未约分的分数，如10/2500
+/// assert_eq!(shutter_speed(10.0 / 2500.0), "1/250");
+/// assert_eq!(shutter_speed(1.0 / 3.0), "1/3");
+/// assert_eq!(shutter_speed(0.3), "0.3");
+/// assert_eq!(shutter_speed(5.0 / 16.0), "0.3");
+/// assert_eq!(shutter_speed(2.5), "2.5");
+/// assert_eq!(shutter_speed(30.0), "30");
+/// assert_eq!(shutter_speed(0.0), "");
+/// ```
+pub fn shutter_speed(seconds: f64) -> String {
+   if !seconds.is_finite() || seconds <= 0.0 {
+      return String::new();
+   }
+   if seconds < 1.0 {
+      let reciprocal = 1.0 / seconds;
+      let rounded = reciprocal.round();
+      if (reciprocal - rounded).abs() <= rounded * FRACTION_TOLERANCE {
+         return format!("1/{rounded}");
+      }
+   }
+   decimal(seconds)
+}
+
+/// # 光圈值，保留一位小数
+///
+/// 手动镜头等写入0的无效值返回空字符串
+///
+/// ```
+/// use lumix_mark::exif::format::aperture;
+///
+/// assert_eq!(aperture(2.8), "F2.8");
+/// assert_eq!(aperture(28.0 / 10.0), "F2.8");
+/// assert_eq!(aperture(8.0), "F8.0");
+/// assert_eq!(aperture(7.1000004), "F7.1");
+/// assert_eq!(aperture(0.0), "");
+/// ```
+pub fn aperture(f_number: f64) -> String {
+   match f_number.is_finite() && f_number > 0.0 {
+      true => format!("F{f_number:.1}"),
+      false => String::new(),
+   }
+}
+
+/// # 焦距，四舍五入为整数毫米
+///
+/// 手动镜头等写入0的无效值返回空字符串
+///
+/// ```
+/// use lumix_mark::exif::format::focal_length;
+///
+/// assert_eq!(focal_length(35.0), "35MM");
+/// assert_eq!(focal_length(24.5), "25MM");
+/// assert_eq!(focal_length(4.38), "4MM");
+/// assert_eq!(focal_length(0.0), "");
+/// ```
+pub fn focal_length(millimeters: f64) -> String {
+   let rounded = millimeters.round();
+   match rounded.is_finite() && rounded > 0.0 {
+      true => format!("{rounded}MM"),
+      false => String::new(),
+   }
+}
+
+/// # ISO感光度
+///
+/// 部分相机在ISO中附带宽容度等数值，只取第一个值；无效值返回空字符串
+///
+/// ```
+/// use lumix_mark::exif::format::iso;
+///
+/// assert_eq!(iso(&[400]), "400");
+/// assert_eq!(iso(&[3200, 0]), "3200");
+/// assert_eq!(iso(&[0]), "");
+/// assert_eq!(iso(&[]), "");
+/// ```
+pub fn iso(values: &[u32]) -> String {
+   match values.first() {
+      Some(&value) if value > 0 => value.to_string(),
+      _ => String::new(),
+   }
+}
+
+/// # 曝光模式的简写
+///
+/// 手动、程序、光圈优先和快门优先显示为`M`、`P`、`A`、`S`，未定义的代码返回空字符串
+///
+/// ```
+/// use lumix_mark::exif::format::exposure_program;
+///
+/// assert_eq!(exposure_program(3), "A");
+/// assert_eq!(exposure_program(7), "PORTRAIT");
+/// assert_eq!(exposure_program(0), "");
+/// ```
+pub fn exposure_program(code: u16) -> String {
+   match code {
+      1 => "M",
+      2 | 5 | 6 => "P",
+      3 => "A",
+      4 => "S",
+      7 => "PORTRAIT",
+      8 => "LANDSCAPE",
+      _ => "",
+   }
+   .to_string()
+}
+
+/// # 测光模式的简写
+///
+/// 评价测光（Pattern）显示为`MULTI`，未知或未定义的代码返回空字符串
+///
+/// ```
+/// use lumix_mark::exif::format::metering_mode;
+///
+/// assert_eq!(metering_mode(5), "MULTI");
+/// assert_eq!(metering_mode(3), "SPOT");
+/// assert_eq!(metering_mode(0), "");
+/// ```
+pub fn metering_mode(code: u16) -> String {
+   match code {
+      1 => "AVERAGE",
+      2 => "CENTER",
+      3 => "SPOT",
+      4 => "MULTI-SPOT",
+      5 => "MULTI",
+      6 => "PARTIAL",
+      _ => "",
+   }
+   .to_string()
+}
+
+/// # 白平衡模式
+///
+/// 自动白平衡为`AWB`，手动白平衡为`MWB`，其他代码返回空字符串
+///
+/// ```
+/// use lumix_mark::exif::format::white_balance;
+///
+/// assert_eq!(white_balance(0), "AWB");
+/// assert_eq!(white_balance(1), "MWB");
+/// ```
+pub fn white_balance(code: u16) -> String {
+   match code {
+      0 => "AWB",
+      1 => "MWB",
+      _ => "",
+   }
+   .to_string()
+}
+
+/// # 曝光补偿，不含单位
+///
+/// 最多一位小数，非0时带符号；无效值返回空字符串
+///
+/// ```
+/// use lumix_mark::exif::format::exposure_bias;
+///
+/// assert_eq!(exposure_bias(2.0 / 3.0), "+0.7");
+/// assert_eq!(exposure_bias(-4.0 / 3.0), "-1.3");
+/// assert_eq!(exposure_bias(1.0), "+1");
+/// assert_eq!(exposure_bias(0.0), "0");
+/// ```
+pub fn exposure_bias(ev: f64) -> String {
+   if !ev.is_finite() {
+      return String::new();
+   }
+   match decimal(ev.abs()).as_str() {
+      "0" => "0".to_string(),
+      value if ev < 0.0 => format!("-{value}"),
+      value => format!("+{value}"),
+   }
+}
+
+/// # 带单位的曝光补偿，用于`{ev}`占位符
+///
+/// 非0时带符号，为0时显示为`±0 EV`；无效值返回空字符串
+///
+/// ```
+/// use lumix_mark::exif::format::exposure_value;
+///
+/// assert_eq!(exposure_value(2.0 / 3.0), "+0.7 EV");
+/// assert_eq!(exposure_value(-4.0 / 3.0), "-1.3 EV");
+/// assert_eq!(exposure_value(0.01), "±0 EV");
+/// ```
+pub fn exposure_value(ev: f64) -> String {
+   match exposure_bias(ev).as_str() {
+      "" => String::new(),
+      "0" => "±0 EV".to_string(),
+      bias => format!("{bias} EV"),
+   }
+}
+
+/// # 闪光灯是否闪光
+///
+/// 闪光为`ON`，未闪光为`OFF`，相机没有闪光功能时返回空字符串
+///
+/// ```
+/// use lumix_mark::exif::format::flash;
+///
+/// assert_eq!(flash(0x19), "ON");
+/// assert_eq!(flash(0x10), "OFF");
+/// assert_eq!(flash(0x20), "");
+/// ```
+pub fn flash(code: u16) -> String {
+   match (code & 0x20 != 0, code & 0x01 != 0) {
+      (true, _) => "",
+      (false, true) => "ON",
+      (false, false) => "OFF",
+   }
+   .to_string()
+}
+
+/// # XMP中的日期时间，转换为EXIF的格式
+///
+/// XMP使用ISO 8601格式，可以省略秒并带有小数秒和时区，转换后不含时区；只有日期或格式无效时返回`None`
+///
+/// ```
+/// use lumix_mark::exif::format::xmp_date_time;
+///
+/// assert_eq!(
+///    xmp_date_time("2024-05-01T17:30:12.50+08:00").as_deref(),
+///    Some("2024:05:01 17:30:12")
+/// );
+/// assert_eq!(xmp_date_time("2024-05-01T17:30Z").as_deref(), Some("2024:05:01 17:30:00"));
+/// assert_eq!(xmp_date_time("2024-05-01"), None);
+/// assert_eq!(xmp_date_time("yesterday"), None);
+/// ```
+pub fn xmp_date_time(value: &str) -> Option<String> {
+   let (date, time) = value.trim().split_once('T')?;
+   let time = time.split(['.', '+', '-', 'Z']).next()?;
+   let date: Vec<_> = date.split('-').collect();
+   let mut time: Vec<_> = time.split(':').collect();
+   if time.len() == 2 {
+      time.push("00");
+   }
+   let digits = |parts: &[&str], lengths: [usize; 3]| {
+      parts.len() == 3
+         && parts.iter().zip(lengths).all(|(part, length)| {
+            part.len() == length && part.bytes().all(|byte| byte.is_ascii_digit())
+         })
+   };
+   (digits(&date, [4, 2, 2]) && digits(&time, [2, 2, 2]))
+      .then(|| format!("{} {}", date.join(":"), time.join(":")))
+}
+
+/// # XMP日期中的亚秒和时差
+///
+/// 时差为相对UTC的分钟数，缺失时亚秒为空、时差为`None`
+///
+/// ```
+/// use lumix_mark::exif::format::xmp_sub_second_offset;
+///
+/// assert_eq!(
+///    xmp_sub_second_offset("2024-05-01T17:30:12.123+08:00"),
+///    ("123".to_string(), Some(480))
+/// );
+/// assert_eq!(xmp_sub_second_offset("2024-05-01T17:30Z"), (String::new(), Some(0)));
+/// assert_eq!(xmp_sub_second_offset("2024-05-01T17:30:12"), (String::new(), None));
+/// ```
+pub fn xmp_sub_second_offset(value: &str) -> (String, Option<i32>) {
+   let Some((_, time)) = value.trim().split_once('T') else {
+      return (String::new(), None);
+   };
+   let zone_start = time.find(['+', '-', 'Z']).unwrap_or(time.len());
+   let (time, zone) = time.split_at(zone_start);
+   let sub_second = time
+      .split_once('.')
+      .map(|(_, digits)| digits.to_string())
+      .filter(|digits| digits.bytes().all(|byte| byte.is_ascii_digit()))
+      .unwrap_or_default();
+   (sub_second, crate::date::parse_offset(zone))
+}
+
+/// 最多一位小数，去掉末尾的0
+fn decimal(value: f64) -> String {
+   let text = format!("{value:.1}");
+   text.trim_end_matches('0').trim_end_matches('.').to_string()
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+
+   #[test]
+   fn shutter_speed_edge_values() {
+      for (seconds, expected) in [
+         // 分子、分母为0的分数
+         (f64::NAN, ""),
+         (f64::INFINITY, ""),
+         (0.0, ""),
+         (-1.0 / 250.0, ""),
+         // 三分之一级的慢速快门按小数显示
+         (1.0 / 1.6, "0.6"),
+         (1.0 / 1.3, "0.8"),
+         (1.0 / 8000.0, "1/8000"),
+         (1.0 / 32000.0, "1/32000"),
+         (1.0, "1"),
+         (60.0 / 4.0, "15"),
+      ] {
+         assert_eq!(shutter_speed(seconds), expected, "seconds:{seconds}");
+      }
+   }
+
+   #[test]
+   fn aperture_and_focal_length_edge_values() {
+      for (f_number, expected) in [
+         (f64::NAN, ""),
+         (f64::INFINITY, ""),
+         (-2.8, ""),
+         (1.4, "F1.4"),
+         (22.0, "F22.0"),
+      ] {
+         assert_eq!(aperture(f_number), expected, "f_number:{f_number}");
+      }
+      for (millimeters, expected) in [
+         (f64::NAN, ""),
+         (f64::INFINITY, ""),
+         (0.4, ""),
+         (-35.0, ""),
+         (600.0, "600MM"),
+      ] {
+         assert_eq!(focal_length(millimeters), expected, "focal:{millimeters}");
+      }
+   }
+
+   #[test]
+   fn iso_edge_values() {
+      for (values, expected) in [
+         (&[65535][..], "65535"),
+         (&[0, 100][..], ""),
+         (&[50, 0, 0][..], "50"),
+      ] {
+         assert_eq!(iso(values), expected, "iso:{values:?}");
+      }
+   }
+
+   #[test]
+   fn exposure_bias_edge_values() {
+      for (ev, bias, value) in [
+         (f64::NAN, "", ""),
+         (f64::INFINITY, "", ""),
+         (2.0 / 3.0, "+0.7", "+0.7 EV"),
+         (-2.0 / 3.0, "-0.7", "-0.7 EV"),
+         (-1.0 / 3.0, "-0.3", "-0.3 EV"),
+         (-5.0, "-5", "-5 EV"),
+         // 舍入后为0时不带符号
+         (-0.04, "0", "±0 EV"),
+      ] {
+         assert_eq!(exposure_bias(ev), bias, "ev:{ev}");
+         assert_eq!(exposure_value(ev), value, "ev:{ev}");
+      }
+   }
+
+   #[test]
+   fn unknown_codes_are_empty() {
+      for code in [9, 255, u16::MAX] {
+         assert_eq!(exposure_program(code), "", "program:{code}");
+      }
+      // 255为“其他”
+      for code in [7, 255, u16::MAX] {
+         assert_eq!(metering_mode(code), "", "metering:{code}");
+      }
+      for code in [2, 255, u16::MAX] {
+         assert_eq!(white_balance(code), "", "white_balance:{code}");
+      }
+      for (code, expected) in [(0x41, "ON"), (0x18, "OFF"), (0x30, "")] {
+         assert_eq!(flash(code), expected, "flash:{code:#x}");
+      }
+   }
+}
@@ -0,0 +1,127 @@
+/// 富士MakerNote的开头，之后为4字节、相对MakerNote开头的IFD偏移量，始终为小端序
+const FUJIFILM_HEADER: &[u8] = b"FUJIFILM";
+/// 松下MakerNote的开头，之后直接是IFD，字节序与EXIF相同
+const PANASONIC_HEADER: &[u8] = b"Panasonic\0\0\0";
+
+/// 富士的饱和度标签，黑白和棕褐色模式记录在这里
+const FUJIFILM_SATURATION: u16 = 0x1003;
+/// 富士的胶片模拟标签
+const FUJIFILM_FILM_MODE: u16 = 0x1401;
+/// 松下的照片风格标签
+const PANASONIC_PHOTO_STYLE: u16 = 0x0089;
+
+/// # MakerNote中的照片风格
+///
+/// 支持富士的胶片模拟（如`Classic Chrome`、`Acros+R`）和松下的照片风格（如`L.Monochrome D`），
+/// 其他厂商或无法识别时返回`None`
+///
+/// # 参数
+/// * `data` - MakerNote标签的原始数据
+/// * `little_endian` - EXIF的字节序，松下的MakerNote使用
+pub(crate) fn picture_profile(data: &[u8], little_endian: bool) -> Option<String> {
+   if let Some(rest) = data.strip_prefix(FUJIFILM_HEADER) {
+      let offset = u32::from_le_bytes(rest.get(..4)?.try_into().ok()?) as usize;
+      let ifd = Ifd::new(data, offset, true);
+      let monochrome = ifd.value(FUJIFILM_SATURATION).and_then(fujifilm_monochrome);
+      let film = || ifd.value(FUJIFILM_FILM_MODE).and_then(fujifilm_film_mode);
+      return monochrome.or_else(film).map(str::to_string);
+   }
+   if data.starts_with(PANASONIC_HEADER) {
+      let ifd = Ifd::new(data, PANASONIC_HEADER.len(), little_endian);
+      return ifd
+         .value(PANASONIC_PHOTO_STYLE)
+         .and_then(panasonic_photo_style)
+         .map(str::to_string);
+   }
+   None
+}
+
+/// 富士胶片模拟的名称
+fn fujifilm_film_mode(code: u16) -> Option<&'static str> {
+   Some(match code {
+      0x000 => "Provia",
+      0x120 => "Astia",
+      0x200 | 0x400 => "Velvia",
+      0x500 => "Pro Neg. Std",
+      0x501 => "Pro Neg. Hi",
+      0x600 => "Classic Chrome",
+      0x700 => "Eterna",
+      0x800 => "Classic Neg.",
+      0x900 => "Eterna Bleach Bypass",
+      0xA00 => "Nostalgic Neg.",
+      0xB00 => "Reala Ace",
+      _ => return None,
+   })
+}
+
+/// 富士黑白和棕褐色模式的名称，彩色模式返回`None`
+fn fujifilm_monochrome(code: u16) -> Option<&'static str> {
+   Some(match code {
+      0x300 => "Monochrome",
+      0x301 => "Monochrome+R",
+      0x302 => "Monochrome+Ye",
+      0x303 => "Monochrome+G",
+      0x310 => "Sepia",
+      0x500 => "Acros",
+      0x501 => "Acros+R",
+      0x502 => "Acros+Ye",
+      0x503 => "Acros+G",
+      _ => return None,
+   })
+}
+
+/// 松下照片风格的名称
+fn panasonic_photo_style(code: u16) -> Option<&'static str> {
+   Some(match code {
+      1 => "Standard",
+      2 => "Vivid",
+      3 => "Natural",
+      4 => "Monochrome",
+      5 => "Scenery",
+      6 => "Portrait",
+      8 => "Cinelike D",
+      9 => "Cinelike V",
+      11 => "L.Monochrome",
+      12 => "Like709",
+      15 => "L.Monochrome D",
+      17 => "V-Log",
+      18 => "Cinelike D2",
+      _ => return None,
+   })
+}
+
+/// MakerNote中的IFD，只读取值直接存放在条目中的短整数
+struct Ifd<'a> {
+   data: &'a [u8],
+   offset: usize,
+   little_endian: bool,
+}
+
+impl<'a> Ifd<'a> {
+   fn new(data: &'a [u8], offset: usize, little_endian: bool) -> Self {
+      Self {
+         data,
+         offset,
+         little_endian,
+      }
+   }
+
+   fn u16_at(&self, position: usize) -> Option<u16> {
+      let bytes: [u8; 2] = self.data.get(position..position + 2)?.try_into().ok()?;
+      Some(match self.little_endian {
+         true => u16::from_le_bytes(bytes),
+         false => u16::from_be_bytes(bytes),
+      })
+   }
+
+   /// 标签`tag`的第一个短整数（SHORT类型）值，标签不存在或类型不符时返回`None`
+   fn value(&self, tag: u16) -> Option<u16> {
+      const SHORT: u16 = 3;
+      let count = self.u16_at(self.offset)? as usize;
+      (0..count)
+         .map(|index| self.offset + 2 + index * 12)
+         .find(|&entry| self.u16_at(entry) == Some(tag))
+         .filter(|&entry| self.u16_at(entry + 2) == Some(SHORT))
+         .and_then(|entry| self.u16_at(entry + 8))
+   }
+}
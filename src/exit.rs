@@ -0,0 +1,50 @@
+use crate::{BatchSummary, LumixMarkError};
+use std::process::ExitCode;
+
+/// # 命令行的退出码
+///
+/// 数值保持稳定，供脚本和CI判断处理结果；命令行参数本身无效时clap同样以2退出
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExitStatus {
+   /// 全部处理成功（包括因输出文件已存在而跳过）
+   Success = 0,
+   /// 部分或全部图片处理失败
+   PartialFailure = 1,
+   /// 配置文件、预设、字体等配置无效
+   ConfigError = 2,
+   /// 没有找到需要处理的图片
+   NoInputs = 3,
+}
+
+impl ExitStatus {
+   /// 退出码的数值
+   pub fn code(self) -> i32 {
+      self as i32
+   }
+}
+
+impl From<ExitStatus> for ExitCode {
+   fn from(status: ExitStatus) -> Self {
+      ExitCode::from(status as u8)
+   }
+}
+
+impl From<&LumixMarkError> for ExitStatus {
+   fn from(err: &LumixMarkError) -> Self {
+      match err {
+         LumixMarkError::InvalidConfig(_) => ExitStatus::ConfigError,
+         _ => ExitStatus::PartialFailure,
+      }
+   }
+}
+
+impl BatchSummary {
+   /// 批量处理结果对应的退出码
+   pub fn exit_status(&self) -> ExitStatus {
+      if self.is_success() {
+         ExitStatus::Success
+      } else {
+         ExitStatus::PartialFailure
+      }
+   }
+}
@@ -0,0 +1,158 @@
+use crate::{LumixMark, MarkStyle, OutputFormat, Result, SaveOptions};
+use clap::ValueEnum;
+use serde::Deserialize;
+use std::cell::RefCell;
+use std::ffi::{CStr, CString, c_char, c_int};
+use std::panic::{AssertUnwindSafe, catch_unwind};
+use std::ptr;
+
+/// 处理成功
+pub const LUMIX_MARK_OK: c_int = 0;
+/// 参数无效：空指针、JSON格式错误或不支持的输出格式
+pub const LUMIX_MARK_INVALID_ARGUMENT: c_int = 1;
+/// 解码、绘制或编码失败
+pub const LUMIX_MARK_FAILED: c_int = 2;
+/// 处理过程中发生panic
+pub const LUMIX_MARK_PANIC: c_int = 3;
+
+/// 输出的图片数据，使用后需要调用`lumix_mark_buffer_free`释放
+#[repr(C)]
+pub struct LumixMarkBuffer {
+   pub data: *mut u8,
+   pub len: usize,
+}
+
+/// # `style_json`的内容
+///
+/// 水印样式的字段与TOML配置文件相同，直接写在顶层，缺省的字段使用默认值
+#[derive(Deserialize)]
+#[serde(default)]
+struct Request {
+   /// 水印相当于短边的比率
+   ratio: f32,
+   /// 输出图片格式
+   format: String,
+   /// 图片质量，用于JPEG/AVIF
+   quality: u8,
+   #[serde(flatten)]
+   style: MarkStyle,
+}
+
+impl Default for Request {
+   fn default() -> Self {
+      Self {
+         ratio: 0.14,
+         format: "jpeg".to_string(),
+         quality: 75,
+         style: MarkStyle::default(),
+      }
+   }
+}
+
+thread_local! {
+   /// 当前线程最近一次失败的错误信息
+   static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// 记录错误信息并返回错误码
+fn fail(code: c_int, message: impl ToString) -> c_int {
+   let message = message.to_string().replace('\0', " ");
+   LAST_ERROR.with(|last| *last.borrow_mut() = CString::new(message).ok());
+   code
+}
+
+/// 解析参数，返回请求和输出格式
+fn parse_request(
+   style_json: Option<&str>,
+) -> std::result::Result<(Request, OutputFormat), String> {
+   let request: Request = match style_json {
+      Some(json) => serde_json::from_str(json).map_err(|err| err.to_string())?,
+      None => Request::default(),
+   };
+   let format = OutputFormat::from_str(&request.format, true)
+      .map_err(|_| format!("不支持的输出格式：{}", request.format))?;
+   Ok((request, format))
+}
+
+/// 执行默认流水线
+fn process(photo: &[u8], request: &Request, format: OutputFormat) -> Result<Vec<u8>> {
+   let options = SaveOptions {
+      quality: request.quality,
+      ..SaveOptions::default()
+   };
+   LumixMark::mark_bytes(photo, request.ratio, &request.style, format, &options)
+}
+
+/// # 为内存中的图片添加水印
+///
+/// 成功时返回`LUMIX_MARK_OK`并将输出写入`out_buf`，失败时返回错误码，
+/// 错误信息通过`lumix_mark_last_error`获取
+///
+/// # Safety
+///
+/// * `buf`指向至少`len`字节的可读内存
+/// * `style_json`为空指针或以`\0`结尾的UTF-8字符串，空指针时使用默认样式和JPEG输出
+/// * `out_buf`指向可写的`LumixMarkBuffer`
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn lumix_mark_process(
+   buf: *const u8,
+   len: usize,
+   style_json: *const c_char,
+   out_buf: *mut LumixMarkBuffer,
+) -> c_int {
+   if buf.is_null() || out_buf.is_null() {
+      return fail(LUMIX_MARK_INVALID_ARGUMENT, "buf和out_buf不能为空指针");
+   }
+   let photo = unsafe { std::slice::from_raw_parts(buf, len) };
+   let style_json = match style_json.is_null() {
+      true => None,
+      false => match unsafe { CStr::from_ptr(style_json) }.to_str() {
+         Ok(json) => Some(json),
+         Err(err) => return fail(LUMIX_MARK_INVALID_ARGUMENT, err),
+      },
+   };
+   let (request, format) = match parse_request(style_json) {
+      Ok(parsed) => parsed,
+      Err(err) => return fail(LUMIX_MARK_INVALID_ARGUMENT, err),
+   };
+   let output =
+      match catch_unwind(AssertUnwindSafe(|| process(photo, &request, format))) {
+         Ok(Ok(output)) => output,
+         Ok(Err(err)) => return fail(LUMIX_MARK_FAILED, err),
+         Err(_) => return fail(LUMIX_MARK_PANIC, "处理图片时发生panic"),
+      };
+   let output = Box::into_raw(output.into_boxed_slice());
+   unsafe {
+      *out_buf = LumixMarkBuffer {
+         data: output.cast(),
+         len: output.len(),
+      };
+   }
+   LUMIX_MARK_OK
+}
+
+/// # 释放`lumix_mark_process`输出的图片数据
+///
+/// # Safety
+///
+/// `buffer`必须来自`lumix_mark_process`且只能释放一次，`data`为空指针时不做任何处理
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn lumix_mark_buffer_free(buffer: LumixMarkBuffer) {
+   if !buffer.data.is_null() {
+      let slice = ptr::slice_from_raw_parts_mut(buffer.data, buffer.len);
+      drop(unsafe { Box::from_raw(slice) });
+   }
+}
+
+/// # 当前线程最近一次失败的错误信息
+///
+/// 没有错误时返回空指针，返回的字符串在同一线程下一次调用`lumix_mark_process`前有效
+#[unsafe(no_mangle)]
+pub extern "C" fn lumix_mark_last_error() -> *const c_char {
+   LAST_ERROR.with(|last| {
+      last
+         .borrow()
+         .as_ref()
+         .map_or(ptr::null(), |message| message.as_ptr())
+   })
+}
@@ -0,0 +1,337 @@
+use crate::shadow::ShadowMask;
+use crate::{CanvasLayout, Color};
+use image::imageops::{FilterType, fast_blur, resize, thumbnail};
+use image::{DynamicImage, GenericImage, ImageResult, Pixel, Rgb, RgbImage};
+use serde::{Deserialize, Serialize};
+
+/// 模糊背景先缩小到的短边长度，在小图上模糊可以大幅减少计算量
+const BLUR_SAMPLE_SIZE: u32 = 100;
+
+/// # 画布背景的填充方式
+///
+/// 填充照片以外的区域，包括水印条和拍立得边框
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Fill {
+   /// 纯色填充
+   Solid(Color),
+   /// 两种颜色之间的线性渐变，或从中心向四角的径向渐变（暗角）
+   Gradient {
+      /// 起点颜色（顶部、左侧或中心）
+      from: Color,
+      /// 终点颜色（底部、右侧或四角）
+      to: Color,
+      /// 渐变方向
+      #[serde(default)]
+      direction: GradientDirection,
+   },
+   /// 按照片的主色调自动选择：水印条和边框为主色调与白色混合后的浅色，分隔线为主色调
+   Auto {
+      /// 主色调在背景中的比例（0 - 1），0为白色
+      #[serde(default = "default_tint")]
+      tint: f32,
+   },
+   /// 使用照片放大铺满画布后的高斯模糊图像填充
+   Blur {
+      /// 模糊半径，相对画布短边的比例
+      radius_ratio: f32,
+   },
+}
+
+fn default_tint() -> f32 {
+   0.25
+}
+
+/// 渐变背景的方向
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GradientDirection {
+   /// 从上到下
+   #[default]
+   Vertical,
+   /// 从左到右
+   Horizontal,
+   /// 从画布中心到四角，终点颜色略深时为柔和的暗角
+   Radial,
+}
+
+impl Default for Fill {
+   fn default() -> Self {
+      Fill::Solid(Color::White)
+   }
+}
+
+impl Fill {
+   /// 默认强度的模糊背景
+   pub fn blur() -> Self {
+      Fill::Blur { radius_ratio: 0.05 }
+   }
+
+   /// 默认比例的主色调背景
+   pub fn auto() -> Self {
+      Fill::Auto {
+         tint: default_tint(),
+      }
+   }
+
+   /// 自动背景按照片的主色调返回纯色背景和分隔线的颜色，其余填充方式原样返回
+   pub(crate) fn resolve(self, photo: &RgbImage) -> (Fill, Option<Color>) {
+      let Fill::Auto { tint } = self else {
+         return (self, None);
+      };
+      let accent = dominant_color(photo);
+      let Rgb([r, g, b]) =
+         accent.map(|value| mix(value, u8::MAX, tint.clamp(0.0, 1.0)));
+      let Rgb([accent_r, accent_g, accent_b]) = accent;
+      (
+         Fill::Solid(Color::RGB(r, g, b)),
+         Some(Color::RGB(accent_r, accent_g, accent_b)),
+      )
+   }
+
+   /// # 创建按填充方式绘制好背景的画布
+   ///
+   /// # 参数
+   /// * `width` - 画布宽度
+   /// * `height` - 画布高度
+   /// * `photo` - 照片，用于生成模糊背景
+   pub fn canvas(&self, width: u32, height: u32, photo: &RgbImage) -> RgbImage {
+      match *self {
+         Fill::Solid(color) => RgbImage::from_pixel(width, height, color.into()),
+         Fill::Gradient { .. } => RgbImage::from_fn(width, height, |x, y| {
+            self.pixel((x, y), (width, height)).expect("渐变背景")
+         }),
+         Fill::Auto { .. } => self.resolve(photo).0.canvas(width, height, photo),
+         Fill::Blur { radius_ratio } => {
+            // 按画布比例缩小并居中裁剪照片，模糊后再放大到画布尺寸
+            let scale = BLUR_SAMPLE_SIZE as f32 / width.min(height).max(1) as f32;
+            let sample_width = ((width as f32 * scale) as u32).max(1);
+            let sample_height = ((height as f32 * scale) as u32).max(1);
+            let sample = DynamicImage::ImageRgb8(photo.clone())
+               .resize_to_fill(sample_width, sample_height, FilterType::Triangle)
+               .to_rgb8();
+            let sigma = radius_ratio * BLUR_SAMPLE_SIZE as f32;
+            let blurred = fast_blur(&sample, sigma);
+            resize(&blurred, width, height, FilterType::Triangle)
+         }
+      }
+   }
+
+   /// # 尺寸为`width`×`height`的画布中`(x, y)`处的背景颜色
+   ///
+   /// 自动背景和模糊背景需要由照片生成，返回`None`
+   pub(crate) fn pixel(
+      &self,
+      (x, y): (u32, u32),
+      (width, height): (u32, u32),
+   ) -> Option<Rgb<u8>> {
+      match *self {
+         Fill::Solid(color) => Some(color.into()),
+         Fill::Gradient {
+            from,
+            to,
+            direction,
+         } => {
+            let position = |value: u32, length: u32| {
+               value as f32 / length.saturating_sub(1).max(1) as f32
+            };
+            let t = match direction {
+               GradientDirection::Vertical => position(y, height),
+               GradientDirection::Horizontal => position(x, width),
+               GradientDirection::Radial => {
+                  // 到中心的距离按到四角的距离归一化，平方后中心区域变化更平缓
+                  let dx = position(x, width) * 2.0 - 1.0;
+                  let dy = position(y, height) * 2.0 - 1.0;
+                  (dx * dx + dy * dy) / 2.0
+               }
+            };
+            let (from, to) = (Rgb::<u8>::from(from), Rgb::<u8>::from(to));
+            Some(from.map2(&to, |from, to| mix(to, from, t)))
+         }
+         Fill::Auto { .. } | Fill::Blur { .. } => None,
+      }
+   }
+
+   /// # 将照片扩展为按布局放置照片、填充好背景的画布
+   ///
+   /// 纯色和渐变背景直接在照片的缓冲区中扩展，不再分配一份完整的画布并复制照片，
+   /// 模糊背景需要先由照片生成背景再放入照片；照片的圆角和投影按遮罩与背景混合
+   pub(crate) fn expand(
+      &self,
+      mut photo: RgbImage,
+      layout: &CanvasLayout,
+      corners: RoundedCorners,
+      shadow: Option<&ShadowMask>,
+   ) -> ImageResult<RgbImage> {
+      let (left, top) = layout.photo;
+      let (width, height) = photo.dimensions();
+      let photo_area = (left, top, left + width, top + height);
+      let size = (layout.width, layout.height);
+      match *self {
+         Fill::Auto { .. } => {
+            let (fill, _) = self.resolve(&photo);
+            fill.expand(photo, layout, corners, shadow)
+         }
+         Fill::Solid(_) | Fill::Gradient { .. } => {
+            let background = |x, y| self.pixel((x, y), size).expect("纯色或渐变背景");
+            corners.apply(&mut photo, |x, y| {
+               let under = background(left + x, top + y);
+               match shadow {
+                  Some(shadow) => shadow.blend(under, (x as i64, y as i64)),
+                  None => under,
+               }
+            });
+            let mut canvas = expand_in_place(photo, layout, background);
+            if let Some(shadow) = shadow {
+               shadow.paint(&mut canvas, photo_area, false);
+            }
+            Ok(canvas)
+         }
+         Fill::Blur { .. } => {
+            let mut canvas = self.canvas(layout.width, layout.height, &photo);
+            if let Some(shadow) = shadow {
+               shadow.paint(&mut canvas, photo_area, true);
+            }
+            corners.apply(&mut photo, |x, y| *canvas.get_pixel(left + x, top + y));
+            canvas.copy_from(&photo, left, top)?;
+            Ok(canvas)
+         }
+      }
+   }
+}
+
+/// 主色调取样时照片缩小到的边长
+const DOMINANT_SAMPLE_SIZE: u32 = 64;
+
+/// # 照片的主色调
+///
+/// 缩小后的照片按每通道3位量化为512种颜色，取数量最多的一组的平均颜色；
+/// 鲜艳的颜色权重更高，避免大面积的灰色天空或阴影成为主色调
+fn dominant_color(photo: &RgbImage) -> Rgb<u8> {
+   let sample = thumbnail(photo, DOMINANT_SAMPLE_SIZE, DOMINANT_SAMPLE_SIZE);
+   let mut buckets = [(0.0f32, [0.0f32; 3]); 512];
+   for &Rgb([r, g, b]) in sample.pixels() {
+      let index = (r as usize >> 5) << 6 | (g as usize >> 5) << 3 | b as usize >> 5;
+      let chroma = (r.max(g).max(b) - r.min(g).min(b)) as f32 / 255.0;
+      let weight = 1.0 + chroma * 2.0;
+      let (total, sum) = &mut buckets[index];
+      *total += weight;
+      for (sum, value) in sum.iter_mut().zip([r, g, b]) {
+         *sum += value as f32 * weight;
+      }
+   }
+   let (total, sum) = buckets
+      .iter()
+      .max_by(|a, b| a.0.total_cmp(&b.0))
+      .copied()
+      .unwrap_or_default();
+   Rgb(sum.map(|sum| (sum / total.max(f32::EPSILON)).round() as u8))
+}
+
+/// # 照片四角的圆角遮罩
+///
+/// 圆弧以外的像素透明，露出下方的背景；圆弧边缘按覆盖的面积抗锯齿
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub(crate) struct RoundedCorners {
+   /// 圆角半径（像素），为0时不圆角
+   radius: f32,
+}
+
+impl RoundedCorners {
+   pub(crate) fn new(radius: f32) -> Self {
+      Self {
+         radius: radius.max(0.0),
+      }
+   }
+
+   /// # 按遮罩将照片四角与背景混合
+   ///
+   /// 只处理四角的像素，`background`返回照片中`(x, y)`处下方的背景颜色
+   pub(crate) fn apply<F>(self, photo: &mut RgbImage, background: F)
+   where
+      F: Fn(u32, u32) -> Rgb<u8>,
+   {
+      let (width, height) = photo.dimensions();
+      let size = self.radius(width, height).ceil() as u32;
+      if size == 0 {
+         return;
+      }
+      let in_corner = |value: u32, length: u32| value < size || value >= length - size;
+      for y in (0..height).filter(|&y| in_corner(y, height)) {
+         for x in (0..width).filter(|&x| in_corner(x, width)) {
+            let alpha = self.coverage((x, y), (width, height));
+            if alpha < 1.0 {
+               let under = background(x, y);
+               photo
+                  .get_pixel_mut(x, y)
+                  .apply2(&under, |top, under| mix(top, under, alpha));
+            }
+         }
+      }
+   }
+
+   /// 尺寸为`width`×`height`的照片中`(x, y)`处像素的不透明度
+   pub(crate) fn coverage(
+      self,
+      (x, y): (u32, u32),
+      (width, height): (u32, u32),
+   ) -> f32 {
+      let radius = self.radius(width, height);
+      if radius == 0.0 {
+         return 1.0;
+      }
+      let (center_x, center_y) = (x as f32 + 0.5, y as f32 + 0.5);
+      // 到所在角圆心的距离，不在四角的圆弧范围内时为0
+      let dx = (radius - center_x)
+         .max(center_x - (width as f32 - radius))
+         .max(0.0);
+      let dy = (radius - center_y)
+         .max(center_y - (height as f32 - radius))
+         .max(0.0);
+      if dx == 0.0 || dy == 0.0 {
+         return 1.0;
+      }
+      (radius - dx.hypot(dy) + 0.5).clamp(0.0, 1.0)
+   }
+
+   /// 实际的半径，不超过短边的一半，四角的区域不会重叠
+   fn radius(self, width: u32, height: u32) -> f32 {
+      self.radius.min(width.min(height) as f32 / 2.0)
+   }
+}
+
+/// 按不透明度`alpha`将`top`混合到`under`上
+pub(crate) fn mix(top: u8, under: u8, alpha: f32) -> u8 {
+   (top as f32 * alpha + under as f32 * (1.0 - alpha)).round() as u8
+}
+
+/// # 在照片的缓冲区中原地扩展为画布
+///
+/// 缓冲区扩大到画布尺寸后从最后一行开始向后移动照片的每一行，
+/// 目标位置不小于原位置，不会覆盖还没有移动的像素；最后填充照片以外的区域
+fn expand_in_place<F>(photo: RgbImage, layout: &CanvasLayout, background: F) -> RgbImage
+where
+   F: Fn(u32, u32) -> Rgb<u8>,
+{
+   let (width, height) = photo.dimensions();
+   let (left, top) = layout.photo;
+   let stride = width as usize * 3;
+   let canvas_stride = layout.width as usize * 3;
+   let mut pixels = photo.into_raw();
+   pixels.resize(canvas_stride * layout.height as usize, 0);
+   for y in (0..height as usize).rev() {
+      let target = (top as usize + y) * canvas_stride + left as usize * 3;
+      pixels.copy_within(y * stride..(y + 1) * stride, target);
+   }
+   let photo_rows = top..top + height;
+   let photo_columns = left..left + width;
+   for (y, row) in (0..).zip(pixels.chunks_exact_mut(canvas_stride)) {
+      for (x, pixel) in (0..).zip(row.chunks_exact_mut(3)) {
+         if !photo_rows.contains(&y) || !photo_columns.contains(&x) {
+            pixel.copy_from_slice(&background(x, y).0);
+         }
+      }
+   }
+   RgbImage::from_raw(layout.width, layout.height, pixels)
+      .expect("缓冲区已扩大到画布尺寸")
+}
@@ -0,0 +1,261 @@
+use crate::font::FontStack;
+use crate::text::{Flow, TextRun};
+use crate::{LumixMarkError, Result};
+
+/// 文字缩小的最小比例，缩小到该比例仍然超出宽度时折行或截断文字
+const MIN_TEXT_SCALE: f32 = 0.6;
+/// 每次缩小的比例
+const SCALE_STEP: f32 = 0.05;
+/// 截断文字后添加的省略号，字体中缺少时使用`...`
+const ELLIPSIS: char = '…';
+
+/// 参与排版的一段文字
+pub(crate) struct FitText<'a> {
+   /// 文字内容，截断后以省略号结尾
+   pub(crate) text: String,
+   /// 缩小后的文字大小
+   pub(crate) size: f32,
+   base_size: f32,
+   fonts: &'a FontStack<'a>,
+   flow: Flow,
+   /// 缩小后仍然超出宽度时是否可以折为两行
+   wrappable: bool,
+}
+
+impl<'a> FitText<'a> {
+   pub(crate) fn new(
+      text: &str,
+      size: f32,
+      fonts: &'a FontStack<'a>,
+      flow: Flow,
+   ) -> Self {
+      Self {
+         text: text.to_string(),
+         size,
+         base_size: size,
+         fonts,
+         flow,
+         wrappable: false,
+      }
+   }
+
+   /// 允许[`fit_texts`]在缩小后仍然超出宽度时将文字折为两行
+   pub(crate) fn wrappable(mut self) -> Self {
+      self.wrappable = true;
+      self
+   }
+
+   /// 按排列方式排好的单行文字，用于绘制
+   pub(crate) fn run(&self) -> TextRun<'a, 'a> {
+      TextRun::new(self.fonts, self.size, &self.text, self.flow)
+   }
+
+   /// 按排列方式排好的每一行文字，未折行时只有一行
+   pub(crate) fn lines(&self) -> Vec<TextRun<'a, 'a>> {
+      self
+         .text
+         .lines()
+         .map(|line| TextRun::new(self.fonts, self.size, line, self.flow))
+         .collect()
+   }
+
+   /// 所有行的总高度，行之间相隔`line_gap`
+   pub(crate) fn height(&self, line_gap: f32) -> f32 {
+      let lines = self.text.lines().count().max(1) as f32;
+      lines * self.size + (lines - 1.0) * line_gap
+   }
+
+   /// 文字的宽度，折行后为最宽一行的宽度
+   pub(crate) fn width(&self) -> u32 {
+      self.measure(&self.text)
+   }
+
+   fn measure(&self, text: &str) -> u32 {
+      text
+         .lines()
+         .map(|line| {
+            TextRun::new(self.fonts, self.size, line, self.flow)
+               .size()
+               .0
+         })
+         .max()
+         .unwrap_or(0)
+   }
+
+   /// # 在空白处将文字折为两行
+   ///
+   /// 逐个尝试每个空白，选择较宽一行最窄的位置，使两行宽度接近（如`35MM F2.8`和`1/250S ISO400`）；
+   /// 已经折行或没有空白时返回`false`
+   fn break_lines(&mut self) -> bool {
+      if self.text.contains('\n') {
+         return false;
+      }
+      let best = self
+         .text
+         .char_indices()
+         .filter(|(_, c)| c.is_whitespace())
+         .map(|(i, _)| {
+            let (first, second) =
+               (self.text[..i].trim_end(), self.text[i..].trim_start());
+            (self.measure(first).max(self.measure(second)), first, second)
+         })
+         .filter(|(_, first, second)| !first.is_empty() && !second.is_empty())
+         .min_by_key(|(width, _, _)| *width);
+      match best {
+         Some((_, first, second)) => {
+            self.text = format!("{first}\n{second}");
+            true
+         }
+         None => false,
+      }
+   }
+
+   /// # 按宽度将文字折为多行
+   ///
+   /// 优先在空白处折行，没有空白（如中文）或单词本身超出宽度时在字符间折行；
+   /// 超过`max_lines`行时最后一行截断并添加省略号
+   pub(crate) fn wrap(&self, max_width: u32, max_lines: usize) -> Vec<FitText<'a>> {
+      let mut lines = Vec::new();
+      let mut rest = self.text.trim();
+      while !rest.is_empty() && lines.len() < max_lines.max(1) {
+         let end = match lines.len() + 1 < max_lines {
+            true => self.line_end(rest, max_width),
+            false => rest.len(),
+         };
+         let mut line = Self {
+            text: rest[..end].trim_end().to_string(),
+            ..*self
+         };
+         if line.width() > max_width {
+            line.truncate(max_width);
+         }
+         lines.push(line);
+         rest = rest[end..].trim_start();
+      }
+      lines
+   }
+
+   /// 宽度不超过`max_width`的第一行的结束位置，至少包含一个字符
+   fn line_end(&self, text: &str, max_width: u32) -> usize {
+      let mut end = 0;
+      let mut space = None;
+      for (i, c) in text.char_indices() {
+         if c.is_whitespace() {
+            space = Some(i);
+            continue;
+         }
+         let next = i + c.len_utf8();
+         let run = TextRun::new(self.fonts, self.size, &text[..next], self.flow);
+         if run.size().0 > max_width {
+            return match space {
+               Some(space) => space,
+               None if end > 0 => end,
+               None => next,
+            };
+         }
+         end = next;
+      }
+      text.len()
+   }
+
+   /// 截断文字使宽度不超过`max_width`，至少保留一个字符，无法截断时保持不变
+   fn truncate(&mut self, max_width: u32) {
+      let ellipsis = match self.fonts.contains(ELLIPSIS) {
+         true => ELLIPSIS.to_string(),
+         false => "...".to_string(),
+      };
+      let mut chars: Vec<char> = self.text.chars().collect();
+      while chars.len() > 1 {
+         chars.pop();
+         let text =
+            format!("{}{ellipsis}", chars.iter().collect::<String>().trim_end());
+         if self.measure(&text) <= max_width {
+            self.text = text;
+            return;
+         }
+      }
+   }
+}
+
+/// # 水印中的一行内容
+///
+/// 一行由固定宽度的元素（边距、间距、Logo、分隔线）和若干段文字组成
+pub(crate) struct FitRow {
+   /// 固定宽度元素的总宽度
+   pub(crate) fixed: u32,
+   /// 该行文字在[`fit_texts`]的`texts`中的序号，宽度不足时按顺序截断
+   pub(crate) texts: Vec<usize>,
+}
+
+impl FitRow {
+   fn width(&self, texts: &[FitText]) -> u32 {
+      self.fixed + self.texts.iter().map(|&i| texts[i].width()).sum::<u32>()
+   }
+}
+
+/// # 调整文字使每一行都不超过可用宽度
+///
+/// 先按相同比例逐步缩小所有文字（最小为原大小的60%）；仍然超出时将可以折行的文字（见[`FitText::wrappable`]）
+/// 在空白处折为两行，再从原大小开始缩小；仍然超出时按行中的顺序截断文字并添加省略号，
+/// 截断后仍然放不下时返回[`LumixMarkError::LayoutOverflow`]
+///
+/// # 参数
+/// * `texts` - 水印中的所有文字，调整后的大小和内容写回其中
+/// * `rows` - 水印中的各行，多行可以包含同一段文字
+/// * `available` - 可用宽度
+pub(crate) fn fit_texts(
+   texts: &mut [FitText],
+   rows: &[FitRow],
+   available: u32,
+) -> Result<()> {
+   let fits = |texts: &[FitText]| rows.iter().all(|row| row.width(texts) <= available);
+   if shrink(texts, fits) {
+      return Ok(());
+   }
+   let mut wrapped = false;
+   for text in texts.iter_mut().filter(|text| text.wrappable) {
+      if text.break_lines() {
+         wrapped = true;
+         tracing::trace!(text = %text.text, "文字超出宽度，折为两行");
+      }
+   }
+   if wrapped && shrink(texts, fits) {
+      return Ok(());
+   }
+   for row in rows {
+      for &i in &row.texts {
+         let width = row.width(texts);
+         if width <= available {
+            break;
+         }
+         let max_width = texts[i].width().saturating_sub(width - available);
+         texts[i].truncate(max_width);
+         tracing::trace!(text = %texts[i].text, max_width, "截断文字");
+      }
+   }
+   match rows.iter().map(|row| row.width(texts)).max() {
+      Some(required) if required > available => Err(LumixMarkError::LayoutOverflow {
+         required,
+         available,
+      }),
+      _ => Ok(()),
+   }
+}
+
+/// 从原大小开始按相同比例逐步缩小所有文字，放得下时返回`true`，缩小到最小比例仍然放不下时返回`false`
+fn shrink(texts: &mut [FitText], fits: impl Fn(&[FitText]) -> bool) -> bool {
+   let mut scale = 1.0;
+   loop {
+      for text in texts.iter_mut() {
+         text.size = text.base_size * scale;
+      }
+      if fits(texts) {
+         return true;
+      }
+      if scale <= MIN_TEXT_SCALE {
+         return false;
+      }
+      scale = (scale - SCALE_STEP).max(MIN_TEXT_SCALE);
+      tracing::trace!(scale, "文字超出宽度，缩小文字");
+   }
+}
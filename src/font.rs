@@ -0,0 +1,346 @@
+use crate::icon::ExifIcon;
+use crate::{LumixMarkError, MarkStyle, Result};
+use ab_glyph::{Font, FontRef, ScaleFont};
+use imageproc::drawing::text_size;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use ttf_parser::{Face, name_id};
+
+/// 主字体缺少字符时依次查找的系统字体：中日韩字体在前，符号字体在后
+const FALLBACK_FAMILIES: &[&str] = &[
+   "Microsoft YaHei",
+   "PingFang SC",
+   "Hiragino Sans GB",
+   "Noto Sans CJK SC",
+   "Source Han Sans SC",
+   "WenQuanYi Micro Hei",
+   "Noto Sans CJK JP",
+   "Yu Gothic",
+   "Hiragino Sans",
+   "Malgun Gothic",
+   "Apple SD Gothic Neo",
+   "Segoe UI Symbol",
+   "Apple Symbols",
+   "Noto Sans Symbols",
+   "Noto Sans Symbols 2",
+   "DejaVu Sans",
+];
+
+/// 字体文件扩展名，`ttc`和`otc`为包含多个字体的集合
+const FONT_EXTENSIONS: &[&str] = &["ttf", "otf", "ttc", "otc"];
+
+/// 加载的字体数据
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FontData {
+   /// 字体文件数据
+   pub bytes: Vec<u8>,
+   /// 字体在字体集合中的序号，单个字体文件为0
+   pub index: u32,
+}
+
+impl FontData {
+   /// # 按路径或字体名称加载字体
+   ///
+   /// `font`为存在的文件路径时读取该文件，否则按字体名称（如`Helvetica Neue`）在系统字体中查找
+   pub fn load(font: &str) -> Result<Self> {
+      let path = Path::new(font);
+      if path.is_file() {
+         Self::from_file(path)
+      } else {
+         Self::system(font)
+      }
+   }
+
+   /// 读取TTF/OTF字体文件
+   pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+      let font = Self {
+         bytes: fs::read(path)?,
+         index: 0,
+      };
+      ab_glyph::FontRef::try_from_slice_and_index(&font.bytes, font.index)?;
+      Ok(font)
+   }
+
+   /// # 在系统字体中按名称查找字体
+   ///
+   /// 名称可以是字体族名（如`Arial`）或完整名称（如`Arial Bold`），不区分大小写；
+   /// 字体族名匹配多个字体时优先使用常规字重
+   pub fn system(name: &str) -> Result<Self> {
+      let query = name.trim().to_lowercase();
+      let mut regular = None;
+      let mut family = None;
+      visit_system_faces(|bytes, index, face| {
+         let matched = |id| face_names(face, id).any(|value| value == query);
+         if matched(name_id::FULL_NAME) || matched(name_id::FAMILY) && is_regular(face)
+         {
+            regular = Some(Self::new(bytes, index));
+            return true;
+         }
+         if family.is_none()
+            && (matched(name_id::FAMILY) || matched(name_id::TYPOGRAPHIC_FAMILY))
+         {
+            family = Some(Self::new(bytes, index));
+         }
+         false
+      });
+      regular.or(family).ok_or_else(|| {
+         LumixMarkError::InvalidConfig(format!(
+            "未找到字体：{}，请指定字体文件路径或已安装的字体名称",
+            name.trim()
+         ))
+      })
+   }
+
+   fn new(bytes: &[u8], index: u32) -> Self {
+      Self {
+         bytes: bytes.to_vec(),
+         index,
+      }
+   }
+}
+
+/// # 系统中的后备字体
+///
+/// 按[`FALLBACK_FAMILIES`]的顺序查找已安装的中日韩字体和符号字体，只在第一次需要时扫描系统字体
+fn system_fallbacks() -> &'static [FontData] {
+   static FALLBACKS: OnceLock<Vec<FontData>> = OnceLock::new();
+   FALLBACKS.get_or_init(|| {
+      let mut found: Vec<Option<FontData>> =
+         FALLBACK_FAMILIES.iter().map(|_| None).collect();
+      visit_system_faces(|bytes, index, face| {
+         for (slot, family) in found.iter_mut().zip(FALLBACK_FAMILIES) {
+            let family = family.to_lowercase();
+            let matched = face_names(face, name_id::FAMILY)
+               .chain(face_names(face, name_id::TYPOGRAPHIC_FAMILY))
+               .any(|value| value == family);
+            // 同一字体族优先使用常规字重
+            if matched && (slot.is_none() || is_regular(face)) {
+               *slot = Some(FontData::new(bytes, index));
+            }
+         }
+         false
+      });
+      found.into_iter().flatten().collect()
+   })
+}
+
+/// 遍历系统字体目录中的所有字体，`visit`返回`true`时停止遍历
+fn visit_system_faces(mut visit: impl FnMut(&[u8], u32, &Face) -> bool) {
+   let mut files = Vec::new();
+   for dir in system_font_dirs() {
+      collect_font_files(&dir, &mut files);
+   }
+   for path in files {
+      let Ok(bytes) = fs::read(&path) else {
+         continue;
+      };
+      let count = ttf_parser::fonts_in_collection(&bytes).unwrap_or(1);
+      for index in 0..count {
+         if let Ok(face) = Face::parse(&bytes, index)
+            && visit(&bytes, index, &face)
+         {
+            return;
+         }
+      }
+   }
+}
+
+/// 字体中指定类型的名称，转换为小写
+fn face_names<'a>(face: &'a Face, id: u16) -> impl Iterator<Item = String> + 'a {
+   face
+      .names()
+      .into_iter()
+      .filter(move |entry| entry.name_id == id)
+      .filter_map(|entry| entry.to_string())
+      .map(|value| value.to_lowercase())
+}
+
+fn is_regular(face: &Face) -> bool {
+   face_names(face, name_id::SUBFAMILY)
+      .any(|value| value == "regular" || value == "book")
+}
+
+/// 各系统的字体目录，不存在的目录会被忽略
+fn system_font_dirs() -> Vec<PathBuf> {
+   let mut dirs = Vec::new();
+   let home = env::var_os("HOME").map(PathBuf::from);
+   if cfg!(target_os = "windows") {
+      if let Some(windir) = env::var_os("WINDIR") {
+         dirs.push(PathBuf::from(windir).join("Fonts"));
+      }
+      if let Some(local) = env::var_os("LOCALAPPDATA") {
+         dirs.push(PathBuf::from(local).join("Microsoft/Windows/Fonts"));
+      }
+   } else if cfg!(target_os = "macos") {
+      dirs.push(PathBuf::from("/System/Library/Fonts"));
+      dirs.push(PathBuf::from("/Library/Fonts"));
+      dirs.extend(home.map(|home| home.join("Library/Fonts")));
+   } else {
+      dirs.push(PathBuf::from("/usr/share/fonts"));
+      dirs.push(PathBuf::from("/usr/local/share/fonts"));
+      if let Some(data) = env::var_os("XDG_DATA_HOME") {
+         dirs.push(PathBuf::from(data).join("fonts"));
+      }
+      if let Some(home) = home {
+         dirs.push(home.join(".local/share/fonts"));
+         dirs.push(home.join(".fonts"));
+      }
+   }
+   dirs
+}
+
+/// 递归收集目录中的字体文件
+fn collect_font_files(dir: &Path, result: &mut Vec<PathBuf>) {
+   let Ok(entries) = fs::read_dir(dir) else {
+      return;
+   };
+   for entry in entries.flatten() {
+      let path = entry.path();
+      if path.is_dir() {
+         collect_font_files(&path, result);
+      } else if path.extension().is_some_and(|ext| {
+         FONT_EXTENSIONS.contains(&ext.to_string_lossy().to_lowercase().as_str())
+      }) {
+         result.push(path);
+      }
+   }
+}
+
+/// 水印中各部分文字使用的字体栈
+pub(crate) struct MarkFonts<'a> {
+   /// 机型的字体
+   pub(crate) model: FontStack<'a>,
+   /// Exif信息和拍摄时间等附加信息的字体
+   pub(crate) exif: FontStack<'a>,
+}
+
+/// 按字体拆分后的文字片段
+pub(crate) enum Run<'f, 'a, 't> {
+   /// 使用同一字体绘制的文字
+   Text(&'f FontRef<'a>, &'t str),
+   /// Exif信息中的图标
+   Icon(ExifIcon),
+}
+
+/// # 按字符选择字体的字体栈
+///
+/// 每个字符使用第一个包含该字符的字体绘制，依次为主字体、样式中的后备字体和系统后备字体；
+/// [`ExifIcon`]的字符不使用字体，按图标绘制
+pub(crate) struct FontStack<'a> {
+   fonts: Vec<FontRef<'a>>,
+}
+
+impl<'a> FontStack<'a> {
+   /// # 创建字体栈
+   ///
+   /// 主字体和后备字体缺少`texts`中的字符时才加入系统后备字体，所有字体都缺少时返回错误
+   ///
+   /// # 参数
+   /// * `primary` - 主字体
+   /// * `style` - 提供后备字体的水印样式
+   /// * `texts` - 需要绘制的文字
+   pub(crate) fn new<'t>(
+      primary: FontRef<'a>,
+      style: &'a MarkStyle,
+      texts: impl IntoIterator<Item = &'t str> + Clone,
+   ) -> Result<Self> {
+      let mut fonts = vec![primary];
+      for font in &style.fallback_fonts {
+         fonts.push(FontRef::try_from_slice_and_index(&font.bytes, font.index)?);
+      }
+      let mut stack = Self { fonts };
+      if stack.missing(texts.clone()).is_empty() {
+         return Ok(stack);
+      }
+      for font in system_fallbacks() {
+         stack
+            .fonts
+            .push(FontRef::try_from_slice_and_index(&font.bytes, font.index)?);
+      }
+      let missing = stack.missing(texts);
+      if missing.is_empty() {
+         Ok(stack)
+      } else {
+         Err(LumixMarkError::MissingGlyphs(missing))
+      }
+   }
+
+   /// 所有字体中都不包含的字符
+   fn missing<'t>(&self, texts: impl IntoIterator<Item = &'t str>) -> String {
+      let mut missing = String::new();
+      for c in texts.into_iter().flat_map(str::chars) {
+         if !self.contains(c) && !c.is_whitespace() && !missing.contains(c) {
+            missing.push(c);
+         }
+      }
+      missing
+   }
+
+   /// 字体栈中是否有字体包含字符`c`
+   pub(crate) fn contains(&self, c: char) -> bool {
+      ExifIcon::from_char(c).is_some() || self.select(c).is_some()
+   }
+
+   /// 第一个包含字符`c`的字体
+   fn select(&self, c: char) -> Option<usize> {
+      self.fonts.iter().position(|font| font.glyph_id(c).0 != 0)
+   }
+
+   /// # 将文字按字体拆分为连续的片段
+   ///
+   /// 空白字符跟随前一个字符的字体，所有字体都缺少的字符使用主字体，每个图标单独作为一个片段
+   pub(crate) fn runs<'t>(&self, text: &'t str) -> Vec<Run<'_, 'a, 't>> {
+      let mut runs = Vec::new();
+      let mut start = 0;
+      let mut current = 0;
+      for (i, c) in text.char_indices() {
+         if c.is_whitespace() {
+            continue;
+         }
+         if let Some(icon) = ExifIcon::from_char(c) {
+            if i > start {
+               runs.push(Run::Text(&self.fonts[current], &text[start..i]));
+            }
+            runs.push(Run::Icon(icon));
+            start = i + c.len_utf8();
+            continue;
+         }
+         let index = self.select(c).unwrap_or(0);
+         if index != current && i > start {
+            runs.push(Run::Text(&self.fonts[current], &text[start..i]));
+            start = i;
+         }
+         current = index;
+      }
+      if start < text.len() {
+         runs.push(Run::Text(&self.fonts[current], &text[start..]));
+      }
+      runs
+   }
+
+   /// 主字体基线到文字顶部的距离
+   pub(crate) fn ascent(&self, size: f32) -> f32 {
+      self.fonts[0].as_scaled(size).ascent()
+   }
+
+   /// 片段相对主字体的基线偏移，使不同字体的文字基线对齐
+   pub(crate) fn baseline_offset(&self, font: &FontRef, size: f32) -> f32 {
+      self.ascent(size) - font.as_scaled(size).ascent()
+   }
+
+   /// 文字的宽度和高度
+   pub(crate) fn text_size(&self, size: f32, text: &str) -> (u32, u32) {
+      self
+         .runs(text)
+         .into_iter()
+         .fold((0, 0), |(width, height), run| {
+            let (w, h) = match run {
+               Run::Text(font, text) => text_size(size, font, text),
+               Run::Icon(_) => (ExifIcon::advance(size), ExifIcon::side(size)),
+            };
+            (width + w, height.max(h))
+         })
+   }
+}
@@ -0,0 +1,144 @@
+use clap::ValueEnum;
+use std::fmt::{self, Display, Formatter};
+
+/// PNG文件头
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+/// 动态照片XMP中的标记（Google Camera、旧版Motion Photo）
+const MOTION_PHOTO_MARKERS: [&[u8]; 3] = [
+   b"MotionPhoto=\"1\"",
+   b"MicroVideo=\"1\"",
+   // 三星动态照片在JPEG末尾追加的视频数据
+   b"MotionPhoto_Data",
+];
+
+/// 输入图片包含多帧时的处理方式
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum MultiFramePolicy {
+   /// 只处理第一帧（动态照片为其中的静态照片）
+   #[default]
+   First,
+   /// 作为错误处理
+   Error,
+   /// 跳过该图片
+   Skip,
+}
+
+/// 多帧图片的种类
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MultiFrame {
+   /// GIF动画
+   Gif,
+   /// WebP动画
+   Webp,
+   /// APNG动画
+   Png,
+   /// 内嵌视频的动态照片JPEG
+   MotionPhoto,
+}
+
+impl Display for MultiFrame {
+   fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+      f.write_str(match self {
+         MultiFrame::Gif => "GIF动画",
+         MultiFrame::Webp => "WebP动画",
+         MultiFrame::Png => "APNG动画",
+         MultiFrame::MotionPhoto => "内嵌视频的动态照片",
+      })
+   }
+}
+
+impl MultiFrame {
+   /// # 根据文件内容判断是否为多帧图片
+   ///
+   /// 只检查文件结构，不解码像素；单帧图片返回`None`
+   pub fn detect(bytes: &[u8]) -> Option<Self> {
+      let (kind, multiple) = if bytes.starts_with(b"GIF8") {
+         (MultiFrame::Gif, gif_frames(bytes) > 1)
+      } else if bytes.starts_with(b"RIFF") && bytes.get(8..12) == Some(b"WEBP") {
+         (MultiFrame::Webp, webp_animated(bytes))
+      } else if bytes.starts_with(PNG_SIGNATURE) {
+         (MultiFrame::Png, png_frames(bytes) > 1)
+      } else if bytes.starts_with(&[0xFF, 0xD8]) {
+         let motion = MOTION_PHOTO_MARKERS
+            .iter()
+            .any(|marker| contains(bytes, marker));
+         (MultiFrame::MotionPhoto, motion)
+      } else {
+         return None;
+      };
+      multiple.then_some(kind)
+   }
+}
+
+fn contains(bytes: &[u8], needle: &[u8]) -> bool {
+   bytes.windows(needle.len()).any(|window| window == needle)
+}
+
+/// GIF中图像描述符的个数，文件不完整时返回已读到的个数
+fn gif_frames(bytes: &[u8]) -> usize {
+   // 文件头6字节，逻辑屏幕描述符7字节，第5字节的最高位表示有全局颜色表
+   let Some(&flags) = bytes.get(10) else {
+      return 0;
+   };
+   let mut offset = 13 + color_table_size(flags);
+   let mut frames = 0;
+   while let Some(&block) = bytes.get(offset) {
+      offset = match block {
+         // 扩展块：标签后为数据子块
+         0x21 => skip_sub_blocks(bytes, offset + 2),
+         // 图像描述符10字节，之后为局部颜色表、LZW最小码长和数据子块
+         0x2C => {
+            frames += 1;
+            let Some(&flags) = bytes.get(offset + 9) else {
+               break;
+            };
+            skip_sub_blocks(bytes, offset + 10 + color_table_size(flags) + 1)
+         }
+         _ => break,
+      };
+   }
+   frames
+}
+
+fn color_table_size(flags: u8) -> usize {
+   match flags & 0x80 {
+      0 => 0,
+      _ => 3 << ((flags & 0x07) + 1),
+   }
+}
+
+/// 跳过以长度为0的子块结尾的数据子块
+fn skip_sub_blocks(bytes: &[u8], mut offset: usize) -> usize {
+   while let Some(&length) = bytes.get(offset) {
+      offset += 1 + length as usize;
+      if length == 0 {
+         return offset;
+      }
+   }
+   usize::MAX
+}
+
+/// WebP的VP8X块中是否设置了动画标志
+fn webp_animated(bytes: &[u8]) -> bool {
+   bytes.get(12..16) == Some(b"VP8X")
+      && bytes.get(20).is_some_and(|flags| flags & 0x02 != 0)
+}
+
+/// APNG的acTL块中的帧数，普通PNG返回1
+fn png_frames(bytes: &[u8]) -> u32 {
+   let mut offset = PNG_SIGNATURE.len();
+   while let Some(header) = bytes.get(offset..offset + 8) {
+      let length = u32::from_be_bytes([header[0], header[1], header[2], header[3]]);
+      match &header[4..8] {
+         b"acTL" => {
+            return bytes.get(offset + 8..offset + 12).map_or(1, |frames| {
+               u32::from_be_bytes([frames[0], frames[1], frames[2], frames[3]])
+            });
+         }
+         // acTL必须在第一个IDAT之前
+         b"IDAT" | b"IEND" => break,
+         _ => offset += 12 + length as usize,
+      }
+   }
+   1
+}
@@ -0,0 +1,178 @@
+use std::fmt::{self, Display, Formatter};
+use std::path::{Component, Path, PathBuf, is_separator};
+use std::str::FromStr;
+
+/// 通配符中的特殊字符，包含这些字符的输入按通配符展开
+const GLOB_CHARS: [char; 3] = ['*', '?', '['];
+
+/// # 路径通配符
+///
+/// 支持`*`（不跨越文件夹的任意字符）、`?`（单个字符）、`[abc]`/`[a-z]`/`[!abc]`（字符集合）
+/// 和`**`（任意层文件夹），`/`和`\`都作为分隔符；匹配时不区分大小写，相机文件的扩展名常为大写
+///
+/// ```
+/// use lumix_mark::GlobPattern;
+/// use std::path::Path;
+///
+/// let pattern: GlobPattern = "**/*.jpg".parse().unwrap();
+/// assert!(pattern.matches(Path::new("2024/07/P1000123.JPG")));
+/// assert!(!pattern.matches(Path::new("2024/P1000123.RW2")));
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GlobPattern {
+   /// 原始的通配符文本
+   text: String,
+   /// 按分隔符拆分、转为小写的各段
+   segments: Vec<Vec<char>>,
+}
+
+impl GlobPattern {
+   /// 文本中是否包含通配符
+   pub fn is_glob(text: &str) -> bool {
+      text.contains(GLOB_CHARS)
+   }
+
+   /// # 拆分为开头不含通配符的文件夹和其余部分
+   ///
+   /// 如`photos/**/*.jpg`拆分为`photos`和`**/*.jpg`，从该文件夹开始查找匹配的文件
+   pub(crate) fn split_root(text: &str) -> Result<(PathBuf, Self), String> {
+      let parts: Vec<&str> = text.split(is_separator).collect();
+      let literal = parts
+         .iter()
+         .take_while(|part| !Self::is_glob(part))
+         .count()
+         .min(parts.len() - 1);
+      let root = match literal {
+         0 => PathBuf::from("."),
+         // 保留开头的分隔符（绝对路径）
+         _ => PathBuf::from(format!("{}/", parts[..literal].join("/"))),
+      };
+      Ok((root, parts[literal..].join("/").parse()?))
+   }
+
+   /// 是否包含分隔符，不包含时只与文件名比较
+   pub(crate) fn has_separator(&self) -> bool {
+      self.segments.len() > 1
+   }
+
+   /// 相对路径是否与通配符匹配
+   pub fn matches(&self, path: &Path) -> bool {
+      match_segments(&self.segments, &components(path), false)
+   }
+
+   /// 文件夹中是否可能有与通配符匹配的文件，用于跳过不需要查找的文件夹
+   pub(crate) fn may_match_under(&self, dir: &Path) -> bool {
+      match_segments(&self.segments, &components(dir), true)
+   }
+}
+
+impl FromStr for GlobPattern {
+   type Err = String;
+
+   fn from_str(text: &str) -> Result<Self, Self::Err> {
+      let segments: Vec<Vec<char>> = text
+         .split(is_separator)
+         .filter(|segment| !segment.is_empty() && *segment != ".")
+         .map(|segment| segment.to_lowercase().chars().collect())
+         .collect();
+      if segments.is_empty() {
+         return Err(format!("通配符为空：{text}"));
+      }
+      for segment in &segments {
+         let mut rest = segment.as_slice();
+         while let Some(start) = rest.iter().position(|&c| c == '[') {
+            rest = &rest[start + 1..];
+            if rest.first() == Some(&'!') {
+               rest = &rest[1..];
+            }
+            match rest.iter().skip(1).position(|&c| c == ']') {
+               Some(end) => rest = &rest[end + 2..],
+               None => return Err(format!("通配符{text}中的[没有对应的]")),
+            }
+         }
+      }
+      Ok(Self {
+         text: text.to_string(),
+         segments,
+      })
+   }
+}
+
+impl Display for GlobPattern {
+   fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+      f.write_str(&self.text)
+   }
+}
+
+fn components(path: &Path) -> Vec<Vec<char>> {
+   path
+      .components()
+      .filter_map(|component| match component {
+         Component::Normal(name) => {
+            Some(name.to_string_lossy().to_lowercase().chars().collect())
+         }
+         _ => None,
+      })
+      .collect()
+}
+
+/// 按段匹配，`**`匹配零或多段；`prefix`为真时路径用完即视为可能匹配
+fn match_segments(segments: &[Vec<char>], path: &[Vec<char>], prefix: bool) -> bool {
+   match (segments.split_first(), path.split_first()) {
+      (None, None) => true,
+      (None, Some(_)) => false,
+      (Some(_), None) => {
+         prefix || segments.iter().all(|segment| segment == &['*', '*'])
+      }
+      (Some((segment, rest)), Some((_, names))) if segment == &['*', '*'] => {
+         match_segments(rest, path, prefix) || match_segments(segments, names, prefix)
+      }
+      (Some((segment, rest)), Some((name, names))) => {
+         match_name(segment, name) && match_segments(rest, names, prefix)
+      }
+   }
+}
+
+/// 匹配单段中的`*`、`?`和字符集合
+fn match_name(pattern: &[char], name: &[char]) -> bool {
+   match pattern.split_first() {
+      None => name.is_empty(),
+      Some(('*', rest)) => (0..=name.len()).any(|skip| match_name(rest, &name[skip..])),
+      Some(('?', rest)) => !name.is_empty() && match_name(rest, &name[1..]),
+      Some(('[', rest)) => {
+         let Some((&c, name_rest)) = name.split_first() else {
+            return false;
+         };
+         let (negate, body) = match rest.split_first() {
+            Some(('!', body)) => (true, body),
+            _ => (false, rest),
+         };
+         // 集合中的第一个字符为]时作为普通字符
+         let Some(end) = body.iter().skip(1).position(|&c| c == ']') else {
+            return false;
+         };
+         let end = end + 1;
+         in_set(&body[..end], c) != negate && match_name(&body[end + 1..], name_rest)
+      }
+      Some((&c, rest)) => name.first() == Some(&c) && match_name(rest, &name[1..]),
+   }
+}
+
+/// 字符是否在`[]`中的集合内，`a-z`表示范围
+fn in_set(set: &[char], c: char) -> bool {
+   let mut i = 0;
+   while i < set.len() {
+      if set.get(i + 1) == Some(&'-') && i + 2 < set.len() {
+         if (set[i]..=set[i + 2]).contains(&c) {
+            return true;
+         }
+         i += 3;
+      } else {
+         if set[i] == c {
+            return true;
+         }
+         i += 1;
+      }
+   }
+   false
+}
@@ -0,0 +1,133 @@
+use crate::Color;
+use clap::ValueEnum;
+use image::{GenericImageView, Pixel, Rgb, Rgba, RgbaImage};
+use serde::{Deserialize, Serialize};
+
+/// 统计直方图时最多采样的像素数，超过时按固定间隔跳过像素
+const MAX_SAMPLES: u64 = 1 << 20;
+/// RGB直方图中红、绿、蓝通道的颜色
+const RGB_COLORS: [[u8; 3]; 3] = [[220, 50, 50], [50, 170, 70], [50, 100, 220]];
+/// RGB直方图中每个通道的不透明度，重叠处混合出其他颜色
+const RGB_OPACITY: f32 = 0.6;
+
+/// 直方图统计的通道
+#[derive(
+   Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, ValueEnum,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum HistogramChannels {
+   /// 亮度，使用直方图的颜色绘制
+   #[default]
+   Luminance,
+   /// 红、绿、蓝三个通道半透明叠加
+   Rgb,
+}
+
+/// # 水印条右侧的直方图
+///
+/// 按照片像素统计，宽度和高度为相对水印高度的比例；叠加水印中不绘制
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Histogram {
+   /// 统计的通道
+   pub channels: HistogramChannels,
+   /// 宽度
+   pub width_ratio: f32,
+   /// 高度
+   pub height_ratio: f32,
+   /// 亮度直方图的颜色，为`Auto`时按所在区域的背景选择
+   pub color: Color,
+   /// 不透明度（0 - 1），与整体不透明度相乘
+   pub opacity: f32,
+}
+
+impl Default for Histogram {
+   fn default() -> Self {
+      Self {
+         channels: HistogramChannels::Luminance,
+         width_ratio: 0.9,
+         height_ratio: 0.4,
+         color: Color::RGB(150, 150, 150),
+         opacity: 0.8,
+      }
+   }
+}
+
+/// 各通道256级的像素数，依次为红、绿、蓝和亮度
+pub(crate) struct HistogramBins(pub(crate) [[u32; 256]; 4]);
+
+impl HistogramBins {
+   /// 统计照片的直方图，大照片按间隔采样
+   pub(crate) fn from_image<I: GenericImageView<Pixel = Rgb<u8>>>(photo: &I) -> Self {
+      let (width, height) = photo.dimensions();
+      let pixels = width as u64 * height as u64;
+      let step = ((pixels as f64 / MAX_SAMPLES as f64).sqrt().ceil() as u32).max(1);
+      let mut bins = [[0; 256]; 4];
+      for y in (0..height).step_by(step as usize) {
+         for x in (0..width).step_by(step as usize) {
+            let pixel = photo.get_pixel(x, y);
+            for (channel, value) in pixel.0.iter().enumerate() {
+               bins[channel][*value as usize] += 1;
+            }
+            bins[3][pixel.to_luma()[0] as usize] += 1;
+         }
+      }
+      Self(bins)
+   }
+
+   /// # 将通道重新分为`columns`列，返回每列相对最高列的高度（0 - 1）
+   ///
+   /// 最暗和最亮的一级常因过曝、欠曝集中大量像素，不参与最大值的计算，超出时截断为1
+   pub(crate) fn columns(&self, channel: usize, columns: u32) -> Vec<f32> {
+      let bins = &self.0[channel];
+      let max = bins[1..255].iter().copied().max().unwrap_or(0).max(1) as f32;
+      (0..columns as usize)
+         .map(|column| {
+            let start = column * 256 / columns as usize;
+            let end = ((column + 1) * 256 / columns as usize).max(start + 1);
+            let count = bins[start..end.min(256)].iter().copied().max().unwrap_or(0);
+            (count as f32 / max).min(1.0)
+         })
+         .collect()
+   }
+}
+
+impl Histogram {
+   /// # 绘制为预乘Alpha的图片
+   ///
+   /// 每列的顶端按高度的小数部分抗锯齿；亮度直方图为白色，绘制时按`color`着色
+   pub(crate) fn render(
+      &self,
+      bins: &HistogramBins,
+      width: u32,
+      height: u32,
+   ) -> RgbaImage {
+      let channels = match self.channels {
+         HistogramChannels::Luminance => vec![(3, [255, 255, 255], 1.0)],
+         HistogramChannels::Rgb => (0..3)
+            .map(|channel| (channel, RGB_COLORS[channel], RGB_OPACITY))
+            .collect(),
+      };
+      let mut image = RgbaImage::new(width, height);
+      for (channel, color, opacity) in channels {
+         for (x, value) in bins.columns(channel, width).into_iter().enumerate() {
+            let bar = value * height as f32;
+            for y in 0..height {
+               let coverage = (bar - (height - 1 - y) as f32).clamp(0.0, 1.0) * opacity;
+               if coverage <= 0.0 {
+                  continue;
+               }
+               // 按覆盖率叠加到已绘制的通道上
+               let keep = 1.0 - coverage;
+               let Rgba(pixel) = image.get_pixel_mut(x as u32, y);
+               for (value, color) in pixel.iter_mut().zip(color) {
+                  *value =
+                     (color as f32 * coverage + *value as f32 * keep).round() as u8;
+               }
+               pixel[3] = (coverage * 255.0 + pixel[3] as f32 * keep).round() as u8;
+            }
+         }
+      }
+      image
+   }
+}
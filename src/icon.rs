@@ -0,0 +1,377 @@
+use crate::Color;
+use image::{GrayImage, Luma};
+use std::f32::consts::PI;
+
+/// 图标的边长，相对文字大小
+const ICON_RATIO: f32 = 0.75;
+/// 图标与后面文字的间距，相对文字大小
+const ICON_GAP_RATIO: f32 = 0.25;
+/// 图标在文字中使用的第一个字符，位于补充私用区，字体中通常不包含这些字符
+const FIRST_CHAR: u32 = 0xF0E00;
+/// 星级的最大值
+const MAX_STARS: usize = 5;
+/// 线条的宽度，相对图标边长
+const STROKE: f32 = 0.08;
+
+/// # Exif信息中曝光参数前的图标，以及星级和颜色标签
+///
+/// 在文字中以私用区字符表示，由字体栈按矢量图形绘制，与文字一起参与宽度计算、截断和竖排
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum ExifIcon {
+   /// 焦距，镜头正面
+   Focal,
+   /// 光圈，六片光圈叶片
+   Aperture,
+   /// 快门速度，秒表
+   Shutter,
+   /// 感光度，对角线分开的加号和减号
+   Iso,
+   /// 实心星，星级中已评的星
+   Star,
+   /// 空心星，星级中未评的星
+   EmptyStar,
+   /// 颜色标签，使用标签自身的颜色绘制的圆点
+   Label(LabelColor),
+}
+
+/// Lightroom等软件的颜色标签
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum LabelColor {
+   Red,
+   Yellow,
+   Green,
+   Blue,
+   Purple,
+}
+
+impl LabelColor {
+   const ALL: [Self; 5] = [
+      Self::Red,
+      Self::Yellow,
+      Self::Green,
+      Self::Blue,
+      Self::Purple,
+   ];
+
+   /// 按XMP中的标签文字识别颜色，支持英文和中文的标签名称，不区分大小写
+   pub(crate) fn from_label(label: &str) -> Option<Self> {
+      match label.trim().to_lowercase().as_str() {
+         "red" | "红色" => Some(Self::Red),
+         "yellow" | "黄色" => Some(Self::Yellow),
+         "green" | "绿色" => Some(Self::Green),
+         "blue" | "蓝色" => Some(Self::Blue),
+         "purple" | "紫色" => Some(Self::Purple),
+         _ => None,
+      }
+   }
+
+   fn color(self) -> Color {
+      match self {
+         Self::Red => Color::RGB(226, 60, 57),
+         Self::Yellow => Color::RGB(240, 196, 32),
+         Self::Green => Color::RGB(76, 175, 80),
+         Self::Blue => Color::RGB(52, 120, 228),
+         Self::Purple => Color::RGB(150, 82, 200),
+      }
+   }
+}
+
+/// 图标的组成部分，坐标相对图标边长，原点在左上角
+enum Shape {
+   /// 圆环
+   Ring { center: (f32, f32), radius: f32 },
+   /// 实心圆
+   Disc { center: (f32, f32), radius: f32 },
+   /// 两端为半圆的线段
+   Line { from: (f32, f32), to: (f32, f32) },
+   /// 多边形，`filled`为`false`时只绘制边框
+   Polygon {
+      points: Vec<(f32, f32)>,
+      filled: bool,
+   },
+}
+
+impl ExifIcon {
+   const ALL: [Self; 6] = [
+      Self::Focal,
+      Self::Aperture,
+      Self::Shutter,
+      Self::Iso,
+      Self::Star,
+      Self::EmptyStar,
+   ];
+
+   /// Exif模板中占位符对应的图标，其他占位符没有图标
+   pub(crate) fn for_field(field: &str) -> Option<Self> {
+      match field {
+         "focal" => Some(Self::Focal),
+         "aperture" => Some(Self::Aperture),
+         "shutter" => Some(Self::Shutter),
+         "iso" => Some(Self::Iso),
+         _ => None,
+      }
+   }
+
+   /// 表示图标的字符，颜色标签排在其他图标之后
+   pub(crate) fn char(self) -> char {
+      let index = match self {
+         Self::Label(color) => Self::ALL.len() + color as usize,
+         icon => Self::ALL
+            .iter()
+            .position(|&other| other == icon)
+            .unwrap_or(0),
+      };
+      char::from_u32(FIRST_CHAR + index as u32).unwrap_or(char::REPLACEMENT_CHARACTER)
+   }
+
+   /// 字符表示的图标
+   pub(crate) fn from_char(c: char) -> Option<Self> {
+      let index = (c as u32).checked_sub(FIRST_CHAR)? as usize;
+      match index.checked_sub(Self::ALL.len()) {
+         None => Some(Self::ALL[index]),
+         Some(label) => LabelColor::ALL.get(label).copied().map(Self::Label),
+      }
+   }
+
+   /// # 星级和颜色标签的图标文字
+   ///
+   /// 星级为1 - 5时依次为实心星和空心星，之后为颜色标签；没有星级（或为拒绝的-1）和可识别的颜色标签时返回`None`
+   pub(crate) fn rating_text(rating: Option<i8>, label: &str) -> Option<String> {
+      let mut text = String::new();
+      if let Some(stars) = rating.filter(|&stars| stars > 0) {
+         let stars = (stars as usize).min(MAX_STARS);
+         text.extend((0..MAX_STARS).map(|i| match i < stars {
+            true => Self::Star.char(),
+            false => Self::EmptyStar.char(),
+         }));
+      }
+      text.extend(LabelColor::from_label(label).map(|color| Self::Label(color).char()));
+      (!text.is_empty()).then_some(text)
+   }
+
+   /// 使用自身颜色绘制的图标的颜色，其他图标与文字颜色相同
+   pub(crate) fn color(self) -> Option<Color> {
+      match self {
+         Self::Label(color) => Some(color.color()),
+         _ => None,
+      }
+   }
+
+   /// 用于记录和输出元素的文字：去掉曝光参数的图标，星级和颜色标签替换为`★`、`☆`和`●`
+   pub(crate) fn plain_text(text: &str) -> String {
+      text
+         .chars()
+         .filter_map(|c| match Self::from_char(c) {
+            None => Some(c),
+            Some(Self::Star) => Some('★'),
+            Some(Self::EmptyStar) => Some('☆'),
+            Some(Self::Label(_)) => Some('●'),
+            Some(_) => None,
+         })
+         .collect()
+   }
+
+   /// 图标的边长（像素）
+   pub(crate) fn side(size: f32) -> u32 {
+      (size * ICON_RATIO).round().max(1.0) as u32
+   }
+
+   /// 图标连同后面的间距占用的宽度
+   pub(crate) fn advance(size: f32) -> u32 {
+      (size * (ICON_RATIO + ICON_GAP_RATIO)).round() as u32
+   }
+
+   /// 绘制为`side`像素见方的灰度图，灰度为覆盖率，按有向距离抗锯齿
+   pub(crate) fn mask(self, side: u32) -> GrayImage {
+      let shapes = self.shapes();
+      let scale = side as f32;
+      GrayImage::from_fn(side, side, |x, y| {
+         let point = ((x as f32 + 0.5) / scale, (y as f32 + 0.5) / scale);
+         let distance = shapes
+            .iter()
+            .map(|shape| shape.distance(point))
+            .fold(f32::INFINITY, f32::min);
+         Luma([((0.5 - distance * scale).clamp(0.0, 1.0) * 255.0).round() as u8])
+      })
+   }
+
+   fn shapes(self) -> Vec<Shape> {
+      let center = (0.5, 0.5);
+      match self {
+         Self::Focal => vec![
+            Shape::Ring {
+               center,
+               radius: 0.42,
+            },
+            Shape::Ring {
+               center,
+               radius: 0.2,
+            },
+            Shape::Disc {
+               center: (0.4, 0.4),
+               radius: 0.05,
+            },
+         ],
+         Self::Aperture => {
+            let radius = 0.42;
+            let mut shapes = vec![Shape::Ring { center, radius }];
+            // 每片叶片的边从中心六边形的顶点延伸到外圈
+            let vertex = |i: usize| {
+               let angle = PI / 3.0 * i as f32 - PI / 2.0;
+               (center.0 + 0.17 * angle.cos(), center.1 + 0.17 * angle.sin())
+            };
+            for i in 0..6 {
+               let (from, next) = (vertex(i), vertex(i + 1));
+               let direction = (next.0 - from.0, next.1 - from.1);
+               let to = extend_to_circle(from, direction, center, radius);
+               shapes.push(Shape::Line { from, to });
+            }
+            shapes
+         }
+         Self::Shutter => {
+            let center = (0.5, 0.57);
+            vec![
+               Shape::Ring {
+                  center,
+                  radius: 0.36,
+               },
+               Shape::Line {
+                  from: (0.5, 0.08),
+                  to: (0.5, 0.18),
+               },
+               Shape::Line {
+                  from: (0.4, 0.06),
+                  to: (0.6, 0.06),
+               },
+               Shape::Line {
+                  from: center,
+                  to: (0.65, 0.4),
+               },
+               Shape::Disc {
+                  center,
+                  radius: 0.06,
+               },
+            ]
+         }
+         Self::Star | Self::EmptyStar => {
+            // 外顶点和内顶点交替的五角星，整体略微下移使视觉上居中
+            let points = (0..10)
+               .map(|i| {
+                  let radius = if i % 2 == 0 { 0.48 } else { 0.2 };
+                  let angle = PI / 5.0 * i as f32 - PI / 2.0;
+                  (0.5 + radius * angle.cos(), 0.54 + radius * angle.sin())
+               })
+               .collect();
+            vec![Shape::Polygon {
+               points,
+               filled: self == Self::Star,
+            }]
+         }
+         Self::Label(_) => vec![Shape::Disc {
+            center,
+            radius: 0.36,
+         }],
+         Self::Iso => {
+            let (left, top, right, bottom) = (0.08, 0.14, 0.92, 0.86);
+            vec![
+               Shape::Line {
+                  from: (left, top),
+                  to: (right, top),
+               },
+               Shape::Line {
+                  from: (right, top),
+                  to: (right, bottom),
+               },
+               Shape::Line {
+                  from: (right, bottom),
+                  to: (left, bottom),
+               },
+               Shape::Line {
+                  from: (left, bottom),
+                  to: (left, top),
+               },
+               Shape::Line {
+                  from: (left, bottom),
+                  to: (right, top),
+               },
+               // 左上的加号和右下的减号
+               Shape::Line {
+                  from: (0.2, 0.36),
+                  to: (0.4, 0.36),
+               },
+               Shape::Line {
+                  from: (0.3, 0.26),
+                  to: (0.3, 0.46),
+               },
+               Shape::Line {
+                  from: (0.6, 0.66),
+                  to: (0.8, 0.66),
+               },
+            ]
+         }
+      }
+   }
+}
+
+impl Shape {
+   /// 点到图形边缘的有向距离，在图形内部为负
+   fn distance(&self, (x, y): (f32, f32)) -> f32 {
+      match *self {
+         Shape::Polygon { ref points, filled } => {
+            let edge = points
+               .iter()
+               .zip(points.iter().cycle().skip(1))
+               .map(|(&from, &to)| segment_distance((x, y), from, to))
+               .fold(f32::INFINITY, f32::min);
+            match filled {
+               // 按射线法判断点是否在多边形内
+               true => match inside(points, (x, y)) {
+                  true => -edge,
+                  false => edge,
+               },
+               false => edge - STROKE / 2.0,
+            }
+         }
+         Shape::Ring { center, radius } => {
+            ((x - center.0).hypot(y - center.1) - radius).abs() - STROKE / 2.0
+         }
+         Shape::Disc { center, radius } => (x - center.0).hypot(y - center.1) - radius,
+         Shape::Line { from, to } => segment_distance((x, y), from, to) - STROKE / 2.0,
+      }
+   }
+}
+
+/// 点到线段的距离
+fn segment_distance((x, y): (f32, f32), from: (f32, f32), to: (f32, f32)) -> f32 {
+   let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+   let t =
+      (((x - from.0) * dx + (y - from.1) * dy) / (dx * dx + dy * dy)).clamp(0.0, 1.0);
+   (x - from.0 - dx * t).hypot(y - from.1 - dy * t)
+}
+
+/// 点是否在多边形内
+fn inside(points: &[(f32, f32)], (x, y): (f32, f32)) -> bool {
+   let mut inside = false;
+   for (&(x1, y1), &(x2, y2)) in points.iter().zip(points.iter().cycle().skip(1)) {
+      if (y1 > y) != (y2 > y) && x < x1 + (y - y1) / (y2 - y1) * (x2 - x1) {
+         inside = !inside;
+      }
+   }
+   inside
+}
+
+/// 从圆内的点`from`沿`direction`延伸到圆周上的点
+fn extend_to_circle(
+   from: (f32, f32),
+   direction: (f32, f32),
+   center: (f32, f32),
+   radius: f32,
+) -> (f32, f32) {
+   let (px, py) = (from.0 - center.0, from.1 - center.1);
+   let (dx, dy) = direction;
+   let a = dx * dx + dy * dy;
+   let b = 2.0 * (px * dx + py * dy);
+   let c = px * px + py * py - radius * radius;
+   let t = (-b + (b * b - 4.0 * a * c).sqrt()) / (2.0 * a);
+   (from.0 + dx * t, from.1 + dy * t)
+}
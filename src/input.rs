@@ -0,0 +1,373 @@
+use crate::{
+   DEFAULT_NAME_TEMPLATE, Exif, ExifCache, GlobPattern, LumixMarkError, Result,
+   name_template_glob, parse_name_template,
+};
+use clap::ValueEnum;
+use std::cmp::Reverse;
+use std::collections::HashSet;
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+/// 目录中的样式覆盖文件名，其中的字段覆盖全局的水印样式
+pub const DIR_STYLE_FILE: &str = ".lumix_mark.toml";
+
+/// 展开文件夹和通配符时的选项，命令行中直接指定的文件不受影响
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ScanOptions {
+   /// 跟随文件夹中指向文件或文件夹的符号链接，已扫描过的文件夹（如链接到上级形成循环）会跳过
+   pub follow_symlinks: bool,
+   /// 包含隐藏的文件和文件夹（`.`开头，如macOS的`.AppleDouble`、`._IMG.jpg`；Windows中还包括带有隐藏属性的）
+   pub include_hidden: bool,
+   /// 只包含这些扩展名（小写，不含`.`）的图片，为空时包含所有支持的格式
+   pub extensions: Vec<String>,
+   /// 排除匹配的文件和文件夹：不含分隔符时与名称比较，否则与相对输入文件夹的路径比较
+   pub exclude: Vec<GlobPattern>,
+   /// 之前生成的输出文件名对应的通配符（见[`name_template_glob`]），匹配的文件不作为输入；
+   /// 默认对应[`DEFAULT_NAME_TEMPLATE`]，即`mark_*.*`
+   pub outputs: Option<GlobPattern>,
+}
+
+impl Default for ScanOptions {
+   fn default() -> Self {
+      Self {
+         follow_symlinks: false,
+         include_hidden: false,
+         extensions: Vec::new(),
+         exclude: Vec::new(),
+         outputs: parse_name_template(DEFAULT_NAME_TEMPLATE)
+            .ok()
+            .and_then(|template| name_template_glob(&template)),
+      }
+   }
+}
+
+impl ScanOptions {
+   /// 文件夹`dir`中的`path`是否需要忽略：隐藏、被排除或位于这样的子文件夹中，扩展名不在`extensions`中，
+   /// 或是之前生成的输出
+   #[cfg(feature = "cli")]
+   pub(crate) fn ignores(&self, path: &Path, dir: &Path) -> bool {
+      let relative = path.strip_prefix(dir).unwrap_or(path);
+      !self.has_extension(path)
+         || self.is_output(path)
+         || relative.ancestors().any(|ancestor| {
+            !ancestor.as_os_str().is_empty()
+               && (!self.include_hidden && is_hidden(&dir.join(ancestor))
+                  || self.excluded(ancestor))
+         })
+   }
+
+   /// 文件扩展名是否在`extensions`中
+   fn has_extension(&self, path: &Path) -> bool {
+      self.extensions.is_empty()
+         || path.extension().is_some_and(|extension| {
+            let ext = extension.to_string_lossy().to_lowercase();
+            self.extensions.contains(&ext)
+         })
+   }
+
+   /// 文件名是否与`outputs`匹配
+   fn is_output(&self, path: &Path) -> bool {
+      let name = Path::new(path.file_name().unwrap_or_default());
+      self
+         .outputs
+         .as_ref()
+         .is_some_and(|pattern| pattern.matches(name))
+   }
+
+   /// 是否匹配`exclude`中的模式，`relative`为相对输入文件夹的路径
+   fn excluded(&self, relative: &Path) -> bool {
+      let name = Path::new(relative.file_name().unwrap_or_default());
+      self
+         .exclude
+         .iter()
+         .any(|pattern| match pattern.has_separator() {
+            true => pattern.matches(relative),
+            false => pattern.matches(name),
+         })
+   }
+}
+
+/// 批量处理时图片的排列顺序，决定处理、汇总和报告中的顺序
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum SortOrder {
+   /// 按路径排序
+   Name,
+   /// 按文件修改时间从早到晚排序
+   Mtime,
+   /// 按EXIF中的拍摄时间从早到晚排序，没有拍摄时间的图片排在最后
+   ExifDate,
+}
+
+impl SortOrder {
+   /// # 按该顺序排列图片
+   ///
+   /// 时间相同或缺失时按路径排序，相同的输入总是得到相同的顺序
+   pub fn sort(self, inputs: &mut [InputImage]) {
+      self.sort_with_cache(inputs, None)
+   }
+
+   /// 按该顺序排列图片，按拍摄时间排序时从`cache`中读取未改变的图片的EXIF信息
+   pub fn sort_with_cache(self, inputs: &mut [InputImage], cache: Option<&ExifCache>) {
+      match self {
+         SortOrder::Name => inputs.sort_by(|a, b| a.path.cmp(&b.path)),
+         SortOrder::Mtime => inputs.sort_by_cached_key(|input| {
+            let modified = fs::metadata(&input.path).and_then(|meta| meta.modified());
+            (modified.ok(), input.path.clone())
+         }),
+         SortOrder::ExifDate => inputs.sort_by_cached_key(|input| {
+            // EXIF中的时间格式为`2024:05:01 17:30:12`，按字符串比较即为时间顺序
+            let exif = match cache {
+               Some(cache) => cache.exif(&input.path),
+               None => Exif::from_image(&input.path),
+            };
+            let shoot_time = exif
+               .ok()
+               .map(|exif| exif.shoot_time.trim().to_string())
+               .filter(|time| !time.is_empty());
+            (
+               Reverse(shoot_time.is_some()),
+               shoot_time,
+               input.path.clone(),
+            )
+         }),
+      }
+   }
+}
+
+/// 待处理的图片
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InputImage {
+   /// 图片路径
+   pub path: PathBuf,
+   /// 图片所在目录相对输入目录的路径（包含输入目录名），直接指定的文件为空
+   pub relative_dir: PathBuf,
+   /// 输入目录到图片所在目录之间各层的样式覆盖文件，按从外到内的顺序依次覆盖
+   pub style_files: Vec<PathBuf>,
+}
+
+impl InputImage {
+   /// 直接指定的图片文件
+   pub fn new(path: PathBuf) -> Self {
+      Self {
+         path,
+         relative_dir: PathBuf::new(),
+         style_files: Vec::new(),
+      }
+   }
+   /// 输入文件夹`dir`中的图片，相对路径与展开文件夹时的规则一致
+   pub fn in_dir(path: PathBuf, dir: &Path) -> Self {
+      let parent = path.parent().unwrap_or(&path);
+      let relative_dir = parent
+         .strip_prefix(base_dir(dir))
+         .unwrap_or(parent)
+         .to_path_buf();
+      let mut dirs: Vec<&Path> = parent
+         .ancestors()
+         .take_while(|ancestor| ancestor.starts_with(dir))
+         .collect();
+      dirs.reverse();
+      let style_files = dirs.into_iter().filter_map(dir_style_file).collect();
+      Self {
+         path,
+         relative_dir,
+         style_files,
+      }
+   }
+}
+
+/// 目录中存在样式覆盖文件时返回其路径
+fn dir_style_file(dir: &Path) -> Option<PathBuf> {
+   let path = dir.join(DIR_STYLE_FILE);
+   path.is_file().then_some(path)
+}
+
+/// 计算相对路径的基准目录：输入目录的上级，使输出中保留输入目录名
+fn base_dir(dir: &Path) -> &Path {
+   match dir.file_name() {
+      Some(_) => dir.parent().unwrap_or(dir),
+      None => dir,
+   }
+}
+
+pub(crate) fn is_image_file(path: &Path) -> bool {
+   if let Some(extension) = path.extension() {
+      let ext = extension.to_string_lossy().to_lowercase();
+      matches!(ext.as_str(), "jpg" | "jpeg" | "png" | "tif" | "tiff")
+         || cfg!(feature = "heic") && matches!(ext.as_str(), "heic" | "heif")
+         || cfg!(feature = "raw")
+            && matches!(
+               ext.as_str(),
+               "rw2" | "rwl" | "arw" | "cr2" | "cr3" | "nef" | "dng" | "pef" | "raf"
+            )
+   } else {
+      false
+   }
+}
+
+/// 直接指定的文件还接受GIF/WebP（可能是动画，按`--multi-frame`处理），扫描文件夹时不包含
+fn is_explicit_image_file(path: &Path) -> bool {
+   is_image_file(path)
+      || path.extension().is_some_and(|extension| {
+         let ext = extension.to_string_lossy().to_lowercase();
+         matches!(ext.as_str(), "gif" | "webp")
+      })
+}
+
+/// 以`.`开头或带有隐藏属性（Windows）的文件和文件夹
+fn is_hidden(path: &Path) -> bool {
+   let dot_file = path
+      .file_name()
+      .is_some_and(|name| name.to_string_lossy().starts_with('.'));
+   dot_file || has_hidden_attribute(path)
+}
+
+#[cfg(windows)]
+fn has_hidden_attribute(path: &Path) -> bool {
+   use std::os::windows::fs::MetadataExt;
+   const FILE_ATTRIBUTE_HIDDEN: u32 = 0x2;
+   fs::symlink_metadata(path)
+      .is_ok_and(|metadata| metadata.file_attributes() & FILE_ATTRIBUTE_HIDDEN != 0)
+}
+
+#[cfg(not(windows))]
+fn has_hidden_attribute(_path: &Path) -> bool {
+   false
+}
+
+/// 递归收集图片时的状态
+struct DirectoryScan<'a> {
+   options: &'a ScanOptions,
+   /// 已扫描过的文件夹的规范路径，跟随符号链接时避免循环和重复
+   visited: HashSet<PathBuf>,
+   result: Vec<InputImage>,
+}
+
+impl DirectoryScan<'_> {
+   /// # 递归收集目录中的图片
+   ///
+   /// `root`为输入文件夹，`base_dir`为计算相对路径的基准目录，`style_files`为上级目录中的样式覆盖文件，
+   /// `pattern`为通配符中`root`之后的部分
+   fn expand(
+      &mut self,
+      dir_path: &Path,
+      root: &Path,
+      style_files: &[PathBuf],
+      pattern: Option<&GlobPattern>,
+   ) -> Result<()> {
+      let entries = fs::read_dir(dir_path)?;
+      let mut style_files = style_files.to_vec();
+      style_files.extend(dir_style_file(dir_path));
+
+      for entry in entries {
+         let entry = entry?;
+         let path = entry.path();
+         let relative = path.strip_prefix(root).unwrap_or(&path);
+         if !self.options.include_hidden && is_hidden(&path) {
+            tracing::debug!("忽略隐藏文件：{:?}", path);
+            continue;
+         }
+         if !self.options.follow_symlinks && entry.file_type()?.is_symlink() {
+            tracing::debug!("忽略符号链接：{:?}", path);
+            continue;
+         }
+         if self.options.excluded(relative) {
+            tracing::debug!("已排除：{:?}", path);
+            continue;
+         }
+         if path.is_file() {
+            if self.options.is_output(&path) {
+               tracing::info!("文件名与输出文件名模板匹配，忽略：{:?}", path);
+               continue;
+            }
+            let selected = match pattern {
+               Some(pattern) => {
+                  pattern.matches(relative) && is_explicit_image_file(&path)
+               }
+               None => is_image_file(&path),
+            };
+            if selected && self.options.has_extension(&path) {
+               // 如果是图片文件，添加到结果中
+               let relative_dir =
+                  dir_path.strip_prefix(base_dir(root)).unwrap_or(dir_path);
+               self.result.push(InputImage {
+                  relative_dir: relative_dir.to_path_buf(),
+                  style_files: style_files.clone(),
+                  path,
+               });
+            }
+         } else if path.is_dir() {
+            // 如果是目录，递归处理；通过符号链接回到已扫描过的文件夹时跳过
+            if pattern.is_some_and(|pattern| !pattern.may_match_under(relative)) {
+               continue;
+            }
+            if !self.visited.insert(fs::canonicalize(&path)?) {
+               tracing::info!("文件夹已扫描过（符号链接循环或重复），跳过：{:?}", path);
+               continue;
+            }
+            self.expand(&path, root, &style_files, pattern)?;
+         }
+      }
+      Ok(())
+   }
+}
+
+/// 展开命令行中的图片和文件夹，文件夹中的图片会保留相对输入目录的路径；
+/// 使用默认的[`ScanOptions`]，不跟随符号链接、不包含隐藏文件
+pub fn expand_directories_images(images: &[PathBuf]) -> Result<Vec<InputImage>> {
+   expand_directories_images_with(images, &ScanOptions::default())
+}
+
+/// # 按`options`展开命令行中的图片、文件夹和通配符
+///
+/// 不存在且包含通配符的输入（如`photos/**/*.jpg`）从开头不含通配符的文件夹开始查找匹配的文件，
+/// 相对路径与展开该文件夹时相同；见[`expand_directories_images`]
+pub fn expand_directories_images_with(
+   images: &[PathBuf],
+   options: &ScanOptions,
+) -> Result<Vec<InputImage>> {
+   let mut scan = DirectoryScan {
+      options,
+      visited: HashSet::new(),
+      result: Vec::new(),
+   };
+   for path in images {
+      let text = path.to_string_lossy();
+      if path.exists() && path.is_file() && is_explicit_image_file(path) {
+         scan.result.push(InputImage::new(path.clone()));
+      } else if path.is_dir() {
+         if scan.visited.insert(fs::canonicalize(path)?) {
+            scan.expand(path, path, &[], None)?;
+         }
+      } else if !path.exists() && GlobPattern::is_glob(&text) {
+         let (root, pattern) =
+            GlobPattern::split_root(&text).map_err(LumixMarkError::InvalidConfig)?;
+         if root.is_dir() {
+            scan.expand(&root, &root, &[], Some(&pattern))?;
+         }
+      }
+   }
+   Ok(scan.result)
+}
+
+/// # 读取文件列表
+///
+/// `path`为`-`时从标准输入读取。每行一个路径（忽略空行和行尾的`\r`），
+/// 包含`\0`时按`\0`分隔（`find -print0`、`fd -0`的输出）；列表中的文件夹和通配符与命令行中的一样展开
+pub fn read_file_list(path: &Path) -> Result<Vec<PathBuf>> {
+   let mut text = String::new();
+   match path.as_os_str() == "-" {
+      true => io::stdin().lock().read_to_string(&mut text)?,
+      false => fs::File::open(path)?.read_to_string(&mut text)?,
+   };
+   let separator = match text.contains('\0') {
+      true => '\0',
+      false => '\n',
+   };
+   Ok(text
+      .split(separator)
+      .map(|line| line.trim_end_matches('\r'))
+      .filter(|line| !line.trim().is_empty())
+      .map(PathBuf::from)
+      .collect())
+}
@@ -0,0 +1,59 @@
+use crate::{ElementKind, Empty, LumixMark, LumixMarkCli};
+use tracing::info;
+
+impl LumixMarkCli {
+   /// # 打印图片解析出的EXIF信息、画布布局和水印中的元素
+   ///
+   /// 按当前的样式和参数完整绘制一次水印但不保存，用于排查水印显示异常的原因
+   pub fn inspect(&self) -> Empty {
+      for input in &self.inputs {
+         let path = &input.path;
+         info!("检查图片：{:?}", path);
+         let style = self.style_for(input)?;
+         let mut lumix_mark =
+            LumixMark::from_image_with_style(path, self.ratio, &style)?;
+         lumix_mark.draw_logo_exif(&style)?;
+         let (start_x, start_y, end_x, end_y) = lumix_mark.mark_area;
+         info!(
+            "画布：{}x{}，水印区域：({}, {}) - ({}, {})，水印高度：{}",
+            lumix_mark.width,
+            lumix_mark.height,
+            start_x,
+            start_y,
+            end_x,
+            end_y,
+            lumix_mark.mark_height
+         );
+         let exif = format!("{:#?}", lumix_mark.exif).replace('\n', "\n  ");
+         info!("EXIF：{}", exif);
+         info!("机型：{}", lumix_mark.exif.model_title);
+         if let Some(provenance) = &lumix_mark.provenance {
+            info!(
+               "来源信息：lumix_mark {}，预设：{}，样式哈希：{}",
+               provenance.version,
+               provenance.preset.as_deref().unwrap_or("无"),
+               provenance.style_hash
+            );
+         }
+         info!("Exif信息：{}", lumix_mark.exif.render(&style.exif_template));
+         if let Some(secondary_text) = lumix_mark.secondary_text(&style) {
+            info!("附加信息：{}", secondary_text);
+         }
+         info!("绘制的元素：");
+         for element in &lumix_mark.elements {
+            let name = match &element.kind {
+               ElementKind::Text(text) => format!("文字 {:?}", text),
+               ElementKind::Divider => "分隔线".to_string(),
+               ElementKind::Logo => "Logo".to_string(),
+               ElementKind::Histogram => "直方图".to_string(),
+               ElementKind::QrCode => "二维码".to_string(),
+            };
+            info!(
+               "  {}：位置({}, {})，尺寸{}x{}",
+               name, element.x, element.y, element.width, element.height
+            );
+         }
+      }
+      Ok(())
+   }
+}
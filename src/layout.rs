@@ -0,0 +1,288 @@
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+/// 水印条所在的边
+#[derive(
+   Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, ValueEnum,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum MarkPosition {
+   /// 照片下方
+   #[default]
+   Bottom,
+   /// 照片上方
+   Top,
+   /// 照片左侧，文字从下往上排列
+   Left,
+   /// 照片右侧，文字从上往下排列
+   Right,
+}
+
+/// 水印内容的排列方式
+#[derive(
+   Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, ValueEnum,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum Layout {
+   /// 机型靠左，Logo、分隔线和Exif信息靠右
+   #[default]
+   Split,
+   /// 机型在上、Exif信息在下，与Logo一起水平居中
+   Centered,
+}
+
+/// 文字方向
+#[derive(
+   Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, ValueEnum,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum TextDirection {
+   /// 按文字内容判断，阿拉伯文、希伯来文等文字从右向左排列
+   #[default]
+   Auto,
+   /// 从左向右
+   Ltr,
+   /// 从右向左，水印内容的排列也左右镜像
+   Rtl,
+   /// 左右两侧的水印条中字符保持正立、从上往下竖排，上下水印条中与auto相同
+   Vertical,
+}
+
+/// 边框样式
+#[derive(
+   Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, ValueEnum,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum Frame {
+   /// 只在一侧添加水印条
+   #[default]
+   Strip,
+   /// 拍立得样式：照片四周添加边框，水印条所在的一侧更宽
+   Polaroid,
+   /// 不扩展画布，在照片的一角叠加Logo和Exif信息
+   Overlay,
+}
+
+/// 叠加水印所在的角
+#[derive(
+   Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, ValueEnum,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum Corner {
+   TopLeft,
+   TopRight,
+   BottomLeft,
+   #[default]
+   BottomRight,
+}
+
+impl Corner {
+   /// 是否靠右
+   pub fn is_right(self) -> bool {
+      matches!(self, Corner::TopRight | Corner::BottomRight)
+   }
+
+   /// # 计算叠加水印的画布布局
+   ///
+   /// 画布与照片大小相同，水印区域为照片顶部或底部厚度为`mark_height`的横条
+   ///
+   /// # 参数
+   /// * `width` - 照片宽度
+   /// * `height` - 照片高度
+   /// * `mark_height` - 水印条的厚度
+   pub fn overlay_layout(
+      self,
+      width: u32,
+      height: u32,
+      mark_height: u32,
+   ) -> CanvasLayout {
+      let mark_height = mark_height.min(height);
+      let mark_area = match self {
+         Corner::TopLeft | Corner::TopRight => (0, 0, width, mark_height),
+         Corner::BottomLeft | Corner::BottomRight => {
+            (0, height - mark_height, width, height)
+         }
+      };
+      CanvasLayout {
+         width,
+         height,
+         photo: (0, 0),
+         mark_area,
+      }
+   }
+}
+
+impl Frame {
+   /// # 计算照片四周的边框宽度
+   ///
+   /// # 参数
+   /// * `mark_height` - 水印条的厚度
+   /// * `border_ratio` - 边框相对水印条厚度的比例
+   pub fn border(self, mark_height: u32, border_ratio: f32) -> u32 {
+      match self {
+         Frame::Strip | Frame::Overlay => 0,
+         Frame::Polaroid => (mark_height as f32 * border_ratio) as u32,
+      }
+   }
+}
+
+/// # 水平镜像
+///
+/// 文字方向为从右向左时，将水印元素的横坐标在`start`到`end`之间左右镜像
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Mirror {
+   start: i32,
+   end: i32,
+   enabled: bool,
+}
+
+impl Mirror {
+   pub(crate) fn new(direction: TextDirection, start: u32, end: u32) -> Self {
+      Self {
+         start: start as i32,
+         end: end as i32,
+         enabled: direction == TextDirection::Rtl,
+      }
+   }
+
+   /// 左边缘为`x`、宽度为`width`的元素镜像后的左边缘
+   pub(crate) fn x(self, x: i32, width: u32) -> i32 {
+      match self.enabled {
+         true => self.start + self.end - x - width as i32,
+         false => x,
+      }
+   }
+}
+
+/// 画布布局：画布尺寸、照片位置和水印区域
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CanvasLayout {
+   /// 画布宽度
+   pub width: u32,
+   /// 画布高度
+   pub height: u32,
+   /// 照片在画布中的位置
+   pub photo: (u32, u32),
+   /// 水印区域（左、上、右、下）
+   pub mark_area: (u32, u32, u32, u32),
+}
+
+/// 水印中绘制的元素种类
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ElementKind {
+   /// 文字及其内容
+   Text(String),
+   /// 分隔线
+   Divider,
+   /// Logo
+   Logo,
+   /// 直方图
+   Histogram,
+   /// 二维码
+   QrCode,
+}
+
+/// 水印中绘制的元素及其在画布中的外接矩形
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MarkElement {
+   pub kind: ElementKind,
+   pub x: u32,
+   pub y: u32,
+   pub width: u32,
+   pub height: u32,
+}
+
+impl MarkElement {
+   /// # 转换为画布中的位置
+   ///
+   /// 元素在横向的水印条中绘制，左右两侧的水印条绘制前旋转为横向，需要旋转回画布方向
+   ///
+   /// # 参数
+   /// * `position` - 水印条所在的边
+   /// * `mark_area` - 水印区域（左、上、右、下）
+   pub(crate) fn into_canvas(
+      self,
+      position: MarkPosition,
+      (start_x, start_y, end_x, end_y): (u32, u32, u32, u32),
+   ) -> Self {
+      let (area_width, area_height) = (end_x - start_x, end_y - start_y);
+      let (x, y, width, height) = match position {
+         MarkPosition::Bottom | MarkPosition::Top => {
+            (self.x, self.y, self.width, self.height)
+         }
+         MarkPosition::Left => (
+            self.y,
+            area_height.saturating_sub(self.x + self.width),
+            self.height,
+            self.width,
+         ),
+         MarkPosition::Right => (
+            area_width.saturating_sub(self.y + self.height),
+            self.x,
+            self.height,
+            self.width,
+         ),
+      };
+      Self {
+         kind: self.kind,
+         x: start_x + x,
+         y: start_y + y,
+         width,
+         height,
+      }
+   }
+}
+
+impl MarkPosition {
+   /// 水印条是否竖向排列
+   pub fn is_vertical(self) -> bool {
+      matches!(self, MarkPosition::Left | MarkPosition::Right)
+   }
+
+   /// # 计算画布布局
+   ///
+   /// 水印条所在的一侧宽度为`mark_height`，其余三侧宽度为`border`，
+   /// 水印区域与照片边缘对齐
+   ///
+   /// # 参数
+   /// * `width` - 照片宽度
+   /// * `height` - 照片高度
+   /// * `mark_height` - 水印条的厚度
+   /// * `border` - 其余三侧的边框宽度
+   pub fn layout(
+      self,
+      width: u32,
+      height: u32,
+      mark_height: u32,
+      border: u32,
+   ) -> CanvasLayout {
+      let b = border;
+      let m = mark_height;
+      match self {
+         MarkPosition::Bottom => CanvasLayout {
+            width: width + b * 2,
+            height: height + b + m,
+            photo: (b, b),
+            mark_area: (b, height + b, width + b, height + b + m),
+         },
+         MarkPosition::Top => CanvasLayout {
+            width: width + b * 2,
+            height: height + b + m,
+            photo: (b, m),
+            mark_area: (b, 0, width + b, m),
+         },
+         MarkPosition::Left => CanvasLayout {
+            width: width + b + m,
+            height: height + b * 2,
+            photo: (m, b),
+            mark_area: (0, b, m, height + b),
+         },
+         MarkPosition::Right => CanvasLayout {
+            width: width + b + m,
+            height: height + b * 2,
+            photo: (b, b),
+            mark_area: (width + b, b, width + b + m, height + b),
+         },
+      }
+   }
+}
@@ -1,167 +1,1486 @@
-use ab_glyph::FontRef;
-use clap::Parser;
-use image::codecs::jpeg::JpegEncoder;
-use image::imageops::{FilterType, resize};
-use image::{GenericImage, Rgb, RgbImage, load_from_memory};
-use imageproc::drawing::{draw_filled_rect_mut, draw_text_mut, text_size};
-use imageproc::rect::Rect;
-use rayon::iter::ParallelIterator;
-use rayon::prelude::*;
-use rexif::ExifTag::*;
-use rexif::{ExifEntry, ExifTag, parse_buffer, parse_file};
+use anchor::Anchoring;
+use clap::{ArgAction, Parser, Subcommand};
+use depth::{DeepPhoto, Rgb16Image};
+use divider::PlacedDivider;
+use draw::{Blend, Logo};
+use fit::{FitRow, FitText, fit_texts};
+use font::{FontStack, MarkFonts};
+use image::codecs::avif::AvifEncoder;
+use image::codecs::png::PngEncoder;
+use image::codecs::tiff::TiffEncoder;
+use image::codecs::webp::WebPEncoder;
+use image::imageops::{FilterType, rotate90, rotate270};
+use image::{
+   DynamicImage, GenericImage, GenericImageView, ImageEncoder, ImageError, ImageReader,
+   RgbImage,
+};
+use jpeg_encoder::ColorType;
+use layout::Mirror;
+#[cfg(feature = "cli")]
+use rayon::{ThreadPoolBuilder, prelude::*};
+use std::borrow::Cow;
 use std::cmp::min;
+use std::collections::HashMap;
+use std::ffi::OsString;
 use std::fs;
-use std::fs::File;
 use std::io::ErrorKind::InvalidInput;
-use std::io::{BufWriter, Error};
+use std::io::{self, Cursor, Error, Read, Write};
+use std::mem;
+use std::num::NonZeroUsize;
+use std::ops::ControlFlow;
 use std::path::{Path, PathBuf};
+#[cfg(feature = "cli")]
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+#[cfg(feature = "cli")]
+use std::time::Instant;
+use std::time::SystemTime;
+use text::Flow;
+use tiled::TiledPhoto;
+#[cfg(feature = "cli")]
+use tracing::warn;
+use tracing::{error, info, info_span};
 
-pub type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+mod anchor;
+#[cfg(feature = "async")]
+mod async_batch;
+mod cache;
+mod caption;
+mod color;
+mod compare;
+mod date;
+mod depth;
+mod divider;
+mod draw;
+mod error;
+pub mod exif;
+mod exit;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+mod fill;
+mod fit;
+mod font;
+mod frames;
+mod glob;
+mod histogram;
+mod icon;
+mod input;
+mod inspect;
+mod layout;
+mod location;
+#[cfg(feature = "cli")]
+mod logging;
+mod logo;
+#[cfg(any(feature = "cli", feature = "async"))]
+mod memory;
+mod metadata;
+mod model;
+mod output;
+mod pipeline;
+mod preset;
+mod provenance;
+mod qr;
+#[cfg(feature = "raw")]
+mod raw;
+mod report;
+#[cfg(feature = "serve")]
+mod serve;
+mod shadow;
+mod sharpen;
+#[cfg(feature = "cli")]
+mod sheet;
+mod sink;
+mod state;
+mod style;
+mod template;
+#[cfg(feature = "test-utils")]
+pub mod testing;
+mod text;
+mod tiled;
+mod unmark;
+#[cfg(feature = "upload")]
+mod upload;
+#[cfg(feature = "wasm")]
+mod wasm;
+#[cfg(feature = "cli")]
+mod watch;
+
+pub use anchor::{Align, Anchor, Anchors};
+pub use cache::ExifCache;
+pub use color::Color;
+pub use compare::CompareLayout;
+pub use date::{DateFormat, ShootTime, TimeZone};
+pub use divider::{Divider, DividerSlot, DividerSpec};
+pub use error::LumixMarkError;
+pub use exif::{Exif, ExifOverride};
+pub use exit::ExitStatus;
+pub use fill::{Fill, GradientDirection};
+pub use font::FontData;
+pub use frames::{MultiFrame, MultiFramePolicy};
+pub use glob::GlobPattern;
+use histogram::HistogramBins;
+pub use histogram::{Histogram, HistogramChannels};
+use icon::ExifIcon;
+pub use input::{
+   DIR_STYLE_FILE, InputImage, ScanOptions, SortOrder, expand_directories_images,
+   expand_directories_images_with, read_file_list,
+};
+pub use layout::{
+   CanvasLayout, Corner, ElementKind, Frame, Layout, MarkElement, MarkPosition,
+   TextDirection,
+};
+pub use location::{CoordinateFormat, Geocoder, GpsPosition, SharedGeocoder};
+pub use logo::{
+   CAMERA_LOGO, L_MOUNT_LOGO, LogoFilter, LogoSize, LogoSource, logo_for_camera,
+};
+#[cfg(feature = "cli")]
+use memory::{MemoryBudget, estimate_memory};
+pub use metadata::{MarkedPolicy, Metadata, SidecarPolicy, is_marked, xmp_property};
+use metadata::{mark_jpeg, mark_png, mark_tiff, mark_webp};
+pub use model::ModelNames;
+pub use output::{
+   ChromaSubsampling, IccPolicy, OutputFormat, OverwritePolicy, SaveOptions,
+   parse_file_size,
+};
+use output::{
+   convert_to_srgb, convert_to_srgb_16, fit_file_size, jpeg_dimensions, jpeg_encoder,
+};
+pub use pipeline::{Pipeline, PipelineContext, Stage, StandardStage};
+pub use preset::{BUILTIN_PRESETS, Preset, PresetRegistry, user_preset_dir};
+pub use provenance::Provenance;
+pub use qr::QrCode;
+#[cfg(feature = "raw")]
+use raw::{decode_raw, is_raw};
+use report::TaskDetails;
+pub use report::{Dimensions, ExifSummary, ReportRecord, ReportStatus};
+pub use shadow::Shadow;
+pub use sharpen::Sharpen;
+#[cfg(feature = "cli")]
+pub use sheet::SheetOptions;
+pub use sink::{DirectorySink, OutputSink, ZipSink};
+pub use state::RunState;
+pub use style::{MarkStyle, MarkStyleBuilder, StyleRef, Theme};
+pub use template::Template;
+#[cfg(feature = "upload")]
+pub use upload::{UploadSink, UploadTarget};
+
+pub type Result<T> = std::result::Result<T, LumixMarkError>;
 pub type Empty = Result<()>;
-/// L卡口Logo图
-static LOGO_BYTES: &[u8] = include_bytes!("../images/logo.jpg");
 /// 字体文件
-static FONT_BYTES: &[u8] = include_bytes!("../fonts/MiSansLatin-Demibold.ttf");
+pub(crate) static FONT_BYTES: &[u8] =
+   include_bytes!("../fonts/MiSansLatin-Demibold.ttf");
 
-fn is_image_file(path: &Path) -> bool {
-   if let Some(file_name) = path.file_name() {
-      let start_mark = file_name.to_string_lossy().starts_with("mark_");
-      if start_mark {
-         println!("======>mark_开头的文件忽略：{:?}", path);
-         return false;
+/// 为配置错误添加说明，已经是配置错误时不重复错误类型的前缀
+fn config_error(context: String, err: LumixMarkError) -> LumixMarkError {
+   match err {
+      LumixMarkError::InvalidConfig(msg) => {
+         LumixMarkError::InvalidConfig(format!("{context}：{msg}"))
       }
-   }
-   if let Some(extension) = path.extension() {
-      let ext = extension.to_string_lossy().to_lowercase();
-      matches!(ext.as_str(), "jpg" | "jpeg")
-   } else {
-      false
+      err => LumixMarkError::InvalidConfig(format!("{context}：{err}")),
    }
 }
 
-fn expand_directory_images(dir_path: &Path, result: &mut Vec<PathBuf>) -> Result<()> {
-   let entries = fs::read_dir(dir_path)?;
+/// 默认的输出文件名模板
+pub const DEFAULT_NAME_TEMPLATE: &str = "mark_{stem}.{ext}";
 
-   for entry in entries {
-      let path = entry?.path();
-      if path.is_file() && is_image_file(&path) {
-         // 如果是图片文件，添加到结果中
-         result.push(path);
-      } else if path.is_dir() {
-         // 如果是目录，递归处理
-         expand_directory_images(&path, result)?;
-      }
-   }
-   Ok(())
+/// # 解析输出文件名模板
+///
+/// 除EXIF占位符外，还可以使用`{stem}`（原文件名）和`{ext}`（输出格式扩展名）
+pub fn parse_name_template(template: &str) -> Result<Template> {
+   let fields: Vec<&str> = ["stem", "ext"]
+      .into_iter()
+      .chain(Exif::FIELDS.iter().copied())
+      .collect();
+   Template::parse(template, &fields)
 }
 
-fn expand_directories_images(images: &mut Vec<PathBuf>) -> Result<()> {
-   let mut expanded_paths = Vec::new();
-   for path in images.drain(..) {
-      if path.exists() && path.is_file() && is_image_file(&path) {
-         expanded_paths.push(path);
-      } else if path.is_dir() {
-         expand_directory_images(&path, &mut expanded_paths)?;
-      }
+/// # 文件名模板生成的输出文件对应的通配符
+///
+/// 占位符替换为`*`，扫描文件夹时排除之前生成的输出。只有模板仅使用`{stem}`和`{ext}`、
+/// 且除`.`外还有其他文字（如`mark_`、`_wm`）时才能从文件名区分输出和原图，否则返回`None`，
+/// 这时依靠输出中写入的标记跳过（见[`MarkedPolicy`]）
+///
+/// ```
+/// use lumix_mark::{name_template_glob, parse_name_template};
+/// use std::path::Path;
+///
+/// let glob = name_template_glob(&parse_name_template("{stem}_wm.{ext}").unwrap()).unwrap();
+/// assert!(glob.matches(Path::new("P1000123_wm.jpg")));
+/// assert!(!glob.matches(Path::new("P1000123.jpg")));
+/// assert!(name_template_glob(&parse_name_template("{date}_{stem}.{ext}").unwrap()).is_none());
+/// ```
+pub fn name_template_glob(template: &Template) -> Option<GlobPattern> {
+   let literal = template.render_with(str::to_string, |_| String::new());
+   let distinct = literal.chars().any(|c| c != '.')
+      && !literal.contains(std::path::is_separator)
+      && template
+         .fields()
+         .all(|field| field == "stem" || field == "ext");
+   if !distinct {
+      return None;
    }
-   // 替换图片列表
-   *images = expanded_paths;
-   Ok(())
+   // 转义字面量中的通配符字符
+   let escape = |text: &str| {
+      text
+         .chars()
+         .map(|c| match c {
+            '*' | '?' | '[' => format!("[{c}]"),
+            c => c.to_string(),
+         })
+         .collect()
+   };
+   template
+      .render_with(escape, |_| "*".to_string())
+      .parse()
+      .ok()
 }
 
-pub fn parse_path(file_path: &PathBuf, target_path: &PathBuf) -> Result<PathBuf> {
-   let file_name = file_path
-      .file_name()
-      .ok_or_else(|| Error::new(InvalidInput, "无效的文件路径"))?;
-   // 为文件名添加mark前缀
-   let marked_file_name = format!("mark_{}", file_name.to_string_lossy());
+/// 默认的Exif信息模板
+pub const DEFAULT_EXIF_TEMPLATE: &str = "{focal} {aperture} {shutter}S ISO{iso}";
 
-   // 判断target_path是否存在
-   if !target_path.exists() || !target_path.is_dir() {
-      fs::create_dir_all(target_path)?;
+/// # 解析水印中Exif信息的模板
+///
+/// 可以使用[`Exif::FIELDS`]中的占位符，如`{focal} | {aperture} | {shutter}s | ISO {iso}`
+pub fn parse_exif_template(template: &str) -> Result<Template> {
+   Template::parse(template, Exif::FIELDS)
+}
+
+/// # 根据文件名模板生成输出文件路径
+///
+/// # 参数
+/// * `file_path` - 原图路径
+/// * `target_path` - 输出文件夹
+/// * `name_template` - 输出文件名模板，见[`parse_name_template`]
+/// * `exif` - 原图的EXIF信息，用于替换EXIF占位符
+/// * `format` - 输出图片格式
+pub fn parse_path(
+   file_path: &Path,
+   target_path: &Path,
+   name_template: &Template,
+   exif: &Exif,
+   format: OutputFormat,
+) -> Result<PathBuf> {
+   let file_stem = file_path
+      .file_stem()
+      .ok_or_else(|| Error::new(InvalidInput, "无效的文件路径"))?
+      .to_string_lossy();
+   // 替换模板占位符，EXIF中的值可能包含路径分隔符等文件名中不允许的字符
+   let file_name = name_template.render(|field| match field {
+      "stem" => file_stem.to_string(),
+      "ext" => format.extension().to_string(),
+      field => sanitize_file_name(&exif.field(field).unwrap_or_default()),
+   });
+   if file_name.trim().is_empty() {
+      return Err(LumixMarkError::InvalidConfig(format!(
+         "文件名模板{name_template}生成的文件名为空：{file_path:?}"
+      )));
    }
-   // 拼接target_path和带前缀的文件名
-   Ok(target_path.join(marked_file_name))
+   // 拼接target_path和生成的文件名，目录在保存时创建
+   Ok(target_path.join(file_name))
+}
+
+/// 将文件名中不允许出现的字符替换为`_`
+fn sanitize_file_name(value: &str) -> String {
+   value
+      .trim()
+      .chars()
+      .map(|c| match c {
+         '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+         c if c.is_control() => '_',
+         c => c,
+      })
+      .collect()
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+   /// 为图片添加水印，省略子命令直接传入图片时等同于此命令
+   Mark {
+      /// 多张图片地址或者文件夹，使用空格分隔
+      images: Vec<PathBuf>,
+      /// 从标准输入读取一张图片，未指定--stdout时按文件名模板保存（{stem}为stdin）
+      #[arg(long, conflicts_with_all = ["images", "files_from"])]
+      stdin: bool,
+      /// 将处理后的一张图片写入标准输出，便于在管道中使用
+      #[arg(long, conflicts_with_all = ["dry_run", "target_zip"])]
+      #[cfg_attr(feature = "upload", arg(conflicts_with = "upload"))]
+      stdout: bool,
+   },
+   /// 打印图片解析出的EXIF信息、水印布局和各元素的位置，不保存图片
+   Inspect {
+      /// 多张图片地址或者文件夹，使用空格分隔
+      images: Vec<PathBuf>,
+   },
+   /// 去除lumix_mark添加的水印条和边框，恢复原图的方向，保存到输出文件夹
+   Unmark {
+      /// 多张lumix_mark输出的图片或者文件夹，使用空格分隔
+      images: Vec<PathBuf>,
+   },
+   /// 将多张图片添加水印后的缩略图排列为一张印样（网格海报），格子下方标注文件名和Exif信息
+   #[cfg(feature = "cli")]
+   Sheet {
+      /// 多张图片地址或者文件夹，使用空格分隔
+      images: Vec<PathBuf>,
+      #[command(flatten)]
+      options: SheetOptions,
+   },
+   /// 列出内置预设和用户预设文件夹中的预设
+   Presets,
+   /// 生成默认的水印样式配置文件
+   InitConfig {
+      /// 配置文件路径
+      #[arg(default_value = "lumix_mark.toml")]
+      path: PathBuf,
+      /// 覆盖已存在的配置文件
+      #[arg(long)]
+      force: bool,
+   },
+   /// 监视文件夹，为新增的图片自动添加水印（可配合其他选项使用）
+   #[cfg(feature = "cli")]
+   Watch {
+      /// 需要监视的文件夹
+      dir: PathBuf,
+      /// 文件停止变化多久后开始处理（毫秒），避免读取未写完的文件
+      #[arg(long, default_value_t = 1000)]
+      debounce: u64,
+   },
+   /// 以HTTP服务运行，POST /mark上传图片（可附带样式JSON），返回添加水印后的图片
+   #[cfg(feature = "serve")]
+   Serve {
+      /// 监听的端口
+      #[arg(long, default_value_t = 8080)]
+      port: u16,
+      /// 监听的地址，0.0.0.0允许其他设备访问
+      #[arg(long, default_value = "127.0.0.1")]
+      host: String,
+   },
 }
 
+/// 图片适用的各个样式覆盖文件及其修改时间
+type StyleFiles = Vec<(PathBuf, SystemTime)>;
+
+/// 给照片添加相机Logo和EXIF信息水印
 #[derive(Parser)]
-#[command(version)]
+#[command(version, args_conflicts_with_subcommands = true)]
 pub struct LumixMarkCli {
+   #[command(subcommand)]
+   pub command: Option<Command>,
    /// 多张图片地址或者文件夹，使用空格分隔
    pub images: Vec<PathBuf>,
-   #[arg(short, long, default_value = ".")]
+   #[arg(skip)]
+   /// 展开文件夹后的待处理图片
+   pub inputs: Vec<InputImage>,
+   #[arg(short, long, global = true, default_value = ".")]
    /// 输出到指定文件夹，不存在则会创建
    pub target_path: PathBuf,
-   #[arg(short, long, default_value_t = 75)]
-   /// 图片质量 （75 - 100）
+   #[arg(short, long, global = true, default_value_t = 75)]
+   /// 图片质量 （75 - 100），用于JPEG/AVIF
    pub quality: u8,
-   #[arg(short, long, default_value_t = 0.14)]
+   #[arg(short, long, global = true, default_value_t = 0.14)]
    /// 水印相当于短边的比率（0.1 - 0.15）
    pub ratio: f32,
+   #[arg(short, long, global = true, value_enum, default_value_t = OutputFormat::Jpeg)]
+   /// 输出图片格式（PNG/WebP/TIFF为无损，忽略质量参数，PNG/TIFF保持16位原图的位深）
+   pub format: OutputFormat,
+   #[arg(short, long, global = true)]
+   /// 保留原图的EXIF/XMP/IPTC元数据（仅JPEG输出）
+   pub keep_metadata: bool,
+   #[arg(long, global = true, value_name = "SIZE", value_parser = parse_file_size)]
+   /// JPEG输出的最大文件大小（如2MB、500KB），超出时降低质量，最高不超过--quality
+   pub max_size: Option<u64>,
+   #[arg(long, global = true, value_enum, default_value_t = ChromaSubsampling::S420)]
+   /// JPEG的色度抽样方式，444时彩色文字和Logo的边缘最清晰，420时文件最小
+   pub jpeg_subsampling: ChromaSubsampling,
+   #[arg(long, global = true)]
+   /// 编码为渐进式JPEG，网页加载时先显示模糊的完整图片
+   pub progressive: bool,
+   #[arg(long, global = true)]
+   /// 分块编码超大照片（如拼接的全景图）：照片不复制到画布中，编码JPEG时逐行读取，只在内存中绘制水印条；仅支持基线JPEG输出、纯色背景、没有投影和顶部或底部的水印条
+   pub tiled: bool,
+   #[arg(long, global = true, value_enum, default_value_t = IccPolicy::Embed)]
+   /// 原图带有ICC色彩配置文件（如Display P3）时，嵌入输出图片或将像素转换为sRGB
+   pub icc: IccPolicy,
+   #[arg(short, long, global = true)]
+   /// 使用自定义Logo图片（以svg特性编译时支持SVG），默认根据相机厂商自动选择
+   pub logo: Option<PathBuf>,
+   #[arg(long, global = true)]
+   /// 字体文件路径或已安装的字体名称（如"Helvetica Neue"），默认使用内置的MiSans Latin
+   pub font: Option<String>,
+   #[arg(long, global = true, value_name = "FONT")]
+   /// Exif信息和附加信息的字体文件路径或字体名称，默认与--font相同，可以与机型使用不同的字重
+   pub exif_font: Option<String>,
+   #[arg(long, global = true, value_name = "FONT")]
+   /// 后备字体的文件路径或名称，可以指定多次，主字体缺少的字符（如中文）依次使用后备字体绘制
+   pub fallback_font: Vec<String>,
+   #[arg(short, long, global = true)]
+   /// 水印样式配置文件（TOML），可通过init-config子命令生成
+   pub config: Option<PathBuf>,
+   #[arg(long, global = true, conflicts_with = "config")]
+   /// 使用内置预设（classic、minimal、dark、centered、polaroid）或用户预设作为水印样式，presets子命令列出所有预设
+   pub preset: Option<String>,
+   #[arg(long, global = true, value_enum)]
+   /// 水印条所在的边，覆盖配置文件中的设置 [默认: bottom]
+   pub position: Option<MarkPosition>,
+   #[arg(long, global = true, value_enum)]
+   /// 边框样式，polaroid为四周添加白色边框，overlay为叠加在照片上，覆盖配置文件中的设置 [默认: strip]
+   pub frame: Option<Frame>,
+   #[arg(long, global = true, value_enum)]
+   /// 水印内容的排列方式，覆盖配置文件中的设置 [默认: split]
+   pub layout: Option<Layout>,
+   #[arg(long, global = true, value_enum)]
+   /// 文字方向，rtl为从右向左并左右镜像水印内容，vertical为左右两侧水印条中的文字正立竖排 [默认: auto]
+   pub text_direction: Option<TextDirection>,
+   #[arg(long, global = true, value_enum)]
+   /// Logo与文字之间的分隔线样式，覆盖配置文件中的设置 [默认: line]
+   pub divider: Option<Divider>,
+   #[arg(long, global = true, value_enum)]
+   /// 叠加水印（--frame overlay）所在的角，覆盖配置文件中的设置 [默认: bottom-right]
+   pub corner: Option<Corner>,
+   #[arg(long, global = true)]
+   /// 文字、分隔线和Logo的不透明度（0 - 1），覆盖配置文件中的设置 [默认: 1]
+   pub opacity: Option<f32>,
+   #[arg(long, global = true, value_name = "COLOR")]
+   /// Logo绘制为该颜色的剪影（如white、#FFFFFFCC、rgb(240, 240, 240)），覆盖配置文件和配色主题中的设置
+   pub logo_tint: Option<Color>,
+   #[arg(long, global = true, value_name = "COLOR")]
+   /// 机型文字颜色，覆盖配置文件和配色主题中的设置
+   pub model_color: Option<Color>,
+   #[arg(long, global = true, value_name = "COLOR")]
+   /// Exif文字颜色，覆盖配置文件和配色主题中的设置
+   pub exif_color: Option<Color>,
+   #[arg(long, global = true, value_name = "COLOR")]
+   /// 拍摄时间、地点和版权信息等附加信息的文字颜色，覆盖配置文件和配色主题中的设置
+   pub date_color: Option<Color>,
+   #[arg(long, global = true, value_name = "COLOR")]
+   /// 分隔线颜色，覆盖配置文件和配色主题中的设置
+   pub divider_color: Option<Color>,
+   #[arg(long, global = true, value_name = "COLOR", conflicts_with_all = ["blur_background", "auto_background"])]
+   /// 水印条和边框的纯色背景，覆盖配置文件和配色主题中的设置
+   pub strip_color: Option<Color>,
+   #[arg(long, global = true, value_name = "RATIO")]
+   /// 机型文字大小（相对水印高度），覆盖配置文件中的设置
+   pub model_text_ratio: Option<f32>,
+   #[arg(long, global = true, value_name = "RATIO")]
+   /// Exif文字大小（相对水印高度），覆盖配置文件中的设置
+   pub exif_text_ratio: Option<f32>,
+   #[arg(long, global = true, value_name = "RATIO")]
+   /// 附加信息的文字大小（相对水印高度），覆盖配置文件中的设置
+   pub date_text_ratio: Option<f32>,
+   #[arg(long, global = true, value_name = "RATIO")]
+   /// 水印左右两侧的边距（相对水印高度），覆盖配置文件中的设置
+   pub padding_ratio: Option<f32>,
+   #[arg(long, global = true, value_name = "RATIO")]
+   /// Logo、分隔线、Exif文字之间的间距（相对水印高度），覆盖配置文件中的设置
+   pub gap_ratio: Option<f32>,
+   #[arg(long, global = true, value_name = "RATIO")]
+   /// 拍立得边框的宽度（相对水印高度），覆盖配置文件中的设置
+   pub border_ratio: Option<f32>,
+   #[arg(long, global = true, value_name = "RATIO")]
+   /// 照片四角的圆角半径（相对水印高度），覆盖配置文件中的设置
+   pub corner_radius_ratio: Option<f32>,
+   #[arg(long, global = true, value_name = "RATIO")]
+   /// 分隔线宽度（相对水印高度），覆盖配置文件中的设置
+   pub divider_width_ratio: Option<f32>,
+   #[arg(long, global = true, value_name = "RATIO")]
+   /// 分隔线高度（相对水印高度），覆盖配置文件中的设置
+   pub divider_height_ratio: Option<f32>,
+   #[arg(long, global = true, value_name = "SCALE")]
+   /// 按比例缩放配置文件中的Logo宽度和高度，如1.2为放大20%
+   pub logo_scale: Option<f32>,
+   #[arg(long, global = true)]
+   /// Logo的不透明度（0 - 1），覆盖配置文件中的设置
+   pub logo_opacity: Option<f32>,
+   #[arg(long, global = true, value_name = "PX")]
+   /// 输出图片长边的最大像素数（包含水印条和边框），超过时先缩小照片再绘制水印，覆盖配置文件中的设置
+   pub max_long_edge: Option<u32>,
+   #[arg(long, global = true)]
+   /// 照片因--max-long-edge缩小后的锐化强度（0 - 1较为自然），覆盖配置文件中的设置 [默认: 不锐化]
+   pub sharpen: Option<f32>,
+   #[arg(long, global = true, value_name = "PX")]
+   /// 锐化的半径（像素），覆盖配置文件中的设置 [默认: 1]
+   pub sharpen_radius: Option<f32>,
+   #[arg(long, global = true, value_enum, num_args = 0..=1, default_missing_value = "luminance", value_name = "CHANNELS")]
+   /// 在水印条右侧绘制照片的亮度（luminance）或RGB直方图，覆盖配置文件中的设置，叠加水印中不绘制
+   pub histogram: Option<HistogramChannels>,
+   #[arg(long, global = true, value_parser = parse_exif_template, value_name = "TEMPLATE")]
+   /// 在水印条一角绘制二维码，内容为按Exif信息填充的链接（如"https://example.com/{date}/{model}"），
+   /// 可用占位符与Exif信息的模板相同，覆盖配置文件中的设置，叠加水印中不绘制
+   pub qr_url: Option<Template>,
+   #[arg(long, global = true, value_parser = parse_exif_template, value_name = "TEMPLATE")]
+   /// 在水印条底部添加一行说明文字（如"Shot on {model} · {date}"），可用占位符与Exif信息的模板相同，
+   /// 过长时折为两行，覆盖配置文件中的设置
+   pub caption: Option<Template>,
+   #[arg(long, global = true, value_enum)]
+   /// 配色主题，dark为深色水印条、浅色文字和白色Logo，覆盖配置文件中的颜色
+   pub theme: Option<Theme>,
+   #[arg(long, global = true)]
+   /// 使用照片的模糊图像填充水印条和边框背景
+   pub blur_background: bool,
+   #[arg(long, global = true, conflicts_with = "blur_background")]
+   /// 按照片的主色调填充浅色的水印条和边框背景，分隔线使用主色调
+   pub auto_background: bool,
+   #[arg(short, long, global = true)]
+   /// 并行处理的线程数，默认使用全部CPU核心
+   pub par_count: Option<NonZeroUsize>,
+   #[arg(long, global = true, value_name = "SIZE", value_parser = parse_file_size)]
+   /// 同时处理的图片估计占用的内存上限（如4GB），超出时等待其他图片完成后再解码，与--par-count无关
+   pub max_memory: Option<u64>,
+   #[arg(long, global = true)]
+   /// 只打印每张图片的处理计划（尺寸、水印高度、输出路径、预计大小），不写入文件
+   pub dry_run: bool,
+   #[arg(long, global = true, value_name = "FILE")]
+   /// 批量处理结束后将每张图片的输入输出路径、尺寸、EXIF摘要、耗时、写入字节数和状态写入JSON文件
+   pub report: Option<PathBuf>,
+   #[arg(long, global = true, value_name = "FILE")]
+   /// 将图片的尺寸和EXIF信息按路径、修改时间和文件大小缓存到JSON文件，重复运行和监视文件夹时不必重新读取未改变的原图
+   pub exif_cache: Option<PathBuf>,
+   #[arg(long, global = true, value_name = "FILE")]
+   /// 每写入一张图片就将其记录到JSON文件，配合--resume在中断后继续处理
+   pub state_file: Option<PathBuf>,
+   #[arg(long, global = true, requires = "state_file")]
+   /// 读取--state-file记录的上次运行状态，跳过已成功写入、输入未改变且输出仍然存在的图片
+   pub resume: bool,
+   #[arg(long, global = true)]
+   /// 任一图片处理失败后不再开始处理其余图片（已开始的图片会继续完成），退出码为1
+   pub fail_fast: bool,
+   #[arg(short, long, global = true, value_enum, default_value_t = OverwritePolicy::Replace)]
+   /// 输出文件已存在时的处理方式：跳过、覆盖或追加数字后缀重命名
+   pub overwrite: OverwritePolicy,
+   #[arg(long, global = true, value_enum, default_value_t = MultiFramePolicy::First)]
+   /// 输入为GIF/WebP/APNG动画或内嵌视频的动态照片时的处理方式：只处理第一帧、报错或跳过
+   pub multi_frame: MultiFramePolicy,
+   #[arg(long, global = true, value_enum, default_value_t = MarkedPolicy::Skip)]
+   /// 输入图片已带有lumix_mark水印（如重新处理输出文件夹）时的处理方式：跳过或警告后再次添加
+   pub marked: MarkedPolicy,
+   #[arg(long, global = true, value_enum, default_value_t = SidecarPolicy::Prefer)]
+   /// 照片旁XMP附属文件（如P1000001.xmp）中的星级、标题、版权和拍摄时间等信息与照片不同时：附属文件优先、只补充缺少的值或忽略附属文件
+   pub sidecar: SidecarPolicy,
+   #[arg(long, global = true, value_enum, num_args = 0..=1, default_missing_value = "side-by-side", conflicts_with = "tiled")]
+   /// 输出原图与水印图左右（side-by-side）或上下（stacked）排列的对比图代替水印图，用于调整样式和预览效果
+   pub compare: Option<CompareLayout>,
+   #[arg(short = 's', long, global = true)]
+   /// 在输出文件夹中保留输入文件夹的目录结构
+   pub preserve_structure: bool,
+   // 预览按输出文件夹解析输出路径，与压缩包中的条目名不同
+   #[arg(long, global = true, value_name = "FILE", conflicts_with_all = ["resume", "dry_run"])]
+   /// 将输出图片直接写入ZIP压缩包（已存在时覆盖），按输入文件夹的目录结构命名条目
+   pub target_zip: Option<PathBuf>,
+   #[cfg(feature = "upload")]
+   #[arg(long, global = true, value_name = "URL", value_parser = UploadTarget::parse, conflicts_with_all = ["target_zip", "resume", "dry_run"])]
+   /// 将输出图片上传到S3兼容的对象存储（s3://bucket/prefix）或WebDAV（davs://host/path），不写入本地文件夹
+   pub upload: Option<UploadTarget>,
+   #[arg(long, global = true)]
+   /// 扫描文件夹时跟随符号链接，已扫描过的文件夹（符号链接循环）会跳过
+   pub follow_symlinks: bool,
+   #[arg(long, global = true)]
+   /// 扫描文件夹时包含隐藏的文件和文件夹（如macOS的.AppleDouble、._开头的文件）
+   pub include_hidden: bool,
+   #[arg(long, global = true, value_delimiter = ',', value_name = "EXT")]
+   /// 扫描文件夹和通配符时只包含这些扩展名的图片，如jpg,png,heic
+   pub ext: Vec<String>,
+   #[arg(long, global = true, value_name = "PATTERN")]
+   /// 扫描文件夹和通配符时排除匹配的文件和文件夹（如--exclude "*_edit.jpg" --exclude "raw/**"），可以指定多次
+   pub exclude: Vec<GlobPattern>,
+   #[arg(long, global = true, value_enum)]
+   /// 图片的处理顺序，也是汇总和报告中的顺序：按路径、修改时间或拍摄时间排序 [默认: 命令行和文件夹中的顺序]
+   pub sort: Option<SortOrder>,
+   #[arg(short, long, global = true, default_value = DEFAULT_NAME_TEMPLATE, value_parser = parse_name_template)]
+   /// 输出文件名模板，可用占位符：{stem} {ext} {make} {model} {title} {date} {time}
+   /// {focal} {aperture} {shutter} {iso} {program} {metering} {wb} {bias} {ev} {flash} {profile}
+   /// {headline} {rating} {label}
+   pub name_template: Template,
+   #[arg(long, global = true, value_parser = parse_exif_template)]
+   /// 水印中Exif信息的模板，可用占位符与文件名模板中的EXIF占位符相同，覆盖配置文件中的设置
+   /// [默认: "{focal} {aperture} {shutter}S ISO{iso}"]
+   pub exif_template: Option<Template>,
+   #[arg(long, global = true)]
+   /// 在Exif信息的焦距、光圈、快门速度和感光度前绘制图标
+   pub exif_icons: bool,
+   #[arg(long, global = true)]
+   /// 曝光补偿为0时仍在{ev}中显示为±0 EV，默认省略
+   pub show_zero_ev: bool,
+   #[arg(long, global = true)]
+   /// 在水印中显示拍摄时间
+   pub date: bool,
+   #[arg(long, global = true, value_parser = DateFormat::parse)]
+   /// 拍摄时间的显示格式（类似strftime，如"%Y.%m.%d %H:%M"），指定时显示拍摄时间
+   pub date_format: Option<DateFormat>,
+   #[arg(long, global = true, value_parser = TimeZone::parse, value_name = "ZONE")]
+   /// 显示拍摄时间的时区：local为相机记录的当地时间，utc或+08:00形式的时差按EXIF中的时差换算 [默认: local]
+   pub time_zone: Option<TimeZone>,
+   #[arg(long, global = true)]
+   /// 在水印中显示拍摄地点的GPS坐标
+   pub location: bool,
+   #[arg(long, global = true)]
+   /// 在水印中显示Lightroom等软件写入XMP（嵌入或.xmp附属文件）的星级和颜色标签
+   pub rating: bool,
+   #[arg(long, global = true, num_args = 0..=1, value_name = "TEXT")]
+   /// 在水印中显示版权信息，默认取自EXIF中的Copyright或Artist，也可以直接指定文字
+   pub copyright: Option<Option<String>>,
+   #[command(flatten)]
+   pub exif_override: ExifOverride,
+   #[arg(long, global = true)]
+   /// 照片没有EXIF信息（机型、Exif信息和附加信息均为空）时只绘制Logo
+   pub logo_only_without_exif: bool,
+   #[arg(long, global = true, value_name = "FILE")]
+   /// 从文件中读取待处理的图片和文件夹（每行一个，-为标准输入），与命令行中的图片一起处理，如find/fd的输出
+   pub files_from: Option<PathBuf>,
+   #[arg(long, conflicts_with_all = ["images", "files_from"])]
+   /// 从标准输入读取一张图片，未指定--stdout时按文件名模板保存（{stem}为stdin）
+   pub stdin: bool,
+   // 输出到文件夹以外的参数是全局参数，在子命令中不存在stdout，冲突关系只能声明在这里
+   #[arg(long, conflicts_with_all = ["dry_run", "target_zip"])]
+   #[cfg_attr(feature = "upload", arg(conflicts_with = "upload"))]
+   /// 将处理后的一张图片写入标准输出，便于在管道中使用
+   pub stdout: bool,
+   #[arg(short, long, global = true, action = ArgAction::Count)]
+   /// 输出调试信息，-v为各阶段的尺寸和耗时，-vv增加文字排版的细节
+   pub verbose: u8,
+   #[arg(skip)]
+   pub style: MarkStyle,
+   /// 处理每张图片的流水线，作为库使用时可以插入自定义阶段
+   #[arg(skip)]
+   pub pipeline: Pipeline,
+   /// 由`exif_cache`打开的EXIF缓存
+   #[arg(skip)]
+   pub cache: Option<ExifCache>,
+   /// 由`state_file`打开的运行状态
+   #[arg(skip)]
+   pub run_state: Option<RunState>,
+   /// 输出图片的写入目标，为空时写入输出文件夹；设置时按输入文件夹的目录结构命名输出
+   #[arg(skip)]
+   pub sink: Option<Box<dyn OutputSink>>,
+   /// 未设置`sink`时使用的输出文件夹
+   #[arg(skip)]
+   directory_sink: DirectorySink,
+   /// 命令行样式选项覆盖前的样式（配置文件和预设），样式覆盖文件合并到这里
+   #[arg(skip)]
+   base_style: MarkStyle,
+   /// 按样式覆盖文件和修改时间缓存合并后的样式，监视时修改覆盖文件会重新读取
+   #[arg(skip)]
+   dir_styles: Mutex<HashMap<StyleFiles, Arc<MarkStyle>>>,
 }
 
 impl LumixMarkCli {
+   /// 命令行参数对应的编码选项
+   pub fn save_options(&self) -> SaveOptions {
+      SaveOptions {
+         quality: self.quality,
+         keep_metadata: self.keep_metadata,
+         icc: self.icc,
+         max_size: self.max_size,
+         subsampling: self.jpeg_subsampling,
+         progressive: self.progressive,
+         tiled: self.tiled,
+         ..SaveOptions::default()
+      }
+   }
+   /// # 解析命令行参数，展开输入的文件夹并生成水印样式
+   ///
+   /// 配置文件、预设或字体无效时打印错误并以[`ExitStatus::ConfigError`]退出
    pub fn parse_image_list() -> Self {
-      let mut config = Self::parse();
-      expand_directories_images(&mut config.images).unwrap();
-      config
-   }
-   pub fn par_draw_logo_exif_task(&self) {
-      let _: Vec<_> = self
-         .images
-         .par_iter()
-         .filter_map(|path| match LumixMark::from_image(path, self.ratio) {
-            Ok(lumix_mark) => {
-               println!("======>开始处理图片：{:?}", path);
-               Some((lumix_mark, path))
+      Self::try_parse_image_list().unwrap_or_else(|err| {
+         eprintln!("===error===>{}", err);
+         std::process::exit(ExitStatus::ConfigError.code());
+      })
+   }
+   /// # 解析命令行参数，展开输入的文件夹并生成水印样式
+   ///
+   /// 读取输入文件夹、配置文件、预设或字体失败时返回错误
+   pub fn try_parse_image_list() -> Result<Self> {
+      Self::try_from_args(std::env::args_os())
+   }
+   /// # 解析指定的参数，展开输入的文件夹并生成水印样式
+   ///
+   /// 与[`LumixMarkCli::try_parse_image_list`]相同，第一个参数为程序名，用于测试或嵌入其他程序；
+   /// 参数无效时打印用法并退出
+   pub fn try_from_args<I, T>(args: I) -> Result<Self>
+   where
+      I: IntoIterator<Item = T>,
+      T: Into<OsString> + Clone,
+   {
+      let mut config = Self::parse_from(args);
+      match &mut config.command {
+         Some(Command::Mark {
+            images,
+            stdin,
+            stdout,
+         }) => {
+            config.images = mem::take(images);
+            config.stdin |= *stdin;
+            config.stdout |= *stdout;
+         }
+         Some(Command::Inspect { images }) => config.images = mem::take(images),
+         #[cfg(feature = "cli")]
+         Some(Command::Sheet { images, .. }) => config.images = mem::take(images),
+         _ => {}
+      }
+      // 日志输出位置取决于--stdout，合并子命令中的参数后再初始化
+      #[cfg(feature = "cli")]
+      config.init_logging();
+      if let Some(path) = &config.files_from {
+         let files = read_file_list(path)
+            .map_err(|err| config_error(format!("读取文件列表{path:?}失败"), err))?;
+         config.images.extend(files);
+      }
+      config.inputs =
+         expand_directories_images_with(&config.images, &config.scan_options())?;
+      config.cache = config.exif_cache.as_ref().map(ExifCache::open);
+      config.run_state = config
+         .state_file
+         .as_ref()
+         .map(|path| RunState::open(path, config.resume));
+      if let Some(path) = &config.target_zip {
+         config.sink = Some(Box::new(ZipSink::new(path, &config.target_path)));
+      }
+      #[cfg(feature = "upload")]
+      if let Some(target) = &config.upload {
+         let sink = UploadSink::new(target.clone(), &config.target_path)
+            .map_err(|err| config_error(format!("配置上传地址{target}失败"), err))?;
+         config.sink = Some(Box::new(sink));
+      }
+      if let Some(sort) = config.sort {
+         sort.sort_with_cache(&mut config.inputs, config.cache.as_ref());
+      }
+      if let Some(path) = &config.config {
+         config.style = MarkStyle::from_file(path)
+            .map_err(|err| config_error(format!("读取配置文件{path:?}失败"), err))?;
+      }
+      if let Some(name) = &config.preset {
+         config.style = PresetRegistry::load()
+            .and_then(|registry| registry.style(name))
+            .map_err(|err| config_error(format!("加载预设{name}失败"), err))?;
+      }
+      if let Some(path) = &config.logo {
+         config.style.logo = Some(LogoSource::File(path.clone()));
+      }
+      if let Some(font) = &config.font {
+         let font = FontData::load(font)
+            .map_err(|err| config_error(format!("加载字体{font:?}失败"), err))?;
+         config.style.font = Cow::Owned(font.bytes);
+         config.style.font_index = font.index;
+      }
+      if let Some(font) = &config.exif_font {
+         let font = FontData::load(font)
+            .map_err(|err| config_error(format!("加载字体{font:?}失败"), err))?;
+         config.style.exif_font = Some(font);
+      }
+      for font in &config.fallback_font {
+         let fallback = FontData::load(font)
+            .map_err(|err| config_error(format!("加载字体{font:?}失败"), err))?;
+         config.style.fallback_fonts.push(fallback);
+      }
+      // 样式覆盖文件合并到命令行选项覆盖前的样式上，见`style_for`
+      let mut style = config.style.clone();
+      config.apply_style_overrides(&mut style);
+      config.base_style = mem::replace(&mut config.style, style);
+      Ok(config)
+   }
+   /// # 将命令行中的样式选项覆盖到样式上
+   ///
+   /// 字体和Logo在解析参数时加载，合并样式覆盖文件时会保留，这里不再处理
+   fn apply_style_overrides(&self, style: &mut MarkStyle) {
+      if let Some(position) = self.position {
+         style.position = position;
+      }
+      if let Some(frame) = self.frame {
+         style.frame = frame;
+      }
+      if let Some(divider) = self.divider {
+         style.divider = divider;
+      }
+      if let Some(direction) = self.text_direction {
+         style.text_direction = direction;
+      }
+      if let Some(layout) = self.layout {
+         style.layout = layout;
+      }
+      if let Some(theme) = self.theme {
+         theme.apply(style);
+      }
+      if let Some(corner) = self.corner {
+         style.corner = corner;
+      }
+      if let Some(opacity) = self.opacity {
+         style.opacity = opacity;
+      }
+      if let Some(tint) = self.logo_tint {
+         style.logo_tint = Some(tint);
+      }
+      for (value, field) in [
+         (self.model_color, &mut style.model_color),
+         (self.exif_color, &mut style.exif_color),
+         (self.date_color, &mut style.date_color),
+         (self.divider_color, &mut style.divider_color),
+      ] {
+         if let Some(color) = value {
+            *field = color;
+         }
+      }
+      if let Some(color) = self.strip_color {
+         style.background = Fill::Solid(color);
+      }
+      for (value, field) in [
+         (self.model_text_ratio, &mut style.model_text_ratio),
+         (self.exif_text_ratio, &mut style.exif_text_ratio),
+         (self.date_text_ratio, &mut style.date_text_ratio),
+         (self.padding_ratio, &mut style.padding_ratio),
+         (self.gap_ratio, &mut style.gap_ratio),
+         (self.border_ratio, &mut style.border_ratio),
+         (self.corner_radius_ratio, &mut style.corner_radius_ratio),
+         (self.divider_width_ratio, &mut style.divider_width_ratio),
+         (self.divider_height_ratio, &mut style.divider_height_ratio),
+         (self.logo_opacity, &mut style.logo_opacity),
+      ] {
+         if let Some(ratio) = value {
+            *field = ratio;
+         }
+      }
+      if let Some(scale) = self.logo_scale {
+         style.logo_width_ratio *= scale;
+         style.logo_height_ratio *= scale;
+      }
+      if let Some(max_long_edge) = self.max_long_edge {
+         style.max_long_edge = Some(max_long_edge);
+      }
+      if self.sharpen.is_some() || self.sharpen_radius.is_some() {
+         let sharpen = style.sharpen.get_or_insert_default();
+         if let Some(amount) = self.sharpen {
+            sharpen.amount = amount;
+         }
+         if let Some(radius) = self.sharpen_radius {
+            sharpen.radius = radius;
+         }
+      }
+      if let Some(channels) = self.histogram {
+         style.histogram.get_or_insert_default().channels = channels;
+      }
+      if let Some(url) = &self.qr_url {
+         style.qr.get_or_insert_default().url = url.clone();
+      }
+      if self.blur_background {
+         style.background = Fill::blur();
+      }
+      if self.auto_background {
+         style.background = Fill::auto();
+      }
+      if let Some(template) = &self.exif_template {
+         style.exif_template = template.clone();
+      }
+      if let Some(template) = &self.caption {
+         style.caption = template.clone();
+      }
+      if let Some(format) = &self.date_format {
+         style.date_format = format.clone();
+      }
+      if let Some(zone) = self.time_zone {
+         style.time_zone = zone;
+      }
+      style.exif_override.merge(&self.exif_override);
+      style.exif_icons |= self.exif_icons;
+      style.show_zero_ev |= self.show_zero_ev;
+      style.logo_only_without_exif |= self.logo_only_without_exif;
+      style.show_date |= self.date || self.date_format.is_some();
+      style.show_location |= self.location;
+      style.show_rating |= self.rating;
+      if let Some(copyright) = &self.copyright {
+         style.show_copyright = true;
+         if copyright.is_some() {
+            style.copyright = copyright.clone();
+         }
+      }
+   }
+   /// 将默认水印样式写入配置文件
+   pub fn init_config(path: &Path, force: bool) -> Empty {
+      if path.exists() && !force {
+         return Err(LumixMarkError::InvalidConfig(format!(
+            "配置文件已存在：{path:?}，使用--force覆盖"
+         )));
+      }
+      MarkStyle::default().write_to_file(path)?;
+      info!("已生成配置文件：{:?}", path);
+      Ok(())
+   }
+   /// 扫描输入文件夹的选项
+   pub fn scan_options(&self) -> ScanOptions {
+      ScanOptions {
+         follow_symlinks: self.follow_symlinks,
+         include_hidden: self.include_hidden,
+         extensions: self
+            .ext
+            .iter()
+            .map(|ext| ext.trim().trim_start_matches('.').to_lowercase())
+            .collect(),
+         exclude: self.exclude.clone(),
+         outputs: name_template_glob(&self.name_template),
+      }
+   }
+   /// 图片的输出目录，保留目录结构时拼接图片相对输入目录的路径
+   pub fn target_dir(&self, input: &InputImage) -> PathBuf {
+      if self.preserve_structure || self.sink.is_some() {
+         self.target_path.join(&input.relative_dir)
+      } else {
+         self.target_path.clone()
+      }
+   }
+   /// # 图片使用的水印样式
+   ///
+   /// 图片所在的各层目录中有样式覆盖文件时，按从外到内的顺序覆盖到配置文件和预设的样式上，
+   /// 再应用命令行选项；覆盖文件没有修改时只读取一次
+   pub fn style_for(&self, input: &InputImage) -> Result<StyleRef<'_>> {
+      if input.style_files.is_empty() {
+         return Ok(StyleRef::Borrowed(&self.style));
+      }
+      let key = input
+         .style_files
+         .iter()
+         .map(|path| Ok((path.clone(), fs::metadata(path)?.modified()?)))
+         .collect::<Result<Vec<_>>>()?;
+      let mut dir_styles = self
+         .dir_styles
+         .lock()
+         .unwrap_or_else(|err| err.into_inner());
+      if let Some(style) = dir_styles.get(&key) {
+         return Ok(StyleRef::Shared(Arc::clone(style)));
+      }
+      let mut style = self.base_style.clone();
+      for path in &input.style_files {
+         let config = fs::read_to_string(path)?;
+         style = style.merge_toml(&config).map_err(|err| match err {
+            LumixMarkError::InvalidConfig(msg) => {
+               LumixMarkError::InvalidConfig(format!("样式覆盖文件{:?}：{}", path, msg))
             }
-            Err(err) => {
-               eprintln!("===error===>解析图片失败：{}, 图片地址：{:?}", err, path);
-               None
+            err => err,
+         })?;
+      }
+      self.apply_style_overrides(&mut style);
+      let style = Arc::new(style);
+      dir_styles.insert(key, Arc::clone(&style));
+      Ok(StyleRef::Shared(style))
+   }
+   /// 输出图片的写入目标
+   pub fn sink(&self) -> &dyn OutputSink {
+      self.sink.as_deref().unwrap_or(&self.directory_sink)
+   }
+   /// 读取图片尺寸和EXIF信息，指定了`exif_cache`时使用缓存，见[`LumixMark::probe`]
+   fn probe(&self, path: &Path) -> Result<(u32, u32, Exif)> {
+      match &self.cache {
+         Some(cache) => cache.probe(path),
+         None => LumixMark::probe(path),
+      }
+   }
+   /// 输出路径，文件名模板使用了EXIF占位符时需要先读取原图的EXIF
+   fn target_file(&self, input: &InputImage, exif: Option<&Exif>) -> Result<PathBuf> {
+      let needs_exif = self
+         .name_template
+         .fields()
+         .any(|field| field != "stem" && field != "ext");
+      let mut probed;
+      let exif = match exif {
+         Some(exif) => exif,
+         None if needs_exif => {
+            probed = self.probe(&input.path)?.2;
+            if let Some(sidecar) = self.sidecar.read(&input.path) {
+               probed.apply_sidecar(&sidecar, self.sidecar);
             }
-         })
-         .map(|(mut lumix_mark, path)| {
-            lumix_mark
-               .draw_logo_exif(
-                  0.35,
-                  FONT_BYTES,
-                  Color::Black,
-                  0.45,
-                  Color::RGB(50, 50, 50),
-                  0.3,
-                  0.12,
-                  Color::HEX("#969696"),
-                  0.01,
-                  0.25,
-                  LOGO_BYTES,
-                  0.35,
-                  0.35,
-               )
-               .unwrap();
-            lumix_mark
-               .save_with_quality(
-                  parse_path(path, &self.target_path).expect(&format!(
-                     "读写文件路径失败：target_path:{:?};path:{:?}",
-                     &self.target_path, path
-                  )),
-                  self.quality,
-               )
-               .expect(&format!(
-                  "保存文件失败：target_path:{:?};path:{:?}",
-                  &self.target_path, path
-               ));
-         })
-         .collect();
+            self.style_for(input)?.prepare_exif(&mut probed);
+            &probed
+         }
+         None => &Exif::default(),
+      };
+      parse_path(
+         &input.path,
+         &self.target_dir(input),
+         &self.name_template,
+         exif,
+         self.format,
+      )
+   }
+   /// 按命令行参数执行流水线，返回包含输出内容的处理状态
+   fn run_pipeline<'a>(
+      &'a self,
+      input: &InputImage,
+      file_bytes: &'a [u8],
+      style: &'a MarkStyle,
+   ) -> Result<PipelineContext<'a>> {
+      let mut context = PipelineContext::new(file_bytes, self.ratio, style)
+         .with_output(self.format, self.save_options())
+         .with_multi_frame(self.multi_frame)
+         .with_marked(self.marked)
+         .with_compare(self.compare)
+         .with_sidecar(self.sidecar.read(&input.path))
+         .with_sidecar_policy(self.sidecar);
+      self.pipeline.run(&mut context)?;
+      Ok(context)
+   }
+   /// 处理单张图片：解析、绘制水印并保存，返回输出文件路径
+   pub fn draw_logo_exif_task(&self, input: &InputImage) -> Result<TaskOutcome> {
+      self.mark_task(input, &mut TaskDetails::default())
+   }
+   /// 按`dry_run`处理或预览单张图片，并记录报告所需的信息
+   #[cfg(feature = "cli")]
+   fn run_task(
+      &self,
+      input: &InputImage,
+      details: &mut TaskDetails,
+   ) -> Result<TaskOutcome> {
+      match self.dry_run {
+         true => self.plan_task(input, details),
+         false => self.mark_task(input, details),
+      }
+   }
+   fn mark_task(
+      &self,
+      input: &InputImage,
+      details: &mut TaskDetails,
+   ) -> Result<TaskOutcome> {
+      let path = &input.path;
+      let _span = info_span!("image", path = ?path).entered();
+      let target = match self.claim_target(input)? {
+         ControlFlow::Continue(target) => target,
+         ControlFlow::Break(outcome) => return Ok(outcome),
+      };
+      let result =
+         fs::read(path)
+            .map_err(LumixMarkError::from)
+            .and_then(|file_bytes| {
+               info!("开始处理图片：{:?}", path);
+               let style = self.style_for(input)?;
+               let context = self.run_pipeline(input, &file_bytes, &style)?;
+               details.record_context(&context, &style);
+               self.sink().write(&target, &context.into_output()?)
+            });
+      if let Err(err) = result {
+         self.release_target(&target);
+         return self.skip_input(err, path, target);
+      }
+      self.record_written(path, &target);
+      Ok(TaskOutcome::Written(target))
+   }
+   /// `multi_frame`或`marked`为`Skip`时将多帧图片、已带有水印的图片的错误转换为跳过
+   fn skip_input(
+      &self,
+      err: LumixMarkError,
+      path: &Path,
+      target: PathBuf,
+   ) -> Result<TaskOutcome> {
+      match err {
+         LumixMarkError::MultiFrame(kind)
+            if self.multi_frame == MultiFramePolicy::Skip =>
+         {
+            info!("输入为{}，跳过：{:?}", kind, path);
+            Ok(TaskOutcome::Skipped(target))
+         }
+         LumixMarkError::AlreadyMarked => {
+            info!("图片已带有水印，跳过：{:?}", path);
+            Ok(TaskOutcome::Skipped(target))
+         }
+         err => Err(err),
+      }
+   }
+   /// # 确定并预留输出路径
+   ///
+   /// 先确定输出路径，已存在且需要跳过时返回`Break`，不再解码图片
+   fn claim_target(
+      &self,
+      input: &InputImage,
+   ) -> Result<ControlFlow<TaskOutcome, PathBuf>> {
+      if let Some(output) = self.resumed(input) {
+         info!("上次运行中已完成，跳过：{:?}", input.path);
+         return Ok(ControlFlow::Break(TaskOutcome::Skipped(output)));
+      }
+      let target = self.target_file(input, None)?;
+      let Some(target) = self.sink().claim(target.clone(), self.overwrite)? else {
+         info!("输出文件已存在，跳过：{:?}", target);
+         return Ok(ControlFlow::Break(TaskOutcome::Skipped(target)));
+      };
+      Ok(ControlFlow::Continue(target))
+   }
+   /// `resume`时上次运行中已完成的图片的输出路径
+   fn resumed(&self, input: &InputImage) -> Option<PathBuf> {
+      let state = self.run_state.as_ref().filter(|_| self.resume)?;
+      state.completed(&input.path)
+   }
+   /// 将已写入的图片记录到运行状态中，写入状态文件失败不影响图片的处理结果
+   fn record_written(&self, input: &Path, target: &Path) {
+      if let Some(Err(err)) = self
+         .run_state
+         .as_ref()
+         .map(|state| state.record(input, target))
+      {
+         error!("写入运行状态失败：{}, path:{:?}", err, self.state_file);
+      }
+   }
+   /// 释放处理失败时预留的输出路径
+   fn release_target(&self, target: &Path) {
+      self.sink().release(target, self.overwrite);
+   }
+   /// 预览单张图片的处理计划：只读取尺寸和EXIF，不解码、不保存
+   pub fn dry_run_task(&self, input: &InputImage) -> Result<TaskOutcome> {
+      self.plan_task(input, &mut TaskDetails::default())
+   }
+   fn plan_task(
+      &self,
+      input: &InputImage,
+      details: &mut TaskDetails,
+   ) -> Result<TaskOutcome> {
+      let path = &input.path;
+      if let Some(output) = self.resumed(input) {
+         info!("[dry-run] 上次运行中已完成，将跳过：{:?}", path);
+         return Ok(TaskOutcome::Skipped(output));
+      }
+      let (width, height, mut exif) = self.probe(path)?;
+      let style = self.style_for(input)?;
+      style.prepare_exif(&mut exif);
+      details.source = Some((width, height).into());
+      details.exif = Some(ExifSummary::new(&exif, &style));
+      let target = self.target_file(input, Some(&exif))?;
+      let Some(target) = self.overwrite.resolve(target.clone()) else {
+         info!("[dry-run] 输出文件已存在，将跳过：{:?}", target);
+         return Ok(TaskOutcome::Skipped(target));
+      };
+      let multi_frame = match self.multi_frame {
+         MultiFramePolicy::First => None,
+         MultiFramePolicy::Error | MultiFramePolicy::Skip => {
+            MultiFrame::detect(&fs::read(path)?)
+         }
+      };
+      if let Some(kind) = multi_frame {
+         if self.multi_frame == MultiFramePolicy::Error {
+            return Err(LumixMarkError::MultiFrame(kind));
+         }
+         info!("[dry-run] 输入为{}，将跳过：{:?}", kind, path);
+         return Ok(TaskOutcome::Skipped(target));
+      }
+      if self.marked == MarkedPolicy::Skip && is_marked(&fs::read(path)?) {
+         info!("[dry-run] 图片已带有水印，将跳过：{:?}", path);
+         return Ok(TaskOutcome::Skipped(target));
+      }
+      let (photo_width, photo_height) =
+         fit_long_edge(width, height, self.ratio, &style, &exif)?;
+      let mark_height = mark_height(photo_width, photo_height, self.ratio);
+      let layout =
+         caption::canvas_layout(&style, &exif, photo_width, photo_height, mark_height)?;
+      details.output = Some((layout.width, layout.height).into());
+      let pixels = layout.width as u64 * layout.height as u64;
+      let mut estimated_size = self.format.estimate_size(pixels, self.quality);
+      if let (OutputFormat::Jpeg, Some(max_size)) = (self.format, self.max_size) {
+         estimated_size = estimated_size.min(max_size);
+      }
+      info!(
+         "[dry-run] {:?} -> {:?}：原图{}x{}，水印高度{}，输出{}x{}，预计{}KB，{} {}",
+         path,
+         target,
+         width,
+         height,
+         mark_height,
+         layout.width,
+         layout.height,
+         estimated_size / 1024,
+         exif.model_title,
+         exif.render(&style.exif_template)
+      );
+      Ok(TaskOutcome::Written(target))
+   }
+   /// # 通过标准输入/输出处理单张图片
+   ///
+   /// `stdin`时从标准输入读取图片，否则读取唯一的输入图片；`stdout`时将结果写入标准输出，
+   /// 否则按文件名模板保存到输出文件夹
+   pub fn stdio_task(&self) -> Empty {
+      let (file_bytes, input) = if self.stdin {
+         let mut file_bytes = Vec::new();
+         io::stdin().lock().read_to_end(&mut file_bytes)?;
+         (file_bytes, InputImage::new(PathBuf::from("stdin")))
+      } else {
+         match self.inputs.as_slice() {
+            [input] => (fs::read(&input.path)?, input.clone()),
+            inputs => {
+               return Err(LumixMarkError::InvalidConfig(format!(
+                  "--stdout只能处理一张图片，当前输入{}张",
+                  inputs.len()
+               )));
+            }
+         }
+      };
+      let _span = info_span!("image", path = ?input.path).entered();
+      let style = self.style_for(&input)?;
+      let context = self.run_pipeline(&input, &file_bytes, &style)?;
+      if self.stdout {
+         let mut stdout = io::stdout().lock();
+         stdout.write_all(&context.into_output()?)?;
+         stdout.flush()?;
+         return Ok(());
+      }
+      let exif = context.mark.as_ref().map(|mark| &mark.exif);
+      let target = self.target_file(&input, exif)?;
+      let Some(target) = self.sink().claim(target.clone(), self.overwrite)? else {
+         info!("输出文件已存在，跳过：{:?}", target);
+         return Ok(());
+      };
+      self.sink().write(&target, &context.into_output()?)?;
+      self.sink().finish()?;
+      info!("已保存：{:?}", target);
+      Ok(())
+   }
+   /// 并行处理所有图片，单张失败不影响其他图片，结束后打印汇总
+   ///
+   /// 并行度由`par_count`限制，所有图片都会被处理（`fail_fast`时遇到失败后不再开始新的图片）；
+   /// 指定`max_memory`时按估计的内存占用限制同时处理的图片；`dry_run`时只预览处理计划
+   #[cfg(feature = "cli")]
+   pub fn par_draw_logo_exif_task(&self) -> BatchSummary {
+      let failed = AtomicBool::new(false);
+      let budget = self
+         .max_memory
+         .filter(|_| !self.dry_run)
+         .map(MemoryBudget::new);
+      let par_draw = || -> Vec<_> {
+         self
+            .inputs
+            .par_iter()
+            .map(|input| {
+               if self.fail_fast && failed.load(Ordering::Relaxed) {
+                  return (input.path.clone(), None);
+               }
+               let _permit = budget
+                  .as_ref()
+                  .map(|budget| budget.acquire(estimate_memory(&input.path)));
+               let start = Instant::now();
+               let mut details = TaskDetails::default();
+               let result = self.run_task(input, &mut details);
+               if let Err(err) = &result {
+                  error!("处理图片失败：{}, 图片地址：{:?}", err, input.path);
+                  failed.store(true, Ordering::Relaxed);
+               }
+               (input.path.clone(), Some((result, details, start.elapsed())))
+            })
+            .collect()
+      };
+      let threads = self.par_count.map_or(0, NonZeroUsize::get);
+      let results = match ThreadPoolBuilder::new().num_threads(threads).build() {
+         Ok(pool) => pool.install(par_draw),
+         Err(err) => {
+            warn!("创建线程池失败：{}，使用默认线程池", err);
+            par_draw()
+         }
+      };
+      let mut summary = BatchSummary::default();
+      for (path, task) in results {
+         match task {
+            Some((result, details, duration)) => {
+               summary.add(path, result, details, duration, self.dry_run)
+            }
+            None => summary.cancel(path),
+         }
+      }
+      self.finish_batch(&mut summary);
+      summary
+   }
+}
+
+/// 单张图片的处理结果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TaskOutcome {
+   /// 已写入（或预览时将写入）的输出路径
+   Written(PathBuf),
+   /// 输出文件已存在（或为多帧图片、已带有水印）而跳过
+   Skipped(PathBuf),
+}
+
+/// 批量处理结果汇总
+#[derive(Debug, Default)]
+pub struct BatchSummary {
+   /// 处理成功的图片及其输出路径
+   pub succeeded: Vec<(PathBuf, PathBuf)>,
+   /// 输出文件已存在（或为多帧图片、已带有水印）而跳过的图片及其输出路径
+   pub skipped: Vec<(PathBuf, PathBuf)>,
+   /// 处理失败的图片及失败原因
+   pub failed: Vec<(PathBuf, LumixMarkError)>,
+   /// `fail_fast`时因前面的图片失败而没有处理的图片
+   pub cancelled: Vec<PathBuf>,
+   /// 按输入顺序排列的每张图片的处理记录
+   pub records: Vec<ReportRecord>,
+   /// 全部图片处理后输出目标完成写入（如写入压缩包的目录）失败的原因
+   pub finish_error: Option<LumixMarkError>,
+}
+
+impl BatchSummary {
+   /// 是否全部处理成功
+   pub fn is_success(&self) -> bool {
+      self.failed.is_empty() && self.finish_error.is_none()
+   }
+   /// 打印处理结果汇总
+   pub fn print(&self) {
+      info!(
+         "处理完成：成功{}张，跳过{}张，失败{}张",
+         self.succeeded.len(),
+         self.skipped.len(),
+         self.failed.len()
+      );
+      if !self.cancelled.is_empty() {
+         info!(
+            "遇到失败后停止（--fail-fast），未处理{}张",
+            self.cancelled.len()
+         );
+      }
+      for (path, err) in &self.failed {
+         error!("{:?}：{}", path, err);
+      }
+      if let Some(err) = &self.finish_error {
+         error!("写入输出失败：{}", err);
+      }
+   }
+}
+
+/// # 限制输出长边后照片的尺寸
+///
+/// 画布与照片近似成比例，按原尺寸画布的长边计算缩放比例，取整导致仍超出时逐像素缩小；
+/// 水印高度随后按缩小后的照片重新计算；水印条包含说明文字时按`exif`计算其行数
+fn fit_long_edge(
+   width: u32,
+   height: u32,
+   mark_ratio: f32,
+   style: &MarkStyle,
+   exif: &Exif,
+) -> Result<(u32, u32)> {
+   let Some(max_long_edge) = style.max_long_edge.filter(|&max| max > 0) else {
+      return Ok((width, height));
+   };
+   let long_edge = |width, height| {
+      let mark_height = mark_height(width, height, mark_ratio);
+      let layout = caption::canvas_layout(style, exif, width, height, mark_height)?;
+      Ok::<_, LumixMarkError>(layout.width.max(layout.height))
+   };
+   let current = long_edge(width, height)?;
+   if current <= max_long_edge {
+      return Ok((width, height));
+   }
+   let mut target = max_long_edge;
+   loop {
+      let scale = target as f64 / current as f64;
+      let size = (
+         ((width as f64 * scale).round() as u32).max(1),
+         ((height as f64 * scale).round() as u32).max(1),
+      );
+      if target <= 1 || long_edge(size.0, size.1)? <= max_long_edge {
+         return Ok(size);
+      }
+      target -= 1;
+   }
+}
+
+/// 将照片缩放到指定尺寸，尺寸不变时原样返回
+fn resize_to(image: DynamicImage, (width, height): (u32, u32)) -> DynamicImage {
+   match image.dimensions() == (width, height) {
+      true => image,
+      false => image.resize_exact(width, height, FilterType::Lanczos3),
    }
 }
 
+/// 根据照片短边和比例计算水印高度
+fn mark_height(width: u32, height: u32, mark_ratio: f32) -> u32 {
+   (min(width, height) as f32 * mark_ratio) as u32
+}
+
+/// 根据ftyp盒子的品牌判断是否为HEIC/HEIF图片
+fn is_heif(bytes: &[u8]) -> bool {
+   bytes.len() >= 12
+      && &bytes[4..8] == b"ftyp"
+      && matches!(
+         &bytes[8..12],
+         b"heic" | b"heix" | b"hevc" | b"hevx" | b"heim" | b"heis" | b"mif1" | b"msf1"
+      )
+}
+
+/// 解码HEIC/HEIF图片，同时提取其中的EXIF数据
+#[cfg(feature = "heic")]
+fn decode_heif(bytes: &[u8]) -> Result<(DynamicImage, Metadata)> {
+   use libheif_rs::{ColorSpace, HeifContext, ItemId, LibHeif, RgbChroma};
+
+   let lib_heif = LibHeif::new();
+   let ctx = HeifContext::read_from_bytes(bytes)?;
+   let handle = ctx.primary_image_handle()?;
+   let mut metadata = Metadata::default();
+   let mut meta_ids: Vec<ItemId> = vec![0; 1];
+   if handle.metadata_block_ids(&mut meta_ids, b"Exif") > 0 {
+      let block = handle.metadata(meta_ids[0])?;
+      // Exif块前4字节为到TIFF头的偏移量
+      if block.len() > 4 {
+         let offset = u32::from_be_bytes([block[0], block[1], block[2], block[3]]);
+         metadata.exif = block.get(4 + offset as usize..).map(<[u8]>::to_vec);
+      }
+   }
+   metadata.icc_profile = handle.color_profile_raw().map(|profile| profile.data);
+   // libheif解码时已经应用了旋转信息，不再根据EXIF旋转
+   metadata.reset_orientation();
+   let image = lib_heif.decode(&handle, ColorSpace::Rgb(RgbChroma::Rgb), None)?;
+   let planes = image.planes();
+   let plane = planes
+      .interleaved
+      .ok_or_else(|| LumixMarkError::Decode("HEIC图片缺少RGB数据".into()))?;
+   let row_len = plane.width as usize * 3;
+   let mut pixels = Vec::with_capacity(row_len * plane.height as usize);
+   for row in plane.data.chunks(plane.stride).take(plane.height as usize) {
+      pixels.extend_from_slice(&row[..row_len]);
+   }
+   let rgb_img = RgbImage::from_raw(plane.width, plane.height, pixels)
+      .ok_or_else(|| LumixMarkError::Decode("HEIC图片数据不完整".into()))?;
+   Ok((DynamicImage::ImageRgb8(rgb_img), metadata))
+}
+
+#[cfg(not(feature = "heic"))]
+fn decode_heif(_bytes: &[u8]) -> Result<(DynamicImage, Metadata)> {
+   Err(LumixMarkError::Decode(
+      "未启用heic特性，无法解码HEIC图片".into(),
+   ))
+}
+
+/// 未启用raw特性时不识别RAW文件，交给image解码
+#[cfg(not(feature = "raw"))]
+fn is_raw(_bytes: &[u8]) -> bool {
+   false
+}
+
+#[cfg(not(feature = "raw"))]
+fn decode_raw(_bytes: &[u8]) -> Result<(DynamicImage, Metadata)> {
+   Err(LumixMarkError::Decode(
+      "未启用raw特性，无法解码RAW文件".into(),
+   ))
+}
+
+/// 有16位像素时按16位编码，否则编码8位画布
+fn write_canvas<E: ImageEncoder>(
+   encoder: E,
+   canvas: &RgbImage,
+   deep: Option<&Rgb16Image>,
+) -> Empty {
+   match deep {
+      Some(deep) => deep.write_with_encoder(encoder)?,
+      None => canvas.write_with_encoder(encoder)?,
+   }
+   Ok(())
+}
+
 pub struct LumixMark {
+   /// 画布，分块编码时只包含照片上方和下方的行
    pub canvas: RgbImage,
    pub exif: Exif,
+   pub metadata: Metadata,
    pub mark_area: (u32, u32, u32, u32),
+   /// 照片在画布中的区域（左、上、右、下）
+   pub photo_area: (u32, u32, u32, u32),
    pub width: u32,
    pub height: u32,
    pub mark_height: f32,
+   /// 绘制水印后各元素在画布中的位置
+   pub elements: Vec<MarkElement>,
+   /// 16位原图的照片像素，编码为PNG/TIFF时与画布合并
+   deep_photo: Option<DeepPhoto>,
+   /// 分块编码时不放入画布的照片
+   tiled: Option<TiledPhoto>,
+   /// 自动背景时按照片主色调选择的分隔线颜色
+   accent: Option<Color>,
+   /// 绘制水印时记录的来源信息，编码时写入输出文件；未绘制水印时为`None`
+   pub provenance: Option<Provenance>,
 }
 
 impl LumixMark {
@@ -171,31 +1490,102 @@ impl LumixMark {
    /// * `file_path` - 需要添加水印的照片文件路径
    /// * `mark_ratio` - 设置水印高度比例 （水印高度 / 照片最短边）
    pub fn from_image<P: AsRef<Path>>(file_path: P, mark_ratio: f32) -> Result<Self> {
-      // 1. 读取图片
+      Self::from_image_with_style(file_path, mark_ratio, &MarkStyle::default())
+   }
+   /// # 按水印样式初始化画布
+   ///
+   /// 照片旁有XMP附属文件时，其中的星级、标题、版权和拍摄时间等信息覆盖照片中的值
+   ///
+   /// # 参数
+   /// * `file_path` - 需要添加水印的照片文件路径
+   /// * `mark_ratio` - 设置水印高度比例 （水印高度 / 照片最短边）
+   /// * `style` - 水印样式，决定水印条所在的边
+   pub fn from_image_with_style<P: AsRef<Path>>(
+      file_path: P,
+      mark_ratio: f32,
+      style: &MarkStyle,
+   ) -> Result<Self> {
       let file_bytes = fs::read(&file_path)?;
-      let exif = Exif::from_bytes(&file_bytes)?;
-      let original_img = load_from_memory(&file_bytes)?;
-      // 根据exif反转图像
-      let rgb_img = match exif.orientation.as_str() {
-         "Straight" => original_img.to_rgb8(),
-         "Rotated to left" => original_img.rotate90().to_rgb8(),
-         "Rotated to right" => original_img.rotate270().to_rgb8(),
-         _ => original_img.to_rgb8(),
-      };
-      let (img_width, img_height) = rgb_img.dimensions();
-      let mark_height = (min(img_width, img_height) as f32 * mark_ratio) as u32;
-      let add_mark_height = img_height + mark_height;
-      // 2. 创建画布
-      let mut canvas =
-         RgbImage::from_pixel(img_width, add_mark_height, Color::White.into());
-      canvas.copy_from(&rgb_img, 0, 0)?;
-      Ok(Self {
-         canvas,
-         width: img_width,
-         height: add_mark_height,
-         mark_height: mark_height as f32,
-         mark_area: (0, img_height, img_width, add_mark_height),
-         exif,
+      let mut context = PipelineContext::new(&file_bytes, mark_ratio, style)
+         .with_sidecar(Metadata::read_sidecar(file_path.as_ref()));
+      for stage in &StandardStage::ALL[..=3] {
+         stage.run(&mut context)?;
+      }
+      context.into_mark()
+   }
+   /// # 从内存中的图片文件内容初始化画布
+   ///
+   /// # 参数
+   /// * `file_bytes` - 图片文件内容（JPEG/PNG等，启用heic特性时支持HEIC）
+   /// * `mark_ratio` - 设置水印高度比例 （水印高度 / 照片最短边）
+   pub fn from_bytes(file_bytes: &[u8], mark_ratio: f32) -> Result<Self> {
+      Self::from_bytes_with_style(file_bytes, mark_ratio, &MarkStyle::default())
+   }
+   /// # 从内存中的图片文件内容按水印样式初始化画布
+   ///
+   /// # 参数
+   /// * `file_bytes` - 图片文件内容（JPEG/PNG等，启用heic特性时支持HEIC）
+   /// * `mark_ratio` - 设置水印高度比例 （水印高度 / 照片最短边）
+   /// * `style` - 水印样式，决定水印条所在的边
+   pub fn from_bytes_with_style(
+      file_bytes: &[u8],
+      mark_ratio: f32,
+      style: &MarkStyle,
+   ) -> Result<Self> {
+      // 解码、旋转、缩小照片并创建画布，不绘制水印
+      let mut context = PipelineContext::new(file_bytes, mark_ratio, style);
+      for stage in &StandardStage::ALL[..=3] {
+         stage.run(&mut context)?;
+      }
+      context.into_mark()
+   }
+   /// # 为内存中的图片添加水印并编码
+   ///
+   /// 依次执行默认流水线的所有阶段，不读写文件，可以在WASM等没有文件系统的环境中使用
+   ///
+   /// # 参数
+   /// * `file_bytes` - 图片文件内容
+   /// * `mark_ratio` - 设置水印高度比例 （水印高度 / 照片最短边）
+   /// * `style` - 水印样式
+   /// * `format` - 输出图片格式
+   /// * `options` - 编码选项
+   pub fn mark_bytes(
+      file_bytes: &[u8],
+      mark_ratio: f32,
+      style: &MarkStyle,
+      format: OutputFormat,
+      options: &SaveOptions,
+   ) -> Result<Vec<u8>> {
+      let mut context = PipelineContext::new(file_bytes, mark_ratio, style)
+         .with_output(format, *options);
+      Pipeline::default().run(&mut context)?;
+      context.into_output()
+   }
+   /// # 读取图片尺寸和EXIF信息，不解码像素
+   ///
+   /// 返回按EXIF方向旋转后的宽、高以及EXIF信息
+   pub fn probe<P: AsRef<Path>>(file_path: P) -> Result<(u32, u32, Exif)> {
+      let file_bytes = fs::read(&file_path)?;
+      if is_heif(&file_bytes) {
+         let (img, metadata) = decode_heif(&file_bytes)?;
+         return Ok((img.width(), img.height(), Exif::from_metadata(&metadata)));
+      }
+      if is_raw(&file_bytes) {
+         let (img, metadata) = decode_raw(&file_bytes)?;
+         let exif = Exif::from_metadata(&metadata);
+         return Ok(match exif.orientation {
+            5..=8 => (img.height(), img.width(), exif),
+            _ => (img.width(), img.height(), exif),
+         });
+      }
+      let exif = Exif::from_metadata(&Metadata::from_bytes(&file_bytes));
+      let (width, height) = ImageReader::new(Cursor::new(&file_bytes))
+         .with_guessed_format()?
+         .into_dimensions()?;
+      // 方向代码5 - 8需要旋转90°，宽高互换
+      Ok(match exif.orientation {
+         5..=8 => (height, width, exif),
+         _ => (width, height, exif),
       })
    }
    /// # 指定质量保存JPEG图片
@@ -204,189 +1594,1104 @@ impl LumixMark {
    /// * `file_name` - 指定保存的文件路径名
    /// * `quality` - 设置保存的图片质量（75 - 100）
    pub fn save_with_quality<P: AsRef<Path>>(&self, file_name: P, quality: u8) -> Empty {
-      let file = File::create(file_name)?;
-      let writer = BufWriter::new(file);
-      let mut encoder = JpegEncoder::new_with_quality(writer, quality);
-      encoder.encode_image(&self.canvas)?;
+      fs::write(file_name, self.encode_jpeg(quality)?)?;
       Ok(())
    }
-   /// 绘制Logo和Exif信息到画布
-   pub fn draw_logo_exif(
-      &mut self,
-      padding_ratio: f32,
-      font_bytes: &[u8],
-      model_color: Color,
-      model_text_size_ratio: f32,
-      exif_color: Color,
-      exif_text_size_ratio: f32,
-      gap_ratio: f32,
-      rect_color: Color,
-      rect_width_ratio: f32,
-      rect_height_ratio: f32,
-      logo_bytes: &[u8],
-      logo_width_ratio: f32,
-      logo_height_ratio: f32,
+   /// # 按指定质量编码为JPEG数据
+   ///
+   /// 结果可以直接写入HTTP响应等，不需要临时文件
+   ///
+   /// # 参数
+   /// * `quality` - 设置图片质量（75 - 100）
+   pub fn encode_jpeg(&self, quality: u8) -> Result<Vec<u8>> {
+      let options = SaveOptions {
+         quality,
+         ..SaveOptions::default()
+      };
+      self.encode(OutputFormat::Jpeg, &options)
+   }
+   /// 按指定质量编码为JPEG数据
+   #[deprecated(note = "使用`encode_jpeg`")]
+   pub fn to_jpeg_bytes(&self, quality: u8) -> Result<Vec<u8>> {
+      self.encode_jpeg(quality)
+   }
+   /// # 按指定格式和编码选项编码图片
+   ///
+   /// # 参数
+   /// * `format` - 输出图片格式
+   /// * `options` - 编码选项（质量、是否保留元数据等）
+   ///
+   /// ```
+   /// use lumix_mark::{LumixMark, OutputFormat, SaveOptions};
+   ///
+   /// let mut photo = Vec::new();
+   /// image::RgbImage::new(600, 400)
+   ///    .write_to(&mut std::io::Cursor::new(&mut photo), image::ImageFormat::Png)
+   ///    .unwrap();
+   /// let mut lumix_mark = LumixMark::from_bytes(&photo, 0.14).unwrap();
+   /// lumix_mark.draw_logo_exif(&Default::default()).unwrap();
+   /// let webp = lumix_mark
+   ///    .encode(OutputFormat::Webp, &SaveOptions::default())
+   ///    .unwrap();
+   /// assert_eq!(&webp[8..12], b"WEBP");
+   /// ```
+   pub fn encode(
+      &self,
+      format: OutputFormat,
+      options: &SaveOptions,
+   ) -> Result<Vec<u8>> {
+      if let Some(tiled) = &self.tiled {
+         return self.encode_tiled(tiled, format, options);
+      }
+      let mut bytes = Vec::new();
+      let icc_profile = self.metadata.icc_profile.as_deref();
+      // AVIF编码器不支持嵌入ICC配置文件，转换为sRGB
+      let convert = match (options.icc, format) {
+         (IccPolicy::Srgb, _) | (IccPolicy::Embed, OutputFormat::Avif) => icc_profile,
+         (IccPolicy::Embed, _) => None,
+      };
+      // PNG/TIFF保持16位原图的位深
+      let mut deep = match format {
+         OutputFormat::Png | OutputFormat::Tiff => self
+            .deep_photo
+            .as_ref()
+            .map(|deep_photo| deep_photo.merge(&self.canvas)),
+         _ => None,
+      };
+      let mut converted = None;
+      if let Some(icc) = convert {
+         match deep.as_mut() {
+            Some(deep) => *deep = convert_to_srgb_16(deep, icc)?,
+            None => converted = Some(convert_to_srgb(&self.canvas, icc)?),
+         }
+      }
+      let canvas = converted.as_ref().unwrap_or(&self.canvas);
+      let embed = match convert {
+         Some(_) => None,
+         None => icc_profile.map(<[u8]>::to_vec),
+      };
+      match format {
+         OutputFormat::Jpeg => {
+            let encode =
+               |quality| self.jpeg_bytes(canvas, quality, embed.clone(), options);
+            bytes = match options.max_size {
+               Some(max_size) => fit_file_size(encode, options.quality, max_size)?,
+               None => encode(options.quality)?,
+            };
+         }
+         OutputFormat::Png => {
+            let mut encoder = PngEncoder::new(&mut bytes);
+            if let Some(icc) = embed {
+               encoder
+                  .set_icc_profile(icc)
+                  .map_err(ImageError::Unsupported)?;
+            }
+            write_canvas(encoder, canvas, deep.as_ref())?;
+            if let Some(provenance) = &self.provenance {
+               bytes = mark_png(bytes, provenance)?;
+            }
+         }
+         OutputFormat::Tiff => {
+            let mut encoder = TiffEncoder::new(Cursor::new(&mut bytes));
+            if let Some(icc) = embed {
+               encoder
+                  .set_icc_profile(icc)
+                  .map_err(ImageError::Unsupported)?;
+            }
+            write_canvas(encoder, canvas, deep.as_ref())?;
+            if let Some(provenance) = &self.provenance {
+               bytes = mark_tiff(bytes, provenance)?;
+            }
+         }
+         OutputFormat::Webp => {
+            let mut encoder = WebPEncoder::new_lossless(&mut bytes);
+            if let Some(icc) = embed {
+               encoder
+                  .set_icc_profile(icc)
+                  .map_err(ImageError::Unsupported)?;
+            }
+            canvas.write_with_encoder(encoder)?;
+            if let Some(provenance) = &self.provenance {
+               bytes = mark_webp(bytes, provenance)?;
+            }
+         }
+         OutputFormat::Avif => {
+            let encoder = AvifEncoder::new_with_speed_quality(
+               &mut bytes,
+               options.avif_speed,
+               options.quality,
+            );
+            canvas.write_with_encoder(encoder)?;
+            if self.provenance.is_some() {
+               tracing::warn!("AVIF输出不写入来源信息，再次处理时无法识别已添加的水印");
+            }
+         }
+      }
+      Ok(bytes)
+   }
+   /// 按指定质量编码JPEG，包含ICC配置文件和需要保留的元数据
+   fn jpeg_bytes(
+      &self,
+      canvas: &RgbImage,
+      quality: u8,
+      icc_profile: Option<Vec<u8>>,
+      options: &SaveOptions,
+   ) -> Result<Vec<u8>> {
+      let (width, height) = jpeg_dimensions(canvas.width(), canvas.height())?;
+      let mut bytes = Vec::new();
+      jpeg_encoder(&mut bytes, quality, options, icc_profile.as_deref())?.encode(
+         canvas.as_raw(),
+         width,
+         height,
+         ColorType::Rgb,
+      )?;
+      self.with_metadata(bytes, options)
+   }
+   /// 需要保留元数据时写入已编码的JPEG数据，已绘制水印时再写入标记和来源信息
+   fn with_metadata(&self, bytes: Vec<u8>, options: &SaveOptions) -> Result<Vec<u8>> {
+      let bytes = match options.keep_metadata && !self.metadata.is_empty() {
+         true => self.metadata.embed_into_jpeg(bytes)?,
+         false => bytes,
+      };
+      match &self.provenance {
+         Some(provenance) => mark_jpeg(bytes, provenance),
+         None => Ok(bytes),
+      }
+   }
+   /// # 按指定格式和编码选项保存图片
+   ///
+   /// # 参数
+   /// * `file_name` - 指定保存的文件路径名
+   /// * `format` - 输出图片格式
+   /// * `options` - 编码选项（质量、是否保留元数据等）
+   pub fn save_as<P: AsRef<Path>>(
+      &self,
+      file_name: P,
+      format: OutputFormat,
+      options: &SaveOptions,
    ) -> Empty {
-      let padding = (self.mark_height * padding_ratio) as u32;
-      let model_text_size = self.mark_height * model_text_size_ratio;
-      let exif_text_size = self.mark_height * exif_text_size_ratio;
-      let gap = (self.mark_height * gap_ratio) as i32;
-      let rect_width = (self.mark_height * rect_width_ratio) as u32;
-      let rect_height = (self.mark_height * rect_height_ratio) as u32;
-      let logo_width = (self.mark_height * logo_width_ratio) as u32;
-      let logo_height = (self.mark_height * logo_height_ratio) as u32;
+      fs::write(file_name, self.encode(format, options)?)?;
+      Ok(())
+   }
+   /// # 绘制Logo和Exif信息到画布
+   ///
+   /// 竖向的水印条先旋转为横向绘制，再旋转回原位置
+   ///
+   /// # 参数
+   /// * `style` - 水印样式，未指定Logo时根据相机厂商自动选择
+   pub fn draw_logo_exif(&mut self, style: &MarkStyle) -> Empty {
+      // 按字符选择主字体或后备字体，所有字体都缺少的字符会绘制为方框，提前报错
+      let exif_text = self
+         .exif
+         .render_with_icons(&style.exif_template, style.exif_icons);
+      let secondary_text = self.secondary_text(style);
+      let caption_text = caption::caption_text(style, &self.exif);
+      let mut texts = vec![exif_text.as_str()];
+      texts.extend(secondary_text.as_deref());
+      texts.extend(caption_text.as_deref());
+      // 叠加水印中不绘制机型
+      let model_texts = match style.frame {
+         Frame::Overlay => None,
+         Frame::Strip | Frame::Polaroid => Some(self.exif.model_title.as_str()),
+      };
+      let fonts = MarkFonts {
+         model: FontStack::new(style.font()?, style, model_texts)?,
+         exif: FontStack::new(style.exif_font()?, style, texts)?,
+      };
+      let logo = match &style.logo {
+         Some(logo) => logo.decode()?,
+         None => LogoSource::for_camera(&self.exif.make, &self.exif.model).decode()?,
+      };
       let (start_x, start_y, end_x, end_y) = self.mark_area;
-      // 加载字体
-      let font = FontRef::try_from_slice(font_bytes)?;
-      let (model_width, _) = text_size(model_text_size, &font, &self.exif.model_title);
-      println!("计算{}的显示宽度:{}", self.exif.model_title, model_width);
+      let canvas_y = match &self.tiled {
+         Some(tiled) => tiled.canvas_y(start_y),
+         None => start_y,
+      };
+      let area = self
+         .canvas
+         .view(start_x, canvas_y, end_x - start_x, end_y - start_y)
+         .to_image();
+      // 叠加水印始终是横向的
+      let position = style.strip_position();
+      let mut strip = match position {
+         MarkPosition::Left => rotate90(&area),
+         MarkPosition::Right => rotate270(&area),
+         MarkPosition::Bottom | MarkPosition::Top => area,
+      };
+      let (strip_width, strip_height) = strip.dimensions();
+      // 说明文字在水印条底部，其余元素在上方的区域中排列
+      let caption = match &caption_text {
+         Some(text) => caption::caption_lines(
+            style,
+            &fonts.exif,
+            text,
+            self.mark_height,
+            strip_width,
+         ),
+         None => Vec::new(),
+      };
+      let caption_height =
+         caption::caption_height(style, self.mark_height, caption.len())
+            .min(strip_height);
+      let strip_area = (0, 0, strip_width, strip_height - caption_height);
+      let logo_only = style.logo_only_without_exif
+         && self.exif.model_title.trim().is_empty()
+         && exif_text.trim().is_empty()
+         && secondary_text.is_none();
+      let mut elements = match (style.frame, style.layout) {
+         _ if logo_only => self.draw_logo_only(&mut strip, strip_area, style, &logo),
+         (Frame::Overlay, _) => {
+            self.draw_overlay_block(&mut strip, strip_area, style, &fonts, &logo)?
+         }
+         (_, Layout::Split) => {
+            self.draw_split_strip(&mut strip, strip_area, style, &fonts, &logo)?
+         }
+         (_, Layout::Centered) => {
+            self.draw_centered_strip(&mut strip, strip_area, style, &fonts, &logo)?
+         }
+      };
+      let caption_area = (0, strip_height - caption_height, strip_width, strip_height);
+      elements.extend(self.draw_caption(&mut strip, caption_area, style, &caption));
+      self.elements = elements
+         .into_iter()
+         .map(|element| element.into_canvas(position, self.mark_area))
+         .collect();
+      let area = match position {
+         MarkPosition::Left => rotate270(&strip),
+         MarkPosition::Right => rotate90(&strip),
+         MarkPosition::Bottom | MarkPosition::Top => strip,
+      };
+      self.canvas.copy_from(&area, start_x, canvas_y)?;
+      // 叠加水印绘制在照片上，无法通过裁剪去除
+      let photo_area = match style.frame {
+         Frame::Overlay => None,
+         Frame::Strip | Frame::Polaroid => Some(self.photo_area),
+      };
+      self.provenance = Some(Provenance::new(style, photo_area, self.exif.orientation));
+      Ok(())
+   }
+   /// # 在横向的水印条中绘制机型、Exif信息、分隔线和Logo
+   ///
+   /// 机型靠左，Exif信息、分隔线和Logo靠右；Exif信息缩小后仍然过长时折为两行
+   ///
+   /// # 参数
+   /// * `canvas` - 绘制的目标图片
+   /// * `mark_area` - 水印区域（左、上、右、下）
+   /// * `style` - 水印样式
+   /// * `fonts` - 机型和Exif信息的字体栈
+   /// * `logo` - 解码后的Logo
+   fn draw_split_strip(
+      &self,
+      canvas: &mut RgbImage,
+      mark_area: (u32, u32, u32, u32),
+      style: &MarkStyle,
+      fonts: &MarkFonts,
+      logo: &Logo,
+   ) -> Result<Vec<MarkElement>> {
+      let padding = (self.mark_height * style.padding_ratio) as u32;
+      let gap = (self.mark_height * style.gap_ratio).max(0.0) as i32;
+      let after_model = self.dividers(style, DividerSlot::AfterModel);
+      let before_logo = self.dividers(style, DividerSlot::BeforeLogo);
+      let after_logo = self.dividers(style, DividerSlot::AfterLogo);
+      // Logo与Exif信息之间没有分隔线时也留出两倍间距
+      let logo_gap = match after_logo.is_empty() {
+         true => gap as u32 * 2,
+         false => gap as u32 + divider::run_width(&after_logo, gap as u32),
+      };
+      let (logo_width, logo_height) = self.logo_size(style, logo);
+      let (start_x, start_y, end_x, end_y) = mark_area;
+      let mut canvas = Blend::new(canvas, style.opacity);
+      let secondary_text = self.secondary_text(style);
+      // Exif信息缩小后仍然过长时折为两行
+      let [model, secondary, exif] =
+         self.fit_texts(style, fonts, secondary_text.as_deref());
+      let mut texts = [model, secondary, exif.wrappable()];
+      // 直方图在最右侧，与Exif信息之间留出两倍间距
+      let histogram = self.histogram_size(style);
+      let histogram_width = histogram.map_or(0, |(width, _)| width + gap as u32 * 2);
+      // 二维码在最左侧，与机型之间留出两倍间距
+      let qr = self.qr_side(style);
+      let qr_width = qr.map_or(0, |side| side + gap as u32 * 2);
+      // 左侧的机型或附加信息（含其后的分隔线）与右侧的分隔线、Logo和Exif信息之间至少留出两倍间距，
+      // 宽度不足时缩小或截断文字，分隔线不会与机型重叠
+      let fixed = padding * 2
+         + divider::run_width(&after_model, gap as u32)
+         + divider::run_width(&before_logo, gap as u32)
+         + logo_width
+         + logo_gap
+         + gap as u32 * 2
+         + histogram_width
+         + qr_width;
+      let rows = [
+         FitRow {
+            fixed,
+            texts: vec![0, 2],
+         },
+         FitRow {
+            fixed,
+            texts: vec![1, 2],
+         },
+      ];
+      fit_texts(&mut texts, &rows, end_x - start_x)?;
+      let [model, secondary, exif] = &texts;
+      // 机型和附加信息靠左，整体垂直居中
+      let left_height = match secondary_text {
+         Some(_) => model.size + gap as f32 + secondary.size,
+         None => model.size,
+      };
+      let model_y = ((start_y + end_y) as f32 - left_height) / 2.0;
+      // 从右向左时机型靠右，Exif信息、分隔线和Logo靠左
+      let mirror = Mirror::new(style.text_direction, start_x, end_x);
+      let anchoring = Anchoring::new(mark_area, padding, self.mark_height);
+      let anchors = &style.anchors;
+      // 绘制二维码
+      if let Some(side) = qr {
+         let x = mirror.x((start_x + padding) as i32, side) as u32;
+         self.draw_qr(&mut canvas, style, (x, (start_y + end_y - side) / 2), side)?;
+      }
       // 绘制机型
-      draw_text_mut(
-         &mut self.canvas,
-         model_color.into(),
-         (start_x + padding) as i32,
-         (((start_y + end_y) as f32 - model_text_size) / 2.0) as i32,
-         model_text_size,
-         &font,
-         &self.exif.model_title,
+      let left_x = (start_x + padding + qr_width) as i32;
+      let (x, y) = anchoring.place(
+         anchors.model,
+         (mirror.x(left_x, model.width()), model_y as i32),
+         model.width(),
       );
-      let exif_text = &self.exif.to_string();
-      let (exif_width, _) = text_size(exif_text_size, &font, exif_text);
-      println!("计算{exif_text}的显示宽度:{}", exif_width);
-      let exif_x = (end_x - exif_width - padding) as i32;
-      // 绘制Exif信息
-      draw_text_mut(
-         &mut self.canvas,
-         exif_color.into(),
-         exif_x,
-         (((start_y + end_y) as f32 - exif_text_size) / 2.0) as i32,
-         exif_text_size,
-         &font,
-         exif_text,
+      canvas.draw_text(style.model_color, x, y, &model.run());
+      // 绘制拍摄时间等附加信息
+      if secondary_text.is_some() {
+         let (x, y) = anchoring.place(
+            anchors.secondary,
+            (
+               mirror.x(left_x, secondary.width()),
+               (model_y + model.size + gap as f32) as i32,
+            ),
+            secondary.width(),
+         );
+         canvas.draw_text(style.date_color, x, y, &secondary.run());
+      }
+      let center_y = (start_y + end_y) as f32 / 2.0;
+      let anchor = anchors.divider;
+      // 绘制机型和附加信息右侧的分隔线
+      let left_width = match secondary_text {
+         Some(_) => model.width().max(secondary.width()),
+         None => model.width(),
+      };
+      let run_x = left_x + left_width as i32 + gap;
+      let position = (run_x, center_y);
+      Self::draw_dividers(
+         &mut canvas,
+         &after_model,
+         position,
+         gap,
+         mirror,
+         &anchoring,
+         anchor,
+      );
+      // 直方图、Exif信息、分隔线和Logo靠右排列
+      if let (Some(histogram), Some(size)) = (&style.histogram, histogram) {
+         let x = end_x - padding - size.0;
+         let y = (start_y + end_y - size.1) / 2;
+         let x = mirror.x(x as i32, size.0) as u32;
+         self.draw_histogram(&mut canvas, style, histogram, (x, y), size);
+      }
+      let exif_width = exif.width();
+      let exif_x = (end_x - exif_width - padding - histogram_width) as i32;
+      // 绘制Exif信息，折行后每行左对齐，整体垂直居中
+      let line_gap = gap as f32 / 2.0;
+      let mut exif_y = ((start_y + end_y) as f32 - exif.height(line_gap)) / 2.0;
+      let (dx, dy) =
+         anchoring.offset(anchors.exif, mirror.x(exif_x, exif_width), exif_width);
+      for line in exif.lines() {
+         canvas.draw_text(
+            style.exif_color,
+            mirror.x(exif_x, line.size().0) + dx,
+            exif_y as i32 + dy,
+            &line,
+         );
+         exif_y += exif.size + line_gap;
+      }
+      let logo_x = (exif_x - logo_gap as i32) as u32 - logo_width;
+      // 绘制Logo两侧的分隔线
+      let position = (logo_x as i32 + logo_width as i32 + gap, center_y);
+      Self::draw_dividers(
+         &mut canvas,
+         &after_logo,
+         position,
+         gap,
+         mirror,
+         &anchoring,
+         anchor,
+      );
+      let run_x = logo_x as i32 - divider::run_width(&before_logo, gap as u32) as i32;
+      let position = (run_x, center_y);
+      Self::draw_dividers(
+         &mut canvas,
+         &before_logo,
+         position,
+         gap,
+         mirror,
+         &anchoring,
+         anchor,
       );
-      let rect_x = exif_x - gap - rect_width as i32;
-      let rect = Rect::at(
-         rect_x,
-         ((start_y + end_y - rect_height) as f32 / 2.0) as i32,
-      )
-      .of_size(rect_width, rect_height);
-      // 绘制分隔矩形
-      draw_filled_rect_mut(&mut self.canvas, rect, rect_color.into());
-      // 加载Logo图片
-      let logo = load_from_memory(logo_bytes)?.to_rgb8();
-      let resize_logo = resize(&logo, logo_width, logo_height, FilterType::CatmullRom);
-      let logo_x = (rect_x - gap) as u32 - logo_width;
       let logo_y = ((start_y + end_y - logo_height) as f32 / 2.0) as u32;
       // 绘制Logo
-      self.canvas.copy_from(&resize_logo, logo_x, logo_y)?;
-      Ok(())
+      let logo_position = anchoring.place_unsigned(
+         anchors.logo,
+         (mirror.x(logo_x as i32, logo_width) as u32, logo_y),
+         logo_width,
+      );
+      self.draw_logo(
+         &mut canvas,
+         style,
+         logo,
+         logo_position,
+         (logo_width, logo_height),
+      );
+      Ok(canvas.into_elements())
    }
-}
-
-#[derive(Default, Debug)]
-pub struct Exif {
-   pub model_title: String,
-   pub shoot_time: String,
-   pub exposure_time: String,
-   pub aperture: String,
-   pub iso: String,
-   pub focal_length: String,
-   pub orientation: String,
-}
-
-impl Exif {
-   /// 从图片文件路径解析EXIF信息
-   pub fn from_image<P: AsRef<Path>>(file_path: P) -> Result<Self> {
-      let mut exif = Exif::default();
-      // 处理所有EXIF条目
-      for entry in parse_file(file_path)?.entries {
-         Self::process_entry(&mut exif, entry.tag, &entry.value_more_readable, &entry);
+   /// # 在横向的水印条中居中绘制机型、Exif信息、分隔线和Logo
+   ///
+   /// 第一行为Logo、分隔线和机型，第二行为Exif信息和拍摄时间等附加信息，两行均水平居中
+   ///
+   /// # 参数
+   /// * `canvas` - 绘制的目标图片
+   /// * `mark_area` - 水印区域（左、上、右、下）
+   /// * `style` - 水印样式
+   /// * `fonts` - 机型和Exif信息的字体栈
+   /// * `logo` - 解码后的Logo
+   fn draw_centered_strip(
+      &self,
+      canvas: &mut RgbImage,
+      mark_area: (u32, u32, u32, u32),
+      style: &MarkStyle,
+      fonts: &MarkFonts,
+      logo: &Logo,
+   ) -> Result<Vec<MarkElement>> {
+      let padding = (self.mark_height * style.padding_ratio) as u32;
+      let gap = (self.mark_height * style.gap_ratio).max(0.0) as u32;
+      let after_model = self.dividers(style, DividerSlot::AfterModel);
+      let before_logo = self.dividers(style, DividerSlot::BeforeLogo);
+      let after_logo = self.dividers(style, DividerSlot::AfterLogo);
+      // Logo与机型之间没有分隔线时也留出两倍间距
+      let logo_gap = match after_logo.is_empty() {
+         true => gap * 2,
+         false => gap + divider::run_width(&after_logo, gap),
+      };
+      let before_width = divider::run_width(&before_logo, gap);
+      let after_width = divider::run_width(&after_model, gap);
+      let (logo_width, logo_height) = self.logo_size(style, logo);
+      let (start_x, start_y, end_x, end_y) = mark_area;
+      let mut canvas = Blend::new(canvas, style.opacity);
+      let secondary_text = self.secondary_text(style);
+      let mut texts = self.fit_texts(style, fonts, secondary_text.as_deref());
+      // 附加信息跟在Exif信息右侧，与Exif信息底部对齐
+      let secondary_gap = secondary_text.as_ref().map_or(0, |_| gap * 2);
+      // 直方图在最右侧，二维码在最左侧，两侧留出相同的宽度使内容保持居中
+      let histogram = self.histogram_size(style);
+      let qr = self.qr_side(style);
+      let histogram_width = histogram
+         .map(|(width, _)| width)
+         .max(qr)
+         .map_or(0, |width| (width + gap * 2) * 2);
+      // 两行中较宽的一行加上两侧边距不能超过水印宽度，宽度不足时优先截断附加信息
+      let rows = [
+         FitRow {
+            fixed: padding * 2
+               + before_width
+               + logo_width
+               + logo_gap
+               + after_width
+               + histogram_width,
+            texts: vec![0],
+         },
+         FitRow {
+            fixed: padding * 2 + secondary_gap + histogram_width,
+            texts: vec![1, 2],
+         },
+      ];
+      fit_texts(&mut texts, &rows, end_x - start_x)?;
+      let [model, secondary, exif] = &texts;
+      let first_line_width =
+         before_width + logo_width + logo_gap + model.width() + after_width;
+      let second_line_width = exif.width() + secondary.width() + secondary_gap;
+      let (model_text_size, exif_text_size) = (model.size, exif.size);
+      // 两行文字和行间距整体垂直居中
+      let content_height = model_text_size + gap as f32 + exif_text_size;
+      let top = (start_y + end_y) as f32 / 2.0 - content_height / 2.0;
+      let first_line_center = top + model_text_size / 2.0;
+      let logo_x = start_x + (end_x - start_x - first_line_width) / 2 + before_width;
+      let logo_y = (first_line_center - logo_height as f32 / 2.0).max(0.0) as u32;
+      // 从右向左时每行的元素左右镜像
+      let mirror = Mirror::new(style.text_direction, start_x, end_x);
+      let anchoring = Anchoring::new(mark_area, padding, self.mark_height);
+      let anchors = &style.anchors;
+      // 绘制直方图
+      if let (Some(histogram), Some(size)) = (&style.histogram, histogram) {
+         let x = end_x - padding - size.0;
+         let y = (start_y + end_y - size.1) / 2;
+         let x = mirror.x(x as i32, size.0) as u32;
+         self.draw_histogram(&mut canvas, style, histogram, (x, y), size);
+      }
+      // 绘制二维码
+      if let Some(side) = qr {
+         let x = mirror.x((start_x + padding) as i32, side) as u32;
+         self.draw_qr(&mut canvas, style, (x, (start_y + end_y - side) / 2), side)?;
+      }
+      // 绘制Logo
+      let logo_position = anchoring.place_unsigned(
+         anchors.logo,
+         (mirror.x(logo_x as i32, logo_width) as u32, logo_y),
+         logo_width,
+      );
+      self.draw_logo(
+         &mut canvas,
+         style,
+         logo,
+         logo_position,
+         (logo_width, logo_height),
+      );
+      // 绘制Logo两侧的分隔线
+      let (gap_x, anchor) = (gap as i32, anchors.divider);
+      let position = ((logo_x - before_width) as i32, first_line_center);
+      Self::draw_dividers(
+         &mut canvas,
+         &before_logo,
+         position,
+         gap_x,
+         mirror,
+         &anchoring,
+         anchor,
+      );
+      let position = ((logo_x + logo_width + gap) as i32, first_line_center);
+      Self::draw_dividers(
+         &mut canvas,
+         &after_logo,
+         position,
+         gap_x,
+         mirror,
+         &anchoring,
+         anchor,
+      );
+      // 绘制机型
+      let model_x = logo_x + logo_width + logo_gap;
+      let (x, y) = anchoring.place(
+         anchors.model,
+         (mirror.x(model_x as i32, model.width()), top as i32),
+         model.width(),
+      );
+      canvas.draw_text(style.model_color, x, y, &model.run());
+      // 绘制机型右侧的分隔线
+      let position = ((model_x + model.width() + gap) as i32, first_line_center);
+      Self::draw_dividers(
+         &mut canvas,
+         &after_model,
+         position,
+         gap_x,
+         mirror,
+         &anchoring,
+         anchor,
+      );
+      // 绘制Exif信息
+      let exif_x = start_x + (end_x - start_x - second_line_width) / 2;
+      let exif_y = top + model_text_size + gap as f32;
+      let (x, y) = anchoring.place(
+         anchors.exif,
+         (mirror.x(exif_x as i32, exif.width()), exif_y as i32),
+         exif.width(),
+      );
+      canvas.draw_text(style.exif_color, x, y, &exif.run());
+      // 绘制拍摄时间等附加信息
+      if secondary_text.is_some() {
+         let secondary_x = exif_x + exif.width() + secondary_gap;
+         let (x, y) = anchoring.place(
+            anchors.secondary,
+            (
+               mirror.x(secondary_x as i32, secondary.width()),
+               (exif_y + exif_text_size - secondary.size) as i32,
+            ),
+            secondary.width(),
+         );
+         canvas.draw_text(style.date_color, x, y, &secondary.run());
       }
-      Ok(exif)
+      Ok(canvas.into_elements())
    }
-
-   pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
-      let mut exif = Exif::default();
-      // 处理所有EXIF条目
-      for entry in parse_buffer(bytes)?.entries {
-         Self::process_entry(&mut exif, entry.tag, &entry.value_more_readable, &entry);
+   /// # 在横向的水印区域中绘制叠加的Logo、分隔线和Exif信息
+   ///
+   /// 按样式中的角靠左或靠右排列，与背景按不透明度混合
+   ///
+   /// # 参数
+   /// * `canvas` - 绘制的目标图片
+   /// * `mark_area` - 水印区域（左、上、右、下）
+   /// * `style` - 水印样式
+   /// * `fonts` - 机型和Exif信息的字体栈
+   /// * `logo` - 解码后的Logo
+   fn draw_overlay_block(
+      &self,
+      canvas: &mut RgbImage,
+      mark_area: (u32, u32, u32, u32),
+      style: &MarkStyle,
+      fonts: &MarkFonts,
+      logo: &Logo,
+   ) -> Result<Vec<MarkElement>> {
+      let padding = (self.mark_height * style.padding_ratio) as u32;
+      let gap = (self.mark_height * style.gap_ratio).max(0.0) as u32;
+      // 叠加水印中没有机型，只绘制Logo两侧的分隔线
+      let before_logo = self.dividers(style, DividerSlot::BeforeLogo);
+      let after_logo = self.dividers(style, DividerSlot::AfterLogo);
+      let logo_gap = match after_logo.is_empty() {
+         true => gap * 2,
+         false => gap + divider::run_width(&after_logo, gap),
+      };
+      let before_width = divider::run_width(&before_logo, gap);
+      let (logo_width, logo_height) = self.logo_size(style, logo);
+      let (start_x, start_y, end_x, end_y) = mark_area;
+      let mut canvas = Blend::new(canvas, style.opacity);
+      let secondary_text = self.secondary_text(style);
+      let mut texts = self.fit_texts(style, fonts, secondary_text.as_deref());
+      // Exif信息和附加信息上下排列，较宽的一行决定整体宽度
+      let fixed = padding * 2 + before_width + logo_width + logo_gap;
+      let rows = [
+         FitRow {
+            fixed,
+            texts: vec![2],
+         },
+         FitRow {
+            fixed,
+            texts: vec![1],
+         },
+      ];
+      fit_texts(&mut texts, &rows, end_x - start_x)?;
+      let [_, secondary, exif] = &texts;
+      let block_width =
+         before_width + logo_width + logo_gap + exif.width().max(secondary.width());
+      let block_x = if style.corner.is_right() {
+         end_x - padding - block_width
+      } else {
+         start_x + padding
+      };
+      let logo_x = block_x + before_width;
+      let center_y = (start_y + end_y) as f32 / 2.0;
+      // 绘制Logo
+      let logo_y = (center_y - logo_height as f32 / 2.0).max(0.0) as u32;
+      // 从右向左时在水印块内左右镜像，Exif信息和附加信息右对齐
+      let mirror = Mirror::new(style.text_direction, block_x, block_x + block_width);
+      let anchoring = Anchoring::new(mark_area, padding, self.mark_height);
+      let anchors = &style.anchors;
+      let logo_position = anchoring.place_unsigned(
+         anchors.logo,
+         (mirror.x(logo_x as i32, logo_width) as u32, logo_y),
+         logo_width,
+      );
+      self.draw_logo(
+         &mut canvas,
+         style,
+         logo,
+         logo_position,
+         (logo_width, logo_height),
+      );
+      // 绘制Logo两侧的分隔线
+      let (gap_x, anchor) = (gap as i32, anchors.divider);
+      let position = (block_x as i32, center_y);
+      Self::draw_dividers(
+         &mut canvas,
+         &before_logo,
+         position,
+         gap_x,
+         mirror,
+         &anchoring,
+         anchor,
+      );
+      let position = ((logo_x + logo_width + gap) as i32, center_y);
+      Self::draw_dividers(
+         &mut canvas,
+         &after_logo,
+         position,
+         gap_x,
+         mirror,
+         &anchoring,
+         anchor,
+      );
+      // Exif信息和附加信息左对齐，整体垂直居中
+      let text_x = (logo_x + logo_width + logo_gap) as i32;
+      let text_height = match secondary_text {
+         Some(_) => exif.size + gap as f32 + secondary.size,
+         None => exif.size,
+      };
+      let exif_y = center_y - text_height / 2.0;
+      // 绘制Exif信息
+      let (x, y) = anchoring.place(
+         anchors.exif,
+         (mirror.x(text_x, exif.width()), exif_y as i32),
+         exif.width(),
+      );
+      canvas.draw_text(style.exif_color, x, y, &exif.run());
+      // 绘制拍摄时间等附加信息
+      if secondary_text.is_some() {
+         let (x, y) = anchoring.place(
+            anchors.secondary,
+            (
+               mirror.x(text_x, secondary.width()),
+               (exif_y + exif.size + gap as f32) as i32,
+            ),
+            secondary.width(),
+         );
+         canvas.draw_text(style.date_color, x, y, &secondary.run());
       }
-      Ok(exif)
+      Ok(canvas.into_elements())
    }
-
-   /// 处理单个EXIF条目，更新Exif结构体字段
-   fn process_entry(exif: &mut Exif, tag: ExifTag, value: &str, _entry: &ExifEntry) {
-      match tag {
-         // 相机型号：处理前缀并修剪空白
-         Model => {
-            exif.model_title = value.trim().replace("DC-", "LUMIX ");
-         }
-         // 拍摄时间：直接使用原始值
-         DateTimeOriginal => {
-            exif.shoot_time = value.to_string();
-         }
-         // 曝光时间：移除空格并转为大写
-         ExposureTime => {
-            exif.exposure_time = value.replace(' ', "").to_uppercase();
-         }
-         // 光圈值：格式化显示
-         FNumber => {
-            exif.aperture = value.replace("f/", "F");
-         }
-         // ISO值：直接使用
-         ISOSpeedRatings => {
-            exif.iso = value.replace(' ', "").to_uppercase();
-         }
-         FocalLengthIn35mmFilm => {
-            if !value.trim().is_empty() {
-               exif.focal_length = value.replace(' ', "").to_uppercase();
-            }
+   /// # 在水印条底部绘制折行后的说明文字
+   ///
+   /// 每行水平居中，叠加水印中靠向所在的角
+   ///
+   /// # 参数
+   /// * `canvas` - 绘制的目标图片
+   /// * `caption_area` - 说明文字的区域（左、上、右、下）
+   /// * `style` - 水印样式
+   /// * `lines` - 折行后的说明文字
+   fn draw_caption(
+      &self,
+      canvas: &mut RgbImage,
+      caption_area: (u32, u32, u32, u32),
+      style: &MarkStyle,
+      lines: &[FitText],
+   ) -> Vec<MarkElement> {
+      let padding = (self.mark_height * style.padding_ratio) as u32;
+      let gap = (self.mark_height * style.gap_ratio).max(0.0);
+      let (start_x, start_y, end_x, _) = caption_area;
+      let mut canvas = Blend::new(canvas, style.opacity);
+      let anchoring = Anchoring::new(caption_area, padding, self.mark_height);
+      let mut y = start_y as f32;
+      for line in lines {
+         let width = line.width();
+         let x = match style.frame {
+            Frame::Overlay if style.corner.is_right() => end_x - padding - width,
+            Frame::Overlay => start_x + padding,
+            Frame::Strip | Frame::Polaroid => start_x + (end_x - start_x - width) / 2,
+         };
+         let (line_x, line_y) =
+            anchoring.place(style.anchors.caption, (x as i32, y as i32), width);
+         canvas.draw_text(style.date_color, line_x, line_y, &line.run());
+         y += line.size + gap;
+      }
+      canvas.into_elements()
+   }
+   /// 参与排版的机型、附加信息和Exif信息，没有附加信息时为空文字
+   fn fit_texts<'a>(
+      &self,
+      style: &MarkStyle,
+      fonts: &'a MarkFonts<'a>,
+      secondary_text: Option<&str>,
+   ) -> [FitText<'a>; 3] {
+      let flow = |text: &str| style.text_direction.flow(text, style.strip_position());
+      let secondary_text = secondary_text.unwrap_or_default();
+      let exif_text = self
+         .exif
+         .render_with_icons(&style.exif_template, style.exif_icons);
+      [
+         FitText::new(
+            &self.exif.model_title,
+            self.mark_height * style.model_text_ratio,
+            &fonts.model,
+            flow(&self.exif.model_title),
+         ),
+         FitText::new(
+            secondary_text,
+            self.mark_height * style.date_text_ratio,
+            &fonts.exif,
+            flow(secondary_text),
+         ),
+         FitText::new(
+            &exif_text,
+            self.mark_height * style.exif_text_ratio,
+            &fonts.exif,
+            flow(&exif_text),
+         ),
+      ]
+   }
+   /// 水印中的附加信息：拍摄时间、地点和版权信息，均未开启或EXIF中没有对应信息时为`None`
+   fn secondary_text(&self, style: &MarkStyle) -> Option<String> {
+      let date = style
+         .show_date
+         .then(|| self.exif.shoot_date_time())
+         .flatten()
+         .map(|time| {
+            style
+               .date_format
+               .format_time(&time.to_zone(style.time_zone))
+         });
+      let location = style
+         .show_location
+         .then_some(self.exif.gps.as_ref())
+         .flatten()
+         .and_then(|gps| style.geocoder.0.locate(gps));
+      let copyright = style
+         .show_copyright
+         .then(|| match &style.copyright {
+            Some(copyright) => Some(copyright.clone()),
+            None => self.exif.copyright_text(),
+         })
+         .flatten();
+      let rating = style
+         .show_rating
+         .then(|| ExifIcon::rating_text(self.exif.rating, &self.exif.label))
+         .flatten();
+      let parts: Vec<String> = rating
+         .into_iter()
+         .chain(date)
+         .chain(location)
+         .chain(copyright)
+         .collect();
+      (!parts.is_empty()).then(|| parts.join("  "))
+   }
+   /// # 只绘制Logo
+   ///
+   /// 照片没有EXIF信息且样式设置了`logo_only_without_exif`时使用，
+   /// 水印条中Logo居中，叠加水印中Logo位于所在的角
+   ///
+   /// # 参数
+   /// * `canvas` - 绘制的目标图片
+   /// * `mark_area` - 水印区域（左、上、右、下）
+   /// * `style` - 水印样式
+   /// * `logo` - 解码后的Logo
+   fn draw_logo_only(
+      &self,
+      canvas: &mut RgbImage,
+      mark_area: (u32, u32, u32, u32),
+      style: &MarkStyle,
+      logo: &Logo,
+   ) -> Vec<MarkElement> {
+      let padding = (self.mark_height * style.padding_ratio) as u32;
+      let (logo_width, logo_height) = self.logo_size(style, logo);
+      let (start_x, start_y, end_x, end_y) = mark_area;
+      let logo_x = match style.frame {
+         Frame::Overlay if style.corner.is_right() => {
+            end_x.saturating_sub(padding + logo_width)
          }
-         // 焦距：格式化显示
-         FocalLength => {
-            if exif.focal_length.is_empty() {
-               exif.focal_length = value.replace(' ', "").to_uppercase();
-            }
+         Frame::Overlay => start_x + padding,
+         Frame::Strip | Frame::Polaroid => {
+            (start_x + end_x).saturating_sub(logo_width) / 2
          }
-         Orientation => {
-            exif.orientation = value.into();
+      };
+      let logo_y = (start_y + end_y).saturating_sub(logo_height) / 2;
+      let mut canvas = Blend::new(canvas, style.opacity);
+      let anchoring = Anchoring::new(mark_area, padding, self.mark_height);
+      let logo_position =
+         anchoring.place_unsigned(style.anchors.logo, (logo_x, logo_y), logo_width);
+      self.draw_logo(
+         &mut canvas,
+         style,
+         logo,
+         logo_position,
+         (logo_width, logo_height),
+      );
+      canvas.into_elements()
+   }
+   /// # Logo在横向水印条中的宽度和高度
+   ///
+   /// `logo_size`为`auto`时只使用`logo_height_ratio`，另一边按Logo的原始宽高比计算，
+   /// 竖排时Logo旋转绘制，水印条方向的长度由宽高比计算
+   fn logo_size(&self, style: &MarkStyle, logo: &Logo) -> (u32, u32) {
+      let height = self.mark_height * style.logo_height_ratio;
+      let width = match style.logo_size {
+         LogoSize::Fixed => self.mark_height * style.logo_width_ratio,
+         LogoSize::Auto => {
+            let (logo_width, logo_height) = logo.dimensions();
+            let aspect = logo_width.max(1) as f32 / logo_height.max(1) as f32;
+            match style.text_direction.flow("", style.strip_position()) {
+               Flow::Vertical { .. } => height / aspect,
+               Flow::Ltr | Flow::Rtl => height * aspect,
+            }
          }
-         // 忽略其他标签
-         _ => {}
+      };
+      (width as u32, height as u32)
+   }
+   /// # 位于`slot`的分隔线，按水印高度计算尺寸
+   ///
+   /// 未设置`dividers`时只有Logo与Exif信息之间的一条默认分隔线；缺省的颜色在自动背景中为照片的主色调，
+   /// 样式为`none`的分隔线不占用宽度
+   fn dividers(&self, style: &MarkStyle, slot: DividerSlot) -> Vec<PlacedDivider> {
+      let default = [DividerSpec::default()];
+      let specs = match style.dividers.is_empty() {
+         true => &default[..],
+         false => &style.dividers[..],
+      };
+      specs
+         .iter()
+         .filter(|spec| spec.slot == slot)
+         .map(|spec| {
+            let divider = spec.style.unwrap_or(style.divider);
+            let size = divider.size(
+               self.mark_height * spec.width_ratio.unwrap_or(style.divider_width_ratio),
+               self.mark_height
+                  * spec.height_ratio.unwrap_or(style.divider_height_ratio),
+            );
+            let color = spec.color.or(self.accent).unwrap_or(style.divider_color);
+            PlacedDivider::new(divider, size, color)
+         })
+         .filter(|divider| divider.style != Divider::None && divider.width() > 0)
+         .collect()
+   }
+   /// # 从`x`开始从左到右依次绘制分隔线，每条分隔线之后留出`gap`
+   ///
+   /// # 参数
+   /// * `canvas` - 绘制的目标图片
+   /// * `dividers` - 同一位置的分隔线
+   /// * `(x, center_y)` - 第一条分隔线的左边缘和所有分隔线的垂直中心
+   /// * `gap` - 分隔线之间的间距
+   /// * `mirror` - 从右向左时的镜像
+   /// * `anchoring` - 位置覆盖，`anchor`为分隔线的覆盖
+   fn draw_dividers(
+      canvas: &mut Blend,
+      dividers: &[PlacedDivider],
+      (x, center_y): (i32, f32),
+      gap: i32,
+      mirror: Mirror,
+      anchoring: &Anchoring,
+      anchor: Option<Anchor>,
+   ) {
+      let mut x = x;
+      for divider in dividers {
+         let width = divider.width();
+         let left = mirror.x(x, width);
+         let (dx, dy) = anchoring.offset(anchor, left, width);
+         canvas.draw_divider(
+            divider.style,
+            (
+               (left + dx) as f32 + width as f32 / 2.0,
+               center_y + dy as f32,
+            ),
+            divider.size,
+            divider.color,
+         );
+         x += width as i32 + gap;
       }
    }
-
-   pub fn to_string(&self) -> String {
-      format!(
-         "{} {} {} {}",
-         self.focal_length, self.aperture, self.exposure_time, self.iso
-      )
+   /// 直方图的宽度和高度，未设置直方图或叠加水印时为`None`
+   fn histogram_size(&self, style: &MarkStyle) -> Option<(u32, u32)> {
+      let histogram = style.histogram.as_ref()?;
+      let width = (self.mark_height * histogram.width_ratio).round() as u32;
+      let height = (self.mark_height * histogram.height_ratio).round() as u32;
+      (style.frame != Frame::Overlay && width > 0 && height > 0)
+         .then_some((width, height))
    }
-}
-
-pub enum Color {
-   Black,
-   White,
-   RGB(u8, u8, u8),
-   HEX(&'static str),
-}
-
-impl From<Color> for Rgb<u8> {
-   fn from(color: Color) -> Self {
-      match color {
-         Color::Black => Rgb([0, 0, 0]),
-         Color::White => Rgb([255, 255, 255]),
-         Color::RGB(r, g, b) => Rgb([r, g, b]),
-         Color::HEX(hex) => {
-            let hex = hex.trim_start_matches('#');
-            if hex.len() != 6 {
-               return Rgb([0, 0, 0]);
-            }
-            let r = u8::from_str_radix(&hex[0..2], 16).unwrap_or_default();
-            let g = u8::from_str_radix(&hex[2..4], 16).unwrap_or_default();
-            let b = u8::from_str_radix(&hex[4..6], 16).unwrap_or_default();
-            Rgb([r, g, b])
+   /// 二维码的边长，未设置二维码、链接为空或叠加水印时为`None`
+   fn qr_side(&self, style: &MarkStyle) -> Option<u32> {
+      let qr = style.qr.as_ref()?;
+      qr.render_url(&self.exif)?;
+      let side = (self.mark_height * qr.size_ratio).round() as u32;
+      (style.frame != Frame::Overlay && side > 0).then_some(side)
+   }
+   /// # 绘制二维码
+   ///
+   /// 竖排时与Logo一样旋转，链接过长无法编码时返回错误
+   fn draw_qr(
+      &self,
+      canvas: &mut Blend,
+      style: &MarkStyle,
+      (x, y): (u32, u32),
+      side: u32,
+   ) -> Empty {
+      let Some(qr) = &style.qr else {
+         return Ok(());
+      };
+      let Some(url) = qr.render_url(&self.exif) else {
+         return Ok(());
+      };
+      let image = QrCode::render(&url, side)?;
+      let image = match style.text_direction.flow("", style.strip_position()) {
+         Flow::Vertical { clockwise: true } => rotate90(&image),
+         Flow::Vertical { clockwise: false } => rotate270(&image),
+         Flow::Ltr | Flow::Rtl => image,
+      };
+      canvas.record(ElementKind::QrCode, x as i64, y as i64, side, side);
+      canvas.draw_silhouette(&image, x, y, qr.color, 1.0);
+      Ok(())
+   }
+   /// # 绘制照片的直方图
+   ///
+   /// 按画布中（分块编码时为画布外）的照片像素统计，竖排时与Logo一样旋转
+   fn draw_histogram(
+      &self,
+      canvas: &mut Blend,
+      style: &MarkStyle,
+      histogram: &Histogram,
+      (x, y): (u32, u32),
+      (width, height): (u32, u32),
+   ) {
+      let bins = match &self.tiled {
+         Some(tiled) => HistogramBins::from_image(tiled.photo()),
+         None => {
+            let (left, top, right, bottom) = self.photo_area;
+            HistogramBins::from_image(&*self.canvas.view(
+               left,
+               top,
+               right - left,
+               bottom - top,
+            ))
+         }
+      };
+      let image = match style.text_direction.flow("", style.strip_position()) {
+         Flow::Vertical { clockwise: true } => {
+            rotate90(&histogram.render(&bins, height, width))
+         }
+         Flow::Vertical { clockwise: false } => {
+            rotate270(&histogram.render(&bins, height, width))
          }
+         Flow::Ltr | Flow::Rtl => histogram.render(&bins, width, height),
+      };
+      canvas.record(
+         ElementKind::Histogram,
+         x as i64,
+         y as i64,
+         image.width(),
+         image.height(),
+      );
+      match histogram.channels {
+         HistogramChannels::Luminance => {
+            canvas.draw_silhouette(&image, x, y, histogram.color, histogram.opacity)
+         }
+         HistogramChannels::Rgb => canvas.draw_image(&image, x, y, histogram.opacity),
+      }
+   }
+   /// # 绘制Logo
+   ///
+   /// 样式指定了`logo_tint`时绘制为该颜色的剪影
+   ///
+   /// # 参数
+   /// * `canvas` - 绘制的目标画布
+   /// * `style` - 水印样式
+   /// * `logo` - 解码后的Logo
+   /// * `(x, y)` - Logo左上角的位置
+   /// * `(width, height)` - Logo缩放后的尺寸
+   fn draw_logo(
+      &self,
+      canvas: &mut Blend,
+      style: &MarkStyle,
+      logo: &Logo,
+      (x, y): (u32, u32),
+      (width, height): (u32, u32),
+   ) {
+      let filter = style.logo_filter;
+      // 竖排时Logo与文字一样旋转，水印条旋转回原位置后保持正立
+      let logo = match style.text_direction.flow("", style.strip_position()) {
+         Flow::Vertical { clockwise: true } => {
+            rotate90(&logo.render(height, width, filter))
+         }
+         Flow::Vertical { clockwise: false } => {
+            rotate270(&logo.render(height, width, filter))
+         }
+         Flow::Ltr | Flow::Rtl => logo.render(width, height, filter),
+      };
+      canvas.record(
+         ElementKind::Logo,
+         x as i64,
+         y as i64,
+         logo.width(),
+         logo.height(),
+      );
+      match style.logo_tint {
+         Some(tint) => canvas.draw_silhouette(&logo, x, y, tint, style.logo_opacity),
+         None => canvas.draw_image(&logo, x, y, style.logo_opacity),
       }
    }
 }
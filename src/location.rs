@@ -0,0 +1,125 @@
+use rexif::{ExifEntry, ExifTag, TagValue};
+use serde::{Deserialize, Serialize};
+use std::fmt::{self, Debug, Formatter};
+use std::sync::{Arc, LazyLock};
+
+/// # GPS位置
+///
+/// 经纬度为十进制度数，南纬和西经为负数
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct GpsPosition {
+   /// 纬度
+   pub latitude: f64,
+   /// 经度
+   pub longitude: f64,
+   /// 海拔（米），海平面以下为负数
+   pub altitude: Option<f64>,
+}
+
+impl GpsPosition {
+   /// 从EXIF条目中读取GPS位置，缺少经纬度时返回`None`
+   pub fn from_entries(entries: &[ExifEntry]) -> Option<Self> {
+      let find = |tag| entries.iter().find(|entry| entry.tag == tag);
+      // 参考方向为`S`、`W`时取负数
+      let reference = |tag, negative: &str| match find(tag).map(|entry| &entry.value) {
+         Some(TagValue::Ascii(value)) if value.trim() == negative => -1.0,
+         _ => 1.0,
+      };
+      let degrees = |tag| {
+         let value = &find(tag)?.value;
+         let (d, m, s) = (value.to_f64(0)?, value.to_f64(1)?, value.to_f64(2)?);
+         Some(d + m / 60.0 + s / 3600.0).filter(|degrees| degrees.is_finite())
+      };
+      let latitude = degrees(ExifTag::GPSLatitude)?;
+      let longitude = degrees(ExifTag::GPSLongitude)?;
+      // 海拔参考为1时表示海平面以下
+      let altitude = find(ExifTag::GPSAltitude)
+         .and_then(|entry| entry.value.to_f64(0))
+         .filter(|altitude| altitude.is_finite())
+         .map(|altitude| {
+            match find(ExifTag::GPSAltitudeRef).and_then(|entry| entry.value.to_i64(0))
+            {
+               Some(1) => -altitude,
+               _ => altitude,
+            }
+         });
+      Some(Self {
+         latitude: latitude * reference(ExifTag::GPSLatitudeRef, "S"),
+         longitude: longitude * reference(ExifTag::GPSLongitudeRef, "W"),
+         altitude,
+      })
+   }
+}
+
+/// # 将GPS位置转换为显示的地点
+///
+/// 可以实现该trait接入在线或离线的逆地理编码服务，将坐标显示为“Tokyo, Japan”这样的地名；
+/// 闭包`Fn(&GpsPosition) -> Option<String>`也实现了该trait
+pub trait Geocoder: Send + Sync {
+   /// 返回`None`时水印中不显示地点
+   fn locate(&self, position: &GpsPosition) -> Option<String>;
+}
+
+impl<F> Geocoder for F
+where
+   F: Fn(&GpsPosition) -> Option<String> + Send + Sync,
+{
+   fn locate(&self, position: &GpsPosition) -> Option<String> {
+      self(position)
+   }
+}
+
+/// 默认的离线地点格式，显示为度分秒形式的坐标，如`35°39'29"N 139°42'2"E 40M`
+#[derive(Clone, Copy, Debug, Default)]
+pub struct CoordinateFormat;
+
+impl Geocoder for CoordinateFormat {
+   fn locate(&self, position: &GpsPosition) -> Option<String> {
+      let dms = |value: f64, positive: char, negative: char| {
+         let seconds = (value.abs() * 3600.0).round() as u64;
+         let direction = if value < 0.0 { negative } else { positive };
+         format!(
+            "{}°{}'{}\"{direction}",
+            seconds / 3600,
+            seconds / 60 % 60,
+            seconds % 60
+         )
+      };
+      let mut location = format!(
+         "{} {}",
+         dms(position.latitude, 'N', 'S'),
+         dms(position.longitude, 'E', 'W')
+      );
+      if let Some(altitude) = position.altitude {
+         location.push_str(&format!(" {}M", altitude.round()));
+      }
+      Some(location)
+   }
+}
+
+/// 水印样式中使用的地点转换器，默认为[`CoordinateFormat`]
+#[derive(Clone)]
+pub struct SharedGeocoder(pub Arc<dyn Geocoder>);
+
+/// 默认转换器共用同一个实例，使默认样式之间相等
+static COORDINATE_FORMAT: LazyLock<Arc<dyn Geocoder>> =
+   LazyLock::new(|| Arc::new(CoordinateFormat));
+
+impl Default for SharedGeocoder {
+   fn default() -> Self {
+      Self(COORDINATE_FORMAT.clone())
+   }
+}
+
+impl Debug for SharedGeocoder {
+   fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+      f.write_str("SharedGeocoder")
+   }
+}
+
+/// 指向同一个转换器时相等
+impl PartialEq for SharedGeocoder {
+   fn eq(&self, other: &Self) -> bool {
+      Arc::ptr_eq(&self.0, &other.0)
+   }
+}
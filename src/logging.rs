@@ -0,0 +1,80 @@
+use crate::LumixMarkCli;
+use std::fmt;
+use std::io;
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::fmt::format::Writer;
+use tracing_subscriber::fmt::writer::MakeWriterExt;
+use tracing_subscriber::fmt::{FmtContext, FormatEvent, FormatFields, FormattedFields};
+use tracing_subscriber::registry::LookupSpan;
+
+/// # 命令行的日志格式
+///
+/// 进度信息保持`======>`前缀，警告和错误使用`===error===>`前缀；
+/// 调试信息在消息前显示所在的图片和流水线阶段
+struct MarkFormat;
+
+impl<S, N> FormatEvent<S, N> for MarkFormat
+where
+   S: Subscriber + for<'a> LookupSpan<'a>,
+   N: for<'a> FormatFields<'a> + 'static,
+{
+   fn format_event(
+      &self,
+      ctx: &FmtContext<'_, S, N>,
+      mut writer: Writer<'_>,
+      event: &Event<'_>,
+   ) -> fmt::Result {
+      let level = *event.metadata().level();
+      match level {
+         Level::ERROR | Level::WARN => write!(writer, "===error===>")?,
+         Level::INFO => write!(writer, "======>")?,
+         Level::DEBUG => write!(writer, "======>[debug] ")?,
+         Level::TRACE => write!(writer, "======>[trace] ")?,
+      }
+      if level > Level::INFO {
+         for span in ctx
+            .event_scope()
+            .into_iter()
+            .flat_map(|scope| scope.from_root())
+         {
+            write!(writer, "{}", span.name())?;
+            let extensions = span.extensions();
+            if let Some(fields) = extensions
+               .get::<FormattedFields<N>>()
+               .filter(|fields| !fields.is_empty())
+            {
+               write!(writer, "{{{}}}", fields)?;
+            }
+            write!(writer, ": ")?;
+         }
+      }
+      ctx.field_format().format_fields(writer.by_ref(), event)?;
+      writeln!(writer)
+   }
+}
+
+impl LumixMarkCli {
+   /// # 按`--verbose`的次数输出日志
+   ///
+   /// 默认只输出进度信息，`-v`输出各阶段的尺寸、耗时等调试信息，`-vv`输出文字排版的细节；
+   /// 进度信息写入标准输出（使用`--stdout`时写入标准错误），其他信息写入标准错误。
+   /// 已经设置了全局的tracing订阅者时不做任何处理
+   pub fn init_logging(&self) {
+      let level = match self.verbose {
+         0 => Level::INFO,
+         1 => Level::DEBUG,
+         _ => Level::TRACE,
+      };
+      let info_to_stdout = !self.stdout;
+      let writer = io::stdout
+         .with_filter(move |metadata| {
+            info_to_stdout && *metadata.level() == Level::INFO
+         })
+         .or_else(io::stderr);
+      let _ = tracing_subscriber::fmt()
+         .with_max_level(level)
+         .with_writer(writer)
+         .event_format(MarkFormat)
+         .try_init();
+   }
+}
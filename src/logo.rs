@@ -0,0 +1,201 @@
+use crate::draw::{Logo, unpremultiply};
+use crate::{LumixMarkError, Result};
+use image::RgbaImage;
+use image::imageops::FilterType;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::fs;
+use std::path::PathBuf;
+
+/// L卡口Logo图（松下、适马）
+pub static L_MOUNT_LOGO: &[u8] = include_bytes!("../images/logo.jpg");
+/// 通用相机图标，未识别品牌时使用
+pub static CAMERA_LOGO: &[u8] = include_bytes!("../images/logos/camera.png");
+
+/// 品牌Logo注册表中的一项
+struct BrandLogo {
+   /// EXIF厂商中包含的关键字
+   makes: &'static [&'static str],
+   /// 厂商缺失或未识别时，型号开头的前缀
+   model_prefixes: &'static [&'static str],
+   logo: &'static [u8],
+}
+
+/// 品牌Logo注册表，按顺序匹配
+///
+/// `images/logos`中除L卡口Logo外都是占位图标（品牌首字母），不是各厂商的商标，
+/// 需要正式的Logo时使用`--logo`指定
+static LOGO_REGISTRY: &[BrandLogo] = &[
+   BrandLogo {
+      makes: &["leica"],
+      model_prefixes: &["leica "],
+      logo: include_bytes!("../images/logos/leica.png"),
+   },
+   BrandLogo {
+      makes: &["panasonic", "lumix", "sigma"],
+      model_prefixes: &["dc-", "dmc-"],
+      logo: L_MOUNT_LOGO,
+   },
+   BrandLogo {
+      makes: &["canon"],
+      model_prefixes: &["canon "],
+      logo: include_bytes!("../images/logos/canon.png"),
+   },
+   BrandLogo {
+      makes: &["nikon"],
+      model_prefixes: &["nikon "],
+      logo: include_bytes!("../images/logos/nikon.png"),
+   },
+   BrandLogo {
+      makes: &["sony"],
+      model_prefixes: &["ilce-", "dsc-"],
+      logo: include_bytes!("../images/logos/sony.png"),
+   },
+   BrandLogo {
+      makes: &["fujifilm"],
+      model_prefixes: &["gfx"],
+      logo: include_bytes!("../images/logos/fujifilm.png"),
+   },
+   BrandLogo {
+      makes: &["olympus", "om digital"],
+      model_prefixes: &["om-"],
+      logo: include_bytes!("../images/logos/olympus.png"),
+   },
+];
+
+/// # 根据EXIF中的厂商和型号选择品牌Logo
+///
+/// 先按厂商匹配；厂商缺失或未识别时再按型号的前缀匹配，型号中间出现的关键字不算；
+/// 都未命中时返回通用相机图标
+///
+/// ```
+/// use lumix_mark::{CAMERA_LOGO, L_MOUNT_LOGO, logo_for_camera};
+///
+/// assert_eq!(logo_for_camera("Panasonic", "DC-S5M2"), L_MOUNT_LOGO);
+/// assert_eq!(logo_for_camera("", "DC-S5M2"), L_MOUNT_LOGO);
+/// assert_eq!(logo_for_camera("Acme", "Theos 1"), CAMERA_LOGO);
+/// ```
+pub fn logo_for_camera(make: &str, model: &str) -> &'static [u8] {
+   let make = make.trim().to_lowercase();
+   let model = model.trim().to_lowercase();
+   let by_make = LOGO_REGISTRY.iter().find(|brand| {
+      !make.is_empty() && brand.makes.iter().any(|key| make.contains(key))
+   });
+   let by_model = || {
+      LOGO_REGISTRY.iter().find(|brand| {
+         brand
+            .model_prefixes
+            .iter()
+            .any(|prefix| model.starts_with(prefix))
+      })
+   };
+   by_make
+      .or_else(by_model)
+      .map_or(CAMERA_LOGO, |brand| brand.logo)
+}
+
+/// Logo缩放使用的重采样算法
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogoFilter {
+   /// 最近邻，适合像素风格的Logo
+   Nearest,
+   /// 双线性插值
+   Triangle,
+   /// Catmull-Rom三次插值
+   #[default]
+   CatmullRom,
+   /// Lanczos3，大幅缩小时边缘和细节更清晰
+   Lanczos3,
+}
+
+impl From<LogoFilter> for FilterType {
+   fn from(filter: LogoFilter) -> Self {
+      match filter {
+         LogoFilter::Nearest => FilterType::Nearest,
+         LogoFilter::Triangle => FilterType::Triangle,
+         LogoFilter::CatmullRom => FilterType::CatmullRom,
+         LogoFilter::Lanczos3 => FilterType::Lanczos3,
+      }
+   }
+}
+
+/// Logo的尺寸计算方式
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogoSize {
+   /// 宽度和高度分别由`logo_width_ratio`和`logo_height_ratio`确定
+   #[default]
+   Fixed,
+   /// 高度由`logo_height_ratio`确定，宽度按Logo的原始宽高比计算
+   Auto,
+}
+
+/// 水印Logo的来源
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LogoSource {
+   /// 编译时内嵌的Logo图
+   Embedded(&'static [u8]),
+   /// 外部Logo图片文件
+   File(PathBuf),
+   /// 内存中的Logo图片数据
+   Bytes(Vec<u8>),
+}
+
+impl From<&'static [u8]> for LogoSource {
+   fn from(bytes: &'static [u8]) -> Self {
+      LogoSource::Embedded(bytes)
+   }
+}
+
+impl From<PathBuf> for LogoSource {
+   fn from(path: PathBuf) -> Self {
+      LogoSource::File(path)
+   }
+}
+
+impl From<Vec<u8>> for LogoSource {
+   fn from(bytes: Vec<u8>) -> Self {
+      LogoSource::Bytes(bytes)
+   }
+}
+
+impl LogoSource {
+   /// 根据EXIF中的厂商和型号选择内嵌的品牌Logo
+   pub fn for_camera(make: &str, model: &str) -> Self {
+      LogoSource::Embedded(logo_for_camera(make, model))
+   }
+
+   /// 加载并解码为RGBA图片（非预乘Alpha），SVG Logo按原始尺寸栅格化
+   pub fn load(&self) -> Result<RgbaImage> {
+      let logo = self.decode()?;
+      let (width, height) = logo.dimensions();
+      let mut image = logo.render(width, height, LogoFilter::default());
+      unpremultiply(&mut image);
+      Ok(image)
+   }
+
+   /// 解码为可以按任意尺寸绘制的Logo
+   pub(crate) fn decode(&self) -> Result<Logo> {
+      let logo = self.bytes().and_then(|bytes| Logo::decode(&bytes));
+      match self {
+         LogoSource::File(path) => logo.map_err(|err| match err {
+            LumixMarkError::InvalidConfig(msg) => {
+               LumixMarkError::InvalidConfig(format!("Logo文件{path:?}：{msg}"))
+            }
+            err => {
+               LumixMarkError::InvalidConfig(format!("无法读取Logo文件{path:?}：{err}"))
+            }
+         }),
+         LogoSource::Embedded(_) | LogoSource::Bytes(_) => logo,
+      }
+   }
+
+   fn bytes(&self) -> Result<Cow<'_, [u8]>> {
+      Ok(match self {
+         LogoSource::Embedded(bytes) => Cow::Borrowed(bytes),
+         LogoSource::File(path) => Cow::Owned(fs::read(path)?),
+         LogoSource::Bytes(bytes) => Cow::Borrowed(bytes),
+      })
+   }
+}
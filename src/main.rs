@@ -1,6 +1,48 @@
-use lumix_mark::LumixMarkCli;
+use lumix_mark::{Command, ExitStatus, LumixMarkCli, PresetRegistry};
+use std::process::ExitCode;
+use std::time::Duration;
 
-fn main() {
-   let cli = LumixMarkCli::parse_image_list();
-   cli.par_draw_logo_exif_task();
+fn main() -> ExitCode {
+   let cli = match LumixMarkCli::try_parse_image_list() {
+      Ok(cli) => cli,
+      Err(err) => {
+         eprintln!("===error===>{}", err);
+         return ExitStatus::ConfigError.into();
+      }
+   };
+   let needs_inputs = matches!(
+      cli.command,
+      Some(Command::Inspect { .. } | Command::Sheet { .. })
+   ) || matches!(cli.command, Some(Command::Mark { .. }) | None)
+      && !cli.stdin;
+   if needs_inputs && cli.inputs.is_empty() {
+      eprintln!("===error===>没有找到需要处理的图片，请检查路径和文件扩展名");
+      return ExitStatus::NoInputs.into();
+   }
+   let result = match &cli.command {
+      Some(Command::Inspect { .. }) => cli.inspect(),
+      Some(Command::Unmark { images }) => cli.unmark(images),
+      Some(Command::Sheet { options, .. }) => cli.sheet(options),
+      Some(Command::Presets) => PresetRegistry::load().map(|registry| registry.print()),
+      Some(Command::InitConfig { path, force }) => {
+         LumixMarkCli::init_config(path, *force)
+      }
+      Some(Command::Watch { dir, debounce }) => {
+         cli.watch(dir, Duration::from_millis(*debounce))
+      }
+      #[cfg(feature = "serve")]
+      Some(Command::Serve { port, host }) => cli.serve(host, *port),
+      // 管道处理由参数决定，与是否写出mark子命令无关
+      Some(Command::Mark { .. }) | None if cli.stdin || cli.stdout => cli.stdio_task(),
+      Some(Command::Mark { .. }) | None => {
+         return cli.par_draw_logo_exif_task().exit_status().into();
+      }
+   };
+   match result {
+      Ok(()) => ExitStatus::Success.into(),
+      Err(err) => {
+         eprintln!("===error===>{}", err);
+         ExitStatus::from(&err).into()
+      }
+   }
 }
@@ -0,0 +1,114 @@
+use image::{ImageDecoder, ImageReader};
+use std::fs;
+#[cfg(feature = "serve")]
+use std::io::Cursor;
+use std::io::{BufRead, Seek};
+use std::path::Path;
+#[cfg(feature = "cli")]
+use std::sync::{Condvar, Mutex};
+#[cfg(feature = "cli")]
+use tracing::debug;
+
+/// 画布、颜色转换和编码缓冲区每像素额外占用的字节数（粗略估计）
+const WORKING_BYTES_PER_PIXEL: u64 = 8;
+/// 无法读取尺寸（如HEIC、RAW）时按文件大小的倍数估计
+const COMPRESSED_FACTOR: u64 = 16;
+
+/// # 估计处理单张图片时占用的内存（字节）
+///
+/// 只读取文件头中的尺寸和像素格式：解码后的照片加上画布等工作缓冲区，再加上文件本身；
+/// 无法读取尺寸时按文件大小估计
+pub(crate) fn estimate_memory(path: &Path) -> u64 {
+   let file_size = fs::metadata(path).map_or(0, |metadata| metadata.len());
+   let reader = ImageReader::open(path).and_then(|reader| reader.with_guessed_format());
+   estimate(reader.ok(), file_size)
+}
+
+/// 与[`estimate_memory`]相同，用于已读入内存的图片
+#[cfg(feature = "serve")]
+pub(crate) fn estimate_memory_bytes(bytes: &[u8]) -> u64 {
+   let reader = ImageReader::new(Cursor::new(bytes)).with_guessed_format();
+   estimate(reader.ok(), bytes.len() as u64)
+}
+
+fn estimate<R: BufRead + Seek>(reader: Option<ImageReader<R>>, file_size: u64) -> u64 {
+   let decoded = reader
+      .and_then(|reader| reader.into_decoder().ok())
+      .map(|decoder| {
+         let (width, height) = decoder.dimensions();
+         let bytes_per_pixel = decoder.color_type().bytes_per_pixel() as u64;
+         width as u64 * height as u64 * (bytes_per_pixel + WORKING_BYTES_PER_PIXEL)
+      });
+   match decoded {
+      Some(decoded) => decoded + file_size,
+      None => file_size * COMPRESSED_FACTOR,
+   }
+}
+
+/// # 内存预算
+///
+/// 解码前按估计的内存占用申请预算，剩余预算不足时等待其他图片处理完成，
+/// 与线程数无关；单张图片超过全部预算时等待其他图片全部完成后单独处理
+#[cfg(feature = "cli")]
+#[derive(Debug)]
+pub(crate) struct MemoryBudget {
+   limit: u64,
+   available: Mutex<u64>,
+   released: Condvar,
+}
+
+#[cfg(feature = "cli")]
+impl MemoryBudget {
+   pub(crate) fn new(limit: u64) -> Self {
+      Self {
+         limit,
+         available: Mutex::new(limit),
+         released: Condvar::new(),
+      }
+   }
+
+   /// 申请`bytes`字节的预算，不足时阻塞等待，返回的`MemoryPermit`释放时归还
+   pub(crate) fn acquire(&self, bytes: u64) -> MemoryPermit<'_> {
+      let bytes = bytes.min(self.limit);
+      let mut available = self.available.lock().unwrap_or_else(|err| err.into_inner());
+      if *available < bytes {
+         debug!(
+            "等待内存预算：需要{}MB，剩余{}MB",
+            bytes / 1_000_000,
+            *available / 1_000_000
+         );
+      }
+      while *available < bytes {
+         available = self
+            .released
+            .wait(available)
+            .unwrap_or_else(|err| err.into_inner());
+      }
+      *available -= bytes;
+      MemoryPermit {
+         budget: self,
+         bytes,
+      }
+   }
+}
+
+/// 已申请的内存预算，释放时归还并唤醒等待的图片
+#[cfg(feature = "cli")]
+#[derive(Debug)]
+pub(crate) struct MemoryPermit<'a> {
+   budget: &'a MemoryBudget,
+   bytes: u64,
+}
+
+#[cfg(feature = "cli")]
+impl Drop for MemoryPermit<'_> {
+   fn drop(&mut self) {
+      let mut available = self
+         .budget
+         .available
+         .lock()
+         .unwrap_or_else(|err| err.into_inner());
+      *available += self.bytes;
+      self.budget.released.notify_all();
+   }
+}
@@ -0,0 +1,628 @@
+use crate::provenance::{Provenance, XMP_NAMESPACE, remove_from_xmp};
+use crate::{LumixMarkError, Result};
+use clap::ValueEnum;
+use img_parts::jpeg::{Jpeg, JpegSegment, markers};
+use img_parts::png::{Png, PngChunk};
+use img_parts::webp::CHUNK_XMP;
+use img_parts::{Bytes, DynImage, ImageEXIF, ImageICC};
+use std::fs;
+use std::path::Path;
+
+/// XMP数据包APP1段前缀
+const XMP_PREFIX: &[u8] = b"http://ns.adobe.com/xap/1.0/\0";
+/// 扩展XMP数据包APP1段前缀
+const XMP_EXTENSION_PREFIX: &[u8] = b"http://ns.adobe.com/xmp/extension/\0";
+/// IPTC所在的Photoshop APP13段前缀
+const IPTC_PREFIX: &[u8] = b"Photoshop 3.0\0";
+/// EXIF中方向标签的编号
+const ORIENTATION_TAG: u16 = 0x0112;
+/// 输出文件中表示已添加水印的标记，写入JPEG的COM段和PNG的tEXt块；WebP和TIFF只写入XMP中的来源信息
+const MARKER: &str = concat!("lumix_mark ", env!("CARGO_PKG_VERSION"));
+/// 检查标记时只比较不含版本号的前缀
+const MARKER_PREFIX: &[u8] = b"lumix_mark ";
+/// PNG中存放标记的tEXt块的关键字
+const PNG_MARKER_KEYWORD: &[u8] = b"Software\0";
+/// PNG中存放XMP的iTXt块的关键字
+const PNG_XMP_KEYWORD: &[u8] = b"XML:com.adobe.xmp\0";
+/// XMP附属文件的扩展名
+const SIDECAR_EXTENSIONS: [&str; 2] = ["xmp", "XMP"];
+/// TIFF中存放XMP数据包的标签
+const TIFF_XMP_TAG: u16 = 700;
+/// TIFF中存放ICC色彩配置文件的标签
+const TIFF_ICC_TAG: u16 = 34675;
+/// TIFF标签的BYTE类型
+const TIFF_BYTE: u16 = 1;
+/// WebP的VP8X块中表示带有XMP块的标志位
+const WEBP_XMP_FLAG: u8 = 0x04;
+/// WebP的VP8X块中表示带有透明通道的标志位
+const WEBP_ALPHA_FLAG: u8 = 0x10;
+
+/// 输入图片已带有lumix_mark水印时的处理方式
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum MarkedPolicy {
+   /// 跳过该图片
+   #[default]
+   Skip,
+   /// 输出警告，仍然再添加一次水印
+   Warn,
+}
+
+/// XMP附属文件中的值与照片中的EXIF、XMP不同时的处理方式
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum SidecarPolicy {
+   /// 附属文件中的值优先，如在Lightroom中修改过的拍摄时间
+   #[default]
+   Prefer,
+   /// 只补充照片中缺少的值
+   Fill,
+   /// 不读取附属文件
+   Ignore,
+}
+
+impl SidecarPolicy {
+   /// 按处理方式读取图片旁的XMP附属文件，见[`Metadata::read_sidecar`]
+   pub fn read(self, path: &Path) -> Option<String> {
+      match self {
+         Self::Ignore => None,
+         _ => Metadata::read_sidecar(path),
+      }
+   }
+}
+
+/// # 图片是否带有lumix_mark写入的标记
+///
+/// 检查JPEG的COM段、PNG的tEXt块，以及XMP中的来源信息（其他软件只保留了XMP时）；
+/// WebP和TIFF只检查XMP中的来源信息，AVIF不写入标记，总是返回`false`
+pub fn is_marked(bytes: &[u8]) -> bool {
+   let has_provenance = |xmp: &[u8]| {
+      let namespace = XMP_NAMESPACE.as_bytes();
+      xmp.windows(namespace.len())
+         .any(|window| window == namespace)
+   };
+   match DynImage::from_bytes(Bytes::copy_from_slice(bytes)) {
+      Ok(Some(DynImage::Jpeg(jpeg))) => jpeg.segments().iter().any(|segment| {
+         let contents = segment.contents();
+         match segment.marker() {
+            markers::COM => contents.starts_with(MARKER_PREFIX),
+            markers::APP1 => {
+               contents.starts_with(XMP_PREFIX) && has_provenance(contents)
+            }
+            _ => false,
+         }
+      }),
+      Ok(Some(DynImage::Png(png))) => png.chunks().iter().any(|chunk| {
+         let contents = chunk.contents();
+         match &chunk.kind() {
+            b"tEXt" => contents
+               .strip_prefix(PNG_MARKER_KEYWORD)
+               .is_some_and(|text| text.starts_with(MARKER_PREFIX)),
+            b"iTXt" => {
+               contents.starts_with(PNG_XMP_KEYWORD) && has_provenance(contents)
+            }
+            _ => false,
+         }
+      }),
+      Ok(Some(DynImage::WebP(webp))) => webp
+         .chunks_by_id(CHUNK_XMP)
+         .filter_map(|chunk| chunk.content().data())
+         .any(|xmp| has_provenance(xmp)),
+      _ => tiff_tag(bytes, TIFF_XMP_TAG).is_some_and(has_provenance),
+   }
+}
+
+/// # 在已编码的JPEG数据中写入标记和来源信息
+///
+/// 已有XMP段（保留的原图元数据）时将来源信息加入其中，否则写入新的XMP段；
+/// XMP段和COM段紧随开头的APP段之后
+pub(crate) fn mark_jpeg(
+   jpeg_bytes: Vec<u8>,
+   provenance: &Provenance,
+) -> Result<Vec<u8>> {
+   let mut jpeg = Jpeg::from_bytes(Bytes::from(jpeg_bytes))
+      .map_err(|err| LumixMarkError::Encode(Box::new(err)))?;
+   let segments = jpeg.segments_mut();
+   let mut index = segments
+      .iter()
+      .take_while(|segment| {
+         (markers::APP0..=markers::APP15).contains(&segment.marker())
+      })
+      .count();
+   let xmp = segments.iter_mut().find(|segment| {
+      segment.marker() == markers::APP1 && segment.contents().starts_with(XMP_PREFIX)
+   });
+   match xmp {
+      Some(segment) => {
+         let packet = String::from_utf8_lossy(&segment.contents()[XMP_PREFIX.len()..]);
+         // 无法解析的XMP保持原样，只写入COM段
+         if let Some(packet) = provenance.merge_into_xmp(&packet) {
+            *segment = xmp_segment(&packet);
+         }
+      }
+      None => {
+         segments.insert(index, xmp_segment(&provenance.to_xmp()));
+         index += 1;
+      }
+   }
+   let comment = JpegSegment::new_with_contents(markers::COM, Bytes::from(MARKER));
+   segments.insert(index, comment);
+   Ok(jpeg.encoder().bytes().to_vec())
+}
+
+fn xmp_segment(packet: &str) -> JpegSegment {
+   let mut contents = XMP_PREFIX.to_vec();
+   contents.extend_from_slice(packet.as_bytes());
+   JpegSegment::new_with_contents(markers::APP1, Bytes::from(contents))
+}
+
+/// 在已编码的PNG数据中写入标记和来源信息，tEXt块和iTXt块位于IEND之前
+pub(crate) fn mark_png(png_bytes: Vec<u8>, provenance: &Provenance) -> Result<Vec<u8>> {
+   let mut png = Png::from_bytes(Bytes::from(png_bytes))
+      .map_err(|err| LumixMarkError::Encode(Box::new(err)))?;
+   let chunks = png.chunks_mut();
+   let mut software = PNG_MARKER_KEYWORD.to_vec();
+   software.extend_from_slice(MARKER.as_bytes());
+   // iTXt：关键字、未压缩、空的语言标签和翻译后的关键字，之后为XMP数据包
+   let mut xmp = PNG_XMP_KEYWORD.to_vec();
+   xmp.extend_from_slice(b"\0\0\0\0");
+   xmp.extend_from_slice(provenance.to_xmp().as_bytes());
+   let end = chunks.len().saturating_sub(1);
+   chunks.insert(end, PngChunk::new(*b"iTXt", Bytes::from(xmp)));
+   chunks.insert(end, PngChunk::new(*b"tEXt", Bytes::from(software)));
+   Ok(png.encoder().bytes().to_vec())
+}
+
+/// # 在已编码的WebP数据中写入来源信息
+///
+/// XMP块位于文件末尾；简单格式（只有VP8L或VP8块）时在开头加入VP8X块并设置XMP标志
+pub(crate) fn mark_webp(
+   webp_bytes: Vec<u8>,
+   provenance: &Provenance,
+) -> Result<Vec<u8>> {
+   let invalid = || LumixMarkError::Encode("无法解析编码后的WebP数据".into());
+   if webp_bytes.get(..4) != Some(b"RIFF") || webp_bytes.get(8..12) != Some(b"WEBP") {
+      return Err(invalid());
+   }
+   let id = webp_bytes.get(12..16).ok_or_else(invalid)?;
+   let payload = webp_bytes.get(20..).ok_or_else(invalid)?;
+   let mut webp = webp_bytes[..12].to_vec();
+   match id {
+      b"VP8X" => {
+         webp.extend_from_slice(&webp_bytes[12..]);
+         webp[20] |= WEBP_XMP_FLAG;
+      }
+      b"VP8L" | b"VP8 " => {
+         let (width, height, alpha) =
+            webp_dimensions(id, payload).ok_or_else(invalid)?;
+         let flags = match alpha {
+            true => WEBP_XMP_FLAG | WEBP_ALPHA_FLAG,
+            false => WEBP_XMP_FLAG,
+         };
+         webp.extend_from_slice(b"VP8X");
+         webp.extend_from_slice(&10u32.to_le_bytes());
+         webp.extend_from_slice(&[flags, 0, 0, 0]);
+         webp.extend_from_slice(&(width - 1).to_le_bytes()[..3]);
+         webp.extend_from_slice(&(height - 1).to_le_bytes()[..3]);
+         webp.extend_from_slice(&webp_bytes[12..]);
+      }
+      _ => return Err(invalid()),
+   }
+   let xmp = provenance.to_xmp();
+   webp.extend_from_slice(b"XMP ");
+   webp.extend_from_slice(&(xmp.len() as u32).to_le_bytes());
+   webp.extend_from_slice(xmp.as_bytes());
+   // 块的长度为奇数时补一个字节
+   if xmp.len() % 2 == 1 {
+      webp.push(0);
+   }
+   let riff_size = u32::try_from(webp.len() - 8).map_err(|_| invalid())?;
+   webp[4..8].copy_from_slice(&riff_size.to_le_bytes());
+   Ok(webp)
+}
+
+/// 简单格式WebP中VP8L或VP8块的宽高和是否带有透明通道
+fn webp_dimensions(id: &[u8], payload: &[u8]) -> Option<(u32, u32, bool)> {
+   match id {
+      b"VP8L" => {
+         // 签名之后为各14位的宽高减一和1位的透明通道标志
+         if *payload.first()? != 0x2F {
+            return None;
+         }
+         let bits = u32::from_le_bytes(payload.get(1..5)?.try_into().ok()?);
+         let width = (bits & 0x3FFF) + 1;
+         let height = ((bits >> 14) & 0x3FFF) + 1;
+         Some((width, height, bits >> 28 & 1 == 1))
+      }
+      _ => {
+         // 关键帧的帧标记和起始码之后为各14位的宽高
+         if payload.get(3..6)? != [0x9D, 0x01, 0x2A] {
+            return None;
+         }
+         let size = |offset: usize| {
+            let bytes = payload.get(offset..offset + 2)?;
+            Some(u32::from(u16::from_le_bytes([bytes[0], bytes[1]]) & 0x3FFF))
+         };
+         Some((size(6)?, size(8)?, false))
+      }
+   }
+}
+
+/// # 在已编码的TIFF数据中写入来源信息
+///
+/// XMP数据包追加到文件末尾，加入XMP标签（700）的新IFD0紧随其后，原来的IFD0不再被引用；
+/// 只支持经典TIFF（不超过4GB）
+pub(crate) fn mark_tiff(mut tiff: Vec<u8>, provenance: &Provenance) -> Result<Vec<u8>> {
+   let invalid = || LumixMarkError::Encode("无法解析编码后的TIFF数据".into());
+   let order = TiffOrder::of(&tiff).ok_or_else(invalid)?;
+   let ifd = order.u32(&tiff, 4).ok_or_else(invalid)? as usize;
+   let count = order.u16(&tiff, ifd).ok_or_else(invalid)? as usize;
+   let end = ifd + 2 + count * 12;
+   let mut entries: Vec<Vec<u8>> = tiff
+      .get(ifd + 2..end)
+      .ok_or_else(invalid)?
+      .chunks_exact(12)
+      .map(<[u8]>::to_vec)
+      .filter(|entry| order.u16(entry, 0) != Some(TIFF_XMP_TAG))
+      .collect();
+   let next_ifd = tiff.get(end..end + 4).ok_or_else(invalid)?.to_vec();
+   // IFD和标签值需要从偶数字节开始
+   let align = |tiff: &mut Vec<u8>| {
+      if tiff.len() % 2 == 1 {
+         tiff.push(0);
+      }
+   };
+   align(&mut tiff);
+   let xmp = provenance.to_xmp();
+   let xmp_offset = tiff.len();
+   tiff.extend_from_slice(xmp.as_bytes());
+   align(&mut tiff);
+   let ifd_offset = tiff.len();
+   let offset = |offset: usize| {
+      u32::try_from(offset)
+         .map_err(|_| LumixMarkError::Encode("TIFF超过4GB，无法写入来源信息".into()))
+   };
+   let mut entry = Vec::with_capacity(12);
+   order.put_u16(&mut entry, TIFF_XMP_TAG);
+   order.put_u16(&mut entry, TIFF_BYTE);
+   order.put_u32(&mut entry, offset(xmp.len())?);
+   order.put_u32(&mut entry, offset(xmp_offset)?);
+   entries.push(entry);
+   // IFD中的标签按编号升序排列
+   entries.sort_by_key(|entry| order.u16(entry, 0));
+   order.put_u16(&mut tiff, entries.len() as u16);
+   for entry in &entries {
+      tiff.extend_from_slice(entry);
+   }
+   tiff.extend_from_slice(&next_ifd);
+   let mut header = Vec::with_capacity(4);
+   order.put_u32(&mut header, offset(ifd_offset)?);
+   tiff[4..8].copy_from_slice(&header);
+   Ok(tiff)
+}
+
+/// TIFF文件中的ICC色彩配置文件
+pub(crate) fn tiff_icc_profile(tiff: &[u8]) -> Option<Vec<u8>> {
+   tiff_tag(tiff, TIFF_ICC_TAG).map(<[u8]>::to_vec)
+}
+
+/// TIFF文件IFD0中BYTE或UNDEFINED类型标签的值，不是TIFF或没有该标签时返回`None`
+fn tiff_tag(tiff: &[u8], tag: u16) -> Option<&[u8]> {
+   let order = TiffOrder::of(tiff)?;
+   let ifd = order.u32(tiff, 4)? as usize;
+   let count = order.u16(tiff, ifd)? as usize;
+   (0..count).find_map(|index| {
+      let entry = ifd + 2 + index * 12;
+      if order.u16(tiff, entry)? != tag {
+         return None;
+      }
+      let length = order.u32(tiff, entry + 4)? as usize;
+      // 不超过4字节的值直接存放在值字段中
+      let offset = match length <= 4 {
+         true => entry + 8,
+         false => order.u32(tiff, entry + 8)? as usize,
+      };
+      tiff.get(offset..offset.checked_add(length)?)
+   })
+}
+
+/// 经典TIFF的字节序
+#[derive(Clone, Copy)]
+struct TiffOrder {
+   big_endian: bool,
+}
+
+impl TiffOrder {
+   fn of(tiff: &[u8]) -> Option<Self> {
+      match tiff.get(..4)? {
+         b"MM\0*" => Some(Self { big_endian: true }),
+         b"II*\0" => Some(Self { big_endian: false }),
+         _ => None,
+      }
+   }
+
+   fn u16(self, bytes: &[u8], offset: usize) -> Option<u16> {
+      let bytes = bytes.get(offset..offset + 2)?.try_into().ok()?;
+      Some(match self.big_endian {
+         true => u16::from_be_bytes(bytes),
+         false => u16::from_le_bytes(bytes),
+      })
+   }
+
+   fn u32(self, bytes: &[u8], offset: usize) -> Option<u32> {
+      let bytes = bytes.get(offset..offset + 4)?.try_into().ok()?;
+      Some(match self.big_endian {
+         true => u32::from_be_bytes(bytes),
+         false => u32::from_le_bytes(bytes),
+      })
+   }
+
+   fn put_u16(self, bytes: &mut Vec<u8>, value: u16) {
+      match self.big_endian {
+         true => bytes.extend_from_slice(&value.to_be_bytes()),
+         false => bytes.extend_from_slice(&value.to_le_bytes()),
+      }
+   }
+
+   fn put_u32(self, bytes: &mut Vec<u8>, value: u32) {
+      match self.big_endian {
+         true => bytes.extend_from_slice(&value.to_be_bytes()),
+         false => bytes.extend_from_slice(&value.to_le_bytes()),
+      }
+   }
+}
+
+/// 原图中需要保留到输出文件的元数据
+#[derive(Default, Debug, Clone)]
+pub struct Metadata {
+   /// EXIF的TIFF数据（不含`Exif\0\0`前缀）
+   pub exif: Option<Vec<u8>>,
+   /// 需要原样复制的APP段（XMP、IPTC），保存为(标记, 段内容)
+   pub segments: Vec<(u8, Vec<u8>)>,
+   /// ICC色彩配置文件，如Display P3、Adobe RGB
+   pub icc_profile: Option<Vec<u8>>,
+}
+
+impl Metadata {
+   /// 从JPEG/PNG/WebP文件内容中提取元数据，无法识别的格式返回空元数据
+   pub fn from_bytes(bytes: &[u8]) -> Self {
+      let Ok(Some(image)) = DynImage::from_bytes(Bytes::copy_from_slice(bytes)) else {
+         return Self::default();
+      };
+      let exif = image.exif().map(|exif| exif.to_vec());
+      let icc_profile = image.icc_profile().map(|icc| icc.to_vec());
+      let segments = match image {
+         DynImage::Jpeg(jpeg) => jpeg
+            .segments()
+            .iter()
+            .filter(|segment| {
+               let contents = segment.contents();
+               match segment.marker() {
+                  markers::APP1 => {
+                     contents.starts_with(XMP_PREFIX)
+                        || contents.starts_with(XMP_EXTENSION_PREFIX)
+                  }
+                  markers::APP13 => contents.starts_with(IPTC_PREFIX),
+                  _ => false,
+               }
+            })
+            .map(|segment| (segment.marker(), segment.contents().to_vec()))
+            .collect(),
+         _ => Vec::new(),
+      };
+      Self {
+         exif,
+         segments,
+         icc_profile,
+      }
+   }
+
+   /// 嵌入的XMP数据包，没有时返回`None`
+   pub fn xmp(&self) -> Option<String> {
+      self
+         .segments
+         .iter()
+         .find(|(marker, contents)| {
+            *marker == markers::APP1 && contents.starts_with(XMP_PREFIX)
+         })
+         .map(|(_, contents)| {
+            String::from_utf8_lossy(&contents[XMP_PREFIX.len()..]).into_owned()
+         })
+   }
+
+   /// # 读取图片旁的XMP附属文件
+   ///
+   /// 依次查找`P1000001.xmp`（Lightroom、Capture One）和`P1000001.JPG.xmp`（darktable），
+   /// 扩展名可以为大写，都不存在时返回`None`
+   pub fn read_sidecar(path: &Path) -> Option<String> {
+      let candidates = SIDECAR_EXTENSIONS.iter().flat_map(|extension| {
+         let mut appended = path.as_os_str().to_owned();
+         appended.push(".");
+         appended.push(extension);
+         [path.with_extension(extension), appended.into()]
+      });
+      candidates
+         .filter(|candidate| candidate != path)
+         .find_map(|candidate| fs::read_to_string(candidate).ok())
+   }
+
+   /// 是否没有需要通过`--keep-metadata`保留的元数据，ICC配置文件不受该选项影响
+   pub fn is_empty(&self) -> bool {
+      self.exif.is_none() && self.segments.is_empty()
+   }
+
+   /// 将EXIF中的方向重置为正常，用于像素已经按方向旋转过的图片
+   pub fn reset_orientation(&mut self) {
+      if let Some(tiff) = self.exif.as_mut() {
+         write_orientation(tiff, 1);
+      }
+   }
+
+   /// 移除XMP中lumix_mark写入的来源信息，移除后不再包含其他描述的XMP段一并删除
+   pub(crate) fn remove_provenance(&mut self) {
+      self.segments.retain_mut(|(marker, contents)| {
+         if *marker != markers::APP1 || !contents.starts_with(XMP_PREFIX) {
+            return true;
+         }
+         let packet = String::from_utf8_lossy(&contents[XMP_PREFIX.len()..]);
+         let packet = remove_from_xmp(&packet);
+         contents.truncate(XMP_PREFIX.len());
+         contents.extend_from_slice(packet.as_bytes());
+         packet.contains("<rdf:Description")
+      });
+   }
+
+   /// # 将元数据写入已编码的JPEG数据
+   ///
+   /// EXIF段写在JFIF段之后，XMP/IPTC段紧随其后；写入的EXIF方向会重置为正常
+   pub fn embed_into_jpeg(&self, jpeg_bytes: Vec<u8>) -> Result<Vec<u8>> {
+      self.embed_into_jpeg_with_orientation(jpeg_bytes, 1)
+   }
+
+   /// 将元数据写入已编码的JPEG数据，EXIF方向改写为`orientation`
+   pub(crate) fn embed_into_jpeg_with_orientation(
+      &self,
+      jpeg_bytes: Vec<u8>,
+      orientation: u16,
+   ) -> Result<Vec<u8>> {
+      let mut jpeg = Jpeg::from_bytes(Bytes::from(jpeg_bytes))
+         .map_err(|err| LumixMarkError::Encode(Box::new(err)))?;
+      jpeg.set_exif(None);
+      let segments = jpeg.segments_mut();
+      // 跳过开头的APP0（JFIF）段
+      let mut index = segments
+         .iter()
+         .take_while(|segment| segment.marker() == markers::APP0)
+         .count();
+      if let Some(exif) = &self.exif {
+         let mut exif = exif.clone();
+         write_orientation(&mut exif, orientation);
+         let mut contents = b"Exif\0\0".to_vec();
+         contents.extend_from_slice(&exif);
+         segments.insert(
+            index,
+            JpegSegment::new_with_contents(markers::APP1, Bytes::from(contents)),
+         );
+         index += 1;
+      }
+      for (marker, contents) in &self.segments {
+         segments.insert(
+            index,
+            JpegSegment::new_with_contents(*marker, Bytes::copy_from_slice(contents)),
+         );
+         index += 1;
+      }
+      Ok(jpeg.encoder().bytes().to_vec())
+   }
+}
+
+/// 将TIFF数据中IFD0的Orientation标签值改写为`orientation`（1为正常）
+fn write_orientation(tiff: &mut [u8], orientation: u16) {
+   let big_endian = match tiff.get(0..2) {
+      Some(b"MM") => true,
+      Some(b"II") => false,
+      _ => return,
+   };
+   let read_u16 = |bytes: &[u8]| {
+      let bytes = [bytes[0], bytes[1]];
+      if big_endian {
+         u16::from_be_bytes(bytes)
+      } else {
+         u16::from_le_bytes(bytes)
+      }
+   };
+   let Some(ifd_offset) = tiff.get(4..8).map(|bytes| {
+      let bytes = [bytes[0], bytes[1], bytes[2], bytes[3]];
+      if big_endian {
+         u32::from_be_bytes(bytes)
+      } else {
+         u32::from_le_bytes(bytes)
+      }
+   }) else {
+      return;
+   };
+   let ifd_offset = ifd_offset as usize;
+   let Some(count) = tiff.get(ifd_offset..ifd_offset + 2).map(read_u16) else {
+      return;
+   };
+   for i in 0..count as usize {
+      let entry = ifd_offset + 2 + i * 12;
+      let Some(tag) = tiff.get(entry..entry + 2).map(read_u16) else {
+         return;
+      };
+      if tag == ORIENTATION_TAG {
+         // SHORT类型的值存放在值字段的前两个字节
+         if let Some(value) = tiff.get_mut(entry + 8..entry + 10) {
+            let orientation = if big_endian {
+               orientation.to_be_bytes()
+            } else {
+               orientation.to_le_bytes()
+            };
+            value.copy_from_slice(&orientation);
+         }
+         return;
+      }
+   }
+}
+
+/// # 读取XMP数据包中的属性值
+///
+/// 支持属性形式（`xmp:Rating="3"`）和元素形式（`<xmp:Rating>3</xmp:Rating>`），
+/// 元素中为`rdf:Alt`、`rdf:Seq`等列表时取`x-default`语言的项或第一项；
+/// `name`需要包含软件通常使用的命名空间前缀；值中的XML实体会被还原
+///
+/// ```
+/// use lumix_mark::xmp_property;
+///
+/// let packet = r#"<rdf:Description xmp:Rating="4" xmp:Label="Red">
+///    <dc:title><rdf:Alt><rdf:li xml:lang="x-default">Tom &amp; Jerry</rdf:li></rdf:Alt></dc:title>
+/// </rdf:Description>"#;
+/// assert_eq!(xmp_property(packet, "xmp:Rating").as_deref(), Some("4"));
+/// assert_eq!(xmp_property(packet, "xmp:Label").as_deref(), Some("Red"));
+/// assert_eq!(xmp_property(packet, "dc:title").as_deref(), Some("Tom & Jerry"));
+/// assert_eq!(xmp_property(packet, "xmp:CreatorTool"), None);
+/// ```
+pub fn xmp_property(packet: &str, name: &str) -> Option<String> {
+   let attribute = packet.match_indices(name).find_map(|(start, _)| {
+      // 属性名前为空白，避免匹配到其他命名空间中的同名属性
+      let before = packet[..start].chars().next_back()?;
+      let rest = packet[start + name.len()..]
+         .trim_start()
+         .strip_prefix('=')?;
+      let rest = rest.trim_start();
+      let quote = rest.chars().next().filter(|c| *c == '"' || *c == '\'')?;
+      let value = &rest[1..];
+      before
+         .is_whitespace()
+         .then(|| value.find(quote).map(|end| &value[..end]))
+         .flatten()
+   });
+   let element = || {
+      let open = format!("<{name}>");
+      let start = packet.find(&open)? + open.len();
+      let end = packet[start..].find(&format!("</{name}>"))? + start;
+      Some(&packet[start..end])
+   };
+   let mut value = attribute.or_else(element)?.trim();
+   if value.contains("<rdf:li") {
+      let items: Vec<_> = value
+         .split("<rdf:li")
+         .skip(1)
+         .filter_map(|item| {
+            let (attributes, rest) = item.split_once('>')?;
+            Some((attributes, &rest[..rest.find("</rdf:li>")?]))
+         })
+         .collect();
+      value = items
+         .iter()
+         .find(|(attributes, _)| attributes.contains("x-default"))
+         .or(items.first())
+         .map(|(_, item)| item.trim())?;
+   }
+   Some(
+      value
+         .replace("&lt;", "<")
+         .replace("&gt;", ">")
+         .replace("&quot;", "\"")
+         .replace("&apos;", "'")
+         .replace("&amp;", "&"),
+   )
+}
@@ -0,0 +1,89 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// 前缀规则的通配符，型号中前缀之后的部分替换到显示名称中的通配符位置
+const WILDCARD: char = '*';
+
+/// # 内置的机型显示名称
+///
+/// 按[`ModelNames`]的规则匹配，以`*`结尾的为前缀规则
+static BUILTIN_MODEL_NAMES: &[(&str, &str)] = &[
+   // 松下
+   ("DC-*", "LUMIX *"),
+   ("DMC-*", "LUMIX *"),
+   // 索尼：型号中的M2、M3等表示第几代
+   ("ILCE-1", "SONY α1"),
+   ("ILCE-1M2", "SONY α1 II"),
+   ("ILCE-7M3", "SONY α7 III"),
+   ("ILCE-7M4", "SONY α7 IV"),
+   ("ILCE-7RM3", "SONY α7R III"),
+   ("ILCE-7RM4", "SONY α7R IV"),
+   ("ILCE-7RM5", "SONY α7R V"),
+   ("ILCE-7SM3", "SONY α7S III"),
+   ("ILCE-7C", "SONY α7C"),
+   ("ILCE-7CM2", "SONY α7C II"),
+   ("ILCE-7CR", "SONY α7CR"),
+   ("ILCE-9M3", "SONY α9 III"),
+   ("ILCE-*", "SONY α*"),
+   // 尼康：型号中的_2表示II代
+   ("NIKON Z 6_2", "NIKON Z 6II"),
+   ("NIKON Z 7_2", "NIKON Z 7II"),
+   // 奥林巴斯
+   ("E-M1MarkII", "OLYMPUS E-M1 Mark II"),
+   ("E-M1MarkIII", "OLYMPUS E-M1 Mark III"),
+   ("E-M5MarkIII", "OLYMPUS E-M5 Mark III"),
+];
+
+/// # 机型显示名称的映射表
+///
+/// 将EXIF中的原始型号转换为水印中显示的名称，如`ILCE-7M4`显示为`SONY α7 IV`；
+/// 键为完整型号（不区分大小写），以`*`结尾时为前缀规则，如`"DC-*" = "LUMIX *"`，
+/// 前缀之后的部分替换到显示名称中的`*`位置。
+/// 先匹配配置中的映射再匹配内置映射，完整型号优先于前缀规则，多个前缀规则命中时使用最长的前缀，
+/// 都未命中时显示原始型号
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct ModelNames(BTreeMap<String, String>);
+
+impl ModelNames {
+   /// 添加一条映射，覆盖内置的同名映射
+   pub fn insert<K: Into<String>, V: Into<String>>(&mut self, model: K, title: V) {
+      self.0.insert(model.into(), title.into());
+   }
+
+   /// 原始型号对应的显示名称
+   pub fn title(&self, model: &str) -> String {
+      let model = model.trim();
+      let user = self
+         .0
+         .iter()
+         .map(|(key, value)| (key.as_str(), value.as_str()));
+      lookup(user, model)
+         .or_else(|| lookup(BUILTIN_MODEL_NAMES.iter().copied(), model))
+         .unwrap_or_else(|| model.to_string())
+   }
+}
+
+/// 在一组映射中查找型号，完整型号优先，其次为最长的前缀
+fn lookup<'a, I>(names: I, model: &str) -> Option<String>
+where
+   I: Iterator<Item = (&'a str, &'a str)> + Clone,
+{
+   let exact = names
+      .clone()
+      .find(|(key, _)| key.eq_ignore_ascii_case(model))
+      .map(|(_, title)| title.to_string());
+   exact.or_else(|| {
+      names
+         .filter_map(|(key, title)| {
+            let prefix = key.strip_suffix(WILDCARD)?;
+            let rest = model
+               .get(..prefix.len())
+               .filter(|head| head.eq_ignore_ascii_case(prefix))
+               .map(|_| &model[prefix.len()..])?;
+            Some((prefix.len(), title.replace(WILDCARD, rest)))
+         })
+         .max_by_key(|(len, _)| *len)
+         .map(|(_, title)| title)
+   })
+}
@@ -0,0 +1,353 @@
+use crate::depth::Rgb16Image;
+use crate::{LumixMarkError, Result};
+use clap::ValueEnum;
+use image::RgbImage;
+use jpeg_encoder::{Encoder, SamplingFactor};
+use moxcms::{ColorProfile, Layout, Transform8BitExecutor, TransformOptions};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// 输出图片格式
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+   #[default]
+   Jpeg,
+   /// 无损PNG，16位原图保持16位
+   Png,
+   /// 无损WebP
+   Webp,
+   Avif,
+   /// 无损TIFF，16位原图保持16位
+   Tiff,
+}
+
+impl OutputFormat {
+   /// 输出文件的扩展名
+   pub fn extension(&self) -> &'static str {
+      match self {
+         OutputFormat::Jpeg => "jpg",
+         OutputFormat::Png => "png",
+         OutputFormat::Webp => "webp",
+         OutputFormat::Avif => "avif",
+         OutputFormat::Tiff => "tif",
+      }
+   }
+
+   /// 输出图片的MIME类型
+   pub fn mime_type(&self) -> &'static str {
+      match self {
+         OutputFormat::Jpeg => "image/jpeg",
+         OutputFormat::Png => "image/png",
+         OutputFormat::Webp => "image/webp",
+         OutputFormat::Avif => "image/avif",
+         OutputFormat::Tiff => "image/tiff",
+      }
+   }
+
+   /// # 粗略估算输出文件大小（字节）
+   ///
+   /// 按常见照片的每像素字节数经验值估算，仅用于预览
+   pub fn estimate_size(&self, pixels: u64, quality: u8) -> u64 {
+      let quality = quality.min(100) as f64 / 100.0;
+      let bytes_per_pixel = match self {
+         OutputFormat::Jpeg => 0.1 + 0.5 * quality * quality,
+         OutputFormat::Png => 1.8,
+         OutputFormat::Webp => 1.4,
+         OutputFormat::Avif => 0.05 + 0.2 * quality * quality,
+         OutputFormat::Tiff => 3.0,
+      };
+      (pixels as f64 * bytes_per_pixel) as u64
+   }
+}
+
+/// 保存图片时的编码选项
+#[derive(Clone, Copy, Debug)]
+pub struct SaveOptions {
+   /// JPEG/AVIF图片质量（1 - 100），无损格式忽略该参数
+   pub quality: u8,
+   /// AVIF编码速度（1 - 10），越小压缩率越高但越慢
+   pub avif_speed: u8,
+   /// 是否保留原图的EXIF/XMP/IPTC元数据（仅JPEG输出）
+   pub keep_metadata: bool,
+   /// 原图带有ICC色彩配置文件时的处理方式
+   pub icc: IccPolicy,
+   /// JPEG输出的最大字节数，超出时在`quality`以下查找满足大小的最高质量
+   pub max_size: Option<u64>,
+   /// JPEG的色度抽样方式
+   pub subsampling: ChromaSubsampling,
+   /// 是否编码为渐进式JPEG
+   pub progressive: bool,
+   /// # 分块编码
+   ///
+   /// 照片不复制到画布中，编码JPEG时逐行读取，只在内存中绘制水印条和边框；
+   /// 样式不支持时（见`--tiled`）使用完整画布
+   pub tiled: bool,
+}
+
+impl Default for SaveOptions {
+   fn default() -> Self {
+      Self {
+         quality: 75,
+         avif_speed: 6,
+         keep_metadata: false,
+         icc: IccPolicy::Embed,
+         max_size: None,
+         subsampling: ChromaSubsampling::S420,
+         progressive: false,
+         tiled: false,
+      }
+   }
+}
+
+/// # JPEG的色度抽样方式
+///
+/// 人眼对颜色细节不敏感，抽样可以减小文件，但彩色文字和Logo的边缘会略微模糊
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum ChromaSubsampling {
+   /// 不抽样，彩色文字和Logo边缘最清晰
+   #[value(name = "444")]
+   S444,
+   /// 水平和垂直方向都减半，文件最小
+   #[default]
+   #[value(name = "420")]
+   S420,
+}
+
+impl ChromaSubsampling {
+   fn sampling_factor(self) -> SamplingFactor {
+      match self {
+         ChromaSubsampling::S444 => SamplingFactor::R_4_4_4,
+         ChromaSubsampling::S420 => SamplingFactor::R_4_2_0,
+      }
+   }
+}
+
+/// # 原图ICC色彩配置文件的处理方式
+///
+/// 广色域（Display P3、Adobe RGB）的照片丢失配置文件后会按sRGB显示，颜色发灰
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum IccPolicy {
+   /// 将配置文件嵌入输出图片；AVIF不支持嵌入，转换为sRGB
+   #[default]
+   Embed,
+   /// 将像素转换为sRGB，不嵌入配置文件
+   Srgb,
+}
+
+/// # 解析文件大小
+///
+/// 支持`B`、`KB`、`MB`、`GB`（1000进制）和`KiB`、`MiB`、`GiB`（1024进制），不区分大小写，
+/// 单位可以省略`B`，没有单位时为字节数
+///
+/// ```
+/// use lumix_mark::parse_file_size;
+///
+/// assert_eq!(parse_file_size("2MB"), Ok(2_000_000));
+/// assert_eq!(parse_file_size("1.5 mib"), Ok(1_572_864));
+/// assert_eq!(parse_file_size("500k"), Ok(500_000));
+/// assert_eq!(parse_file_size("4096"), Ok(4096));
+/// assert!(parse_file_size("2TB").is_err());
+/// ```
+pub fn parse_file_size(text: &str) -> std::result::Result<u64, String> {
+   let text = text.trim();
+   let split = text
+      .find(|c: char| !c.is_ascii_digit() && c != '.')
+      .unwrap_or(text.len());
+   let (number, unit) = text.split_at(split);
+   let number: f64 = number
+      .parse()
+      .map_err(|_| format!("无效的文件大小：{text}"))?;
+   let scale = match unit.trim().to_ascii_lowercase().as_str() {
+      "" | "b" => 1.0,
+      "k" | "kb" => 1e3,
+      "m" | "mb" => 1e6,
+      "g" | "gb" => 1e9,
+      "kib" => 1024.0,
+      "mib" => 1024.0 * 1024.0,
+      "gib" => 1024.0 * 1024.0 * 1024.0,
+      unit => return Err(format!("无效的文件大小单位：{unit}")),
+   };
+   Ok((number * scale) as u64)
+}
+
+/// # 二分查找编码结果不超过`max_size`字节的最高质量
+///
+/// `max_quality`满足时直接使用，最低质量仍然超出时返回最低质量的结果
+pub(crate) fn fit_file_size<F>(
+   encode: F,
+   max_quality: u8,
+   max_size: u64,
+) -> Result<Vec<u8>>
+where
+   F: Fn(u8) -> Result<Vec<u8>>,
+{
+   let bytes = encode(max_quality)?;
+   if bytes.len() as u64 <= max_size || max_quality <= 1 {
+      return Ok(bytes);
+   }
+   let (mut low, mut high) = (1, max_quality - 1);
+   let mut fitted = None;
+   while low <= high {
+      let quality = low + (high - low) / 2;
+      let bytes = encode(quality)?;
+      if bytes.len() as u64 <= max_size {
+         fitted = Some(bytes);
+         low = quality + 1;
+      } else if quality == 1 {
+         tracing::warn!("质量降到1仍超过{max_size}字节，输出{}字节", bytes.len());
+         return Ok(bytes);
+      } else {
+         high = quality - 1;
+      }
+   }
+   match fitted {
+      Some(bytes) => Ok(bytes),
+      None => encode(1),
+   }
+}
+
+/// # 按编码选项创建JPEG编码器
+///
+/// 使用`options`中的色度抽样方式和渐进式编码，`icc_profile`写入APP2段
+///
+/// # 参数
+/// * `bytes` - 写入编码结果
+/// * `quality` - 图片质量（1 - 100）
+/// * `options` - 编码选项
+/// * `icc_profile` - 嵌入的ICC色彩配置文件
+pub(crate) fn jpeg_encoder<'a>(
+   bytes: &'a mut Vec<u8>,
+   quality: u8,
+   options: &SaveOptions,
+   icc_profile: Option<&[u8]>,
+) -> Result<Encoder<&'a mut Vec<u8>>> {
+   let mut encoder = Encoder::new(bytes, quality);
+   encoder.set_sampling_factor(options.subsampling.sampling_factor());
+   encoder.set_progressive(options.progressive);
+   if let Some(icc_profile) = icc_profile {
+      encoder.add_icc_profile(icc_profile)?;
+   }
+   Ok(encoder)
+}
+
+/// # 检查JPEG的宽高
+///
+/// JPEG的宽高最大为65535像素
+pub(crate) fn jpeg_dimensions(width: u32, height: u32) -> Result<(u16, u16)> {
+   match (u16::try_from(width), u16::try_from(height)) {
+      (Ok(width), Ok(height)) => Ok((width, height)),
+      _ => Err(LumixMarkError::Encode(
+         format!("JPEG的宽高不能超过65535像素，当前为{width}x{height}").into(),
+      )),
+   }
+}
+
+/// 将按`icc_profile`编码的像素转换为sRGB
+pub(crate) fn convert_to_srgb(
+   image: &RgbImage,
+   icc_profile: &[u8],
+) -> Result<RgbImage> {
+   let mut converted = RgbImage::new(image.width(), image.height());
+   srgb_transform(icc_profile)?.convert(image.as_raw(), &mut converted)?;
+   Ok(converted)
+}
+
+/// 从ICC配置文件到sRGB的8位像素转换，创建一次后可以逐行转换
+pub(crate) struct SrgbTransform(Arc<Transform8BitExecutor>);
+
+impl SrgbTransform {
+   /// 转换RGB像素，`source`和`target`的长度必须相同
+   pub(crate) fn convert(&self, source: &[u8], target: &mut [u8]) -> Result<()> {
+      self.0.transform(source, target).map_err(color_error)
+   }
+}
+
+pub(crate) fn srgb_transform(icc_profile: &[u8]) -> Result<SrgbTransform> {
+   let transform = source_profile(icc_profile)?
+      .create_transform_8bit(
+         Layout::Rgb,
+         &ColorProfile::new_srgb(),
+         Layout::Rgb,
+         TransformOptions::default(),
+      )
+      .map_err(color_error)?;
+   Ok(SrgbTransform(transform))
+}
+
+/// 将按`icc_profile`编码的16位像素转换为sRGB
+pub(crate) fn convert_to_srgb_16(
+   image: &Rgb16Image,
+   icc_profile: &[u8],
+) -> Result<Rgb16Image> {
+   let transform = source_profile(icc_profile)?
+      .create_transform_16bit(
+         Layout::Rgb,
+         &ColorProfile::new_srgb(),
+         Layout::Rgb,
+         TransformOptions::default(),
+      )
+      .map_err(color_error)?;
+   let mut converted = Rgb16Image::new(image.width(), image.height());
+   transform
+      .transform(image.as_raw(), &mut converted)
+      .map_err(color_error)?;
+   Ok(converted)
+}
+
+fn source_profile(icc_profile: &[u8]) -> Result<ColorProfile> {
+   ColorProfile::new_from_slice(icc_profile).map_err(color_error)
+}
+
+fn color_error(err: moxcms::CmsError) -> LumixMarkError {
+   LumixMarkError::Encode(Box::new(err))
+}
+
+/// 输出文件已存在时的处理策略
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum OverwritePolicy {
+   /// 跳过已存在的文件
+   Skip,
+   /// 覆盖已存在的文件
+   #[default]
+   Replace,
+   /// 在文件名后追加数字后缀
+   Rename,
+}
+
+impl OverwritePolicy {
+   /// 根据策略确定最终的输出路径，返回`None`表示跳过；文件已存在时视为不可用
+   pub fn resolve(&self, target: PathBuf) -> Option<PathBuf> {
+      self.resolve_with(target, |path| !path.exists())
+   }
+
+   /// # 根据策略确定最终的输出路径，由`available`判断路径是否可用
+   ///
+   /// 并行处理时可以在`available`中同时检查已预留的路径，避免多张图片写入同一文件；
+   /// `Replace`时不检查
+   pub fn resolve_with<F: FnMut(&Path) -> bool>(
+      &self,
+      target: PathBuf,
+      mut available: F,
+   ) -> Option<PathBuf> {
+      match self {
+         OverwritePolicy::Replace => Some(target),
+         OverwritePolicy::Skip => available(&target).then_some(target),
+         OverwritePolicy::Rename => (0..)
+            .map(|index| numbered_path(&target, index))
+            .find(|candidate| available(candidate)),
+      }
+   }
+}
+
+/// 为文件名追加数字后缀，如`mark_a.jpg`变为`mark_a_1.jpg`，序号为0时保持原样
+pub(crate) fn numbered_path(path: &Path, index: u32) -> PathBuf {
+   if index == 0 {
+      return path.to_path_buf();
+   }
+   let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+   let file_name = match path.extension() {
+      Some(ext) => format!("{}_{}.{}", stem, index, ext.to_string_lossy()),
+      None => format!("{}_{}", stem, index),
+   };
+   path.with_file_name(file_name)
+}
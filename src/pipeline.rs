@@ -0,0 +1,509 @@
+use crate::caption;
+use crate::depth::DeepPhoto;
+use crate::tiled::TiledPhoto;
+use crate::{
+   CanvasLayout, CompareLayout, Empty, Exif, LumixMark, LumixMarkError, MarkStyle,
+   MarkedPolicy, Metadata, MultiFrame, MultiFramePolicy, OutputFormat, Result,
+   SaveOptions, SidecarPolicy, decode_heif, decode_raw, fit_long_edge, is_heif,
+   is_marked, is_raw, mark_height, resize_to,
+};
+use image::{DynamicImage, GenericImageView, Rgb, load_from_memory};
+use std::mem;
+use std::time::Instant;
+use tracing::{debug, debug_span, info, warn};
+
+/// # 流水线各阶段共享的处理状态
+///
+/// 阶段按顺序读取、修改其中的字段：`Decode`填充照片和EXIF，`ExpandCanvas`将照片移入画布，
+/// `Encode`写入输出内容
+pub struct PipelineContext<'a> {
+   /// 原图文件内容
+   pub source: &'a [u8],
+   /// 水印高度比例（水印高度 / 照片最短边）
+   pub mark_ratio: f32,
+   /// 水印样式
+   pub style: &'a MarkStyle,
+   /// 输出图片格式
+   pub format: OutputFormat,
+   /// 编码选项
+   pub options: SaveOptions,
+   /// 输入为多帧图片时的处理方式
+   pub multi_frame: MultiFramePolicy,
+   /// 输入图片已带有水印时的处理方式
+   pub marked: MarkedPolicy,
+   /// 输出原图与水印图的对比图时的排列方式，`None`时只输出水印图
+   pub compare: Option<CompareLayout>,
+   /// XMP附属文件的内容
+   pub sidecar: Option<String>,
+   /// XMP附属文件中的值与照片中的EXIF、XMP不同时的处理方式
+   pub sidecar_policy: SidecarPolicy,
+   /// 解码后的照片，创建画布后为`None`
+   pub photo: Option<DynamicImage>,
+   /// 解码后照片的原始尺寸（旋转、缩小前）
+   pub source_size: Option<(u32, u32)>,
+   /// EXIF信息，创建画布时移入画布
+   pub exif: Exif,
+   /// 原图的元数据，创建画布时移入画布
+   pub metadata: Metadata,
+   /// 扩展后的画布，水印绘制在其中
+   pub mark: Option<LumixMark>,
+   /// 编码后的输出文件内容
+   pub output: Option<Vec<u8>>,
+}
+
+impl<'a> PipelineContext<'a> {
+   /// # 创建处理状态，默认输出为默认选项的JPEG
+   ///
+   /// # 参数
+   /// * `source` - 图片文件内容
+   /// * `mark_ratio` - 设置水印高度比例 （水印高度 / 照片最短边）
+   /// * `style` - 水印样式
+   pub fn new(source: &'a [u8], mark_ratio: f32, style: &'a MarkStyle) -> Self {
+      Self {
+         source,
+         mark_ratio,
+         style,
+         format: OutputFormat::Jpeg,
+         options: SaveOptions::default(),
+         multi_frame: MultiFramePolicy::First,
+         marked: MarkedPolicy::Warn,
+         compare: None,
+         sidecar: None,
+         sidecar_policy: SidecarPolicy::Prefer,
+         photo: None,
+         source_size: None,
+         exif: Exif::default(),
+         metadata: Metadata::default(),
+         mark: None,
+         output: None,
+      }
+   }
+
+   /// 设置输出格式和编码选项
+   pub fn with_output(mut self, format: OutputFormat, options: SaveOptions) -> Self {
+      self.format = format;
+      self.options = options;
+      self
+   }
+
+   /// 设置输入为多帧图片时的处理方式
+   pub fn with_multi_frame(mut self, policy: MultiFramePolicy) -> Self {
+      self.multi_frame = policy;
+      self
+   }
+
+   /// 设置输入图片已带有水印时的处理方式
+   pub fn with_marked(mut self, policy: MarkedPolicy) -> Self {
+      self.marked = policy;
+      self
+   }
+
+   /// 设置对比图的排列方式，`None`时只输出水印图
+   pub fn with_compare(mut self, layout: Option<CompareLayout>) -> Self {
+      self.compare = layout;
+      self
+   }
+
+   /// 设置XMP附属文件的内容，见[`Metadata::read_sidecar`]
+   pub fn with_sidecar(mut self, sidecar: Option<String>) -> Self {
+      self.sidecar = sidecar;
+      self
+   }
+
+   /// 设置XMP附属文件中的值与照片中的EXIF、XMP不同时的处理方式
+   pub fn with_sidecar_policy(mut self, policy: SidecarPolicy) -> Self {
+      self.sidecar_policy = policy;
+      self
+   }
+
+   /// 解码后、创建画布前的照片
+   pub fn photo_mut(&mut self) -> Result<&mut DynamicImage> {
+      self.photo.as_mut().ok_or_else(|| missing("照片"))
+   }
+
+   /// 创建画布后的水印画布
+   pub fn mark_mut(&mut self) -> Result<&mut LumixMark> {
+      self.mark.as_mut().ok_or_else(|| missing("画布"))
+   }
+
+   /// 取出水印画布
+   pub fn into_mark(self) -> Result<LumixMark> {
+      self.mark.ok_or_else(|| missing("画布"))
+   }
+
+   /// 取出编码后的输出文件内容
+   pub fn into_output(self) -> Result<Vec<u8>> {
+      self.output.ok_or_else(|| missing("输出内容"))
+   }
+}
+
+/// 前面的阶段没有产生需要的数据
+fn missing(what: &str) -> LumixMarkError {
+   LumixMarkError::InvalidConfig(format!("流水线中缺少{what}，请检查阶段的顺序"))
+}
+
+/// # 流水线中的一个处理阶段
+///
+/// 实现此trait并插入到[`Pipeline`]中，即可在不修改绘制代码的情况下添加自定义的边框、文字等
+pub trait Stage: Send + Sync {
+   /// 阶段名称，用于定位插入位置
+   fn name(&self) -> &str;
+
+   /// 处理并修改共享状态
+   fn run(&self, context: &mut PipelineContext) -> Empty;
+}
+
+/// # 内置的处理阶段
+///
+/// 默认流水线按声明顺序执行
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StandardStage {
+   /// 解码图片，读取元数据和EXIF；多帧图片、已带有水印的图片按处理方式继续处理或返回错误
+   Decode,
+   /// 根据EXIF中的方向旋转、翻转照片
+   Orient,
+   /// 限制输出尺寸时缩小照片并锐化
+   Resize,
+   /// 创建扩展后的画布并放入照片
+   ExpandCanvas,
+   /// 绘制Logo和Exif信息
+   DrawMark,
+   /// 按输出格式编码
+   Encode,
+}
+
+impl StandardStage {
+   /// 所有内置阶段，按执行顺序排列
+   pub const ALL: [StandardStage; 6] = [
+      StandardStage::Decode,
+      StandardStage::Orient,
+      StandardStage::Resize,
+      StandardStage::ExpandCanvas,
+      StandardStage::DrawMark,
+      StandardStage::Encode,
+   ];
+}
+
+impl Stage for StandardStage {
+   fn name(&self) -> &str {
+      match self {
+         StandardStage::Decode => "decode",
+         StandardStage::Orient => "orient",
+         StandardStage::Resize => "resize",
+         StandardStage::ExpandCanvas => "expand-canvas",
+         StandardStage::DrawMark => "draw-mark",
+         StandardStage::Encode => "encode",
+      }
+   }
+
+   fn run(&self, context: &mut PipelineContext) -> Empty {
+      match self {
+         StandardStage::Decode => {
+            let source = context.source;
+            if let Some(kind) = MultiFrame::detect(source) {
+               match context.multi_frame {
+                  MultiFramePolicy::First => info!("输入为{}，只处理第一帧", kind),
+                  MultiFramePolicy::Error | MultiFramePolicy::Skip => {
+                     return Err(LumixMarkError::MultiFrame(kind));
+                  }
+               }
+            }
+            if is_marked(source) {
+               match context.marked {
+                  MarkedPolicy::Skip => return Err(LumixMarkError::AlreadyMarked),
+                  MarkedPolicy::Warn => warn!("图片已带有水印，再次添加水印"),
+               }
+            }
+            let (photo, metadata) = if is_heif(source) {
+               decode_heif(source)?
+            } else if is_raw(source) {
+               decode_raw(source)?
+            } else {
+               (load_from_memory(source)?, Metadata::from_bytes(source))
+            };
+            debug!(
+               width = photo.width(),
+               height = photo.height(),
+               color = ?photo.color(),
+               "解码完成"
+            );
+            context.source_size = Some(photo.dimensions());
+            context.exif = Exif::from_metadata(&metadata);
+            if let Some(sidecar) = &context.sidecar {
+               context.exif.apply_sidecar(sidecar, context.sidecar_policy);
+            }
+            context.style.prepare_exif(&mut context.exif);
+            context.photo = Some(photo);
+            context.metadata = metadata;
+         }
+         StandardStage::Orient => {
+            let orientation = context.exif.image_orientation();
+            context.photo_mut()?.apply_orientation(orientation);
+         }
+         StandardStage::Resize => {
+            let (mark_ratio, style) = (context.mark_ratio, context.style);
+            let photo = context.photo.take().ok_or_else(|| missing("照片"))?;
+            let (width, height) = photo.dimensions();
+            let size = fit_long_edge(width, height, mark_ratio, style, &context.exif)?;
+            if photo.dimensions() != size {
+               debug!(from = ?photo.dimensions(), to = ?size, "缩小照片");
+            }
+            context.photo = Some(match style.sharpen {
+               Some(sharpen) if photo.dimensions() != size => {
+                  sharpen.apply(resize_to(photo, size))
+               }
+               _ => resize_to(photo, size),
+            });
+         }
+         StandardStage::ExpandCanvas => {
+            let photo = context.photo.take().ok_or_else(|| missing("照片"))?;
+            let border =
+               TiledPhoto::border(context.style, context.format, &context.options);
+            // 对比图需要完整的画布
+            let tiled = context.options.tiled && context.compare.is_none();
+            context.mark = Some(match (tiled, border) {
+               (true, Ok(border)) => expand_tiled_canvas(context, photo, border)?,
+               (true, Err(reason)) => {
+                  warn!("无法分块编码（{}），使用完整画布", reason);
+                  expand_canvas(context, photo)?
+               }
+               (false, _) => expand_canvas(context, photo)?,
+            });
+         }
+         StandardStage::DrawMark => {
+            let (style, compare) = (context.style, context.compare);
+            let mark = context.mark_mut()?;
+            let original = compare.map(|_| mark.photo_copy());
+            mark.draw_logo_exif(style)?;
+            debug!(elements = mark.elements.len(), "绘制水印完成");
+            if let (Some(layout), Some(original)) = (compare, original) {
+               mark.compose_comparison(&original, layout, style);
+               debug!(width = mark.width, height = mark.height, "生成对比图");
+            }
+         }
+         StandardStage::Encode => {
+            let mark = context.mark.as_ref().ok_or_else(|| missing("画布"))?;
+            let output = mark.encode(context.format, &context.options)?;
+            debug!(format = ?context.format, bytes = output.len(), "编码完成");
+            context.output = Some(output);
+         }
+      }
+      Ok(())
+   }
+}
+
+/// # 按水印样式创建画布，水印高度按照片的最终尺寸计算
+///
+/// 8位照片的缓冲区直接扩展为画布，峰值内存约为一份画布
+fn expand_canvas(
+   context: &mut PipelineContext,
+   photo: DynamicImage,
+) -> Result<LumixMark> {
+   let style = context.style;
+   let mut deep_photo = DeepPhoto::from_image(&photo);
+   let rgb_img = match &deep_photo {
+      Some(deep_photo) => deep_photo.quantize(),
+      None => photo.into_rgb8(),
+   };
+   let (img_width, img_height) = rgb_img.dimensions();
+   let mark_height = mark_height(img_width, img_height, context.mark_ratio);
+   let layout =
+      caption::canvas_layout(style, &context.exif, img_width, img_height, mark_height)?;
+   debug!(
+      width = layout.width,
+      height = layout.height,
+      mark_height,
+      mark_area = ?layout.mark_area,
+      "创建画布"
+   );
+   let corners = style.photo_corners(mark_height);
+   let shadow = style.photo_shadow((img_width, img_height), mark_height);
+   let (background, accent) = style.background.resolve(&rgb_img);
+   let canvas = background.expand(rgb_img, &layout, corners, shadow.as_ref())?;
+   if let Some(deep_photo) = deep_photo.as_mut() {
+      deep_photo.place(layout.photo.0, layout.photo.1);
+   }
+   Ok(LumixMark {
+      canvas,
+      width: layout.width,
+      height: layout.height,
+      mark_height: mark_height as f32,
+      mark_area: layout.mark_area,
+      photo_area: photo_area(&layout, img_width, img_height),
+      elements: Vec::new(),
+      exif: mem::take(&mut context.exif),
+      metadata: mem::take(&mut context.metadata),
+      deep_photo,
+      tiled: None,
+      accent,
+      provenance: None,
+   })
+}
+
+fn photo_area(layout: &CanvasLayout, width: u32, height: u32) -> (u32, u32, u32, u32) {
+   let (left, top) = layout.photo;
+   (left, top, left + width, top + height)
+}
+
+/// # 创建只包含照片以外的行的画布
+///
+/// 照片转换为8位后保留在画布外，编码时逐行拼接，不再复制一份完整的画布；
+/// `border`为照片两侧边框的颜色
+fn expand_tiled_canvas(
+   context: &mut PipelineContext,
+   photo: DynamicImage,
+   border: Rgb<u8>,
+) -> Result<LumixMark> {
+   let style = context.style;
+   let mut photo = photo.into_rgb8();
+   let (img_width, img_height) = photo.dimensions();
+   let mark_height = mark_height(img_width, img_height, context.mark_ratio);
+   let layout =
+      caption::canvas_layout(style, &context.exif, img_width, img_height, mark_height)?;
+   debug!(
+      width = layout.width,
+      height = layout.height,
+      mark_height,
+      mark_area = ?layout.mark_area,
+      "创建分块画布"
+   );
+   let canvas =
+      style
+         .background
+         .canvas(layout.width, layout.height - img_height, &photo);
+   // 分块编码只支持纯色背景，照片四角与边框颜色混合
+   style
+      .photo_corners(mark_height)
+      .apply(&mut photo, |_, _| border);
+   Ok(LumixMark {
+      canvas,
+      width: layout.width,
+      height: layout.height,
+      mark_height: mark_height as f32,
+      mark_area: layout.mark_area,
+      photo_area: photo_area(&layout, img_width, img_height),
+      elements: Vec::new(),
+      exif: mem::take(&mut context.exif),
+      metadata: mem::take(&mut context.metadata),
+      deep_photo: None,
+      tiled: Some(TiledPhoto::new(photo, layout.photo, border)),
+      accent: None,
+      provenance: None,
+   })
+}
+
+/// # 可扩展的处理流水线
+///
+/// 默认依次执行`Decode → Orient → Resize → ExpandCanvas → DrawMark → Encode`，
+/// 可以在任意内置或自定义阶段前后插入新的阶段，或替换、移除已有阶段
+///
+/// ```
+/// use lumix_mark::{Empty, Pipeline, PipelineContext, Stage, StandardStage};
+///
+/// struct Border;
+///
+/// impl Stage for Border {
+///    fn name(&self) -> &str {
+///       "border"
+///    }
+///
+///    fn run(&self, context: &mut PipelineContext) -> Empty {
+///       let mark = context.mark_mut()?;
+///       let width = mark.width;
+///       for x in 0..width {
+///          mark.canvas.put_pixel(x, 0, image::Rgb([0, 0, 0]));
+///       }
+///       Ok(())
+///    }
+/// }
+///
+/// let pipeline = Pipeline::default()
+///    .insert_after(StandardStage::DrawMark.name(), Border)
+///    .unwrap();
+/// assert_eq!(
+///    pipeline.names().collect::<Vec<_>>(),
+///    ["decode", "orient", "resize", "expand-canvas", "draw-mark", "border", "encode"]
+/// );
+/// ```
+pub struct Pipeline {
+   stages: Vec<Box<dyn Stage>>,
+}
+
+impl Default for Pipeline {
+   fn default() -> Self {
+      Self {
+         stages: StandardStage::ALL
+            .into_iter()
+            .map(|stage| Box::new(stage) as Box<dyn Stage>)
+            .collect(),
+      }
+   }
+}
+
+impl Pipeline {
+   /// 按执行顺序排列的阶段名称
+   pub fn names(&self) -> impl Iterator<Item = &str> {
+      self.stages.iter().map(|stage| stage.name())
+   }
+
+   /// 在流水线末尾添加阶段
+   pub fn push(mut self, stage: impl Stage + 'static) -> Self {
+      self.stages.push(Box::new(stage));
+      self
+   }
+
+   /// 在名为`name`的阶段之前插入阶段
+   pub fn insert_before(
+      mut self,
+      name: &str,
+      stage: impl Stage + 'static,
+   ) -> Result<Self> {
+      let index = self.position(name)?;
+      self.stages.insert(index, Box::new(stage));
+      Ok(self)
+   }
+
+   /// 在名为`name`的阶段之后插入阶段
+   pub fn insert_after(
+      mut self,
+      name: &str,
+      stage: impl Stage + 'static,
+   ) -> Result<Self> {
+      let index = self.position(name)?;
+      self.stages.insert(index + 1, Box::new(stage));
+      Ok(self)
+   }
+
+   /// 用新的阶段替换名为`name`的阶段
+   pub fn replace(mut self, name: &str, stage: impl Stage + 'static) -> Result<Self> {
+      let index = self.position(name)?;
+      self.stages[index] = Box::new(stage);
+      Ok(self)
+   }
+
+   /// 移除名为`name`的阶段
+   pub fn remove(mut self, name: &str) -> Result<Self> {
+      let index = self.position(name)?;
+      self.stages.remove(index);
+      Ok(self)
+   }
+
+   /// # 依次执行所有阶段，任一阶段失败时立即返回错误
+   ///
+   /// 每个阶段在名为`stage`的tracing span中执行，结束时输出`debug`级别的耗时
+   pub fn run(&self, context: &mut PipelineContext) -> Empty {
+      self.stages.iter().try_for_each(|stage| {
+         let _span = debug_span!("stage", name = stage.name()).entered();
+         let start = Instant::now();
+         stage.run(context)?;
+         debug!(elapsed = ?start.elapsed(), "阶段完成");
+         Ok(())
+      })
+   }
+
+   fn position(&self, name: &str) -> Result<usize> {
+      self.names().position(|stage| stage == name).ok_or_else(|| {
+         LumixMarkError::InvalidConfig(format!("流水线中没有{name}阶段"))
+      })
+   }
+}
@@ -0,0 +1,189 @@
+use crate::{Color, Divider, Frame, Layout, LumixMarkError, MarkStyle, Result, Theme};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::info;
+
+/// 内置预设的名称和说明，按显示顺序排列
+pub const BUILTIN_PRESETS: &[(&str, &str)] = &[
+   (
+      "classic",
+      "白色水印条，机型靠左，Logo、分隔线和Exif信息靠右（默认样式）",
+   ),
+   ("minimal", "较小的浅色文字，不绘制分隔线"),
+   ("dark", "深色水印条、浅色文字和白色Logo"),
+   ("centered", "机型和Logo在上、Exif信息在下，整体居中"),
+   ("polaroid", "拍立得白色边框，水印内容居中"),
+];
+
+/// 内置预设对应的水印样式
+fn builtin_style(name: &str) -> Option<MarkStyle> {
+   let mut style = MarkStyle::default();
+   match name {
+      "classic" => {}
+      "minimal" => {
+         style.divider = Divider::None;
+         style.model_text_ratio = 0.36;
+         style.exif_text_ratio = 0.26;
+         style.exif_color = Color::RGB(120, 120, 120);
+         style.padding_ratio = 0.5;
+      }
+      "dark" => Theme::Dark.apply(&mut style),
+      "centered" => style.layout = Layout::Centered,
+      "polaroid" => {
+         style.frame = Frame::Polaroid;
+         style.layout = Layout::Centered;
+      }
+      _ => return None,
+   }
+   Some(style)
+}
+
+/// 水印样式预设
+#[derive(Clone, Debug)]
+pub struct Preset {
+   /// 预设名称，用户预设为文件名（不含扩展名）
+   pub name: String,
+   /// 说明，用户预设取自文件第一行的注释
+   pub description: String,
+   /// 用户预设的文件路径，内置预设为`None`
+   pub path: Option<PathBuf>,
+   /// 水印样式
+   pub style: MarkStyle,
+}
+
+/// # 预设注册表
+///
+/// 包含内置预设和用户预设文件夹中的`*.toml`样式配置，用户预设与内置预设同名时覆盖内置预设
+#[derive(Clone, Debug)]
+pub struct PresetRegistry {
+   presets: Vec<Preset>,
+}
+
+impl PresetRegistry {
+   /// 只包含内置预设的注册表
+   pub fn builtin() -> Self {
+      let presets = BUILTIN_PRESETS
+         .iter()
+         .filter_map(|&(name, description)| {
+            Some(Preset {
+               name: name.to_string(),
+               description: description.to_string(),
+               path: None,
+               style: builtin_style(name)?,
+            })
+         })
+         .collect();
+      Self { presets }
+   }
+
+   /// 内置预设和用户预设文件夹中的预设，文件夹不存在时只包含内置预设
+   pub fn load() -> Result<Self> {
+      let registry = Self::builtin();
+      match user_preset_dir() {
+         Some(dir) if dir.is_dir() => registry.load_dir(&dir),
+         _ => Ok(registry),
+      }
+   }
+
+   /// 加载文件夹中的`*.toml`样式配置，文件名（不含扩展名）为预设名称
+   pub fn load_dir(mut self, dir: &Path) -> Result<Self> {
+      let mut paths: Vec<PathBuf> = fs::read_dir(dir)?
+         .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+         .filter(|path| path.extension().is_some_and(|ext| ext == "toml"))
+         .collect();
+      paths.sort();
+      for path in paths {
+         let Some(name) = path.file_stem().and_then(|stem| stem.to_str()) else {
+            continue;
+         };
+         let text = fs::read_to_string(&path)?;
+         let style = MarkStyle::from_toml(&text).map_err(|err| match err {
+            LumixMarkError::InvalidConfig(msg) => {
+               LumixMarkError::InvalidConfig(format!("预设{:?}：{}", path, msg))
+            }
+            err => err,
+         })?;
+         let description = text
+            .lines()
+            .next()
+            .and_then(|line| line.strip_prefix('#'))
+            .map_or_else(|| "用户预设".to_string(), |line| line.trim().to_string());
+         let preset = Preset {
+            name: name.to_string(),
+            description,
+            path: Some(path.clone()),
+            style,
+         };
+         match self.presets.iter_mut().find(|preset| preset.name == name) {
+            Some(existing) => *existing = preset,
+            None => self.presets.push(preset),
+         }
+      }
+      Ok(self)
+   }
+
+   /// 所有预设，内置预设在前
+   pub fn presets(&self) -> &[Preset] {
+      &self.presets
+   }
+
+   /// 按名称查找预设
+   pub fn get(&self, name: &str) -> Option<&Preset> {
+      self.presets.iter().find(|preset| preset.name == name)
+   }
+
+   /// 按名称获取预设的水印样式，名称不存在时返回包含所有可用名称的错误
+   pub fn style(&self, name: &str) -> Result<MarkStyle> {
+      match self.get(name) {
+         Some(preset) => Ok(MarkStyle {
+            preset: Some(preset.name.clone()),
+            ..preset.style.clone()
+         }),
+         None => {
+            let names: Vec<&str> = self
+               .presets
+               .iter()
+               .map(|preset| preset.name.as_str())
+               .collect();
+            Err(LumixMarkError::InvalidConfig(format!(
+               "未知的预设：{}，可用的预设：{}",
+               name,
+               names.join("、")
+            )))
+         }
+      }
+   }
+
+   /// 打印所有预设的名称、说明和来源
+   pub fn print(&self) {
+      for preset in &self.presets {
+         match &preset.path {
+            Some(path) => {
+               info!("{}：{}（{:?}）", preset.name, preset.description, path)
+            }
+            None => info!("{}：{}", preset.name, preset.description),
+         }
+      }
+   }
+}
+
+/// # 用户预设文件夹
+///
+/// Windows为`%APPDATA%\lumix_mark\presets`，macOS为`~/Library/Application Support/lumix_mark/presets`，
+/// 其他系统为`$XDG_CONFIG_HOME/lumix_mark/presets`或`~/.config/lumix_mark/presets`
+pub fn user_preset_dir() -> Option<PathBuf> {
+   let config_dir = if cfg!(target_os = "windows") {
+      env::var_os("APPDATA").map(PathBuf::from)
+   } else if cfg!(target_os = "macos") {
+      env::var_os("HOME")
+         .map(|home| PathBuf::from(home).join("Library/Application Support"))
+   } else {
+      env::var_os("XDG_CONFIG_HOME")
+         .map(PathBuf::from)
+         .or_else(|| {
+            env::var_os("HOME").map(|home| PathBuf::from(home).join(".config"))
+         })
+   };
+   config_dir.map(|dir| dir.join("lumix_mark").join("presets"))
+}
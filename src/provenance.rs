@@ -0,0 +1,169 @@
+use crate::MarkStyle;
+
+/// lumix_mark的XMP命名空间
+pub(crate) const XMP_NAMESPACE: &str = "https://github.com/losenli/lumix_mark/ns/1.0/";
+/// 写入的XMP描述的开头，再次写入时据此移除之前的描述
+const DESCRIPTION_START: &str = "<rdf:Description rdf:about=\"\" xmlns:lumix_mark=";
+
+/// # 输出文件的来源信息
+///
+/// 记录lumix_mark版本、使用的预设和水印样式的哈希，写入输出文件的XMP中，
+/// 可以据此判断图片已添加过水印，以及找回生成该文件的设置；
+/// 照片区域和原图的EXIF方向用于`unmark`子命令去除水印
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Provenance {
+   /// lumix_mark版本
+   pub version: String,
+   /// 使用的预设名称
+   pub preset: Option<String>,
+   /// 水印样式配置（与配置文件的字段相同）的哈希，16位十六进制
+   pub style_hash: String,
+   /// 照片在画布中的区域（左、上、右、下），叠加水印时为`None`
+   pub photo_area: Option<(u32, u32, u32, u32)>,
+   /// 原图的EXIF方向，照片已按该方向旋转为正常
+   pub orientation: u16,
+}
+
+impl Provenance {
+   /// # 记录绘制水印时使用的样式
+   ///
+   /// 字体、Logo等不在配置文件中的设置不参与哈希
+   ///
+   /// # 参数
+   /// * `style` - 水印样式
+   /// * `photo_area` - 照片在画布中的区域，叠加水印时为`None`
+   /// * `orientation` - 原图的EXIF方向
+   pub fn new(
+      style: &MarkStyle,
+      photo_area: Option<(u32, u32, u32, u32)>,
+      orientation: u16,
+   ) -> Self {
+      let config = serde_json::to_vec(style).unwrap_or_default();
+      Self {
+         version: env!("CARGO_PKG_VERSION").to_string(),
+         preset: style.preset.clone(),
+         style_hash: format!("{:016x}", fnv1a(&config)),
+         photo_area,
+         orientation,
+      }
+   }
+
+   /// # 从lumix_mark输出的文件内容中读取来源信息
+   ///
+   /// 直接查找写入的XMP描述，不解析完整的XMP；没有来源信息时返回`None`
+   pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+      let start = DESCRIPTION_START.as_bytes();
+      let start = bytes
+         .windows(start.len())
+         .position(|window| window == start)?;
+      let end = bytes[start..]
+         .windows(2)
+         .position(|window| window == b"/>")?;
+      let description = String::from_utf8_lossy(&bytes[start..start + end]);
+      let attribute = |name: &str| {
+         let prefix = format!(" lumix_mark:{name}=\"");
+         let value = &description[description.find(&prefix)? + prefix.len()..];
+         Some(unescape_xml(&value[..value.find('"')?]))
+      };
+      let photo_area = attribute("PhotoArea").and_then(|area| {
+         let area: Vec<u32> = area
+            .split(',')
+            .map(str::parse)
+            .collect::<std::result::Result<_, _>>()
+            .ok()?;
+         match area[..] {
+            [left, top, right, bottom] => Some((left, top, right, bottom)),
+            _ => None,
+         }
+      });
+      Some(Self {
+         version: attribute("Version")?,
+         preset: attribute("Preset"),
+         style_hash: attribute("StyleHash")?,
+         photo_area,
+         orientation: attribute("Orientation")
+            .and_then(|orientation| orientation.parse().ok())
+            .unwrap_or(1),
+      })
+   }
+
+   /// XMP中的`rdf:Description`元素
+   fn description(&self) -> String {
+      let preset = match &self.preset {
+         Some(preset) => format!(" lumix_mark:Preset=\"{}\"", escape_xml(preset)),
+         None => String::new(),
+      };
+      let photo_area = match self.photo_area {
+         Some((left, top, right, bottom)) => {
+            format!(" lumix_mark:PhotoArea=\"{left},{top},{right},{bottom}\"")
+         }
+         None => String::new(),
+      };
+      format!(
+         "{DESCRIPTION_START}\"{XMP_NAMESPACE}\" lumix_mark:Version=\"{}\"{} lumix_mark:StyleHash=\"{}\"{} lumix_mark:Orientation=\"{}\"/>",
+         escape_xml(&self.version),
+         preset,
+         self.style_hash,
+         photo_area,
+         self.orientation
+      )
+   }
+
+   /// 只包含来源信息的XMP数据包
+   pub fn to_xmp(&self) -> String {
+      format!(
+         "<?xpacket begin=\"\u{feff}\" id=\"W5M0MpCehiHzreSzNTczkc9d\"?>\
+          <x:xmpmeta xmlns:x=\"adobe:ns:meta/\">\
+          <rdf:RDF xmlns:rdf=\"http://www.w3.org/1999/02/22-rdf-syntax-ns#\">{}</rdf:RDF>\
+          </x:xmpmeta><?xpacket end=\"w\"?>",
+         self.description()
+      )
+   }
+
+   /// # 将来源信息加入原图的XMP数据包
+   ///
+   /// 作为`rdf:RDF`中单独的描述加入，替换之前写入的来源信息；找不到`rdf:RDF`时返回`None`
+   pub fn merge_into_xmp(&self, xmp: &str) -> Option<String> {
+      let mut xmp = remove_from_xmp(xmp);
+      let rdf = xmp.find("<rdf:RDF")?;
+      let insert = xmp[rdf..].find('>')? + rdf + 1;
+      xmp.insert_str(insert, &self.description());
+      Some(xmp)
+   }
+}
+
+/// 移除XMP数据包中lumix_mark写入的来源信息
+pub(crate) fn remove_from_xmp(xmp: &str) -> String {
+   let mut xmp = xmp.to_string();
+   let description = xmp.find(DESCRIPTION_START).and_then(|start| {
+      let end = xmp[start..].find("/>")?;
+      Some(start..start + end + 2)
+   });
+   if let Some(description) = description {
+      xmp.replace_range(description, "");
+   }
+   xmp
+}
+
+/// 64位FNV-1a哈希，结果不随Rust版本和平台变化
+fn fnv1a(bytes: &[u8]) -> u64 {
+   bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+      (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+   })
+}
+
+fn escape_xml(text: &str) -> String {
+   text
+      .replace('&', "&amp;")
+      .replace('<', "&lt;")
+      .replace('>', "&gt;")
+      .replace('"', "&quot;")
+}
+
+fn unescape_xml(text: &str) -> String {
+   text
+      .replace("&quot;", "\"")
+      .replace("&gt;", ">")
+      .replace("&lt;", "<")
+      .replace("&amp;", "&")
+}
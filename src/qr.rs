@@ -0,0 +1,75 @@
+use crate::style::{deserialize_exif_template, serialize_template};
+use crate::{Color, Exif, LumixMarkError, Result, Template, parse_exif_template};
+use image::{Rgba, RgbaImage};
+use qrcode::{EcLevel, QrCode as Code};
+use serde::{Deserialize, Serialize};
+
+/// # 水印条一角的二维码
+///
+/// 内容为按Exif信息填充的链接，如作品集或原图的地址；边长为相对水印高度的比例，叠加水印中不绘制
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct QrCode {
+   /// 链接模板，可用占位符与Exif信息的模板相同，填入的值会进行百分号编码
+   #[serde(
+      serialize_with = "serialize_template",
+      deserialize_with = "deserialize_exif_template"
+   )]
+   pub url: Template,
+   /// 边长
+   pub size_ratio: f32,
+   /// 深色模块的颜色，为`Auto`时按所在区域的背景选择
+   pub color: Color,
+}
+
+impl Default for QrCode {
+   fn default() -> Self {
+      Self {
+         url: parse_exif_template("").expect("空模板无效"),
+         size_ratio: 0.7,
+         color: Color::Auto,
+      }
+   }
+}
+
+impl QrCode {
+   /// 按照片的Exif信息生成链接，模板为空时返回`None`
+   pub fn render_url(&self, exif: &Exif) -> Option<String> {
+      let url = self
+         .url
+         .render(|field| percent_encode(&exif.field(field).unwrap_or_default()));
+      (!url.trim().is_empty()).then_some(url)
+   }
+
+   /// # 绘制为`side`像素见方、预乘Alpha的图片
+   ///
+   /// 深色模块为不透明的白色，绘制时按`color`着色；浅色模块透明，露出水印条的背景
+   pub(crate) fn render(url: &str, side: u32) -> Result<RgbaImage> {
+      let code = Code::with_error_correction_level(url, EcLevel::M).map_err(|err| {
+         LumixMarkError::InvalidConfig(format!("无法生成二维码：{err}，链接：{url}"))
+      })?;
+      let modules = code.width() as u32;
+      let colors = code.to_colors();
+      Ok(RgbaImage::from_fn(side, side, |x, y| {
+         let (column, row) = (x * modules / side, y * modules / side);
+         match colors[(row * modules + column) as usize] {
+            qrcode::Color::Dark => Rgba([255, 255, 255, 255]),
+            qrcode::Color::Light => Rgba([0, 0, 0, 0]),
+         }
+      }))
+   }
+}
+
+/// 对URL中不安全的字符进行百分号编码，保留字母、数字和`-._~`
+fn percent_encode(value: &str) -> String {
+   let mut encoded = String::new();
+   for byte in value.trim().bytes() {
+      match byte {
+         b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+            encoded.push(byte as char)
+         }
+         _ => encoded.push_str(&format!("%{byte:02X}")),
+      }
+   }
+   encoded
+}
@@ -0,0 +1,507 @@
+use crate::metadata::Metadata;
+use crate::{LumixMarkError, Result};
+use image::{DynamicImage, load_from_memory};
+
+/// 富士RAF文件头
+const RAF_MAGIC: &[u8] = b"FUJIFILMCCD-RAW";
+/// 松下RW2/RWL使用的非标准TIFF魔数
+const PANASONIC_MAGIC: &[u8] = b"IIU\0";
+/// IFD链和SubIFD的最大遍历数量，防止损坏文件中的循环引用
+const MAX_IFDS: usize = 32;
+
+const TAG_COMPRESSION: u16 = 0x0103;
+const TAG_PHOTOMETRIC: u16 = 0x0106;
+const TAG_STRIP_OFFSETS: u16 = 0x0111;
+const TAG_STRIP_BYTE_COUNTS: u16 = 0x0117;
+const TAG_SUB_IFDS: u16 = 0x014A;
+const TAG_JPEG_OFFSET: u16 = 0x0201;
+const TAG_JPEG_LENGTH: u16 = 0x0202;
+const TAG_EXIF_IFD: u16 = 0x8769;
+const TAG_GPS_IFD: u16 = 0x8825;
+const TAG_MAKER_NOTE: u16 = 0x927C;
+const TAG_INTEROP_IFD: u16 = 0xA005;
+const TAG_DNG_VERSION: u16 = 0xC612;
+/// 松下RW2中完整尺寸的JPEG预览图
+const TAG_PANASONIC_JPEG: u16 = 0x002E;
+/// IFD0中保留到EXIF的标签：描述、厂商、型号、方向、软件、修改时间、作者、版权
+const IFD0_TAGS: &[u16] = &[
+   0x010E, 0x010F, 0x0110, 0x0112, 0x0131, 0x0132, 0x013B, 0x8298,
+];
+/// 未经去马赛克的RAW数据的PhotometricInterpretation（CFA、LinearRaw）
+const RAW_PHOTOMETRIC: &[u32] = &[32803, 34892];
+/// JPEG压缩（旧式、新式）
+const JPEG_COMPRESSION: &[u32] = &[6, 7];
+
+/// # 根据文件内容判断是否为RAW文件
+///
+/// 支持松下RW2/RWL、索尼ARW、佳能CR2/CR3、尼康NEF、宾得PEF、DNG和富士RAF；
+/// 标准TIFF结构的RAW通过是否包含CFA/LinearRaw数据与普通TIFF图片区分
+pub(crate) fn is_raw(bytes: &[u8]) -> bool {
+   bytes.starts_with(RAF_MAGIC)
+      || is_cr3(bytes)
+      || Tiff::new(bytes).is_some_and(|tiff| tiff.is_raw())
+}
+
+/// # 解码RAW文件中嵌入的JPEG预览图
+///
+/// 不进行去马赛克，使用相机生成的尺寸最大的预览图，EXIF从RAW的TIFF结构中重新生成，
+/// 没有时使用预览图自带的EXIF
+pub(crate) fn decode_raw(bytes: &[u8]) -> Result<(DynamicImage, Metadata)> {
+   let (preview, exif) = if bytes.starts_with(RAF_MAGIC) {
+      (raf_preview(bytes), None)
+   } else if is_cr3(bytes) {
+      (cr3_preview(bytes), cr3_exif(bytes))
+   } else {
+      let tiff = Tiff::new(bytes);
+      (
+         tiff.and_then(|tiff| tiff.preview()),
+         tiff.and_then(|tiff| tiff.compact_exif()),
+      )
+   };
+   let preview = preview.ok_or_else(|| {
+      LumixMarkError::Decode("RAW文件中没有可以解码的JPEG预览图".into())
+   })?;
+   let mut metadata = Metadata::from_bytes(preview);
+   if exif.is_some() {
+      metadata.exif = exif;
+   }
+   Ok((load_from_memory(preview)?, metadata))
+}
+
+/// 根据ftyp盒子的品牌判断是否为CR3文件
+fn is_cr3(bytes: &[u8]) -> bool {
+   bytes.len() >= 12 && &bytes[4..8] == b"ftyp" && &bytes[8..12] == b"crx "
+}
+
+/// RAF文件头中记录了JPEG预览图的偏移和长度
+fn raf_preview(bytes: &[u8]) -> Option<&[u8]> {
+   let offset = read_be32(bytes, 84)? as usize;
+   let length = read_be32(bytes, 88)? as usize;
+   bytes
+      .get(offset..offset.checked_add(length)?)
+      .filter(|jpeg| is_viewable_jpeg(jpeg))
+}
+
+/// CR3的PRVW盒子中为约1620×1080的JPEG预览图
+fn cr3_preview(bytes: &[u8]) -> Option<&[u8]> {
+   let content = cr3_box(bytes, b"PRVW")?;
+   let start = content
+      .get(..content.len().min(32))?
+      .windows(3)
+      .position(|window| window == [0xFF, 0xD8, 0xFF])?;
+   Some(&content[start..]).filter(|jpeg| is_viewable_jpeg(jpeg))
+}
+
+/// CR3的CMT1、CMT2、CMT4盒子分别为IFD0、Exif IFD和GPS IFD，各自是完整的TIFF结构
+fn cr3_exif(bytes: &[u8]) -> Option<Vec<u8>> {
+   let first_ifd = |kind| {
+      let tiff = Tiff::new(cr3_box(bytes, kind)?)?;
+      tiff.ifd(tiff.first_ifd()?).map(|(ifd, _)| ifd)
+   };
+   compact_exif(first_ifd(b"CMT1")?, first_ifd(b"CMT2"), first_ifd(b"CMT4"))
+}
+
+/// # 在CR3文件中查找指定类型的盒子内容
+///
+/// 元数据和预览图位于佳能自定义的uuid盒子中，直接在文件中搜索盒子类型，并用盒子大小校验
+fn cr3_box<'a>(bytes: &'a [u8], kind: &[u8; 4]) -> Option<&'a [u8]> {
+   bytes
+      .windows(4)
+      .enumerate()
+      .skip(4)
+      .filter(|(_, window)| window == kind)
+      .find_map(|(at, _)| {
+         let size = read_be32(bytes, at - 4)? as usize;
+         bytes.get(at + 4..(at - 4).checked_add(size)?)
+      })
+}
+
+fn read_be32(bytes: &[u8], offset: usize) -> Option<u32> {
+   let bytes = bytes.get(offset..offset + 4)?.try_into().ok()?;
+   Some(u32::from_be_bytes(bytes))
+}
+
+/// 是否为可以解码的JPEG（基线或渐进式），排除RAW数据使用的无损JPEG
+fn is_viewable_jpeg(data: &[u8]) -> bool {
+   if !data.starts_with(&[0xFF, 0xD8]) {
+      return false;
+   }
+   let mut at = 2;
+   while let Some(segment) = data.get(at..at + 4) {
+      if segment[0] != 0xFF {
+         return false;
+      }
+      match segment[1] {
+         0xC0..=0xC2 => return true,
+         0xC3 | 0xC5..=0xC7 | 0xC9..=0xCB | 0xCD..=0xCF | 0xDA => return false,
+         _ => at += 2 + u16::from_be_bytes([segment[2], segment[3]]) as usize,
+      }
+   }
+   false
+}
+
+/// TIFF数据类型的单个值字节数
+fn type_size(kind: u16) -> Option<usize> {
+   match kind {
+      1 | 2 | 6 | 7 => Some(1),
+      3 | 8 => Some(2),
+      4 | 9 | 11 | 13 => Some(4),
+      5 | 10 | 12 => Some(8),
+      _ => None,
+   }
+}
+
+/// TIFF结构的只读视图
+#[derive(Clone, Copy)]
+struct Tiff<'a> {
+   data: &'a [u8],
+   big_endian: bool,
+}
+
+/// IFD中的一个条目，值为所在TIFF中的原始字节
+#[derive(Clone, Copy)]
+struct Entry<'a> {
+   tag: u16,
+   kind: u16,
+   count: u32,
+   value: &'a [u8],
+   big_endian: bool,
+}
+
+impl<'a> Tiff<'a> {
+   /// 按字节序标记解析TIFF头，不检查魔数以兼容RW2
+   fn new(data: &'a [u8]) -> Option<Self> {
+      let big_endian = match data.get(0..2)? {
+         b"II" => false,
+         b"MM" => true,
+         _ => return None,
+      };
+      Some(Self { data, big_endian })
+   }
+
+   fn u16(&self, offset: usize) -> Option<u16> {
+      let bytes = self.data.get(offset..offset + 2)?.try_into().ok()?;
+      Some(match self.big_endian {
+         true => u16::from_be_bytes(bytes),
+         false => u16::from_le_bytes(bytes),
+      })
+   }
+
+   fn u32(&self, offset: usize) -> Option<u32> {
+      let bytes = self.data.get(offset..offset + 4)?.try_into().ok()?;
+      Some(match self.big_endian {
+         true => u32::from_be_bytes(bytes),
+         false => u32::from_le_bytes(bytes),
+      })
+   }
+
+   fn first_ifd(&self) -> Option<usize> {
+      self.u32(4).map(|offset| offset as usize)
+   }
+
+   /// 读取偏移处的IFD，返回其中的条目和下一个IFD的偏移
+   fn ifd(&self, offset: usize) -> Option<(Vec<Entry<'a>>, usize)> {
+      let count = self.u16(offset)? as usize;
+      let entries = (0..count)
+         .filter_map(|index| self.entry(offset + 2 + index * 12))
+         .collect();
+      let next = self.u32(offset + 2 + count * 12).unwrap_or(0) as usize;
+      Some((entries, next))
+   }
+
+   fn entry(&self, at: usize) -> Option<Entry<'a>> {
+      let kind = self.u16(at + 2)?;
+      let count = self.u32(at + 4)?;
+      let size = type_size(kind)?.checked_mul(count as usize)?;
+      let value = match size <= 4 {
+         true => self.data.get(at + 8..at + 8 + size)?,
+         false => {
+            let offset = self.u32(at + 8)? as usize;
+            self.data.get(offset..offset.checked_add(size)?)?
+         }
+      };
+      Some(Entry {
+         tag: self.u16(at)?,
+         kind,
+         count,
+         value,
+         big_endian: self.big_endian,
+      })
+   }
+
+   /// 沿IFD链和SubIFD遍历所有IFD
+   fn all_ifds(&self) -> Vec<Vec<Entry<'a>>> {
+      let mut pending: Vec<usize> = self.first_ifd().into_iter().collect();
+      let mut visited = Vec::new();
+      let mut ifds = Vec::new();
+      while let Some(offset) = pending.pop() {
+         if offset == 0 || visited.contains(&offset) || visited.len() >= MAX_IFDS {
+            continue;
+         }
+         visited.push(offset);
+         let Some((entries, next)) = self.ifd(offset) else {
+            continue;
+         };
+         pending.push(next);
+         if let Some(sub_ifds) = find(&entries, TAG_SUB_IFDS) {
+            pending
+               .extend(sub_ifds.numbers().into_iter().map(|offset| offset as usize));
+         }
+         ifds.push(entries);
+      }
+      ifds
+   }
+
+   /// RW2/CR2通过文件头识别，其他格式需要包含CFA/LinearRaw数据或DNG版本号
+   fn is_raw(&self) -> bool {
+      if self.data.starts_with(PANASONIC_MAGIC) || self.data.get(8..10) == Some(b"CR") {
+         return true;
+      }
+      if self.u16(2) != Some(42) {
+         return false;
+      }
+      self.all_ifds().iter().any(|entries| {
+         find(entries, TAG_DNG_VERSION).is_some()
+            || find(entries, TAG_PHOTOMETRIC)
+               .and_then(Entry::number)
+               .is_some_and(|value| RAW_PHOTOMETRIC.contains(&value))
+      })
+   }
+
+   /// 所有IFD中尺寸最大的可解码JPEG预览图
+   fn preview(&self) -> Option<&'a [u8]> {
+      let mut previews = Vec::new();
+      for entries in self.all_ifds() {
+         if let Some(jpeg) = find(&entries, TAG_PANASONIC_JPEG) {
+            previews.push(jpeg.value);
+         }
+         previews.extend(self.region(&entries, TAG_JPEG_OFFSET, TAG_JPEG_LENGTH));
+         let compression = find(&entries, TAG_COMPRESSION).and_then(Entry::number);
+         if compression.is_some_and(|value| JPEG_COMPRESSION.contains(&value)) {
+            previews.extend(self.region(
+               &entries,
+               TAG_STRIP_OFFSETS,
+               TAG_STRIP_BYTE_COUNTS,
+            ));
+         }
+      }
+      previews
+         .into_iter()
+         .filter(|jpeg| is_viewable_jpeg(jpeg))
+         .max_by_key(|jpeg| jpeg.len())
+   }
+
+   /// 由偏移和长度两个标签指定的单个数据块
+   fn region(
+      &self,
+      entries: &[Entry],
+      offset_tag: u16,
+      length_tag: u16,
+   ) -> Option<&'a [u8]> {
+      let offset = find(entries, offset_tag)?.number()? as usize;
+      let length = find(entries, length_tag)?.number()? as usize;
+      self.data.get(offset..offset.checked_add(length)?)
+   }
+
+   /// IFD0及其中的Exif IFD、GPS IFD重新生成的EXIF
+   fn compact_exif(&self) -> Option<Vec<u8>> {
+      let (ifd0, _) = self.ifd(self.first_ifd()?)?;
+      let sub_ifd = |tag| {
+         let offset = find(&ifd0, tag)?.number()?;
+         self.ifd(offset as usize).map(|(entries, _)| entries)
+      };
+      let (exif, gps) = (sub_ifd(TAG_EXIF_IFD), sub_ifd(TAG_GPS_IFD));
+      compact_exif(ifd0, exif, gps)
+   }
+}
+
+impl Entry<'_> {
+   /// 整数类型（SHORT/LONG/IFD）条目的所有值
+   fn numbers(&self) -> Vec<u32> {
+      match self.kind {
+         3 => self
+            .value
+            .chunks_exact(2)
+            .map(|bytes| {
+               let bytes = [bytes[0], bytes[1]];
+               match self.big_endian {
+                  true => u16::from_be_bytes(bytes) as u32,
+                  false => u16::from_le_bytes(bytes) as u32,
+               }
+            })
+            .collect(),
+         4 | 13 => self
+            .value
+            .chunks_exact(4)
+            .map(|bytes| {
+               let bytes = [bytes[0], bytes[1], bytes[2], bytes[3]];
+               match self.big_endian {
+                  true => u32::from_be_bytes(bytes),
+                  false => u32::from_le_bytes(bytes),
+               }
+            })
+            .collect(),
+         _ => Vec::new(),
+      }
+   }
+
+   /// 整数类型条目的第一个值
+   fn number(&self) -> Option<u32> {
+      self.numbers().first().copied()
+   }
+}
+
+fn find<'e, 'a>(entries: &'e [Entry<'a>], tag: u16) -> Option<&'e Entry<'a>> {
+   entries.iter().find(|entry| entry.tag == tag)
+}
+
+/// # 重新生成只包含拍摄信息的EXIF
+///
+/// RAW文件本身是TIFF结构但包含整个RAW数据，只保留IFD0中的描述性标签、Exif IFD（不含MakerNote）
+/// 和GPS IFD，使EXIF足够小，可以写入JPEG的APP1段；字节序与IFD0不同的IFD被忽略
+fn compact_exif(
+   ifd0: Vec<Entry>,
+   exif: Option<Vec<Entry>>,
+   gps: Option<Vec<Entry>>,
+) -> Option<Vec<u8>> {
+   let big_endian = ifd0.first()?.big_endian;
+   let same_order =
+      |entries: &Vec<Entry>| entries.iter().all(|e| e.big_endian == big_endian);
+   let exif = exif.filter(same_order);
+   let gps = gps.filter(same_order);
+   let mut writer = TiffWriter::new(big_endian);
+   let mut fields: Vec<Field> = ifd0
+      .iter()
+      .filter(|entry| IFD0_TAGS.contains(&entry.tag))
+      .map(Field::from)
+      .collect();
+   for (tag, ifd) in [(TAG_EXIF_IFD, &exif), (TAG_GPS_IFD, &gps)] {
+      if ifd.is_some() {
+         fields.push(Field::pointer(tag));
+      }
+   }
+   let pointers = writer.write_ifd(fields);
+   for (tag, ifd) in [(TAG_EXIF_IFD, exif), (TAG_GPS_IFD, gps)] {
+      let (Some(entries), Some(&(_, at))) =
+         (ifd, pointers.iter().find(|(t, _)| *t == tag))
+      else {
+         continue;
+      };
+      let offset = writer.bytes.len() as u32;
+      writer.put_u32(at, offset);
+      writer.write_ifd(
+         entries
+            .iter()
+            .filter(|entry| !matches!(entry.tag, TAG_MAKER_NOTE | TAG_INTEROP_IFD))
+            .map(Field::from)
+            .collect(),
+      );
+   }
+   Some(writer.bytes)
+}
+
+/// 待写入的IFD条目，值为目标字节序的原始字节
+struct Field {
+   tag: u16,
+   kind: u16,
+   count: u32,
+   value: Vec<u8>,
+}
+
+impl Field {
+   /// 指向子IFD的条目，偏移在子IFD写入后回填
+   fn pointer(tag: u16) -> Self {
+      Self {
+         tag,
+         kind: 4,
+         count: 1,
+         value: vec![0; 4],
+      }
+   }
+}
+
+impl From<&Entry<'_>> for Field {
+   fn from(entry: &Entry) -> Self {
+      Self {
+         tag: entry.tag,
+         kind: entry.kind,
+         count: entry.count,
+         value: entry.value.to_vec(),
+      }
+   }
+}
+
+struct TiffWriter {
+   big_endian: bool,
+   bytes: Vec<u8>,
+}
+
+impl TiffWriter {
+   /// 写入TIFF头，IFD0紧随其后
+   fn new(big_endian: bool) -> Self {
+      let mut writer = Self {
+         big_endian,
+         bytes: match big_endian {
+            true => b"MM".to_vec(),
+            false => b"II".to_vec(),
+         },
+      };
+      writer.push_u16(42);
+      writer.push_u32(8);
+      writer
+   }
+
+   fn push_u16(&mut self, value: u16) {
+      let bytes = match self.big_endian {
+         true => value.to_be_bytes(),
+         false => value.to_le_bytes(),
+      };
+      self.bytes.extend_from_slice(&bytes);
+   }
+
+   fn push_u32(&mut self, value: u32) {
+      let at = self.bytes.len();
+      self.bytes.extend_from_slice(&[0; 4]);
+      self.put_u32(at, value);
+   }
+
+   fn put_u32(&mut self, at: usize, value: u32) {
+      let bytes = match self.big_endian {
+         true => value.to_be_bytes(),
+         false => value.to_le_bytes(),
+      };
+      self.bytes[at..at + 4].copy_from_slice(&bytes);
+   }
+
+   /// # 在末尾写入一个IFD及其数据区
+   ///
+   /// 返回每个条目的标签和值字段的位置，用于回填子IFD的偏移
+   fn write_ifd(&mut self, mut fields: Vec<Field>) -> Vec<(u16, usize)> {
+      fields.sort_by_key(|field| field.tag);
+      let data_start = self.bytes.len() + 2 + fields.len() * 12 + 4;
+      let mut data = Vec::new();
+      let mut positions = Vec::with_capacity(fields.len());
+      self.push_u16(fields.len() as u16);
+      for field in &fields {
+         self.push_u16(field.tag);
+         self.push_u16(field.kind);
+         self.push_u32(field.count);
+         positions.push((field.tag, self.bytes.len()));
+         if field.value.len() <= 4 {
+            let mut inline = field.value.clone();
+            inline.resize(4, 0);
+            self.bytes.extend_from_slice(&inline);
+         } else {
+            self.push_u32((data_start + data.len()) as u32);
+            data.extend_from_slice(&field.value);
+            // 值的偏移需要按字对齐
+            if data.len() % 2 == 1 {
+               data.push(0);
+            }
+         }
+      }
+      self.push_u32(0);
+      self.bytes.extend_from_slice(&data);
+      positions
+   }
+}
@@ -0,0 +1,244 @@
+use crate::{BatchSummary, Empty, Exif, MarkStyle, PipelineContext};
+#[cfg(any(feature = "cli", feature = "async"))]
+use crate::{ExifCache, LumixMarkCli, LumixMarkError, RunState, TaskOutcome};
+use serde::Serialize;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+#[cfg(any(feature = "cli", feature = "async"))]
+use std::time::Duration;
+#[cfg(any(feature = "cli", feature = "async"))]
+use tracing::{error, info};
+
+/// 图片尺寸
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub struct Dimensions {
+   pub width: u32,
+   pub height: u32,
+}
+
+impl From<(u32, u32)> for Dimensions {
+   fn from((width, height): (u32, u32)) -> Self {
+      Self { width, height }
+   }
+}
+
+/// 报告中的EXIF信息摘要
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct ExifSummary {
+   pub make: String,
+   pub model: String,
+   /// 水印中显示的机型
+   pub title: String,
+   pub shoot_time: String,
+   pub focal_length: String,
+   pub aperture: String,
+   pub exposure_time: String,
+   pub iso: String,
+   /// 按模板生成的Exif信息文字
+   pub text: String,
+}
+
+impl ExifSummary {
+   pub(crate) fn new(exif: &Exif, style: &MarkStyle) -> Self {
+      Self {
+         make: exif.make.clone(),
+         model: exif.model.clone(),
+         title: exif.model_title.clone(),
+         shoot_time: exif.shoot_time.clone(),
+         focal_length: exif.focal_length.clone(),
+         aperture: exif.aperture.clone(),
+         exposure_time: exif.exposure_time.clone(),
+         iso: exif.iso.clone(),
+         text: exif.render(&style.exif_template),
+      }
+   }
+}
+
+/// 单张图片的处理状态
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReportStatus {
+   /// 已写入输出文件
+   Written,
+   /// 预览模式（`--dry-run`）下将写入输出文件
+   Planned,
+   /// 输出文件已存在，或按`--multi-frame skip`、`--marked skip`跳过多帧图片、已带有水印的图片
+   Skipped,
+   /// 处理失败
+   Failed,
+   /// `--fail-fast`时因前面的图片失败而没有处理
+   Cancelled,
+}
+
+/// 单张图片处理过程中收集的信息，失败时只包含失败前已经得到的部分
+#[derive(Clone, Debug, Default)]
+pub(crate) struct TaskDetails {
+   pub(crate) source: Option<Dimensions>,
+   pub(crate) output: Option<Dimensions>,
+   pub(crate) exif: Option<ExifSummary>,
+   pub(crate) bytes: Option<u64>,
+}
+
+impl TaskDetails {
+   /// 从执行完流水线的处理状态中读取尺寸、EXIF和输出大小
+   pub(crate) fn record_context(
+      &mut self,
+      context: &PipelineContext,
+      style: &MarkStyle,
+   ) {
+      self.source = context.source_size.map(Dimensions::from);
+      if let Some(mark) = &context.mark {
+         self.output = Some((mark.width, mark.height).into());
+         self.exif = Some(ExifSummary::new(&mark.exif, style));
+      }
+      self.bytes = context.output.as_ref().map(|output| output.len() as u64);
+   }
+}
+
+/// # 单张图片的处理记录
+///
+/// 写入`--report`指定的JSON文件，缺少的信息为`null`
+#[derive(Clone, Debug, Serialize)]
+pub struct ReportRecord {
+   /// 输入图片路径
+   pub input: PathBuf,
+   /// 输出图片路径，确定输出路径前失败时为空
+   pub output: Option<PathBuf>,
+   /// 处理状态
+   pub status: ReportStatus,
+   /// 失败原因
+   pub error: Option<String>,
+   /// 原图尺寸（旋转前）
+   pub source: Option<Dimensions>,
+   /// 输出图片尺寸
+   pub dimensions: Option<Dimensions>,
+   /// EXIF信息摘要
+   pub exif: Option<ExifSummary>,
+   /// 处理耗时（毫秒）
+   pub duration_ms: f64,
+   /// 写入的字节数
+   pub bytes: Option<u64>,
+}
+
+/// JSON报告的内容
+#[derive(Serialize)]
+struct Report<'a> {
+   succeeded: usize,
+   skipped: usize,
+   failed: usize,
+   cancelled: usize,
+   /// 输出目标完成写入失败的原因
+   #[serde(skip_serializing_if = "Option::is_none")]
+   finish_error: Option<String>,
+   files: &'a [ReportRecord],
+}
+
+impl BatchSummary {
+   /// 记录单张图片的处理结果
+   #[cfg(any(feature = "cli", feature = "async"))]
+   pub(crate) fn add(
+      &mut self,
+      input: PathBuf,
+      result: Result<TaskOutcome, LumixMarkError>,
+      details: TaskDetails,
+      duration: Duration,
+      dry_run: bool,
+   ) {
+      let (status, output, error) = match &result {
+         Ok(TaskOutcome::Written(target)) if dry_run => {
+            (ReportStatus::Planned, Some(target.clone()), None)
+         }
+         Ok(TaskOutcome::Written(target)) => {
+            (ReportStatus::Written, Some(target.clone()), None)
+         }
+         Ok(TaskOutcome::Skipped(target)) => {
+            (ReportStatus::Skipped, Some(target.clone()), None)
+         }
+         Err(err) => (ReportStatus::Failed, None, Some(err.to_string())),
+      };
+      self.records.push(ReportRecord {
+         input: input.clone(),
+         output,
+         status,
+         error,
+         source: details.source,
+         dimensions: details.output,
+         exif: details.exif,
+         duration_ms: duration.as_secs_f64() * 1000.0,
+         bytes: details.bytes,
+      });
+      match result {
+         Ok(TaskOutcome::Written(target)) => self.succeeded.push((input, target)),
+         Ok(TaskOutcome::Skipped(target)) => self.skipped.push((input, target)),
+         Err(err) => self.failed.push((input, err)),
+      }
+   }
+
+   /// 记录因`fail_fast`而没有处理的图片
+   #[cfg(any(feature = "cli", feature = "async"))]
+   pub(crate) fn cancel(&mut self, input: PathBuf) {
+      self.records.push(ReportRecord {
+         input: input.clone(),
+         output: None,
+         status: ReportStatus::Cancelled,
+         error: None,
+         source: None,
+         dimensions: None,
+         exif: None,
+         duration_ms: 0.0,
+         bytes: None,
+      });
+      self.cancelled.push(input);
+   }
+
+   /// # 将每张图片的处理记录写入JSON文件
+   ///
+   /// 包含成功、跳过、失败的数量和按输入顺序排列的`files`
+   pub fn write_report(&self, path: &Path) -> Empty {
+      let report = Report {
+         succeeded: self.succeeded.len(),
+         skipped: self.skipped.len(),
+         failed: self.failed.len(),
+         cancelled: self.cancelled.len(),
+         finish_error: self.finish_error.as_ref().map(ToString::to_string),
+         files: &self.records,
+      };
+      let json = serde_json::to_vec_pretty(&report).map_err(io::Error::from)?;
+      if let Some(parent) = path
+         .parent()
+         .filter(|parent| !parent.as_os_str().is_empty())
+      {
+         fs::create_dir_all(parent)?;
+      }
+      fs::write(path, json)?;
+      Ok(())
+   }
+}
+
+#[cfg(any(feature = "cli", feature = "async"))]
+impl LumixMarkCli {
+   /// # 完成输出目标的写入，打印处理结果汇总，指定了`--report`时写入JSON报告
+   ///
+   /// 输出目标完成写入失败时（如压缩包的目录没有写入）记录到汇总中，整批按失败处理
+   pub(crate) fn finish_batch(&self, summary: &mut BatchSummary) {
+      if let Err(err) = self.sink().finish() {
+         summary.finish_error = Some(err);
+      }
+      summary.print();
+      if let Some(Err(err)) = self.cache.as_ref().map(ExifCache::save) {
+         error!("写入EXIF缓存失败：{}, path:{:?}", err, self.exif_cache);
+      }
+      // 没有写入任何图片时也覆盖上次的状态，预览时不改变状态文件
+      let state = self.run_state.as_ref().filter(|_| !self.dry_run);
+      if let Some(Err(err)) = state.map(RunState::save) {
+         error!("写入运行状态失败：{}, path:{:?}", err, self.state_file);
+      }
+      if let Some(path) = &self.report {
+         match summary.write_report(path) {
+            Ok(()) => info!("已写入报告：{:?}", path),
+            Err(err) => error!("写入报告失败：{}, path:{:?}", err, path),
+         }
+      }
+   }
+}
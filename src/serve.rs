@@ -0,0 +1,260 @@
+use crate::memory::{MemoryBudget, estimate_memory_bytes};
+use crate::{Empty, LumixMark, LumixMarkCli, LumixMarkError, OutputFormat};
+use clap::ValueEnum;
+use rayon::ThreadPoolBuilder;
+use std::io::{self, Read};
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::Instant;
+use tiny_http::{Header, Method, Request, Response, Server};
+use tracing::{error, info, warn};
+
+/// 请求体大小上限（字节）
+const MAX_BODY: usize = 200_000_000;
+/// 每个工作线程最多排队的请求数，超出时直接返回503
+const QUEUE_PER_WORKER: usize = 4;
+
+/// HTTP响应：状态码、Content-Type和内容
+type Reply = (u16, &'static str, Vec<u8>);
+
+/// multipart/form-data中的一个字段
+struct FormField<'a> {
+   name: String,
+   data: &'a [u8],
+}
+
+impl LumixMarkCli {
+   /// # 以HTTP服务运行
+   ///
+   /// `POST /mark`的请求体为图片，或为`multipart/form-data`表单：`image`字段为图片，可选的`style`字段为
+   /// 样式JSON，覆盖命令行的样式；查询参数`format`指定输出格式，默认与`--format`相同。`GET /health`
+   /// 用于健康检查。请求在线程池中处理，线程数由`par_count`限制，指定`max_memory`时按估计的内存占用
+   /// 限制同时处理的图片；排队的请求过多时返回503。一直运行直到进程退出
+   ///
+   /// # 参数
+   /// * `host` - 监听的地址
+   /// * `port` - 监听的端口
+   pub fn serve(&self, host: &str, port: u16) -> Empty {
+      let server = Server::http((host, port)).map_err(io::Error::other)?;
+      let threads = self.par_count.map_or_else(
+         || thread::available_parallelism().map_or(1, NonZeroUsize::get),
+         NonZeroUsize::get,
+      );
+      let pool = ThreadPoolBuilder::new()
+         .num_threads(threads)
+         .build()
+         .map_err(io::Error::other)?;
+      let budget = self.max_memory.map(MemoryBudget::new);
+      let pending = AtomicUsize::new(0);
+      info!("HTTP服务已启动：http://{}:{}，按Ctrl+C退出", host, port);
+      pool.in_place_scope(|scope| {
+         for request in server.incoming_requests() {
+            if pending.load(Ordering::Acquire) >= threads * QUEUE_PER_WORKER {
+               let reply = text(503, "服务繁忙，请稍后重试");
+               respond(request, reply, Instant::now());
+               continue;
+            }
+            pending.fetch_add(1, Ordering::AcqRel);
+            let (budget, pending) = (budget.as_ref(), &pending);
+            scope.spawn(move |_| {
+               self.handle(request, budget);
+               pending.fetch_sub(1, Ordering::AcqRel);
+            });
+         }
+      });
+      Ok(())
+   }
+
+   /// 处理一个请求并返回响应
+   fn handle(&self, mut request: Request, budget: Option<&MemoryBudget>) {
+      let start = Instant::now();
+      let (path, query) = request.url().split_once('?').unwrap_or((request.url(), ""));
+      let reply = match (request.method(), path) {
+         (Method::Get, "/health") => text(200, "ok"),
+         (Method::Post, "/mark") => {
+            let format =
+               query_format(query).map(|format| format.unwrap_or(self.format));
+            match format {
+               Ok(format) => self.mark_request(&mut request, format, budget),
+               Err(reply) => reply,
+            }
+         }
+         (_, "/mark" | "/health") => text(405, "不支持的请求方法"),
+         _ => text(404, "不存在的路径"),
+      };
+      respond(request, reply, start);
+   }
+
+   /// 读取上传的图片和样式，添加水印后返回
+   fn mark_request(
+      &self,
+      request: &mut Request,
+      format: OutputFormat,
+      budget: Option<&MemoryBudget>,
+   ) -> Reply {
+      if request
+         .body_length()
+         .is_some_and(|length| length > MAX_BODY)
+      {
+         return text(413, "上传的文件过大");
+      }
+      let mut body = Vec::new();
+      let read = request
+         .as_reader()
+         .take(MAX_BODY as u64 + 1)
+         .read_to_end(&mut body);
+      if let Err(err) = read {
+         return text(400, &format!("读取请求失败：{err}"));
+      }
+      if body.len() > MAX_BODY {
+         return text(413, "上传的文件过大");
+      }
+      let boundary = request
+         .headers()
+         .iter()
+         .find(|header| header.field.equiv("Content-Type"))
+         .and_then(|header| boundary(header.value.as_str()));
+      let (image, style) = match &boundary {
+         Some(boundary) => {
+            let fields = parse_multipart(&body, boundary);
+            let field = |name| fields.iter().find(|field| field.name == name);
+            let Some(image) = field("image") else {
+               return text(400, "表单中缺少image字段");
+            };
+            let style = field("style")
+               .map(|style| String::from_utf8_lossy(style.data))
+               .filter(|style| !style.trim().is_empty())
+               .map(|style| self.style.merge_json(&style));
+            (image.data, style)
+         }
+         None => (body.as_slice(), None),
+      };
+      if image.is_empty() {
+         return text(400, "没有上传图片");
+      }
+      let style = match style {
+         Some(Ok(style)) => style,
+         Some(Err(err)) => return error_reply(&err),
+         None => self.style.clone(),
+      };
+      let _permit = budget.map(|budget| budget.acquire(estimate_memory_bytes(image)));
+      let options = self.save_options();
+      match LumixMark::mark_bytes(image, self.ratio, &style, format, &options) {
+         Ok(bytes) => (200, format.mime_type(), bytes),
+         Err(err) => error_reply(&err),
+      }
+   }
+}
+
+/// 读取查询参数中的输出格式，未指定时返回`None`
+fn query_format(query: &str) -> Result<Option<OutputFormat>, Reply> {
+   let Some(value) = query
+      .split('&')
+      .filter_map(|pair| pair.split_once('='))
+      .find_map(|(key, value)| (key == "format").then_some(value))
+   else {
+      return Ok(None);
+   };
+   OutputFormat::from_str(value, true)
+      .map(Some)
+      .map_err(|_| text(400, &format!("不支持的输出格式：{value}")))
+}
+
+/// 处理失败的响应，服务端的问题返回500，其余返回400
+fn error_reply(err: &LumixMarkError) -> Reply {
+   let status = match err {
+      LumixMarkError::Io(_)
+      | LumixMarkError::Encode(_)
+      | LumixMarkError::FontLoad(_) => 500,
+      _ => 400,
+   };
+   text(status, &err.to_string())
+}
+
+fn text(status: u16, message: &str) -> Reply {
+   (
+      status,
+      "text/plain; charset=utf-8",
+      message.as_bytes().to_vec(),
+   )
+}
+
+/// 发送响应并记录日志，客户端断开等发送失败只记录日志
+fn respond(request: Request, (status, content_type, body): Reply, start: Instant) {
+   let method = request.method().clone();
+   let url = request.url().to_string();
+   let mut response = Response::from_data(body).with_status_code(status);
+   if let Ok(header) = Header::from_bytes("Content-Type", content_type) {
+      response = response.with_header(header);
+   }
+   if let Err(err) = request.respond(response) {
+      warn!("发送响应失败：{}, {} {}", err, method, url);
+   }
+   let elapsed = start.elapsed().as_millis();
+   match status {
+      500.. => error!("{} {} {} {}ms", method, url, status, elapsed),
+      _ => info!("{} {} {} {}ms", method, url, status, elapsed),
+   }
+}
+
+/// multipart/form-data的分隔符，其他Content-Type返回`None`
+fn boundary(content_type: &str) -> Option<String> {
+   let (mime, params) = content_type.split_once(';')?;
+   if !mime.trim().eq_ignore_ascii_case("multipart/form-data") {
+      return None;
+   }
+   params
+      .split(';')
+      .filter_map(|param| param.trim().split_once('='))
+      .find(|(key, _)| key.eq_ignore_ascii_case("boundary"))
+      .map(|(_, value)| value.trim_matches('"').to_string())
+}
+
+/// 拆分multipart/form-data请求体，跳过格式不正确的部分
+fn parse_multipart<'a>(body: &'a [u8], boundary: &str) -> Vec<FormField<'a>> {
+   let delimiter = format!("--{boundary}");
+   let mut fields = Vec::new();
+   let Some(start) = find(body, delimiter.as_bytes()) else {
+      return fields;
+   };
+   let mut rest = &body[start + delimiter.len()..];
+   let separator = format!("\r\n--{boundary}");
+   // 最后一个分隔符之后为`--`
+   while !rest.starts_with(b"--") {
+      let Some(end) = find(rest, separator.as_bytes()) else {
+         break;
+      };
+      let part = rest[..end].strip_prefix(b"\r\n").unwrap_or(&rest[..end]);
+      rest = &rest[end + separator.len()..];
+      let Some(split) = find(part, b"\r\n\r\n") else {
+         continue;
+      };
+      let headers = String::from_utf8_lossy(&part[..split]);
+      let name = headers
+         .lines()
+         .filter(|line| {
+            line
+               .to_ascii_lowercase()
+               .starts_with("content-disposition:")
+         })
+         .flat_map(|line| line.split(';'))
+         .filter_map(|param| param.trim().strip_prefix("name="))
+         .map(|name| name.trim_matches('"').to_string())
+         .next();
+      if let Some(name) = name {
+         fields.push(FormField {
+            name,
+            data: &part[split + 4..],
+         });
+      }
+   }
+   fields
+}
+
+/// `needle`在`haystack`中第一次出现的位置
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+   haystack
+      .windows(needle.len())
+      .position(|window| window == needle)
+}
@@ -0,0 +1,130 @@
+use crate::Color;
+use crate::fill::{RoundedCorners, mix};
+use image::imageops::fast_blur;
+use image::{GrayImage, Luma, Pixel, Rgb, RgbImage, Rgba};
+use serde::{Deserialize, Serialize};
+
+/// # 照片下方的投影
+///
+/// 照片的形状（包括圆角）模糊后作为透明度遮罩，按偏移量绘制在照片后方的背景上；
+/// 数值为相对水印高度的比例，叠加水印中不绘制
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Shadow {
+   /// 水平偏移，向右为正
+   pub offset_x: f32,
+   /// 垂直偏移，向下为正
+   pub offset_y: f32,
+   /// 模糊半径（高斯模糊的标准差）
+   pub blur_ratio: f32,
+   /// 不透明度（0 - 1），与颜色自身的不透明度相乘
+   pub opacity: f32,
+   /// 投影颜色，为`Auto`时按背景选择
+   pub color: Color,
+}
+
+impl Default for Shadow {
+   fn default() -> Self {
+      Self {
+         offset_x: 0.0,
+         offset_y: 0.04,
+         blur_ratio: 0.08,
+         opacity: 0.35,
+         color: Color::Black,
+      }
+   }
+}
+
+impl Shadow {
+   /// # 生成尺寸为`size`的照片的投影遮罩
+   ///
+   /// # 参数
+   /// * `size` - 照片尺寸
+   /// * `corners` - 照片的圆角，投影保持相同的形状
+   /// * `mark_height` - 水印高度
+   pub(crate) fn mask(
+      &self,
+      size: (u32, u32),
+      corners: RoundedCorners,
+      mark_height: f32,
+   ) -> ShadowMask {
+      let sigma = (self.blur_ratio * mark_height).max(0.0);
+      // 高斯模糊在三倍标准差以外几乎为0
+      let margin = (sigma * 3.0).ceil() as u32;
+      let (width, height) = size;
+      let mut alpha = GrayImage::new(width + margin * 2, height + margin * 2);
+      for y in 0..height {
+         for x in 0..width {
+            let coverage = corners.coverage((x, y), size);
+            alpha.put_pixel(x + margin, y + margin, Luma([(coverage * 255.0) as u8]));
+         }
+      }
+      if sigma >= 0.5 {
+         alpha = fast_blur(&alpha, sigma);
+      }
+      let offset = |ratio: f32| (ratio * mark_height).round() as i64 - margin as i64;
+      ShadowMask {
+         alpha,
+         origin: (offset(self.offset_x), offset(self.offset_y)),
+         color: self.color,
+         opacity: (self.opacity * self.color.opacity()).clamp(0.0, 1.0),
+      }
+   }
+}
+
+/// 模糊后的投影遮罩，坐标以照片的左上角为原点
+pub(crate) struct ShadowMask {
+   alpha: GrayImage,
+   /// 遮罩左上角相对照片左上角的位置
+   origin: (i64, i64),
+   color: Color,
+   opacity: f32,
+}
+
+impl ShadowMask {
+   /// 将投影混合到照片中`(x, y)`处下方的背景`under`上
+   pub(crate) fn blend(&self, under: Rgb<u8>, (x, y): (i64, i64)) -> Rgb<u8> {
+      let (mask_x, mask_y) = (x - self.origin.0, y - self.origin.1);
+      let inside = (0..self.alpha.width() as i64).contains(&mask_x)
+         && (0..self.alpha.height() as i64).contains(&mask_y);
+      if !inside {
+         return under;
+      }
+      let Luma([value]) = *self.alpha.get_pixel(mask_x as u32, mask_y as u32);
+      let alpha = value as f32 / 255.0 * self.opacity;
+      let Rgba([r, g, b, _]) = self.color.resolve(under).into();
+      under.map2(&Rgb([r, g, b]), |under, shadow| mix(shadow, under, alpha))
+   }
+
+   /// # 将投影绘制到画布上
+   ///
+   /// # 参数
+   /// * `canvas` - 画布
+   /// * `photo` - 照片在画布中的区域（左、上、右、下）
+   /// * `under_photo` - 是否绘制被照片覆盖的部分，照片已在画布中时为`false`
+   pub(crate) fn paint(
+      &self,
+      canvas: &mut RgbImage,
+      photo: (u32, u32, u32, u32),
+      under_photo: bool,
+   ) {
+      let (left, top, right, bottom) = photo;
+      let start_x = (left as i64 + self.origin.0).max(0);
+      let start_y = (top as i64 + self.origin.1).max(0);
+      let end_x = (left as i64 + self.origin.0 + self.alpha.width() as i64)
+         .min(canvas.width() as i64);
+      let end_y = (top as i64 + self.origin.1 + self.alpha.height() as i64)
+         .min(canvas.height() as i64);
+      for y in start_y..end_y {
+         for x in start_x..end_x {
+            let covered = (left as i64..right as i64).contains(&x)
+               && (top as i64..bottom as i64).contains(&y);
+            if covered && !under_photo {
+               continue;
+            }
+            let pixel = canvas.get_pixel_mut(x as u32, y as u32);
+            *pixel = self.blend(*pixel, (x - left as i64, y - top as i64));
+         }
+      }
+   }
+}
@@ -0,0 +1,45 @@
+use image::imageops::blur;
+use image::{DynamicImage, Rgb32FImage};
+use serde::{Deserialize, Serialize};
+
+/// # 缩小照片后的USM锐化
+///
+/// 照片缩小后细节会变软，原图减去高斯模糊图像得到边缘，再按强度叠加回原图
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Sharpen {
+   /// 锐化强度，0为不锐化
+   pub amount: f32,
+   /// 高斯模糊的半径（像素）
+   pub radius: f32,
+}
+
+impl Default for Sharpen {
+   fn default() -> Self {
+      Self {
+         amount: 0.5,
+         radius: 1.0,
+      }
+   }
+}
+
+impl Sharpen {
+   /// 锐化照片，16位原图保持16位
+   pub(crate) fn apply(&self, image: DynamicImage) -> DynamicImage {
+      if self.amount <= 0.0 || self.radius <= 0.0 {
+         return image;
+      }
+      let color = image.color();
+      let deep = color.bits_per_pixel() / color.channel_count() as u16 > 8;
+      let mut sharpened = image.to_rgb32f();
+      let blurred: Rgb32FImage = blur(&sharpened, self.radius);
+      for (value, blurred) in sharpened.iter_mut().zip(blurred.iter()) {
+         *value = (*value + self.amount * (*value - blurred)).clamp(0.0, 1.0);
+      }
+      let sharpened = DynamicImage::ImageRgb32F(sharpened);
+      match deep {
+         true => DynamicImage::ImageRgb16(sharpened.to_rgb16()),
+         false => DynamicImage::ImageRgb8(sharpened.to_rgb8()),
+      }
+   }
+}
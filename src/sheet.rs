@@ -0,0 +1,230 @@
+use crate::draw::Blend;
+use crate::font::FontStack;
+use crate::text::{Flow, TextRun};
+use crate::{
+   Color, Empty, Exif, Fill, InputImage, LumixMark, LumixMarkCli, LumixMarkError,
+   Metadata, Pipeline, PipelineContext, Result, is_marked,
+};
+use clap::Args;
+use image::{Rgb, RgbImage, imageops, load_from_memory};
+use rayon::prelude::*;
+use std::fs;
+use std::path::PathBuf;
+use tracing::{error, info};
+
+/// 格子下方说明文字的大小，相对格子边长
+const LABEL_RATIO: f32 = 0.045;
+/// 标题文字的大小，相对格子边长
+const TITLE_RATIO: f32 = 0.1;
+/// 文字的最小字号
+const MIN_TEXT_SIZE: f32 = 12.0;
+/// 行高相对文字大小的比例
+const LINE_HEIGHT_RATIO: f32 = 1.4;
+
+/// 印样（多张缩略图排列成的网格）的排版参数
+#[derive(Args, Clone, Debug, PartialEq, Eq)]
+pub struct SheetOptions {
+   #[arg(long, default_value_t = 4, value_parser = clap::value_parser!(u32).range(1..))]
+   /// 每行的格子数
+   pub columns: u32,
+   #[arg(long, default_value_t = 600, value_name = "PX", value_parser = clap::value_parser!(u32).range(16..))]
+   /// 格子的边长（像素），添加水印后的缩略图长边不超过该长度
+   pub cell_size: u32,
+   #[arg(long, default_value_t = 24, value_name = "PX")]
+   /// 格子之间和四周的间距（像素）
+   pub spacing: u32,
+   #[arg(long)]
+   /// 印样顶部的标题
+   pub title: Option<String>,
+   #[arg(long, value_name = "FILE")]
+   /// 输出文件名，保存到输出文件夹中 [默认: sheet.{输出格式的扩展名}]
+   pub output: Option<PathBuf>,
+}
+
+/// 一个格子的内容
+struct Cell {
+   thumbnail: RgbImage,
+   /// 文件名
+   name: String,
+   /// 机型和Exif信息
+   exif: String,
+}
+
+impl LumixMarkCli {
+   /// # 将所有输入图片添加水印后的缩略图排列为一张印样
+   ///
+   /// 缩略图按输入顺序（`--sort`）从左到右、从上到下排列，下方标注文件名、机型和Exif信息；
+   /// 已带有水印的图片（如输出文件夹中的图片）直接缩小。单张图片失败时跳过该图片，其余图片仍然排入印样
+   pub fn sheet(&self, options: &SheetOptions) -> Empty {
+      let cell_size = options.cell_size;
+      let results: Vec<_> = self
+         .inputs
+         .par_iter()
+         .map(|input| (input, self.sheet_cell(input, cell_size)))
+         .collect();
+      let mut cells = Vec::new();
+      let mut first_error = None;
+      for (input, result) in results {
+         match result {
+            Ok(cell) => cells.push(cell),
+            Err(err) => {
+               error!("生成缩略图失败：{}, 图片地址：{:?}", err, input.path);
+               first_error.get_or_insert(err);
+            }
+         }
+      }
+      if cells.is_empty() {
+         return Err(first_error.unwrap_or_else(|| {
+            LumixMarkError::InvalidConfig("没有可以排入印样的图片".to_string())
+         }));
+      }
+      let canvas = self.draw_sheet(&cells, options)?;
+      let target = self.target_path.join(match &options.output {
+         Some(output) => output.clone(),
+         None => PathBuf::from(format!("sheet.{}", self.format.extension())),
+      });
+      if let Some(parent) = target.parent() {
+         fs::create_dir_all(parent)?;
+      }
+      let Some(target) = self.overwrite.resolve(target.clone()) else {
+         info!("输出文件已存在，跳过：{:?}", target);
+         return first_error.map_or(Ok(()), Err);
+      };
+      let (width, height) = canvas.dimensions();
+      let sheet = LumixMark {
+         canvas,
+         exif: Exif::default(),
+         metadata: Metadata::default(),
+         mark_area: (0, 0, 0, 0),
+         photo_area: (0, 0, width, height),
+         width,
+         height,
+         mark_height: 0.0,
+         elements: Vec::new(),
+         deep_photo: None,
+         tiled: None,
+         accent: None,
+         provenance: None,
+      };
+      fs::write(&target, sheet.encode(self.format, &self.save_options())?)?;
+      info!("已保存印样：{:?}，共{}张图片", target, cells.len());
+      first_error.map_or(Ok(()), Err)
+   }
+
+   /// 添加水印并缩小到格子中，水印按缩小后的尺寸绘制
+   fn sheet_cell(&self, input: &InputImage, cell_size: u32) -> Result<Cell> {
+      let bytes = fs::read(&input.path)?;
+      let (thumbnail, exif) = if is_marked(&bytes) {
+         let exif = Exif::from_metadata(&Metadata::from_bytes(&bytes));
+         (load_from_memory(&bytes)?.into_rgb8(), exif)
+      } else {
+         let mut style = self.style_for(input)?.into_owned();
+         style.max_long_edge = Some(cell_size);
+         let mut context = PipelineContext::new(&bytes, self.ratio, &style)
+            .with_multi_frame(self.multi_frame)
+            .with_sidecar(self.sidecar.read(&input.path))
+            .with_sidecar_policy(self.sidecar);
+         Pipeline::default().remove("encode")?.run(&mut context)?;
+         let mark = context.into_mark()?;
+         (mark.canvas, mark.exif)
+      };
+      // 取整后仍可能略超出格子，已带有水印的图片也在这里缩小
+      let thumbnail = match thumbnail.width().max(thumbnail.height()) > cell_size {
+         true => imageops::thumbnail(&thumbnail, cell_size, cell_size),
+         false => thumbnail,
+      };
+      let exif_text = exif.render(&self.style.exif_template);
+      Ok(Cell {
+         thumbnail,
+         name: input
+            .path
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into_owned(),
+         exif: format!("{} {}", exif.model_title, exif_text)
+            .trim()
+            .to_string(),
+      })
+   }
+
+   /// 绘制印样：顶部为标题，格子中的缩略图水平居中、垂直居中，下方两行说明文字
+   fn draw_sheet(&self, cells: &[Cell], options: &SheetOptions) -> Result<RgbImage> {
+      let SheetOptions {
+         cell_size, spacing, ..
+      } = *options;
+      let columns = options.columns.min(cells.len() as u32);
+      let rows = (cells.len() as u32).div_ceil(columns);
+      let label_size = (cell_size as f32 * LABEL_RATIO).max(MIN_TEXT_SIZE);
+      let line_height = (label_size * LINE_HEIGHT_RATIO).ceil() as u32;
+      let label_height = line_height * 2 + spacing / 2;
+      let title_size = (cell_size as f32 * TITLE_RATIO).max(MIN_TEXT_SIZE);
+      let title_height = match &options.title {
+         Some(_) => (title_size * LINE_HEIGHT_RATIO).ceil() as u32 + spacing,
+         None => 0,
+      };
+      let width = columns * cell_size + (columns + 1) * spacing;
+      let height =
+         title_height + rows * (cell_size + label_height) + (rows + 1) * spacing;
+      // 自动和模糊背景需要照片，印样使用白色背景；渐变背景使用起点颜色
+      let background = match self.style.background {
+         Fill::Solid(color) | Fill::Gradient { from: color, .. } => Rgb::from(color),
+         Fill::Auto { .. } | Fill::Blur { .. } => Rgb([255, 255, 255]),
+      };
+      let mut canvas = RgbImage::from_pixel(width, height, background);
+
+      let texts = cells
+         .iter()
+         .flat_map(|cell| [cell.name.as_str(), cell.exif.as_str()])
+         .chain(options.title.as_deref());
+      let fonts = FontStack::new(self.style.font()?, &self.style, texts.clone())?;
+      let exif_fonts = FontStack::new(self.style.exif_font()?, &self.style, texts)?;
+      let mut labels = Vec::new();
+      for (index, cell) in cells.iter().enumerate() {
+         let (column, row) = (index as u32 % columns, index as u32 / columns);
+         let cell_x = spacing + column * (cell_size + spacing);
+         let cell_y =
+            spacing + title_height + row * (cell_size + label_height + spacing);
+         let (thumb_width, thumb_height) = cell.thumbnail.dimensions();
+         imageops::replace(
+            &mut canvas,
+            &cell.thumbnail,
+            (cell_x + (cell_size - thumb_width) / 2) as i64,
+            (cell_y + (cell_size - thumb_height) / 2) as i64,
+         );
+         let label_y = cell_y + cell_size + spacing / 2;
+         labels.push((&fonts, &cell.name, cell_x, label_y));
+         labels.push((&exif_fonts, &cell.exif, cell_x, label_y + line_height));
+      }
+
+      let mut blend = Blend::new(&mut canvas, 1.0);
+      if let Some(title) = &options.title {
+         let title = truncate(&fonts, title_size, title, width - spacing * 2);
+         let run = TextRun::new(&fonts, title_size, &title, Flow::Ltr);
+         let x = width.saturating_sub(run.size().0) / 2;
+         blend.draw_text(Color::Auto, x as i32, spacing as i32, &run);
+      }
+      for (fonts, text, cell_x, y) in labels {
+         let text = truncate(fonts, label_size, text, cell_size);
+         let run = TextRun::new(fonts, label_size, &text, Flow::Ltr);
+         let x = cell_x + cell_size.saturating_sub(run.size().0) / 2;
+         blend.draw_text(Color::Auto, x as i32, y as i32, &run);
+      }
+      Ok(canvas)
+   }
+}
+
+/// 文字超出宽度时截断，末尾添加`...`
+fn truncate(fonts: &FontStack, size: f32, text: &str, max_width: u32) -> String {
+   if fonts.text_size(size, text).0 <= max_width {
+      return text.to_string();
+   }
+   let mut chars: Vec<char> = text.chars().collect();
+   while chars.pop().is_some() {
+      let truncated = format!("{}...", chars.iter().collect::<String>().trim_end());
+      if fonts.text_size(size, &truncated).0 <= max_width {
+         return truncated;
+      }
+   }
+   String::new()
+}
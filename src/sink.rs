@@ -0,0 +1,249 @@
+use crate::date::ShootTime;
+use crate::output::numbered_path;
+use crate::{Empty, LumixMarkError, OverwritePolicy, Result};
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+use std::time::{SystemTime, UNIX_EPOCH};
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, DateTime, ZipWriter};
+
+/// # 输出图片的写入目标
+///
+/// 批量处理时多个线程同时调用；默认的[`DirectorySink`]写入输出文件夹，
+/// [`ZipSink`]将所有输出写入一个ZIP压缩包
+pub trait OutputSink: Send + Sync {
+   /// # 按覆盖策略预留输出路径
+   ///
+   /// 返回实际使用的路径（`Rename`时可能追加了数字后缀），已存在且需要跳过时返回`None`
+   fn claim(
+      &self,
+      target: PathBuf,
+      overwrite: OverwritePolicy,
+   ) -> Result<Option<PathBuf>>;
+
+   /// 写入`claim`返回的路径处的图片
+   fn write(&self, target: &Path, bytes: &[u8]) -> Empty;
+
+   /// 处理失败时释放`claim`预留的路径
+   fn release(&self, target: &Path, overwrite: OverwritePolicy);
+
+   /// 所有图片处理完成后调用
+   fn finish(&self) -> Empty {
+      Ok(())
+   }
+}
+
+/// # 将输出图片写入文件夹，文件夹不存在时创建
+///
+/// 先写入同一文件夹中的隐藏临时文件再重命名为输出路径，进程被中断时输出路径上不会留下不完整的文件
+#[derive(Default)]
+pub struct DirectorySink {
+   /// 本次运行中已预留的输出路径，并行处理的图片不会写入同一个文件
+   claimed: Mutex<HashSet<PathBuf>>,
+}
+
+impl DirectorySink {
+   fn lock(&self) -> MutexGuard<'_, HashSet<PathBuf>> {
+      self.claimed.lock().unwrap_or_else(|err| err.into_inner())
+   }
+}
+
+impl OutputSink for DirectorySink {
+   fn claim(
+      &self,
+      target: PathBuf,
+      overwrite: OverwritePolicy,
+   ) -> Result<Option<PathBuf>> {
+      if let Some(parent) = target.parent() {
+         fs::create_dir_all(parent)?;
+      }
+      let mut claimed = self.lock();
+      let target = overwrite
+         .resolve_with(target, |path| !claimed.contains(path) && !path.exists());
+      if let Some(target) = &target {
+         claimed.insert(target.clone());
+      }
+      Ok(target)
+   }
+
+   fn write(&self, target: &Path, bytes: &[u8]) -> Empty {
+      let file_name = target.file_name().unwrap_or_default().to_string_lossy();
+      let temp = target.with_file_name(format!(".{file_name}.tmp"));
+      let written = fs::write(&temp, bytes).and_then(|_| fs::rename(&temp, target));
+      if written.is_err() {
+         let _ = fs::remove_file(&temp);
+      }
+      Ok(written?)
+   }
+
+   fn release(&self, target: &Path, _overwrite: OverwritePolicy) {
+      self.lock().remove(target);
+   }
+}
+
+/// # 将输出图片写入ZIP压缩包
+///
+/// 每张图片编码后立即写入压缩包，不在磁盘上保留中间文件；条目名为输出路径相对输出文件夹的部分，
+/// 输出路径表示为压缩包路径拼接条目名（如`album.zip/2024/mark_a.jpg`）。照片已经过压缩，
+/// 条目不再压缩；压缩包在第一次写入时创建，已存在时覆盖，[`OutputSink::finish`]后才是完整的
+pub struct ZipSink {
+   path: PathBuf,
+   base: PathBuf,
+   state: Mutex<ZipState>,
+}
+
+struct ZipState {
+   writer: Option<ZipWriter<BufWriter<File>>>,
+   /// 已预留的输出路径，条目名不能重复
+   claimed: HashSet<PathBuf>,
+   finished: bool,
+}
+
+impl ZipSink {
+   /// # 创建
+   ///
+   /// # 参数
+   /// * `path` - ZIP压缩包路径
+   /// * `base` - 输出文件夹，条目名为输出路径相对该文件夹的部分
+   pub fn new<P: Into<PathBuf>, B: Into<PathBuf>>(path: P, base: B) -> Self {
+      Self {
+         path: path.into(),
+         base: base.into(),
+         state: Mutex::new(ZipState {
+            writer: None,
+            claimed: HashSet::new(),
+            finished: false,
+         }),
+      }
+   }
+
+   /// 压缩包中的路径
+   fn archive_path(&self, target: &Path) -> PathBuf {
+      self.path.join(relative_name(target, &self.base))
+   }
+
+   /// ZIP条目名，使用`/`分隔
+   fn entry_name(&self, target: &Path) -> Result<String> {
+      let relative = target.strip_prefix(&self.path).map_err(|_| {
+         LumixMarkError::InvalidConfig(format!("输出路径不在压缩包中：{target:?}"))
+      })?;
+      let parts: Vec<_> = relative
+         .components()
+         .map(|component| component.as_os_str().to_string_lossy())
+         .collect();
+      Ok(parts.join("/"))
+   }
+
+   fn lock(&self) -> MutexGuard<'_, ZipState> {
+      // 其他线程在写入时panic，已写入的条目仍然有效
+      self.state.lock().unwrap_or_else(|err| err.into_inner())
+   }
+}
+
+impl OutputSink for ZipSink {
+   /// 压缩包中已有同名条目时按`Skip`跳过，`Rename`和`Replace`都追加数字后缀（已写入的条目无法替换）
+   fn claim(
+      &self,
+      target: PathBuf,
+      overwrite: OverwritePolicy,
+   ) -> Result<Option<PathBuf>> {
+      let target = self.archive_path(&target);
+      let mut state = self.lock();
+      if overwrite == OverwritePolicy::Skip {
+         return Ok(state.claimed.insert(target.clone()).then_some(target));
+      }
+      let mut index = 0;
+      loop {
+         let candidate = numbered_path(&target, index);
+         if state.claimed.insert(candidate.clone()) {
+            return Ok(Some(candidate));
+         }
+         index += 1;
+      }
+   }
+
+   fn write(&self, target: &Path, bytes: &[u8]) -> Empty {
+      let name = self.entry_name(target)?;
+      let mut state = self.lock();
+      if state.finished {
+         return Err(LumixMarkError::InvalidConfig(format!(
+            "压缩包已经写完：{:?}",
+            self.path
+         )));
+      }
+      let writer = match state.writer.take() {
+         Some(writer) => writer,
+         None => {
+            if let Some(parent) = self.path.parent() {
+               fs::create_dir_all(parent)?;
+            }
+            ZipWriter::new(BufWriter::new(File::create(&self.path)?))
+         }
+      };
+      let writer = state.writer.insert(writer);
+      let options = SimpleFileOptions::default()
+         .compression_method(CompressionMethod::Stored)
+         .last_modified_time(modified_now())
+         .large_file(bytes.len() as u64 >= u32::MAX as u64);
+      writer.start_file(name, options).map_err(io::Error::other)?;
+      writer.write_all(bytes)?;
+      Ok(())
+   }
+
+   fn release(&self, target: &Path, _overwrite: OverwritePolicy) {
+      self.lock().claimed.remove(target);
+   }
+
+   /// 写入压缩包的目录，没有写入任何图片时不创建压缩包
+   fn finish(&self) -> Empty {
+      let mut state = self.lock();
+      state.finished = true;
+      let Some(writer) = state.writer.take() else {
+         return Ok(());
+      };
+      let mut file = writer.finish().map_err(io::Error::other)?;
+      file.flush()?;
+      Ok(())
+   }
+}
+
+/// 输出路径相对输出文件夹`base`的部分，使用`/`分隔；输出文件夹之外的路径只保留文件名
+pub(crate) fn relative_name(target: &Path, base: &Path) -> String {
+   let relative = target.strip_prefix(base).ok().filter(|relative| {
+      relative
+         .components()
+         .all(|component| matches!(component, Component::Normal(_)))
+   });
+   let parts: Vec<_> = match relative {
+      Some(relative) => relative
+         .components()
+         .map(|component| component.as_os_str().to_string_lossy())
+         .collect(),
+      None => target
+         .file_name()
+         .map(|name| name.to_string_lossy())
+         .into_iter()
+         .collect(),
+   };
+   parts.join("/")
+}
+
+/// 当前的UTC时间，ZIP条目的修改时间
+fn modified_now() -> DateTime {
+   let seconds = SystemTime::now()
+      .duration_since(UNIX_EPOCH)
+      .map_or(0, |elapsed| elapsed.as_secs() as i64);
+   let time = ShootTime::from_unix(seconds);
+   DateTime::from_date_and_time(
+      time.year as u16,
+      time.month as u8,
+      time.day as u8,
+      time.hour as u8,
+      time.minute as u8,
+      time.second as u8,
+   )
+   .unwrap_or_default()
+}
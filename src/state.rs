@@ -0,0 +1,157 @@
+use crate::Empty;
+use crate::cache::FileStamp;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+
+/// 运行状态文件的内容
+#[derive(Default, Serialize, Deserialize)]
+struct StateFile {
+   /// 已成功写入的图片，键为输入图片的绝对路径
+   completed: HashMap<PathBuf, Completed>,
+}
+
+/// 日志中的一行，每写入一张图片追加一行
+#[derive(Serialize, Deserialize)]
+struct JournalEntry {
+   input: PathBuf,
+   #[serde(flatten)]
+   completed: Completed,
+}
+
+/// 一张已成功写入的图片
+#[derive(Clone, Serialize, Deserialize)]
+struct Completed {
+   /// 输出图片路径
+   output: PathBuf,
+   /// 处理时输入图片的修改时间和文件大小
+   stamp: FileStamp,
+}
+
+/// # 批量处理的运行状态
+///
+/// 每写入一张图片就追加一行到状态文件旁的JSONL日志（`<状态文件>.jsonl`），处理结束时合并到状态文件并删除日志；
+/// 进程崩溃或被中断后使用`--resume`重新运行时，读取状态文件和日志，跳过输入未改变、输出仍然存在的图片
+pub struct RunState {
+   path: PathBuf,
+   journal: PathBuf,
+   state: Mutex<Progress>,
+}
+
+struct Progress {
+   file: StateFile,
+   /// 本次运行追加写入的日志，第一次记录时打开
+   journal: Option<File>,
+}
+
+impl RunState {
+   /// # 打开运行状态文件
+   ///
+   /// # 参数
+   /// * `path` - 状态文件路径
+   /// * `resume` - 是否继续上次的运行，为`false`时从空状态开始，第一次写入时覆盖原有文件
+   pub fn open<P: Into<PathBuf>>(path: P, resume: bool) -> Self {
+      let path = path.into();
+      let mut journal = OsString::from(&path);
+      journal.push(".jsonl");
+      let journal = PathBuf::from(journal);
+      let file = match resume {
+         true => Self::read(&path, &journal),
+         false => StateFile::default(),
+      };
+      Self {
+         path,
+         journal,
+         state: Mutex::new(Progress {
+            file,
+            journal: None,
+         }),
+      }
+   }
+
+   /// 读取状态文件并按顺序合并日志中的记录
+   fn read(path: &Path, journal: &Path) -> StateFile {
+      let file = fs::read(path)
+         .ok()
+         .and_then(|bytes| serde_json::from_slice::<StateFile>(&bytes).ok());
+      let lines = fs::read_to_string(journal).ok();
+      if file.is_none() && lines.is_none() {
+         tracing::warn!("运行状态文件不存在或无法解析，从头开始：{:?}", path);
+      }
+      let mut file = file.unwrap_or_default();
+      // 中断时最后一行可能没有写完，跳过无法解析的行
+      let entries = lines
+         .iter()
+         .flat_map(|lines| lines.lines())
+         .filter_map(|line| serde_json::from_str::<JournalEntry>(line).ok());
+      for entry in entries {
+         file.completed.insert(entry.input, entry.completed);
+      }
+      file
+   }
+
+   /// 上次运行中已完成的图片的输出路径，输入图片改变或输出文件已被删除时返回`None`
+   pub fn completed(&self, input: &Path) -> Option<PathBuf> {
+      let key = fs::canonicalize(input).ok()?;
+      let completed = self.lock().file.completed.get(&key).cloned()?;
+      let unchanged = FileStamp::read(input) == Some(completed.stamp);
+      (unchanged && completed.output.is_file()).then_some(completed.output)
+   }
+
+   /// 记录已成功写入的图片并立即追加到日志，中断时已完成的图片不会丢失
+   pub fn record(&self, input: &Path, output: &Path) -> Empty {
+      let (Ok(key), Some(stamp)) = (fs::canonicalize(input), FileStamp::read(input))
+      else {
+         return Ok(());
+      };
+      let output = fs::canonicalize(output).unwrap_or_else(|_| output.to_path_buf());
+      let entry = JournalEntry {
+         input: key,
+         completed: Completed { output, stamp },
+      };
+      let mut line = serde_json::to_vec(&entry).map_err(io::Error::from)?;
+      line.push(b'\n');
+      let mut guard = self.lock();
+      let progress = &mut *guard;
+      let journal = match &mut progress.journal {
+         Some(journal) => journal,
+         None => {
+            // 先写入已有的状态并清空上次的日志，不继续时同时覆盖上次的状态文件
+            self.write(&progress.file)?;
+            progress.journal.insert(File::create(&self.journal)?)
+         }
+      };
+      journal.write_all(&line)?;
+      progress.file.completed.insert(entry.input, entry.completed);
+      Ok(())
+   }
+
+   /// 将日志合并到状态文件后删除日志，没有处理任何图片时也会覆盖原有文件
+   pub fn save(&self) -> Empty {
+      let mut progress = self.lock();
+      self.write(&progress.file)?;
+      progress.journal = None;
+      match fs::remove_file(&self.journal) {
+         Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err.into()),
+         _ => Ok(()),
+      }
+   }
+
+   /// 先写入临时文件再替换，写入中断时不会损坏原有的状态
+   fn write(&self, state: &StateFile) -> Empty {
+      let json = serde_json::to_vec(state).map_err(io::Error::from)?;
+      let temp = self.path.with_extension("tmp");
+      fs::write(&temp, json)?;
+      fs::rename(&temp, &self.path)?;
+      Ok(())
+   }
+
+   fn lock(&self) -> MutexGuard<'_, Progress> {
+      // 其他线程在持有锁时panic不影响状态的有效性
+      self.state.lock().unwrap_or_else(|err| err.into_inner())
+   }
+}
@@ -0,0 +1,771 @@
+use crate::fill::RoundedCorners;
+use crate::shadow::ShadowMask;
+use crate::{
+   Anchors, CanvasLayout, Color, Corner, DEFAULT_EXIF_TEMPLATE, DateFormat, Divider,
+   DividerSpec, Exif, ExifOverride, FONT_BYTES, Fill, FontData, Frame, Geocoder,
+   Histogram, Layout, LogoFilter, LogoSize, LogoSource, LumixMarkError, MarkPosition,
+   ModelNames, QrCode, Result, Shadow, SharedGeocoder, Sharpen, Template,
+   TextDirection, TimeZone, parse_exif_template,
+};
+use ab_glyph::FontRef;
+use clap::ValueEnum;
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::borrow::Cow;
+use std::fs;
+use std::ops::Deref;
+use std::path::Path;
+use std::sync::Arc;
+
+/// # 水印样式
+///
+/// 除颜色外的数值均为相对水印高度的比例，配置文件中缺省的字段使用默认值
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MarkStyle {
+   /// 水印条所在的边
+   pub position: MarkPosition,
+   /// 水印内容的排列方式
+   pub layout: Layout,
+   /// 边框样式
+   pub frame: Frame,
+   /// 文字方向
+   pub text_direction: TextDirection,
+   /// 拍立得边框的宽度
+   pub border_ratio: f32,
+   /// 照片四角的圆角半径，为0时不圆角，叠加水印中不生效
+   pub corner_radius_ratio: f32,
+   /// 照片下方的投影，为空时不绘制，叠加水印中不生效
+   pub shadow: Option<Shadow>,
+   /// 水印条和边框的背景
+   pub background: Fill,
+   /// 叠加水印所在的角
+   pub corner: Corner,
+   /// 输出图片长边的最大像素数（包含水印条和边框），超过时先缩小照片再绘制水印
+   pub max_long_edge: Option<u32>,
+   /// 照片因`max_long_edge`缩小后的锐化，照片未缩小时不生效
+   pub sharpen: Option<Sharpen>,
+   /// 水印条右侧的照片直方图，为空时不绘制
+   pub histogram: Option<Histogram>,
+   /// 水印条一角的二维码，为空时不绘制
+   pub qr: Option<QrCode>,
+   /// 各元素的位置覆盖，未设置的元素按布局排列
+   pub anchors: Anchors,
+   /// 文字、分隔线和Logo的整体不透明度（0 - 1），与各颜色自身的不透明度相乘
+   pub opacity: f32,
+   /// Logo的不透明度（0 - 1）
+   pub logo_opacity: f32,
+   /// 设置后Logo绘制为该颜色的剪影，用于深色背景
+   pub logo_tint: Option<Color>,
+   /// 水印左右两侧的边距
+   pub padding_ratio: f32,
+   /// 机型显示名称的映射，覆盖内置的映射，如`"ILCE-7M4" = "SONY α7 IV"`
+   pub model_names: ModelNames,
+   /// 手动指定的EXIF信息，覆盖照片中的值
+   pub exif_override: ExifOverride,
+   /// 照片没有机型、Exif信息和附加信息时只绘制Logo
+   pub logo_only_without_exif: bool,
+   /// 机型文字颜色
+   pub model_color: Color,
+   /// 机型文字大小
+   pub model_text_ratio: f32,
+   /// Exif文字颜色
+   pub exif_color: Color,
+   /// Exif文字大小
+   pub exif_text_ratio: f32,
+   /// Exif信息的模板，如`{focal} | {aperture} | {shutter}s | ISO {iso}`
+   #[serde(
+      serialize_with = "serialize_template",
+      deserialize_with = "deserialize_exif_template"
+   )]
+   pub exif_template: Template,
+   /// 是否在Exif信息的焦距、光圈、快门速度和感光度前绘制图标
+   pub exif_icons: bool,
+   /// 曝光补偿为0时是否显示`{ev}`（显示为`±0 EV`），默认省略
+   pub show_zero_ev: bool,
+   /// 是否显示拍摄时间
+   pub show_date: bool,
+   /// 拍摄时间的显示格式，见[`DateFormat`]
+   pub date_format: DateFormat,
+   /// 显示拍摄时间的时区，见[`TimeZone`]
+   pub time_zone: TimeZone,
+   /// 是否显示拍摄地点，与拍摄时间显示在同一行
+   pub show_location: bool,
+   /// 是否显示版权信息，与拍摄时间显示在同一行
+   pub show_copyright: bool,
+   /// 版权信息，为空时取自EXIF中的Copyright或Artist
+   pub copyright: Option<String>,
+   /// 是否显示XMP中的星级和颜色标签，与拍摄时间显示在同一行
+   pub show_rating: bool,
+   /// 水印条底部说明文字的模板，如`Shot on {model} · {date}`，可用占位符与Exif信息的模板相同，为空时不绘制；
+   /// 文字过长时折为两行，水印条按行数加厚
+   #[serde(
+      serialize_with = "serialize_template",
+      deserialize_with = "deserialize_exif_template"
+   )]
+   pub caption: Template,
+   /// 拍摄时间、地点和版权信息的文字颜色
+   pub date_color: Color,
+   /// 拍摄时间、地点和版权信息的文字大小
+   pub date_text_ratio: f32,
+   /// Logo、分隔线、Exif文字之间的间距
+   pub gap_ratio: f32,
+   /// 分隔线样式
+   pub divider: Divider,
+   /// 分隔线颜色
+   pub divider_color: Color,
+   /// 分隔线宽度
+   pub divider_width_ratio: f32,
+   /// 分隔线高度
+   pub divider_height_ratio: f32,
+   /// 各分隔线的位置和样式，为空时只在Logo与Exif信息之间绘制一条上述样式的分隔线
+   pub dividers: Vec<DividerSpec>,
+   /// Logo的尺寸计算方式，`auto`时按Logo的宽高比计算宽度
+   pub logo_size: LogoSize,
+   /// Logo宽度
+   pub logo_width_ratio: f32,
+   /// Logo高度
+   pub logo_height_ratio: f32,
+   /// Logo缩放使用的重采样算法
+   pub logo_filter: LogoFilter,
+   /// Logo图片，为空时根据相机厂商自动选择
+   #[serde(skip)]
+   pub logo: Option<LogoSource>,
+   /// 将GPS位置转换为显示的地点，默认显示坐标
+   #[serde(skip)]
+   pub geocoder: SharedGeocoder,
+   /// 字体文件数据
+   #[serde(skip)]
+   pub font: Cow<'static, [u8]>,
+   /// 字体在字体集合（TTC）中的序号
+   #[serde(skip)]
+   pub font_index: u32,
+   /// Exif信息和拍摄时间等附加信息的字体，为空时与机型使用相同的字体
+   #[serde(skip)]
+   pub exif_font: Option<FontData>,
+   /// 主字体缺少字符时依次使用的后备字体，都缺少时查找系统中的中日韩字体和符号字体
+   #[serde(skip)]
+   pub fallback_fonts: Vec<FontData>,
+   /// 样式来自的预设名称，记录在输出文件的来源信息中
+   #[serde(skip)]
+   pub preset: Option<String>,
+}
+
+impl Default for MarkStyle {
+   fn default() -> Self {
+      Self {
+         position: MarkPosition::Bottom,
+         layout: Layout::Split,
+         frame: Frame::Strip,
+         text_direction: TextDirection::Auto,
+         border_ratio: 0.3,
+         corner_radius_ratio: 0.0,
+         shadow: None,
+         background: Fill::default(),
+         corner: Corner::BottomRight,
+         max_long_edge: None,
+         sharpen: None,
+         histogram: None,
+         qr: None,
+         anchors: Anchors::default(),
+         opacity: 1.0,
+         logo_opacity: 1.0,
+         logo_tint: None,
+         padding_ratio: 0.35,
+         model_names: ModelNames::default(),
+         exif_override: ExifOverride::default(),
+         logo_only_without_exif: false,
+         model_color: Color::Black,
+         model_text_ratio: 0.45,
+         exif_color: Color::RGB(50, 50, 50),
+         exif_text_ratio: 0.3,
+         exif_template: parse_exif_template(DEFAULT_EXIF_TEMPLATE)
+            .expect("默认Exif模板无效"),
+         exif_icons: false,
+         show_zero_ev: false,
+         show_date: false,
+         date_format: DateFormat::default(),
+         time_zone: TimeZone::default(),
+         show_location: false,
+         show_copyright: false,
+         copyright: None,
+         show_rating: false,
+         caption: parse_exif_template("").expect("空模板无效"),
+         date_color: Color::RGB(130, 130, 130),
+         date_text_ratio: 0.22,
+         gap_ratio: 0.12,
+         divider: Divider::Line,
+         divider_color: Color::RGB(150, 150, 150),
+         divider_width_ratio: 0.01,
+         divider_height_ratio: 0.25,
+         dividers: Vec::new(),
+         logo_size: LogoSize::Fixed,
+         logo_width_ratio: 0.35,
+         logo_height_ratio: 0.35,
+         logo_filter: LogoFilter::CatmullRom,
+         logo: None,
+         geocoder: SharedGeocoder::default(),
+         font: Cow::Borrowed(FONT_BYTES),
+         font_index: 0,
+         exif_font: None,
+         fallback_fonts: Vec::new(),
+         preset: None,
+      }
+   }
+}
+
+impl MarkStyle {
+   /// 以默认样式为基础构建水印样式
+   pub fn builder() -> MarkStyleBuilder {
+      MarkStyleBuilder::default()
+   }
+
+   /// # 计算画布布局
+   ///
+   /// # 参数
+   /// * `width` - 照片宽度
+   /// * `height` - 照片高度
+   /// * `mark_height` - 水印条的厚度
+   pub fn canvas_layout(
+      &self,
+      width: u32,
+      height: u32,
+      mark_height: u32,
+   ) -> CanvasLayout {
+      self.caption_layout(width, height, mark_height, 0)
+   }
+
+   /// 计算画布布局，水印条额外加厚`caption_height`用于绘制说明文字，边框宽度不变
+   pub(crate) fn caption_layout(
+      &self,
+      width: u32,
+      height: u32,
+      mark_height: u32,
+      caption_height: u32,
+   ) -> CanvasLayout {
+      let thickness = mark_height + caption_height;
+      if self.frame == Frame::Overlay {
+         return self.corner.overlay_layout(width, height, thickness);
+      }
+      let border = self.frame.border(mark_height, self.border_ratio);
+      self.position.layout(width, height, thickness, border)
+   }
+
+   /// 照片四角的圆角遮罩，叠加水印的照片铺满画布，不圆角
+   pub(crate) fn photo_corners(&self, mark_height: u32) -> RoundedCorners {
+      match self.frame {
+         Frame::Overlay => RoundedCorners::default(),
+         Frame::Strip | Frame::Polaroid => {
+            RoundedCorners::new(mark_height as f32 * self.corner_radius_ratio)
+         }
+      }
+   }
+
+   /// 按照片尺寸生成的投影遮罩，叠加水印和未设置投影时为`None`
+   pub(crate) fn photo_shadow(
+      &self,
+      size: (u32, u32),
+      mark_height: u32,
+   ) -> Option<ShadowMask> {
+      let shadow = self.shadow.filter(|_| self.frame != Frame::Overlay)?;
+      Some(shadow.mask(size, self.photo_corners(mark_height), mark_height as f32))
+   }
+
+   /// 横向绘制时水印条所在的边，叠加水印始终是横向的
+   pub(crate) fn strip_position(&self) -> MarkPosition {
+      match self.frame {
+         Frame::Overlay => MarkPosition::Bottom,
+         Frame::Strip | Frame::Polaroid => self.position,
+      }
+   }
+
+   /// 应用手动指定的EXIF信息和曝光补偿为0时的显示方式，并按机型映射表生成显示的机型名称
+   pub(crate) fn prepare_exif(&self, exif: &mut Exif) {
+      self.exif_override.apply(exif);
+      // 四舍五入后显示为0的曝光补偿
+      let zero_ev = exif.exposure_compensation.is_some_and(|ev| ev.abs() < 0.05);
+      if zero_ev && !self.show_zero_ev {
+         exif.exposure_compensation = None;
+      }
+      exif.model_title = self.model_names.title(&exif.model);
+   }
+
+   /// 解析样式中的字体
+   pub fn font(&self) -> Result<FontRef<'_>> {
+      Ok(FontRef::try_from_slice_and_index(
+         &self.font,
+         self.font_index,
+      )?)
+   }
+
+   /// 解析Exif信息使用的字体，未单独指定时与[`MarkStyle::font`]相同
+   pub fn exif_font(&self) -> Result<FontRef<'_>> {
+      match &self.exif_font {
+         Some(font) => Ok(FontRef::try_from_slice_and_index(&font.bytes, font.index)?),
+         None => self.font(),
+      }
+   }
+
+   /// 从TOML配置文件读取水印样式
+   pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+      Self::from_toml(&fs::read_to_string(path)?)
+   }
+
+   /// 从TOML配置文本读取水印样式，格式与配置文件相同
+   pub fn from_toml(config: &str) -> Result<Self> {
+      Ok(toml::from_str(config)?)
+   }
+
+   /// # 用TOML配置文本中出现的字段覆盖当前样式
+   ///
+   /// 顶层字段整体替换（如`[model_names]`替换全部映射），字体、Logo等不在配置文件中的设置保持不变
+   pub fn merge_toml(&self, config: &str) -> Result<Self> {
+      // 先单独解析一次，字段无效时错误信息中包含所在的行
+      Self::from_toml(config)?;
+      let mut table = toml::Table::try_from(self)?;
+      table.extend(toml::from_str::<toml::Table>(config)?);
+      Ok(self.keep_resources(table.try_into()?))
+   }
+
+   /// # 用JSON文本中出现的字段覆盖当前样式
+   ///
+   /// 字段与配置文件相同，与[`MarkStyle::merge_toml`]一样整体替换顶层字段
+   ///
+   /// ```
+   /// use lumix_mark::MarkStyle;
+   ///
+   /// let style = MarkStyle::default().merge_json(r#"{"show_date": true}"#).unwrap();
+   /// assert!(style.show_date);
+   /// assert!(MarkStyle::default().merge_json("[1, 2]").is_err());
+   /// ```
+   pub fn merge_json(&self, config: &str) -> Result<Self> {
+      let invalid = |err: serde_json::Error| {
+         LumixMarkError::InvalidConfig(format!("样式JSON无效：{err}"))
+      };
+      let serde_json::Value::Object(overrides) =
+         serde_json::from_str(config).map_err(invalid)?
+      else {
+         return Err(LumixMarkError::InvalidConfig(
+            "样式JSON应为对象".to_string(),
+         ));
+      };
+      let mut object = match serde_json::to_value(self).map_err(invalid)? {
+         serde_json::Value::Object(object) => object,
+         _ => serde_json::Map::new(),
+      };
+      object.extend(overrides);
+      let style = serde_json::from_value(object.into()).map_err(invalid)?;
+      Ok(self.keep_resources(style))
+   }
+
+   /// 将当前样式中不在配置文件中的字体、Logo等设置复制到`style`
+   fn keep_resources(&self, mut style: MarkStyle) -> MarkStyle {
+      style.logo = self.logo.clone();
+      style.geocoder = self.geocoder.clone();
+      style.font = self.font.clone();
+      style.font_index = self.font_index;
+      style.exif_font = self.exif_font.clone();
+      style.fallback_fonts = self.fallback_fonts.clone();
+      style.preset = self.preset.clone();
+      style
+   }
+
+   /// 将水印样式写入TOML配置文件
+   pub fn write_to_file<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+      fs::write(path, toml::to_string_pretty(self)?)?;
+      Ok(())
+   }
+}
+
+/// 模板在配置文件中写为原始的模板文本
+pub(crate) fn serialize_template<S: Serializer>(
+   template: &Template,
+   serializer: S,
+) -> std::result::Result<S::Ok, S::Error> {
+   serializer.collect_str(template)
+}
+
+pub(crate) fn deserialize_exif_template<'de, D: Deserializer<'de>>(
+   deserializer: D,
+) -> std::result::Result<Template, D::Error> {
+   let template = String::deserialize(deserializer)?;
+   parse_exif_template(&template).map_err(D::Error::custom)
+}
+
+/// 水印配色主题
+#[derive(
+   Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, ValueEnum,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum Theme {
+   /// 白色水印条、深色文字
+   #[default]
+   Light,
+   /// 深色水印条、浅色文字和白色Logo
+   Dark,
+}
+
+impl Theme {
+   /// 将主题的背景、文字、分隔线和Logo颜色应用到水印样式
+   pub fn apply(self, style: &mut MarkStyle) {
+      match self {
+         Theme::Light => {
+            let light = MarkStyle::default();
+            style.background = light.background;
+            style.model_color = light.model_color;
+            style.exif_color = light.exif_color;
+            style.date_color = light.date_color;
+            style.divider_color = light.divider_color;
+            style.logo_tint = light.logo_tint;
+         }
+         Theme::Dark => {
+            style.background = Fill::Solid(Color::RGB(24, 24, 24));
+            style.model_color = Color::White;
+            style.exif_color = Color::RGB(205, 205, 205);
+            style.date_color = Color::RGB(150, 150, 150);
+            style.divider_color = Color::RGB(105, 105, 105);
+            style.logo_tint = Some(Color::White);
+         }
+      }
+   }
+}
+
+/// # 图片使用的水印样式
+///
+/// 见[`LumixMarkCli::style_for`](crate::LumixMarkCli::style_for)：没有样式覆盖文件时借用命令行的样式，
+/// 否则共享缓存中合并后的样式，不复制其中的字体和Logo数据
+#[derive(Clone, Debug)]
+pub enum StyleRef<'a> {
+   Borrowed(&'a MarkStyle),
+   Shared(Arc<MarkStyle>),
+}
+
+impl StyleRef<'_> {
+   /// 取得可修改的样式
+   pub fn into_owned(self) -> MarkStyle {
+      match self {
+         Self::Borrowed(style) => style.clone(),
+         Self::Shared(style) => Arc::unwrap_or_clone(style),
+      }
+   }
+}
+
+impl Deref for StyleRef<'_> {
+   type Target = MarkStyle;
+
+   fn deref(&self) -> &MarkStyle {
+      match self {
+         Self::Borrowed(style) => style,
+         Self::Shared(style) => style,
+      }
+   }
+}
+
+/// # 水印样式构建器
+///
+/// ```
+/// use lumix_mark::{Color, L_MOUNT_LOGO, MarkStyle};
+///
+/// let style = MarkStyle::builder()
+///    .model_color(Color::Black)
+///    .exif_text_ratio(0.3)
+///    .logo(L_MOUNT_LOGO)
+///    .build();
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct MarkStyleBuilder {
+   style: MarkStyle,
+}
+
+impl MarkStyleBuilder {
+   pub fn position(mut self, position: MarkPosition) -> Self {
+      self.style.position = position;
+      self
+   }
+
+   pub fn layout(mut self, layout: Layout) -> Self {
+      self.style.layout = layout;
+      self
+   }
+
+   pub fn frame(mut self, frame: Frame) -> Self {
+      self.style.frame = frame;
+      self
+   }
+
+   pub fn border_ratio(mut self, ratio: f32) -> Self {
+      self.style.border_ratio = ratio;
+      self
+   }
+
+   pub fn corner_radius_ratio(mut self, ratio: f32) -> Self {
+      self.style.corner_radius_ratio = ratio;
+      self
+   }
+
+   pub fn shadow(mut self, shadow: Shadow) -> Self {
+      self.style.shadow = Some(shadow);
+      self
+   }
+
+   pub fn background(mut self, background: Fill) -> Self {
+      self.style.background = background;
+      self
+   }
+
+   pub fn text_direction(mut self, direction: TextDirection) -> Self {
+      self.style.text_direction = direction;
+      self
+   }
+
+   pub fn corner(mut self, corner: Corner) -> Self {
+      self.style.corner = corner;
+      self
+   }
+
+   pub fn max_long_edge(mut self, pixels: u32) -> Self {
+      self.style.max_long_edge = Some(pixels);
+      self
+   }
+
+   pub fn sharpen(mut self, sharpen: Sharpen) -> Self {
+      self.style.sharpen = Some(sharpen);
+      self
+   }
+
+   pub fn histogram(mut self, histogram: Histogram) -> Self {
+      self.style.histogram = Some(histogram);
+      self
+   }
+
+   pub fn qr(mut self, qr: QrCode) -> Self {
+      self.style.qr = Some(qr);
+      self
+   }
+
+   /// 覆盖各元素的位置，见[`Anchors`]
+   pub fn anchors(mut self, anchors: Anchors) -> Self {
+      self.style.anchors = anchors;
+      self
+   }
+
+   pub fn opacity(mut self, opacity: f32) -> Self {
+      self.style.opacity = opacity;
+      self
+   }
+
+   pub fn logo_opacity(mut self, opacity: f32) -> Self {
+      self.style.logo_opacity = opacity;
+      self
+   }
+
+   pub fn logo_tint(mut self, tint: Color) -> Self {
+      self.style.logo_tint = Some(tint);
+      self
+   }
+
+   /// 应用配色主题，覆盖之前设置的背景、文字、分隔线和Logo颜色
+   pub fn theme(mut self, theme: Theme) -> Self {
+      theme.apply(&mut self.style);
+      self
+   }
+
+   pub fn padding_ratio(mut self, ratio: f32) -> Self {
+      self.style.padding_ratio = ratio;
+      self
+   }
+
+   /// 添加机型显示名称的映射，`model`以`*`结尾时为前缀规则
+   pub fn model_name<K: Into<String>, V: Into<String>>(
+      mut self,
+      model: K,
+      title: V,
+   ) -> Self {
+      self.style.model_names.insert(model, title);
+      self
+   }
+
+   pub fn model_color(mut self, color: Color) -> Self {
+      self.style.model_color = color;
+      self
+   }
+
+   pub fn model_text_ratio(mut self, ratio: f32) -> Self {
+      self.style.model_text_ratio = ratio;
+      self
+   }
+
+   pub fn exif_color(mut self, color: Color) -> Self {
+      self.style.exif_color = color;
+      self
+   }
+
+   pub fn exif_text_ratio(mut self, ratio: f32) -> Self {
+      self.style.exif_text_ratio = ratio;
+      self
+   }
+
+   /// 显示拍摄时间，`format`见[`DateFormat`]
+   pub fn date(mut self, format: DateFormat) -> Self {
+      self.style.show_date = true;
+      self.style.date_format = format;
+      self
+   }
+
+   /// 将拍摄时间换算到时区`zone`后显示，见[`TimeZone`]
+   pub fn time_zone(mut self, zone: TimeZone) -> Self {
+      self.style.time_zone = zone;
+      self
+   }
+
+   /// 显示拍摄地点，`geocoder`将GPS位置转换为地点，如[`CoordinateFormat`](crate::CoordinateFormat)
+   pub fn location<G: Geocoder + 'static>(mut self, geocoder: G) -> Self {
+      self.style.show_location = true;
+      self.style.geocoder = SharedGeocoder(Arc::new(geocoder));
+      self
+   }
+
+   pub fn exif_icons(mut self, icons: bool) -> Self {
+      self.style.exif_icons = icons;
+      self
+   }
+
+   /// 曝光补偿为0时仍显示`{ev}`
+   pub fn show_zero_ev(mut self) -> Self {
+      self.style.show_zero_ev = true;
+      self
+   }
+
+   /// 显示版权信息，`copyright`为`None`时取自EXIF中的Copyright或Artist
+   pub fn copyright(mut self, copyright: Option<String>) -> Self {
+      self.style.show_copyright = true;
+      self.style.copyright = copyright;
+      self
+   }
+
+   /// 显示XMP中的星级和颜色标签
+   pub fn rating(mut self) -> Self {
+      self.style.show_rating = true;
+      self
+   }
+
+   /// 指定水印条底部说明文字的模板，见[`parse_exif_template`](crate::parse_exif_template)
+   pub fn caption(mut self, template: Template) -> Self {
+      self.style.caption = template;
+      self
+   }
+
+   /// 手动指定EXIF信息，用于没有EXIF或EXIF不完整的照片
+   pub fn exif_override(mut self, exif: ExifOverride) -> Self {
+      self.style.exif_override = exif;
+      self
+   }
+
+   pub fn logo_only_without_exif(mut self, logo_only: bool) -> Self {
+      self.style.logo_only_without_exif = logo_only;
+      self
+   }
+
+   pub fn date_color(mut self, color: Color) -> Self {
+      self.style.date_color = color;
+      self
+   }
+
+   pub fn date_text_ratio(mut self, ratio: f32) -> Self {
+      self.style.date_text_ratio = ratio;
+      self
+   }
+
+   /// 指定Exif信息的模板，见[`parse_exif_template`](crate::parse_exif_template)
+   pub fn exif_template(mut self, template: Template) -> Self {
+      self.style.exif_template = template;
+      self
+   }
+
+   pub fn gap_ratio(mut self, ratio: f32) -> Self {
+      self.style.gap_ratio = ratio;
+      self
+   }
+
+   pub fn divider(mut self, divider: Divider) -> Self {
+      self.style.divider = divider;
+      self
+   }
+
+   pub fn divider_color(mut self, color: Color) -> Self {
+      self.style.divider_color = color;
+      self
+   }
+
+   pub fn divider_width_ratio(mut self, ratio: f32) -> Self {
+      self.style.divider_width_ratio = ratio;
+      self
+   }
+
+   pub fn divider_height_ratio(mut self, ratio: f32) -> Self {
+      self.style.divider_height_ratio = ratio;
+      self
+   }
+
+   /// 添加一条分隔线，添加后不再绘制默认的分隔线，见[`DividerSpec`]
+   pub fn add_divider(mut self, divider: DividerSpec) -> Self {
+      self.style.dividers.push(divider);
+      self
+   }
+
+   pub fn logo_size(mut self, size: LogoSize) -> Self {
+      self.style.logo_size = size;
+      self
+   }
+
+   pub fn logo_width_ratio(mut self, ratio: f32) -> Self {
+      self.style.logo_width_ratio = ratio;
+      self
+   }
+
+   pub fn logo_height_ratio(mut self, ratio: f32) -> Self {
+      self.style.logo_height_ratio = ratio;
+      self
+   }
+
+   pub fn logo_filter(mut self, filter: LogoFilter) -> Self {
+      self.style.logo_filter = filter;
+      self
+   }
+
+   /// 指定Logo图片，支持内嵌数据、文件路径和内存数据
+   pub fn logo<L: Into<LogoSource>>(mut self, logo: L) -> Self {
+      self.style.logo = Some(logo.into());
+      self
+   }
+
+   /// 指定字体文件数据
+   pub fn font<F: Into<Cow<'static, [u8]>>>(mut self, font: F) -> Self {
+      self.style.font = font.into();
+      self.style.font_index = 0;
+      self
+   }
+
+   /// 为Exif信息和附加信息单独指定字体（如机型使用粗体、Exif信息使用常规字重）
+   pub fn exif_font(mut self, font: FontData) -> Self {
+      self.style.exif_font = Some(font);
+      self
+   }
+
+   /// 添加后备字体，主字体缺少的字符按添加的顺序使用后备字体绘制
+   pub fn fallback_font(mut self, font: FontData) -> Self {
+      self.style.fallback_fonts.push(font);
+      self
+   }
+
+   /// 使用按路径或名称加载的字体，见[`FontData::load`]
+   pub fn font_data(mut self, font: FontData) -> Self {
+      self.style.font = Cow::Owned(font.bytes);
+      self.style.font_index = font.index;
+      self
+   }
+
+   pub fn build(self) -> MarkStyle {
+      self.style
+   }
+}
@@ -0,0 +1,265 @@
+use crate::{LumixMarkError, Result};
+use std::fmt::{self, Display, Formatter};
+
+/// 模板片段
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Segment {
+   Text(String),
+   Field(String),
+}
+
+/// # 带占位符的文本模板
+///
+/// 占位符写作`{name}`，`{{`和`}}`分别表示字面量的`{`和`}`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Template {
+   source: String,
+   segments: Vec<Segment>,
+}
+
+impl Template {
+   /// # 解析模板
+   ///
+   /// # 参数
+   /// * `template` - 模板文本
+   /// * `fields` - 允许使用的占位符，出现其他占位符时返回错误
+   pub fn parse(template: &str, fields: &[&str]) -> Result<Self> {
+      let invalid = |msg: String| LumixMarkError::InvalidConfig(msg);
+      let mut segments = Vec::new();
+      let mut text = String::new();
+      let mut chars = template.chars().peekable();
+      while let Some(c) = chars.next() {
+         match c {
+            '{' if chars.peek() == Some(&'{') => {
+               chars.next();
+               text.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+               chars.next();
+               text.push('}');
+            }
+            '{' => {
+               let mut name = String::new();
+               loop {
+                  match chars.next() {
+                     Some('}') => break,
+                     Some(c) => name.push(c),
+                     None => {
+                        return Err(invalid(format!(
+                           "模板中的占位符未闭合：{template}"
+                        )));
+                     }
+                  }
+               }
+               let name = name.trim();
+               if !fields.contains(&name) {
+                  return Err(invalid(format!(
+                     "模板中存在未知的占位符{{{name}}}，可用的占位符：{}",
+                     fields.join(", ")
+                  )));
+               }
+               if !text.is_empty() {
+                  segments.push(Segment::Text(std::mem::take(&mut text)));
+               }
+               segments.push(Segment::Field(name.to_string()));
+            }
+            '}' => {
+               return Err(invalid(format!("模板中存在多余的}}：{template}")));
+            }
+            c => text.push(c),
+         }
+      }
+      if !text.is_empty() {
+         segments.push(Segment::Text(text));
+      }
+      Ok(Self {
+         source: template.to_string(),
+         segments,
+      })
+   }
+
+   /// 模板中使用的占位符
+   pub fn fields(&self) -> impl Iterator<Item = &str> {
+      self.segments.iter().filter_map(|segment| match segment {
+         Segment::Field(name) => Some(name.as_str()),
+         Segment::Text(_) => None,
+      })
+   }
+
+   /// 使用`value`提供的值替换占位符
+   pub fn render<F: Fn(&str) -> String>(&self, value: F) -> String {
+      self.render_with(str::to_string, value)
+   }
+
+   /// 使用`text`转换字面量文本、`value`提供的值替换占位符后拼接
+   pub(crate) fn render_with<T, F>(&self, text: T, value: F) -> String
+   where
+      T: Fn(&str) -> String,
+      F: Fn(&str) -> String,
+   {
+      self
+         .segments
+         .iter()
+         .map(|segment| match segment {
+            Segment::Text(literal) => text(literal),
+            Segment::Field(name) => value(name),
+         })
+         .collect()
+   }
+}
+
+/// 按空白拆分后的模板片段
+enum Token {
+   /// 空白
+   Space(String),
+   /// 不含占位符的文字，如分隔符`|`
+   Literal(String),
+   /// 包含占位符的单词，如`ISO{iso}`；紧邻占位符之前的标签（如`ISO {iso}`中的`ISO `）也属于该单词
+   Word(Vec<Segment>),
+}
+
+impl Template {
+   /// # 使用`value`提供的值替换占位符，省略值为空的部分
+   ///
+   /// 模板按空白拆分为单词，单词中的占位符都为空时省略整个单词（如`ISO{iso}`），
+   /// 并省略它与下一个保留的单词之间的分隔符，开头和结尾多余的分隔符也会被省略
+   ///
+   /// ```
+   /// use lumix_mark::Template;
+   ///
+   /// let fields = ["focal", "aperture", "shutter", "iso"];
+   /// let template = Template::parse("{focal} | {aperture} | {shutter}s | ISO {iso}", &fields)?;
+   /// let value = |name: &str| match name {
+   ///    "focal" => "35MM".to_string(),
+   ///    "shutter" => "1/250".to_string(),
+   ///    _ => String::new(),
+   /// };
+   /// assert_eq!(template.render_compact(value), "35MM | 1/250s");
+   /// # Ok::<(), lumix_mark::LumixMarkError>(())
+   /// ```
+   pub fn render_compact<F: Fn(&str) -> String>(&self, value: F) -> String {
+      self.render_compact_with(value, |_| None)
+   }
+
+   /// 与[`Template::render_compact`]相同，保留的单词前添加`prefix`为其中第一个占位符提供的字符
+   pub(crate) fn render_compact_with<F, P>(&self, value: F, prefix: P) -> String
+   where
+      F: Fn(&str) -> String,
+      P: Fn(&str) -> Option<char>,
+   {
+      let mut output = String::new();
+      let mut pending = String::new();
+      // 省略了单词后不再收集分隔符，直到下一个保留的单词
+      let mut skipped = false;
+      for token in self.tokens() {
+         let segments = match token {
+            Token::Space(text) | Token::Literal(text) => {
+               if !skipped {
+                  pending.push_str(&text);
+               }
+               continue;
+            }
+            Token::Word(segments) => segments,
+         };
+         let mut filled = false;
+         let mut word: String = segments
+            .iter()
+            .map(|segment| match segment {
+               Segment::Text(text) => text.clone(),
+               Segment::Field(name) => {
+                  let value = value(name);
+                  filled |= !value.is_empty();
+                  value
+               }
+            })
+            .collect();
+         let first = segments.iter().find_map(|segment| match segment {
+            Segment::Field(name) => Some(name),
+            Segment::Text(_) => None,
+         });
+         if let Some(c) = first.and_then(|name| prefix(name)) {
+            word.insert(0, c);
+         }
+         if filled {
+            output.push_str(&std::mem::take(&mut pending));
+            output.push_str(&word);
+         }
+         skipped = !filled;
+      }
+      if !skipped {
+         output.push_str(&pending);
+      }
+      output
+   }
+
+   /// 将模板拆分为空白、文字和包含占位符的单词
+   fn tokens(&self) -> Vec<Token> {
+      let mut tokens = Vec::new();
+      // 当前单词中相邻的文字和占位符
+      let mut word = Vec::new();
+      let flush = |tokens: &mut Vec<Token>, word: &mut Vec<Segment>| {
+         if word.is_empty() {
+            return;
+         }
+         let word = std::mem::take(word);
+         if word
+            .iter()
+            .any(|segment| matches!(segment, Segment::Field(_)))
+         {
+            tokens.push(Token::Word(word));
+         } else {
+            let text = word
+               .iter()
+               .filter_map(|segment| match segment {
+                  Segment::Text(text) => Some(text.as_str()),
+                  Segment::Field(_) => None,
+               })
+               .collect();
+            tokens.push(Token::Literal(text));
+         }
+      };
+      for segment in &self.segments {
+         let Segment::Text(text) = segment else {
+            word.push(segment.clone());
+            continue;
+         };
+         let mut rest = text.as_str();
+         while let Some(first) = rest.chars().next() {
+            let space = first.is_whitespace();
+            let end = rest
+               .find(|c: char| c.is_whitespace() != space)
+               .unwrap_or(rest.len());
+            if space {
+               flush(&mut tokens, &mut word);
+               tokens.push(Token::Space(rest[..end].to_string()));
+            } else {
+               word.push(Segment::Text(rest[..end].to_string()));
+            }
+            rest = &rest[end..];
+         }
+      }
+      flush(&mut tokens, &mut word);
+      // 包含字母或数字的文字后面紧跟单词时作为该单词的标签，如`ISO {iso}`
+      let mut merged: Vec<Token> = Vec::with_capacity(tokens.len());
+      for token in tokens {
+         let Token::Word(mut segments) = token else {
+            merged.push(token);
+            continue;
+         };
+         if let [.., Token::Literal(label), Token::Space(space)] = merged.as_slice()
+            && label.chars().any(char::is_alphanumeric)
+         {
+            segments.insert(0, Segment::Text(format!("{label}{space}")));
+            merged.truncate(merged.len() - 2);
+         }
+         merged.push(Token::Word(segments));
+      }
+      merged
+   }
+}
+
+impl Display for Template {
+   fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+      f.write_str(&self.source)
+   }
+}
@@ -0,0 +1,233 @@
+//! # 布局回归测试工具
+//!
+//! 在合成照片上按固定的EXIF绘制水印，并与提交在仓库中的参考PNG按感知差异比较，
+//! 不依赖真实照片和系统字体，结果在不同机器上保持一致
+//!
+//! ```
+//! use lumix_mark::MarkStyle;
+//! use lumix_mark::testing::{compare_images, fixed_exif, render_mark, synthetic_photo};
+//!
+//! let style = MarkStyle::default();
+//! let first = render_mark(synthetic_photo(240, 160), fixed_exif(), 0.14, &style).unwrap();
+//! let second = render_mark(synthetic_photo(240, 160), fixed_exif(), 0.14, &style).unwrap();
+//! assert_eq!(compare_images(&first, &second, 0.0).differing_pixels, 0);
+//! ```
+
+use crate::{Exif, MarkStyle, PipelineContext, Result, Stage, StandardStage};
+use image::{DynamicImage, Rgb, RgbImage};
+use std::env;
+use std::path::Path;
+
+/// 设置此环境变量时`assert_golden`写入新的参考图片而不比较
+pub const UPDATE_GOLDEN_ENV: &str = "LUMIX_MARK_UPDATE_GOLDEN";
+
+/// # 固定的合成照片
+///
+/// 6列4行的纯色色块，相同尺寸的结果完全相同，作为参考图片时压缩后的体积很小
+pub fn synthetic_photo(width: u32, height: u32) -> DynamicImage {
+   const COLORS: [[u8; 3]; 24] = [
+      [115, 82, 68],
+      [194, 150, 130],
+      [98, 122, 157],
+      [87, 108, 67],
+      [133, 128, 177],
+      [103, 189, 170],
+      [214, 126, 44],
+      [80, 91, 166],
+      [193, 90, 99],
+      [94, 60, 108],
+      [157, 188, 64],
+      [224, 163, 46],
+      [56, 61, 150],
+      [70, 148, 73],
+      [175, 54, 60],
+      [231, 199, 31],
+      [187, 86, 149],
+      [8, 133, 161],
+      [243, 243, 242],
+      [200, 200, 200],
+      [160, 160, 160],
+      [122, 122, 121],
+      [85, 85, 85],
+      [52, 52, 52],
+   ];
+   let photo = RgbImage::from_fn(width, height, |x, y| {
+      let column = (x * 6 / width.max(1)) as usize;
+      let row = (y * 4 / height.max(1)) as usize;
+      Rgb(COLORS[row * 6 + column])
+   });
+   DynamicImage::ImageRgb8(photo)
+}
+
+/// 固定的EXIF信息：Panasonic DC-S5M2，35MM F2.8 1/250 ISO400
+pub fn fixed_exif() -> Exif {
+   Exif {
+      make: "Panasonic".to_string(),
+      model: "DC-S5M2".to_string(),
+      shoot_time: "2024:05:01 17:30:12".to_string(),
+      exposure_time: "1/250".to_string(),
+      aperture: "F2.8".to_string(),
+      iso: "400".to_string(),
+      focal_length: "35MM".to_string(),
+      orientation: 1,
+      ..Exif::default()
+   }
+}
+
+/// # 在照片上按样式绘制水印，返回绘制后的画布
+///
+/// 跳过解码阶段，其余阶段与默认流水线相同（不编码）
+///
+/// # 参数
+/// * `photo` - 照片
+/// * `exif` - EXIF信息，机型显示名称和手动指定的EXIF按样式处理
+/// * `mark_ratio` - 设置水印高度比例 （水印高度 / 照片最短边）
+/// * `style` - 水印样式
+pub fn render_mark(
+   photo: DynamicImage,
+   mut exif: Exif,
+   mark_ratio: f32,
+   style: &MarkStyle,
+) -> Result<RgbImage> {
+   style.prepare_exif(&mut exif);
+   let mut context = PipelineContext::new(&[], mark_ratio, style);
+   context.photo = Some(photo);
+   context.exif = exif;
+   for stage in &StandardStage::ALL[1..5] {
+      stage.run(&mut context)?;
+   }
+   Ok(context.into_mark()?.canvas)
+}
+
+/// 感知差异的阈值
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DiffThreshold {
+   /// 单个像素的感知差异（0 - 1）超过此值时视为不同
+   pub pixel: f32,
+   /// 允许不同的像素占全部像素的比例
+   pub max_ratio: f64,
+}
+
+impl Default for DiffThreshold {
+   fn default() -> Self {
+      Self {
+         pixel: 0.1,
+         max_ratio: 0.001,
+      }
+   }
+}
+
+/// 两张图片的比较结果
+#[derive(Clone, Debug, PartialEq)]
+pub struct ImageDiff {
+   /// 实际图片的尺寸
+   pub actual_size: (u32, u32),
+   /// 参考图片的尺寸
+   pub expected_size: (u32, u32),
+   /// 感知差异超过阈值的像素数，尺寸不同时为全部像素
+   pub differing_pixels: u64,
+   /// 参与比较的像素数
+   pub total_pixels: u64,
+   /// 最大的单像素感知差异（0 - 1）
+   pub max_delta: f32,
+}
+
+impl ImageDiff {
+   /// 不同像素的比例
+   pub fn ratio(&self) -> f64 {
+      self.differing_pixels as f64 / self.total_pixels.max(1) as f64
+   }
+
+   /// 尺寸相同且不同像素的比例不超过阈值
+   pub fn within(&self, threshold: &DiffThreshold) -> bool {
+      self.actual_size == self.expected_size && self.ratio() <= threshold.max_ratio
+   }
+}
+
+/// # 按感知差异比较两张图片
+///
+/// 单像素的差异为YIQ色彩空间中按人眼敏感度加权的距离，归一化到0 - 1，
+/// 抗锯齿边缘的轻微偏差远小于文字或Logo移位造成的差异
+pub fn compare_images(
+   actual: &RgbImage,
+   expected: &RgbImage,
+   pixel_threshold: f32,
+) -> ImageDiff {
+   let actual_size = actual.dimensions();
+   let expected_size = expected.dimensions();
+   if actual_size != expected_size {
+      let total_pixels = actual_size.0 as u64 * actual_size.1 as u64;
+      return ImageDiff {
+         actual_size,
+         expected_size,
+         differing_pixels: total_pixels,
+         total_pixels,
+         max_delta: 1.0,
+      };
+   }
+   let mut differing_pixels = 0;
+   let mut max_delta: f32 = 0.0;
+   for (a, e) in actual.pixels().zip(expected.pixels()) {
+      let delta = perceptual_delta(a, e);
+      max_delta = max_delta.max(delta);
+      if delta > pixel_threshold {
+         differing_pixels += 1;
+      }
+   }
+   ImageDiff {
+      actual_size,
+      expected_size,
+      differing_pixels,
+      total_pixels: actual.pixels().len() as u64,
+      max_delta,
+   }
+}
+
+/// YIQ色彩空间中的加权距离，归一化到0 - 1
+fn perceptual_delta(a: &Rgb<u8>, b: &Rgb<u8>) -> f32 {
+   let [r, g, b] = [0, 1, 2].map(|i| a.0[i] as f32 - b.0[i] as f32);
+   let y = r * 0.298_895_3 + g * 0.586_622_5 + b * 0.114_482_23;
+   let i = r * 0.595_977_99 - g * 0.274_176_1 - b * 0.321_801_9;
+   let q = r * 0.211_470_17 - g * 0.522_617_1 + b * 0.311_146_94;
+   // 黑与白之间的距离为最大值
+   ((0.5053 * y * y + 0.299 * i * i + 0.1957 * q * q) / 35215.0).sqrt()
+}
+
+/// # 与参考图片比较，超出阈值时panic
+///
+/// 设置环境变量`LUMIX_MARK_UPDATE_GOLDEN`时写入新的参考图片；
+/// 不一致时将实际结果保存在参考图片旁的`*.actual.png`，便于对比
+pub fn assert_golden<P: AsRef<Path>>(
+   actual: &RgbImage,
+   golden: P,
+   threshold: DiffThreshold,
+) {
+   let golden = golden.as_ref();
+   if env::var_os(UPDATE_GOLDEN_ENV).is_some() {
+      if let Some(parent) = golden.parent() {
+         std::fs::create_dir_all(parent).expect("创建参考图片文件夹失败");
+      }
+      actual.save(golden).expect("写入参考图片失败");
+      return;
+   }
+   let expected = match image::open(golden) {
+      Ok(expected) => expected.to_rgb8(),
+      Err(err) => panic!(
+         "读取参考图片失败：{err}, path:{golden:?}，设置{UPDATE_GOLDEN_ENV}=1生成参考图片"
+      ),
+   };
+   let diff = compare_images(actual, &expected, threshold.pixel);
+   if !diff.within(&threshold) {
+      let actual_path = golden.with_extension("actual.png");
+      let _ = actual.save(&actual_path);
+      panic!(
+         "与参考图片不一致：{golden:?}，尺寸{:?}/{:?}，不同像素{}/{}（{:.4}%），最大差异{:.3}，实际结果：{actual_path:?}",
+         diff.actual_size,
+         diff.expected_size,
+         diff.differing_pixels,
+         diff.total_pixels,
+         diff.ratio() * 100.0,
+         diff.max_delta
+      );
+   }
+}
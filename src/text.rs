@@ -0,0 +1,157 @@
+use crate::font::FontStack;
+use crate::{MarkPosition, TextDirection};
+
+/// 竖排时空白字符占用的长度，相对文字大小
+const VERTICAL_SPACE_RATIO: f32 = 0.5;
+
+/// # 文字在横向水印条中的排列方式
+///
+/// 左右两侧的水印条先旋转为横向再绘制，竖排文字需要在横向水印条中逐个旋转字符
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Flow {
+   /// 从左向右，其中从右向左书写的片段倒序显示
+   Ltr,
+   /// 从右向左，其中连续的拉丁字母和数字仍然从左向右显示
+   Rtl,
+   /// # 竖排
+   ///
+   /// 字符逐个旋转绘制，水印条旋转回原位置后字符保持正立、从上往下排列；
+   /// `clockwise`为`true`时字符顺时针旋转且在横向水印条中从右向左排列（左侧水印条）
+   Vertical { clockwise: bool },
+}
+
+impl TextDirection {
+   /// # 文字的排列方式
+   ///
+   /// # 参数
+   /// * `text` - 绘制的文字，`Auto`时按第一个有方向的字符判断从左向右或从右向左
+   /// * `position` - 水印条所在的边，只有左右两侧的水印条可以竖排
+   pub(crate) fn flow(self, text: &str, position: MarkPosition) -> Flow {
+      match (self, position) {
+         (TextDirection::Ltr, _) => Flow::Ltr,
+         (TextDirection::Rtl, _) => Flow::Rtl,
+         (TextDirection::Vertical, MarkPosition::Left) => {
+            Flow::Vertical { clockwise: true }
+         }
+         (TextDirection::Vertical, MarkPosition::Right) => {
+            Flow::Vertical { clockwise: false }
+         }
+         (TextDirection::Auto | TextDirection::Vertical, _) => {
+            match text.chars().find_map(strong_direction) {
+               Some(true) => Flow::Rtl,
+               _ => Flow::Ltr,
+            }
+         }
+      }
+   }
+}
+
+/// # 按排列方式排好的一段文字
+///
+/// 横排时文字已转换为从左到右的显示顺序，竖排时保持原顺序，由绘制时逐个旋转字符
+pub(crate) struct TextRun<'f, 'a> {
+   /// 绘制文字的字体栈
+   pub(crate) fonts: &'f FontStack<'a>,
+   /// 文字大小
+   pub(crate) size: f32,
+   /// 排列方式
+   pub(crate) flow: Flow,
+   /// 显示顺序的文字
+   pub(crate) text: String,
+}
+
+impl<'f, 'a> TextRun<'f, 'a> {
+   pub(crate) fn new(
+      fonts: &'f FontStack<'a>,
+      size: f32,
+      text: &str,
+      flow: Flow,
+   ) -> Self {
+      let text = match flow {
+         Flow::Ltr => visual_order(text, false),
+         Flow::Rtl => visual_order(text, true),
+         Flow::Vertical { .. } => text.to_string(),
+      };
+      Self {
+         fonts,
+         size,
+         flow,
+         text,
+      }
+   }
+
+   /// 文字在横向水印条中的宽度和高度
+   pub(crate) fn size(&self) -> (u32, u32) {
+      match self.flow {
+         Flow::Ltr | Flow::Rtl => self.fonts.text_size(self.size, &self.text),
+         Flow::Vertical { .. } => {
+            let length: f32 = self.text.chars().map(|c| self.advance(c)).sum();
+            (length.ceil() as u32, self.size.ceil() as u32)
+         }
+      }
+   }
+
+   /// 竖排时字符沿水印条占用的长度
+   pub(crate) fn advance(&self, c: char) -> f32 {
+      match c.is_whitespace() {
+         true => self.size * VERTICAL_SPACE_RATIO,
+         false => self.size,
+      }
+   }
+}
+
+/// 字符的书写方向，从右向左为`true`，空格、标点等没有方向的字符为`None`
+fn strong_direction(c: char) -> Option<bool> {
+   // 希伯来文、阿拉伯文等从右向左书写的文字区段
+   let rtl = matches!(
+      c as u32,
+      0x0590..=0x08FF
+         | 0xFB1D..=0xFDFF
+         | 0xFE70..=0xFEFF
+         | 0x10800..=0x10FFF
+         | 0x1E800..=0x1EFFF
+   );
+   match rtl {
+      true => Some(true),
+      false => c.is_alphanumeric().then_some(false),
+   }
+}
+
+/// # 将文字转换为从左到右的显示顺序
+///
+/// 简化的双向文字算法：没有方向的字符前后方向相同时跟随前后的字符，否则跟随整段文字的方向；
+/// 从右向左的片段内部倒序，整段从右向左时片段之间也倒序。不进行阿拉伯文的字形连接
+fn visual_order(text: &str, rtl: bool) -> String {
+   let chars: Vec<char> = text.chars().collect();
+   let strong: Vec<Option<bool>> = chars.iter().map(|&c| strong_direction(c)).collect();
+   // 全部为从左向右的文字时不需要调整
+   if !rtl && !strong.contains(&Some(true)) {
+      return text.to_string();
+   }
+   let resolved: Vec<bool> = (0..chars.len())
+      .map(|i| {
+         strong[i].unwrap_or_else(|| {
+            let before = strong[..i].iter().rev().find_map(|d| *d).unwrap_or(rtl);
+            let after = strong[i + 1..].iter().find_map(|d| *d).unwrap_or(rtl);
+            if before == after { before } else { rtl }
+         })
+      })
+      .collect();
+   let mut runs: Vec<String> = Vec::new();
+   let mut start = 0;
+   for end in 1..=chars.len() {
+      if end < chars.len() && resolved[end] == resolved[start] {
+         continue;
+      }
+      let run = &chars[start..end];
+      runs.push(match resolved[start] {
+         true => run.iter().rev().collect(),
+         false => run.iter().collect(),
+      });
+      start = end;
+   }
+   if rtl {
+      runs.reverse();
+   }
+   runs.concat()
+}
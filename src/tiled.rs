@@ -0,0 +1,190 @@
+use crate::output::{SrgbTransform, jpeg_dimensions, jpeg_encoder, srgb_transform};
+use crate::{
+   Fill, Frame, IccPolicy, LumixMark, LumixMarkError, MarkPosition, MarkStyle,
+   OutputFormat, Result, SaveOptions, fit_file_size,
+};
+use image::{Rgb, RgbImage};
+use jpeg_encoder::{ImageBuffer, JpegColorType, rgb_to_ycbcr};
+use std::cell::RefCell;
+
+/// # 分块编码时不放入画布的照片
+///
+/// 画布中只保留照片上方和下方的行（水印条和边框），编码时逐行拼接照片、两侧的边框和画布，
+/// 超大的全景图不需要再复制一份完整的画布
+#[derive(Clone, Debug)]
+pub(crate) struct TiledPhoto {
+   photo: RgbImage,
+   /// 照片在完整画布中的左上角坐标
+   origin: (u32, u32),
+   /// 照片两侧边框的颜色
+   border: Rgb<u8>,
+}
+
+impl TiledPhoto {
+   /// # 检查样式和输出格式能否分块编码，返回照片两侧边框的颜色
+   ///
+   /// 只支持基线JPEG输出、纯色背景、没有投影，以及位于顶部或底部的水印条（含拍立得边框），不支持时返回原因
+   pub(crate) fn border(
+      style: &MarkStyle,
+      format: OutputFormat,
+      options: &SaveOptions,
+   ) -> std::result::Result<Rgb<u8>, &'static str> {
+      if format != OutputFormat::Jpeg {
+         return Err("只支持JPEG输出");
+      }
+      // 渐进式JPEG要保存全部系数后才能编码，逐行读取照片不能减少内存
+      if options.progressive {
+         return Err("不支持渐进式JPEG");
+      }
+      if style.frame == Frame::Overlay {
+         return Err("不支持叠加水印");
+      }
+      if style.shadow.is_some() {
+         return Err("不支持照片投影");
+      }
+      if let MarkPosition::Left | MarkPosition::Right = style.position {
+         return Err("只支持顶部或底部的水印条");
+      }
+      match style.background {
+         Fill::Solid(color) => Ok(color.into()),
+         Fill::Gradient { .. } => Err("不支持渐变背景"),
+         Fill::Auto { .. } => Err("不支持按主色调的背景"),
+         Fill::Blur { .. } => Err("不支持模糊背景"),
+      }
+   }
+
+   pub(crate) fn new(photo: RgbImage, origin: (u32, u32), border: Rgb<u8>) -> Self {
+      Self {
+         photo,
+         origin,
+         border,
+      }
+   }
+
+   /// 不放入画布的照片
+   pub(crate) fn photo(&self) -> &RgbImage {
+      &self.photo
+   }
+
+   /// 完整画布中的第`y`行在只保留照片以外的行的画布中的位置
+   pub(crate) fn canvas_y(&self, y: u32) -> u32 {
+      match y >= self.origin.1 + self.photo.height() {
+         true => y - self.photo.height(),
+         false => y,
+      }
+   }
+
+   /// 读取完整画布中第`y`行的像素
+   fn read_row(&self, canvas: &RgbImage, y: u32, row: &mut [u8]) {
+      let (left, top) = self.origin;
+      let photo_rows = top..top + self.photo.height();
+      if !photo_rows.contains(&y) {
+         let stride = canvas.width() as usize * 3;
+         let start = self.canvas_y(y) as usize * stride;
+         row.copy_from_slice(&canvas.as_raw()[start..start + stride]);
+         return;
+      }
+      for pixel in row.chunks_exact_mut(3) {
+         pixel.copy_from_slice(&self.border.0);
+      }
+      let stride = self.photo.width() as usize * 3;
+      let start = (y - top) as usize * stride;
+      let left = left as usize * 3;
+      row[left..left + stride]
+         .copy_from_slice(&self.photo.as_raw()[start..start + stride]);
+   }
+}
+
+/// # 逐行提供给JPEG编码器的完整画布
+///
+/// 编码器读取一行时拼接照片、两侧的边框和画布，需要时转换为sRGB；
+/// 读取接口不能返回错误，转换失败时记录第一个错误，编码完成后返回
+struct TiledRows<'a> {
+   tiled: &'a TiledPhoto,
+   canvas: &'a RgbImage,
+   transform: Option<&'a SrgbTransform>,
+   size: (u16, u16),
+   /// 读取的一行像素和转换为sRGB前的像素
+   buffers: RefCell<(Vec<u8>, Vec<u8>)>,
+   error: RefCell<Option<LumixMarkError>>,
+}
+
+impl ImageBuffer for &TiledRows<'_> {
+   fn get_jpeg_color_type(&self) -> JpegColorType {
+      JpegColorType::Ycbcr
+   }
+
+   fn width(&self) -> u16 {
+      self.size.0
+   }
+
+   fn height(&self) -> u16 {
+      self.size.1
+   }
+
+   fn fill_buffers(&self, y: u16, buffers: &mut [Vec<u8>; 4]) {
+      let (row, source) = &mut *self.buffers.borrow_mut();
+      row.resize(self.size.0 as usize * 3, 0);
+      match self.transform {
+         Some(transform) => {
+            source.resize(row.len(), 0);
+            self.tiled.read_row(self.canvas, y.into(), source);
+            if let Err(err) = transform.convert(source, row) {
+               self.error.borrow_mut().get_or_insert(err);
+            }
+         }
+         None => self.tiled.read_row(self.canvas, y.into(), row),
+      }
+      for pixel in row.chunks_exact(3) {
+         let (luma, cb, cr) = rgb_to_ycbcr(pixel[0], pixel[1], pixel[2]);
+         buffers[0].push(luma);
+         buffers[1].push(cb);
+         buffers[2].push(cr);
+      }
+   }
+}
+
+impl LumixMark {
+   /// # 逐行拼接照片和画布编码为JPEG
+   ///
+   /// 基线编码每次只转换一行MCU的像素，需要转换为sRGB时逐行转换
+   pub(crate) fn encode_tiled(
+      &self,
+      tiled: &TiledPhoto,
+      format: OutputFormat,
+      options: &SaveOptions,
+   ) -> Result<Vec<u8>> {
+      if format != OutputFormat::Jpeg {
+         return Err(LumixMarkError::InvalidConfig(
+            "分块编码的画布只能编码为JPEG".to_string(),
+         ));
+      }
+      let icc_profile = self.metadata.icc_profile.as_deref();
+      let transform = match (options.icc, icc_profile) {
+         (IccPolicy::Srgb, Some(icc)) => Some(srgb_transform(icc)?),
+         _ => None,
+      };
+      let embed = icc_profile.filter(|_| transform.is_none());
+      let size = jpeg_dimensions(self.width, self.height)?;
+      let encode = |quality| {
+         let rows = TiledRows {
+            tiled,
+            canvas: &self.canvas,
+            transform: transform.as_ref(),
+            size,
+            buffers: RefCell::default(),
+            error: RefCell::default(),
+         };
+         let mut bytes = Vec::new();
+         jpeg_encoder(&mut bytes, quality, options, embed)?.encode_image(&rows)?;
+         if let Some(err) = rows.error.into_inner() {
+            return Err(err);
+         }
+         self.with_metadata(bytes, options)
+      };
+      match options.max_size {
+         Some(max_size) => fit_file_size(encode, options.quality, max_size),
+         None => encode(options.quality),
+      }
+   }
+}
@@ -0,0 +1,180 @@
+use crate::metadata::tiff_icc_profile;
+use crate::{Empty, LumixMarkCli, LumixMarkError, Metadata, Provenance};
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::png::PngEncoder;
+use image::codecs::tiff::TiffEncoder;
+use image::codecs::webp::WebPEncoder;
+use image::metadata::Orientation;
+use image::{ImageEncoder, ImageError, ImageFormat, guess_format, load_from_memory};
+use std::ffi::OsString;
+use std::fs;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use tracing::{error, info};
+
+/// 默认文件名模板添加的前缀，去除水印后还原为原图的文件名
+const MARK_PREFIX: &str = "mark_";
+
+impl LumixMarkCli {
+   /// # 去除lumix_mark添加的水印
+   ///
+   /// 按输出文件中记录的照片区域裁去水印条和边框，保留了EXIF时将照片旋转回原图的方向并恢复EXIF方向，
+   /// 保存到输出文件夹，文件名去掉`mark_`前缀；没有来源信息的图片和叠加水印会跳过。
+   /// 缩小过的照片无法恢复原始尺寸，JPEG会重新编码
+   ///
+   /// # 参数
+   /// * `images` - 多张图片地址或者文件夹，文件夹中查找JPEG/PNG/WebP/TIFF图片
+   pub fn unmark(&self, images: &[PathBuf]) -> Empty {
+      let mut files = Vec::new();
+      for path in images {
+         collect_files(path, &mut files)?;
+      }
+      if files.is_empty() {
+         return Err(LumixMarkError::InvalidConfig(
+            "没有找到需要去除水印的JPEG/PNG/WebP/TIFF图片".to_string(),
+         ));
+      }
+      fs::create_dir_all(&self.target_path)?;
+      let mut first_error = None;
+      for path in &files {
+         if let Err(err) = self.unmark_file(path) {
+            error!("去除水印失败：{}, 图片地址：{:?}", err, path);
+            first_error.get_or_insert(err);
+         }
+      }
+      first_error.map_or(Ok(()), Err)
+   }
+
+   fn unmark_file(&self, path: &Path) -> Empty {
+      let bytes = fs::read(path)?;
+      let Some(provenance) = Provenance::from_bytes(&bytes) else {
+         info!("没有lumix_mark的来源信息，跳过：{:?}", path);
+         return Ok(());
+      };
+      let Some((left, top, right, bottom)) = provenance.photo_area else {
+         info!("叠加水印绘制在照片上，无法去除，跳过：{:?}", path);
+         return Ok(());
+      };
+      let image = load_from_memory(&bytes)?;
+      if left >= right
+         || top >= bottom
+         || right > image.width()
+         || bottom > image.height()
+      {
+         return Err(LumixMarkError::InvalidConfig(format!(
+            "记录的照片区域({left}, {top}) - ({right}, {bottom})超出图片范围{}x{}",
+            image.width(),
+            image.height()
+         )));
+      }
+      let mut photo = image.crop_imm(left, top, right - left, bottom - top);
+      let mut metadata = Metadata::from_bytes(&bytes);
+      metadata.remove_provenance();
+      // 没有EXIF时无法记录方向，保持正向的像素
+      if metadata.exif.is_some() {
+         photo.apply_orientation(inverse(provenance.orientation));
+      }
+      let target = self.target_path.join(original_name(path));
+      let Some(target) = self.overwrite.resolve(target.clone()) else {
+         info!("输出文件已存在，跳过：{:?}", target);
+         return Ok(());
+      };
+      let icc_profile = metadata.icc_profile.clone();
+      let mut output = Vec::new();
+      match guess_format(&bytes)? {
+         ImageFormat::Jpeg => {
+            let mut encoder = JpegEncoder::new_with_quality(&mut output, self.quality);
+            if let Some(icc) = icc_profile {
+               encoder
+                  .set_icc_profile(icc)
+                  .map_err(ImageError::Unsupported)?;
+            }
+            encoder.encode_image(&photo.to_rgb8())?;
+            if !metadata.is_empty() {
+               output = metadata
+                  .embed_into_jpeg_with_orientation(output, provenance.orientation)?;
+            }
+         }
+         ImageFormat::Png => {
+            let mut encoder = PngEncoder::new(&mut output);
+            if let Some(icc) = icc_profile {
+               encoder
+                  .set_icc_profile(icc)
+                  .map_err(ImageError::Unsupported)?;
+            }
+            photo.write_with_encoder(encoder)?;
+         }
+         ImageFormat::WebP => {
+            let mut encoder = WebPEncoder::new_lossless(&mut output);
+            if let Some(icc) = icc_profile {
+               encoder
+                  .set_icc_profile(icc)
+                  .map_err(ImageError::Unsupported)?;
+            }
+            photo.to_rgb8().write_with_encoder(encoder)?;
+         }
+         ImageFormat::Tiff => {
+            let mut encoder = TiffEncoder::new(Cursor::new(&mut output));
+            if let Some(icc) = icc_profile.or_else(|| tiff_icc_profile(&bytes)) {
+               encoder
+                  .set_icc_profile(icc)
+                  .map_err(ImageError::Unsupported)?;
+            }
+            photo.write_with_encoder(encoder)?;
+         }
+         format => {
+            return Err(LumixMarkError::InvalidConfig(format!(
+               "不支持去除{format:?}图片的水印"
+            )));
+         }
+      }
+      fs::write(&target, output)?;
+      info!("已去除水印：{:?} -> {:?}", path, target);
+      Ok(())
+   }
+}
+
+/// 直接指定的文件都会检查，文件夹中递归查找JPEG/PNG/WebP/TIFF图片
+fn collect_files(path: &Path, files: &mut Vec<PathBuf>) -> Empty {
+   if path.is_file() {
+      files.push(path.to_path_buf());
+   } else if path.is_dir() {
+      for entry in fs::read_dir(path)? {
+         let path = entry?.path();
+         let is_marked_format = path.extension().is_some_and(|extension| {
+            let ext = extension.to_string_lossy().to_lowercase();
+            matches!(
+               ext.as_str(),
+               "jpg" | "jpeg" | "png" | "webp" | "tif" | "tiff"
+            )
+         });
+         if path.is_dir() || is_marked_format {
+            collect_files(&path, files)?;
+         }
+      }
+   }
+   Ok(())
+}
+
+/// 去掉文件名中的`mark_`前缀
+fn original_name(path: &Path) -> OsString {
+   let file_name = path.file_name().unwrap_or_default();
+   match file_name.to_string_lossy().strip_prefix(MARK_PREFIX) {
+      Some(name) if !name.is_empty() => name.into(),
+      _ => file_name.to_os_string(),
+   }
+}
+
+/// 将正向的照片变换回原图存储方向的变换
+fn inverse(orientation: u16) -> Orientation {
+   let orientation = u8::try_from(orientation)
+      .ok()
+      .and_then(Orientation::from_exif)
+      .unwrap_or(Orientation::NoTransforms);
+   match orientation {
+      Orientation::Rotate90 => Orientation::Rotate270,
+      Orientation::Rotate270 => Orientation::Rotate90,
+      // 其余变换的逆变换为自身
+      orientation => orientation,
+   }
+}
@@ -0,0 +1,483 @@
+use crate::date::ShootTime;
+use crate::output::numbered_path;
+use crate::sink::relative_name;
+use crate::{Empty, LumixMarkError, OutputSink, OverwritePolicy, Result};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::env;
+use std::fmt::{self, Display, Formatter};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use ureq::{Agent, AgentBuilder, Request};
+
+/// # 上传地址
+///
+/// `s3://bucket/prefix`为S3兼容的对象存储，`dav://host/path`和`davs://host/path`为WebDAV
+/// （分别使用HTTP和HTTPS）；路径部分为上传到的前缀或文件夹，可以为空
+///
+/// ```
+/// use lumix_mark::UploadTarget;
+///
+/// let s3: UploadTarget = "s3://gallery/2024/client/".parse().unwrap();
+/// assert_eq!(s3, UploadTarget::S3 { bucket: "gallery".into(), prefix: "2024/client".into() });
+/// let dav: UploadTarget = "davs://cloud.example.com/remote.php/dav/files/me".parse().unwrap();
+/// assert_eq!(dav.to_string(), "davs://cloud.example.com/remote.php/dav/files/me");
+/// assert!("ftp://example.com".parse::<UploadTarget>().is_err());
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum UploadTarget {
+   /// S3兼容的对象存储
+   S3 {
+      /// 存储桶名称
+      bucket: String,
+      /// 对象键的前缀，不包含首尾的`/`
+      prefix: String,
+   },
+   /// WebDAV服务器
+   WebDav {
+      /// 是否使用HTTPS
+      secure: bool,
+      /// 主机名（可以包含端口）和路径，不包含末尾的`/`
+      location: String,
+   },
+}
+
+impl UploadTarget {
+   /// 解析上传地址，无效时返回错误
+   pub fn parse(url: &str) -> Result<Self> {
+      url.parse()
+   }
+}
+
+impl FromStr for UploadTarget {
+   type Err = LumixMarkError;
+
+   fn from_str(url: &str) -> Result<Self> {
+      let invalid = || {
+         LumixMarkError::InvalidConfig(format!(
+            "上传地址{url}无效，应为s3://bucket/prefix、dav://host/path或davs://host/path"
+         ))
+      };
+      let (scheme, rest) = url.split_once("://").ok_or_else(invalid)?;
+      let rest = rest.trim_end_matches('/');
+      let target = match scheme.to_ascii_lowercase().as_str() {
+         "s3" => {
+            let (bucket, prefix) = rest.split_once('/').unwrap_or((rest, ""));
+            UploadTarget::S3 {
+               bucket: bucket.to_string(),
+               prefix: prefix.trim_matches('/').to_string(),
+            }
+         }
+         "dav" | "davs" => UploadTarget::WebDav {
+            secure: scheme.eq_ignore_ascii_case("davs"),
+            location: rest.to_string(),
+         },
+         _ => return Err(invalid()),
+      };
+      match &target {
+         UploadTarget::S3 { bucket, .. } if bucket.is_empty() => Err(invalid()),
+         UploadTarget::WebDav { location, .. } if location.is_empty() => Err(invalid()),
+         _ => Ok(target),
+      }
+   }
+}
+
+impl Display for UploadTarget {
+   fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+      match self {
+         UploadTarget::S3 { bucket, prefix } if prefix.is_empty() => {
+            write!(f, "s3://{bucket}")
+         }
+         UploadTarget::S3 { bucket, prefix } => write!(f, "s3://{bucket}/{prefix}"),
+         UploadTarget::WebDav { secure, location } => {
+            let scheme = if *secure { "davs" } else { "dav" };
+            write!(f, "{scheme}://{location}")
+         }
+      }
+   }
+}
+
+/// # 将输出图片上传到S3兼容的对象存储或WebDAV
+///
+/// 对象键（或文件路径）为输出路径相对输出文件夹的部分，输出路径表示为上传地址拼接该部分
+/// （如`s3://gallery/2024/mark_a.jpg`）。凭据从环境变量读取：
+/// * S3 - `AWS_ACCESS_KEY_ID`、`AWS_SECRET_ACCESS_KEY`和可选的`AWS_SESSION_TOKEN`，
+///   区域为`AWS_REGION`（默认`us-east-1`），MinIO、R2等使用`AWS_ENDPOINT_URL`指定服务地址
+/// * WebDAV - `LUMIX_WEBDAV_USER`和`LUMIX_WEBDAV_PASSWORD`，未设置时不进行身份验证
+pub struct UploadSink {
+   target: UploadTarget,
+   base: PathBuf,
+   remote: Remote,
+   agent: Agent,
+   /// 已预留的对象键，并行处理的图片不会上传到同一位置
+   claimed: Mutex<HashSet<String>>,
+}
+
+enum Remote {
+   S3(S3Bucket),
+   WebDav(WebDav),
+}
+
+struct S3Bucket {
+   /// 服务地址，不包含末尾的`/`
+   endpoint: String,
+   /// 服务地址中的主机名和端口，参与签名
+   host: String,
+   bucket: String,
+   prefix: String,
+   region: String,
+   access_key: String,
+   secret_key: String,
+   session_token: Option<String>,
+}
+
+struct WebDav {
+   /// 上传文件夹的地址，不包含末尾的`/`
+   url: String,
+   authorization: Option<String>,
+   /// 已确认存在的文件夹
+   collections: Mutex<HashSet<String>>,
+}
+
+impl UploadSink {
+   /// # 创建
+   ///
+   /// 读取环境变量中的凭据，S3缺少访问密钥时返回错误
+   ///
+   /// # 参数
+   /// * `target` - 上传地址
+   /// * `base` - 输出文件夹，对象键为输出路径相对该文件夹的部分
+   pub fn new<B: Into<PathBuf>>(target: UploadTarget, base: B) -> Result<Self> {
+      let remote = match &target {
+         UploadTarget::S3 { bucket, prefix } => {
+            let credential = |name: &str| {
+               env::var(name).map_err(|_| {
+                  LumixMarkError::InvalidConfig(format!(
+                     "上传到S3需要设置环境变量{name}"
+                  ))
+               })
+            };
+            let region = env::var("AWS_REGION")
+               .or_else(|_| env::var("AWS_DEFAULT_REGION"))
+               .unwrap_or_else(|_| "us-east-1".to_string());
+            let endpoint = env::var("AWS_ENDPOINT_URL")
+               .unwrap_or_else(|_| format!("https://s3.{region}.amazonaws.com"))
+               .trim_end_matches('/')
+               .to_string();
+            let host = endpoint
+               .split_once("://")
+               .map_or(endpoint.as_str(), |(_, rest)| rest)
+               .to_string();
+            Remote::S3(S3Bucket {
+               endpoint,
+               host,
+               bucket: bucket.clone(),
+               prefix: prefix.clone(),
+               region,
+               access_key: credential("AWS_ACCESS_KEY_ID")?,
+               secret_key: credential("AWS_SECRET_ACCESS_KEY")?,
+               session_token: env::var("AWS_SESSION_TOKEN").ok(),
+            })
+         }
+         UploadTarget::WebDav { secure, location } => {
+            let scheme = if *secure { "https" } else { "http" };
+            let authorization = env::var("LUMIX_WEBDAV_USER").ok().map(|user| {
+               let password = env::var("LUMIX_WEBDAV_PASSWORD").unwrap_or_default();
+               format!("Basic {}", BASE64.encode(format!("{user}:{password}")))
+            });
+            Remote::WebDav(WebDav {
+               url: format!("{scheme}://{location}"),
+               authorization,
+               collections: Mutex::new(HashSet::new()),
+            })
+         }
+      };
+      let agent = AgentBuilder::new()
+         .timeout_connect(Duration::from_secs(10))
+         .user_agent(concat!("lumix_mark/", env!("CARGO_PKG_VERSION")))
+         .build();
+      Ok(Self {
+         target,
+         base: base.into(),
+         remote,
+         agent,
+         claimed: Mutex::new(HashSet::new()),
+      })
+   }
+
+   /// 输出路径对应的对象键
+   fn key(&self, target: &Path) -> Result<String> {
+      let root = format!("{}/", self.target);
+      let target = target.to_string_lossy();
+      target
+         .strip_prefix(&root)
+         .map(str::to_string)
+         .ok_or_else(|| {
+            LumixMarkError::InvalidConfig(format!("输出路径不在上传地址中：{target}"))
+         })
+   }
+
+   /// 对象是否已存在
+   fn exists(&self, key: &str) -> Result<bool> {
+      let request = match &self.remote {
+         Remote::S3(bucket) => bucket.request(&self.agent, "HEAD", key, &[]),
+         Remote::WebDav(dav) => dav.request(&self.agent, "HEAD", key),
+      };
+      match request.call() {
+         Ok(_) => Ok(true),
+         Err(ureq::Error::Status(404, _)) => Ok(false),
+         Err(err) => Err(upload_error(format!("查询{key}"), err)),
+      }
+   }
+
+   /// 上传对象，WebDAV先创建所在的文件夹
+   fn put(&self, key: &str, bytes: &[u8]) -> Empty {
+      let request = match &self.remote {
+         Remote::S3(bucket) => bucket.request(&self.agent, "PUT", key, bytes),
+         Remote::WebDav(dav) => {
+            dav.create_parents(&self.agent, key)?;
+            dav.request(&self.agent, "PUT", key)
+         }
+      };
+      request
+         .set("Content-Type", content_type(key))
+         .send_bytes(bytes)
+         .map_err(|err| upload_error(format!("上传{key}"), err))?;
+      Ok(())
+   }
+
+   fn lock(&self) -> MutexGuard<'_, HashSet<String>> {
+      self.claimed.lock().unwrap_or_else(|err| err.into_inner())
+   }
+}
+
+impl OutputSink for UploadSink {
+   /// 先在本次运行中预留对象键，再查询服务器上是否已存在，查询期间不阻塞其他图片
+   fn claim(
+      &self,
+      target: PathBuf,
+      overwrite: OverwritePolicy,
+   ) -> Result<Option<PathBuf>> {
+      let key = relative_name(&target, &self.base);
+      let (parent, name) = match key.rsplit_once('/') {
+         Some((parent, name)) => (format!("{parent}/"), name),
+         None => (String::new(), key.as_str()),
+      };
+      let mut index = 0;
+      loop {
+         let numbered = numbered_path(Path::new(name), index);
+         let candidate = format!("{parent}{}", numbered.to_string_lossy());
+         index += 1;
+         if !self.lock().insert(candidate.clone()) {
+            if overwrite == OverwritePolicy::Skip {
+               return Ok(None);
+            }
+            continue;
+         }
+         let available = match overwrite {
+            OverwritePolicy::Replace => Ok(true),
+            OverwritePolicy::Skip | OverwritePolicy::Rename => {
+               self.exists(&candidate).map(|exists| !exists)
+            }
+         };
+         match available {
+            Ok(true) => {
+               return Ok(Some(PathBuf::from(format!("{}/{candidate}", self.target))));
+            }
+            Ok(false) => {
+               self.lock().remove(&candidate);
+               if overwrite == OverwritePolicy::Skip {
+                  return Ok(None);
+               }
+            }
+            Err(err) => {
+               self.lock().remove(&candidate);
+               return Err(err);
+            }
+         }
+      }
+   }
+
+   fn write(&self, target: &Path, bytes: &[u8]) -> Empty {
+      self.put(&self.key(target)?, bytes)
+   }
+
+   fn release(&self, target: &Path, _overwrite: OverwritePolicy) {
+      if let Ok(key) = self.key(target) {
+         self.lock().remove(&key);
+      }
+   }
+}
+
+impl S3Bucket {
+   /// # 按AWS Signature Version 4签名的请求
+   ///
+   /// 使用路径形式的地址（`endpoint/bucket/key`），兼容不支持虚拟主机形式的服务
+   fn request(
+      &self,
+      agent: &Agent,
+      method: &str,
+      key: &str,
+      payload: &[u8],
+   ) -> Request {
+      let key = match self.prefix.is_empty() {
+         true => key.to_string(),
+         false => format!("{}/{key}", self.prefix),
+      };
+      let path = format!("/{}/{}", self.bucket, encode_path(&key));
+      let payload_hash = hex(&Sha256::digest(payload));
+      let now = SystemTime::now()
+         .duration_since(UNIX_EPOCH)
+         .map_or(0, |elapsed| elapsed.as_secs() as i64);
+      let now = ShootTime::from_unix(now);
+      let date = format!("{:04}{:02}{:02}", now.year, now.month, now.day);
+      let timestamp =
+         format!("{date}T{:02}{:02}{:02}Z", now.hour, now.minute, now.second);
+      // 参与签名的请求头，按名称排序
+      let mut headers = vec![
+         ("host", self.host.clone()),
+         ("x-amz-content-sha256", payload_hash.clone()),
+         ("x-amz-date", timestamp.clone()),
+      ];
+      if let Some(token) = &self.session_token {
+         headers.push(("x-amz-security-token", token.clone()));
+      }
+      let canonical_headers: String = headers
+         .iter()
+         .map(|(name, value)| format!("{name}:{value}\n"))
+         .collect();
+      let signed_headers = headers
+         .iter()
+         .map(|(name, _)| *name)
+         .collect::<Vec<_>>()
+         .join(";");
+      let canonical_request = format!(
+         "{method}\n{path}\n\n{canonical_headers}\n{signed_headers}\n{payload_hash}"
+      );
+      let scope = format!("{date}/{}/s3/aws4_request", self.region);
+      let string_to_sign = format!(
+         "AWS4-HMAC-SHA256\n{timestamp}\n{scope}\n{}",
+         hex(&Sha256::digest(canonical_request.as_bytes()))
+      );
+      let mut signing_key = format!("AWS4{}", self.secret_key).into_bytes();
+      for part in [date.as_str(), &self.region, "s3", "aws4_request"] {
+         signing_key = hmac_sha256(&signing_key, part);
+      }
+      let signature = hex(&hmac_sha256(&signing_key, &string_to_sign));
+      let authorization = format!(
+         "AWS4-HMAC-SHA256 Credential={}/{scope}, SignedHeaders={signed_headers}, Signature={signature}",
+         self.access_key
+      );
+      // Host由ureq按地址设置
+      headers
+         .iter()
+         .skip(1)
+         .fold(
+            agent.request(method, &format!("{}{path}", self.endpoint)),
+            |request, (name, value)| request.set(name, value),
+         )
+         .set("Authorization", &authorization)
+   }
+}
+
+impl WebDav {
+   fn request(&self, agent: &Agent, method: &str, path: &str) -> Request {
+      let request =
+         agent.request(method, &format!("{}/{}", self.url, encode_path(path)));
+      match &self.authorization {
+         Some(authorization) => request.set("Authorization", authorization),
+         None => request,
+      }
+   }
+
+   /// 逐级创建`key`所在的文件夹，已存在时服务器返回405
+   fn create_parents(&self, agent: &Agent, key: &str) -> Empty {
+      let Some((parent, _)) = key.rsplit_once('/') else {
+         return Ok(());
+      };
+      let mut collection = String::new();
+      for part in parent.split('/') {
+         if !collection.is_empty() {
+            collection.push('/');
+         }
+         collection.push_str(part);
+         let created = self.lock().contains(&collection);
+         if created {
+            continue;
+         }
+         match self
+            .request(agent, "MKCOL", &format!("{collection}/"))
+            .call()
+         {
+            Ok(_) | Err(ureq::Error::Status(405, _)) => {
+               self.lock().insert(collection.clone());
+            }
+            Err(err) => {
+               return Err(upload_error(format!("创建文件夹{collection}"), err));
+            }
+         }
+      }
+      Ok(())
+   }
+
+   fn lock(&self) -> MutexGuard<'_, HashSet<String>> {
+      self
+         .collections
+         .lock()
+         .unwrap_or_else(|err| err.into_inner())
+   }
+}
+
+/// 按RFC 3986编码路径中的每一段，保留`/`
+fn encode_path(path: &str) -> String {
+   let mut encoded = String::with_capacity(path.len());
+   for byte in path.bytes() {
+      match byte {
+         b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => {
+            encoded.push(byte as char)
+         }
+         _ => encoded.push_str(&format!("%{byte:02X}")),
+      }
+   }
+   encoded
+}
+
+/// 按扩展名确定上传的内容类型，图库按此显示图片
+fn content_type(key: &str) -> &'static str {
+   let extension = key.rsplit_once('.').map_or("", |(_, extension)| extension);
+   match extension.to_ascii_lowercase().as_str() {
+      "jpg" | "jpeg" => "image/jpeg",
+      "png" => "image/png",
+      "webp" => "image/webp",
+      "avif" => "image/avif",
+      "tif" | "tiff" => "image/tiff",
+      _ => "application/octet-stream",
+   }
+}
+
+fn hmac_sha256(key: &[u8], data: &str) -> Vec<u8> {
+   let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC接受任意长度的密钥");
+   mac.update(data.as_bytes());
+   mac.finalize().into_bytes().to_vec()
+}
+
+fn hex(bytes: &[u8]) -> String {
+   bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+/// 上传失败转换为读写错误，HTTP错误包含服务器返回的状态码
+fn upload_error(action: String, err: ureq::Error) -> LumixMarkError {
+   let message = match err {
+      ureq::Error::Status(code, response) => {
+         format!("{action}失败：HTTP {code} {}", response.status_text())
+      }
+      err => format!("{action}失败：{err}"),
+   };
+   LumixMarkError::Io(io::Error::other(message))
+}
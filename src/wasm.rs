@@ -0,0 +1,36 @@
+use crate::{LumixMark, MarkStyle, OutputFormat, SaveOptions};
+use clap::ValueEnum;
+use wasm_bindgen::prelude::*;
+
+/// # 为照片添加水印，供浏览器中的JavaScript调用
+///
+/// 与命令行使用相同的解码、绘制和编码流程，照片不需要上传到服务器
+///
+/// # 参数
+/// * `photo` - 图片文件内容
+/// * `format` - 输出图片格式（jpeg、png、webp、avif、tiff）
+/// * `quality` - 图片质量（75 - 100），用于JPEG/AVIF
+/// * `mark_ratio` - 水印相当于短边的比率（0.1 - 0.15）
+/// * `config` - TOML格式的水印样式，与配置文件相同，缺省时使用默认样式
+#[wasm_bindgen(js_name = markPhoto)]
+pub fn mark_photo(
+   photo: &[u8],
+   format: &str,
+   quality: u8,
+   mark_ratio: f32,
+   config: Option<String>,
+) -> Result<Vec<u8>, JsError> {
+   let format =
+      OutputFormat::from_str(format, true).map_err(|err| JsError::new(&err))?;
+   let style = match config {
+      Some(config) => MarkStyle::from_toml(&config)?,
+      None => MarkStyle::default(),
+   };
+   let options = SaveOptions {
+      quality,
+      ..SaveOptions::default()
+   };
+   Ok(LumixMark::mark_bytes(
+      photo, mark_ratio, &style, format, &options,
+   )?)
+}
@@ -0,0 +1,83 @@
+use crate::input::{InputImage, is_image_file};
+use crate::{Empty, ExifCache, LumixMarkCli, LumixMarkError, TaskOutcome};
+use notify_debouncer_mini::new_debouncer;
+use notify_debouncer_mini::notify::RecursiveMode;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::time::Duration;
+use tracing::{error, info};
+
+impl LumixMarkCli {
+   /// # 监视文件夹，为新增或修改的图片添加水印
+   ///
+   /// 文件在`debounce`时间内没有再变化才会处理，避免读取未写完的文件；一直运行直到进程退出
+   ///
+   /// # 参数
+   /// * `dir` - 需要监视的文件夹，包含子文件夹
+   /// * `debounce` - 文件停止变化后等待的时间
+   pub fn watch(&self, dir: &Path, debounce: Duration) -> Empty {
+      if self.target_zip.is_some() {
+         // 一直运行不会结束，压缩包无法写完
+         return Err(LumixMarkError::InvalidConfig(
+            "监视文件夹时不能使用--target-zip".to_string(),
+         ));
+      }
+      // 事件中的路径为绝对路径，相对路径的文件夹无法计算输出目录和样式覆盖文件
+      let dir = &fs::canonicalize(dir)?;
+      let (tx, rx) = mpsc::channel();
+      let mut debouncer = new_debouncer(debounce, tx)?;
+      debouncer.watcher().watch(dir, RecursiveMode::Recursive)?;
+      info!("开始监视文件夹：{:?}，按Ctrl+C退出", dir);
+      // 记录已写入的输出文件，输出目录位于监视目录中时不再重复处理
+      let mut written = HashSet::new();
+      // 记录已处理图片的修改时间，读取图片产生的事件不会触发重复处理
+      let mut processed = HashMap::new();
+      for result in rx {
+         let events = match result {
+            Ok(events) => events,
+            Err(err) => {
+               error!("监视文件夹失败：{}", err);
+               continue;
+            }
+         };
+         let mut paths: Vec<PathBuf> =
+            events.into_iter().map(|event| event.path).collect();
+         paths.sort();
+         paths.dedup();
+         for path in paths {
+            let Ok(canonical) = fs::canonicalize(&path) else {
+               // 文件已被删除或移走
+               continue;
+            };
+            if written.contains(&canonical)
+               || !path.is_file()
+               || !is_image_file(&path)
+               || self.scan_options().ignores(&path, dir)
+            {
+               continue;
+            }
+            let modified = fs::metadata(&path).and_then(|meta| meta.modified()).ok();
+            if modified.is_some() && processed.get(&canonical) == Some(&modified) {
+               continue;
+            }
+            processed.insert(canonical, modified);
+            match self.draw_logo_exif_task(&InputImage::in_dir(path.clone(), dir)) {
+               Ok(TaskOutcome::Written(target)) => {
+                  info!("已保存：{:?}", target);
+                  written.extend(fs::canonicalize(&target));
+               }
+               Ok(TaskOutcome::Skipped(_)) => {}
+               Err(err) => {
+                  error!("处理图片失败：{}, 图片地址：{:?}", err, path)
+               }
+            }
+         }
+         if let Some(Err(err)) = self.cache.as_ref().map(ExifCache::save) {
+            error!("写入EXIF缓存失败：{}, path:{:?}", err, self.exif_cache);
+         }
+      }
+      Ok(())
+   }
+}
@@ -0,0 +1,68 @@
+//! 批量处理的测试：`--par-count`只限制并行度，每张输入图片都有且只有一个输出
+
+use lumix_mark::LumixMarkCli;
+use lumix_mark::testing::synthetic_photo;
+use std::collections::HashSet;
+use std::fs;
+use std::path::PathBuf;
+
+const IMAGE_COUNT: usize = 12;
+
+/// 在临时文件夹中生成`IMAGE_COUNT`张图片，返回输入和输出文件夹
+fn prepare(name: &str) -> (PathBuf, PathBuf) {
+   let root = std::env::temp_dir()
+      .join(format!("lumix_mark_batch_{}_{name}", std::process::id()));
+   let _ = fs::remove_dir_all(&root);
+   let (input, output) = (root.join("input"), root.join("output"));
+   fs::create_dir_all(&input).unwrap();
+   for index in 0..IMAGE_COUNT {
+      synthetic_photo(160, 120)
+         .to_rgb8()
+         .save(input.join(format!("img{index:02}.jpg")))
+         .unwrap();
+   }
+   (input, output)
+}
+
+fn check(name: &str, par_count: usize) {
+   let (input, output) = prepare(name);
+   let cli = LumixMarkCli::try_from_args([
+      "lumix_mark".into(),
+      input.clone().into_os_string(),
+      "-t".into(),
+      output.clone().into_os_string(),
+      "-p".into(),
+      par_count.to_string().into(),
+   ])
+   .unwrap();
+   let summary = cli.par_draw_logo_exif_task();
+   assert!(summary.is_success(), "par_count:{par_count}");
+   assert_eq!(
+      summary.succeeded.len(),
+      IMAGE_COUNT,
+      "par_count:{par_count}"
+   );
+
+   let outputs: Vec<String> = fs::read_dir(&output)
+      .unwrap()
+      .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+      .filter(|name| name.starts_with("mark_"))
+      .collect();
+   let unique: HashSet<&String> = outputs.iter().collect();
+   assert_eq!(outputs.len(), IMAGE_COUNT, "par_count:{par_count}");
+   assert_eq!(unique.len(), IMAGE_COUNT, "par_count:{par_count}");
+   for index in 0..IMAGE_COUNT {
+      assert!(unique.contains(&format!("mark_img{index:02}.jpg")));
+   }
+   let _ = fs::remove_dir_all(input.parent().unwrap());
+}
+
+#[test]
+fn single_thread_marks_every_image() {
+   check("single", 1);
+}
+
+#[test]
+fn parallel_marks_every_image() {
+   check("parallel", 4);
+}
@@ -0,0 +1,64 @@
+//! 水印布局的参考图片测试，设置`LUMIX_MARK_UPDATE_GOLDEN=1`重新生成参考图片：
+//!
+//! ```shell
+//! cargo test --features test-utils --test golden
+//! ```
+
+use lumix_mark::testing::{
+   DiffThreshold, assert_golden, fixed_exif, render_mark, synthetic_photo,
+};
+use lumix_mark::{Divider, Frame, Layout, MarkPosition, MarkStyle, Theme};
+use std::path::PathBuf;
+
+fn check(name: &str, style: &MarkStyle) {
+   let canvas = render_mark(synthetic_photo(480, 320), fixed_exif(), 0.14, style)
+      .unwrap_or_else(|err| panic!("绘制水印失败：{err}, case:{name}"));
+   let golden = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+      .join("tests/golden")
+      .join(format!("{name}.png"));
+   assert_golden(&canvas, golden, DiffThreshold::default());
+}
+
+#[test]
+fn split_bottom() {
+   check("split_bottom", &MarkStyle::default());
+}
+
+#[test]
+fn centered_top() {
+   let style = MarkStyle {
+      layout: Layout::Centered,
+      position: MarkPosition::Top,
+      ..MarkStyle::default()
+   };
+   check("centered_top", &style);
+}
+
+#[test]
+fn left_strip() {
+   let style = MarkStyle {
+      position: MarkPosition::Left,
+      ..MarkStyle::default()
+   };
+   check("left_strip", &style);
+}
+
+#[test]
+fn polaroid_dot_divider() {
+   let style = MarkStyle {
+      frame: Frame::Polaroid,
+      divider: Divider::Dot,
+      ..MarkStyle::default()
+   };
+   check("polaroid_dot_divider", &style);
+}
+
+#[test]
+fn dark_with_date() {
+   let mut style = MarkStyle {
+      show_date: true,
+      ..MarkStyle::default()
+   };
+   Theme::Dark.apply(&mut style);
+   check("dark_with_date", &style);
+}